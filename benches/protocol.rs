@@ -0,0 +1,82 @@
+//! Benchmarks of the protocol stack: the cipher, JSON encoding and decoding
+//! of typical payloads, and response cache lookups.
+//!
+//! Run with `cargo bench --features bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tplink::bench::Cache;
+use tplink::wire::{decrypt, decrypt_with_header, encrypt, encrypt_with_header};
+use tplink::{HS100Info, LB110Info, Request};
+
+const HS110_SYSINFO: &str = include_str!("../fixtures/hs110_hw2.json");
+const LB130_SYSINFO: &str = include_str!("../fixtures/lb130_hw1.json");
+
+fn crypto(c: &mut Criterion) {
+    let payload = HS110_SYSINFO.as_bytes();
+    let encrypted = encrypt(payload);
+    let encrypted_with_header = encrypt_with_header(payload);
+
+    let mut group = c.benchmark_group("crypto");
+    group.throughput(Throughput::Bytes(payload.len() as u64));
+    group.bench_function("encrypt", |b| b.iter(|| encrypt(black_box(payload))));
+    group.bench_function("decrypt", |b| b.iter(|| decrypt(black_box(&encrypted))));
+    group.bench_function("encrypt_with_header", |b| {
+        b.iter(|| encrypt_with_header(black_box(payload)))
+    });
+    group.bench_function("decrypt_with_header", |b| {
+        b.iter(|| decrypt_with_header(black_box(&encrypted_with_header)))
+    });
+    group.finish();
+}
+
+fn json(c: &mut Criterion) {
+    let request = json!({ "smartlife.iot.smartbulb.lightingservice": {
+        "transition_light_state": {
+            "on_off": 1,
+            "brightness": 30,
+            "color_temp": 2700,
+            "transition_period": 500,
+        },
+    } });
+    let plug: Value = serde_json::from_str(HS110_SYSINFO).unwrap();
+    let plug = plug["system"]["get_sysinfo"].clone();
+    let bulb: Value = serde_json::from_str(LB130_SYSINFO).unwrap();
+    let bulb = bulb["system"]["get_sysinfo"].clone();
+
+    let mut group = c.benchmark_group("json");
+    group.bench_function("encode_request", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&request)).unwrap())
+    });
+    group.bench_function("decode_response", |b| {
+        b.iter(|| serde_json::from_str::<Value>(black_box(HS110_SYSINFO)).unwrap())
+    });
+    group.bench_function("decode_plug_sysinfo", |b| {
+        b.iter(|| serde_json::from_value::<HS100Info>(black_box(plug.clone())).unwrap())
+    });
+    group.bench_function("decode_bulb_sysinfo", |b| {
+        b.iter(|| serde_json::from_value::<LB110Info>(black_box(bulb.clone())).unwrap())
+    });
+    group.finish();
+}
+
+fn cache(c: &mut Criterion) {
+    let mut cache = Cache::with_ttl(Duration::from_secs(60));
+    let namespaces = ["system", "time", "emeter", "schedule", "cnCloud", "netif"];
+    for ns in &namespaces {
+        for command in &["get_sysinfo", "get_time", "get_realtime", "get_rules"] {
+            cache.insert(Request::new(ns, command, None), json!({ "err_code": 0 }));
+        }
+    }
+    let hit = Request::new("emeter", "get_realtime", None);
+    let miss = Request::new("emeter", "get_daystat", Some(json!({ "year": 2020 })));
+
+    let mut group = c.benchmark_group("cache");
+    group.bench_function("hit", |b| b.iter(|| cache.get(black_box(&hit)).is_some()));
+    group.bench_function("miss", |b| b.iter(|| cache.get(black_box(&miss)).is_some()));
+    group.finish();
+}
+
+criterion_group!(benches, crypto, json, cache);
+criterion_main!(benches);
@@ -6,14 +6,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut bulb = tplink::Bulb::new([192, 168, 1, 107]);
 
     bulb.turn_on()?;
-    assert_eq!(bulb.is_on()?, true);
+    assert!(bulb.is_on()?);
 
     if let Err(e) = bulb.set_brightness(0) {
         println!("{}", e);
     }
 
     bulb.turn_off()?;
-    assert_eq!(bulb.is_on()?, false);
+    assert!(!bulb.is_on()?);
 
     println!("supports brightness: {}", bulb.is_dimmable()?);
     println!("supports color: {}", bulb.is_color()?);
@@ -8,7 +8,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_cache_enabled(Duration::from_secs(3), None)
         .build();
 
-    let mut bulb = tplink::Bulb::with_config(config);
+    let bulb = tplink::Bulb::with_config(config);
     println!("{}", bulb.is_on()?);
 
     Ok(())
@@ -11,7 +11,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("[{}] => {}", ip, plug.alias()?);
 
                 plug.turn_off()?;
-                assert_eq!(plug.is_on()?, false);
+                assert!(!plug.is_on()?);
             }
             DeviceKind::Bulb(mut bulb) => {
                 println!("[{}] => {}", ip, bulb.alias()?);
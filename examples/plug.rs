@@ -6,16 +6,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut plug = tplink::Plug::new([192, 168, 1, 100]);
 
     plug.turn_on()?;
-    assert_eq!(plug.is_on()?, true);
+    assert!(plug.is_on()?);
 
     plug.turn_off()?;
-    assert_eq!(plug.is_on()?, false);
+    assert!(!plug.is_on()?);
 
     plug.turn_off_led()?;
-    assert_eq!(plug.is_led_on()?, false);
+    assert!(!plug.is_led_on()?);
 
     plug.turn_on_led()?;
-    assert_eq!(plug.is_led_on()?, true);
+    assert!(plug.is_led_on()?);
 
     println!("location: {}", plug.location()?);
     println!("alias: {}", plug.alias()?);
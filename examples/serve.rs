@@ -0,0 +1,15 @@
+//! `cargo run --example serve --features serve`
+//!
+//! ```text
+//! curl -d '{"jsonrpc": "2.0", "id": 1, "method": "list_devices"}' http://127.0.0.1:8080
+//! ```
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let server = tplink::serve::Server::bind("127.0.0.1:8080")?;
+    println!("listening on {}", server.local_addr()?);
+    server.run()?;
+
+    Ok(())
+}
@@ -0,0 +1,13 @@
+//! Decrypts arbitrary bytes, with and without the length header used over
+//! TCP, and checks that encryption reverses it.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tplink::wire::{decrypt, decrypt_with_header, encrypt};
+
+fuzz_target!(|data: &[u8]| {
+    let plain = decrypt(data);
+    assert_eq!(encrypt(&plain), data);
+    decrypt_with_header(data);
+});
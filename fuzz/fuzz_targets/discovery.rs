@@ -0,0 +1,13 @@
+//! Feeds arbitrary datagrams through the parsing of responses to discovery
+//! broadcasts, which any host on the network can send.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tplink::bench::parse_datagram;
+
+fuzz_target!(|data: &[u8]| {
+    let result = parse_datagram(data);
+    let mut csv = Vec::new();
+    result.export_csv(&mut csv).unwrap();
+});
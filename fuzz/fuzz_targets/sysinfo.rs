@@ -0,0 +1,36 @@
+//! Feeds arbitrary encrypted responses through the decoding and parsing of
+//! the system information of every device type, the way the device handles
+//! read it.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::net::IpAddr;
+use tplink::bench::parse_response;
+use tplink::state::DeviceState;
+use tplink::{wire, Command, Request};
+use tplink::{HS100Info, HS200Info, HS220Info, LB110Info, LightState};
+
+fuzz_target!(|data: &[u8]| {
+    let request = Request::from(Command::GetSysinfo);
+    let sysinfo = match wire::decode_response(&request, data) {
+        Ok(sysinfo) => sysinfo,
+        Err(_) => return,
+    };
+    let host = IpAddr::from([127, 0, 0, 1]);
+
+    if let Ok(info) = parse_response::<HS100Info>(host, sysinfo.clone()) {
+        info.to_string();
+    }
+    if let Ok(info) = parse_response::<HS200Info>(host, sysinfo.clone()) {
+        info.to_string();
+    }
+    if let Ok(info) = parse_response::<HS220Info>(host, sysinfo.clone()) {
+        info.to_string();
+    }
+    if let Ok(info) = parse_response::<LB110Info>(host, sysinfo.clone()) {
+        info.to_string();
+    }
+    let _ = parse_response::<LightState>(host, sysinfo["light_state"].clone());
+    DeviceState::from_sysinfo(&sysinfo);
+});
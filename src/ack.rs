@@ -0,0 +1,102 @@
+use crate::error::{self, Result};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// The acknowledgement returned by a device in response to a command
+/// that changes its state.
+///
+/// Devices report the outcome of a command with an error code (`0` on
+/// success) and an optional error message, along with any fields the
+/// command returns.
+///
+/// # Examples
+///
+/// ```no_run
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+///
+///     let ack = plug.turn_on()?;
+///     if !ack.is_success() {
+///         eprintln!("turn_on failed: {:?}", ack.err_msg());
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ack {
+    #[serde(default)]
+    err_code: i64,
+    err_msg: Option<String>,
+    #[serde(flatten)]
+    fields: Map<String, Value>,
+}
+
+impl Ack {
+    /// Reads the acknowledgement of a command from its response.
+    ///
+    /// A response that is not an object carries no error code, so it fails
+    /// with `ErrorKind::Json` rather than passing for a success.
+    pub(crate) fn from_response(response: Value) -> Result<Ack> {
+        serde_json::from_value(response).map_err(error::json)
+    }
+
+    /// Returns the error code reported by the device, `0` on success.
+    pub fn err_code(&self) -> i64 {
+        self.err_code
+    }
+
+    /// Returns the error message reported by the device, if any.
+    pub fn err_msg(&self) -> Option<&str> {
+        self.err_msg.as_deref()
+    }
+
+    /// Returns whether the device reported that the command succeeded.
+    pub fn is_success(&self) -> bool {
+        self.err_code == 0
+    }
+
+    /// Returns the value of the given field returned by the command, if any.
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.fields.get(field)
+    }
+
+    /// Returns all the fields returned by the command, other than the
+    /// error code and message.
+    pub fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+}
+
+impl fmt::Display for Ack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_string(&self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_response() {
+        let ack = Ack::from_response(json!({ "err_code": 0, "id": "A1" })).unwrap();
+        assert!(ack.is_success());
+        assert_eq!(ack.get("id"), Some(&json!("A1")));
+
+        let ack =
+            Ack::from_response(json!({ "err_code": -3, "err_msg": "invalid argument" })).unwrap();
+        assert!(!ack.is_success());
+        assert_eq!(ack.err_msg(), Some("invalid argument"));
+
+        // A response that is not an object never passes for a success.
+        for response in [Value::Null, json!(0), json!("ok")] {
+            let err = Ack::from_response(response).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::Json(_)));
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Auditing of the commands that change the state of devices.
+//!
+//! A sink set with [`ConfigBuilder::with_audit_sink`] is handed an
+//! [`AuditEntry`] for every state-changing command sent to the device,
+//! i.e. every command other than a `get_*` query, whether it succeeded or
+//! not. Home automation deployments can keep the entries to find out what
+//! switched a device at a given time.
+//!
+//! Credentials in the arguments of the commands are masked, unless log
+//! redaction has been disabled with [`set_log_redaction`].
+//!
+//! [`ConfigBuilder::with_audit_sink`]: ../struct.ConfigBuilder.html#method.with_audit_sink
+//! [`AuditEntry`]: struct.AuditEntry.html
+//! [`set_log_redaction`]: ../fn.set_log_redaction.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use tplink::audit::LogSink;
+//!
+//! let config = tplink::Config::for_host([192, 168, 1, 100])
+//!     .with_audit_sink(Arc::new(LogSink))
+//!     .build();
+//! let mut plug = tplink::Plug::with_config(config);
+//! plug.turn_on()?;  // logged as `192.168.1.100 system.set_relay_state ...`
+//! # Ok::<(), tplink::Error>(())
+//! ```
+
+use crate::error::Error;
+use crate::proto::Request;
+use crate::redact;
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// A sink recording the state-changing commands sent to devices.
+pub trait AuditSink: Send + Sync {
+    /// Records a command sent to a device and its result.
+    fn record(&self, entry: &AuditEntry);
+}
+
+/// A sink logging each command at the info level, under the
+/// `tplink::audit` target.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogSink;
+
+impl AuditSink for LogSink {
+    fn record(&self, entry: &AuditEntry) {
+        log::info!("{}", entry);
+    }
+}
+
+/// A state-changing command sent to a device and its result.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    timestamp: SystemTime,
+    host: IpAddr,
+    target: String,
+    command: String,
+    arg: Option<Value>,
+    error: Option<String>,
+}
+
+impl AuditEntry {
+    fn new(host: IpAddr, request: &Request, result: &Result<Value, Error>) -> AuditEntry {
+        let error = match result {
+            Ok(response) => match response["err_code"].as_i64() {
+                Some(code) if code != 0 => Some(format!(
+                    "error code {}: {}",
+                    code,
+                    response["err_msg"].as_str().unwrap_or("unknown error")
+                )),
+                _ => None,
+            },
+            Err(e) => Some(e.to_string()),
+        };
+        AuditEntry {
+            timestamp: SystemTime::now(),
+            host,
+            target: request.target.clone(),
+            command: request.command.clone(),
+            arg: request.arg.as_ref().map(redact::redacted),
+            error,
+        }
+    }
+
+    /// Returns when the result of the command was known.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Returns the address of the device.
+    pub fn host(&self) -> IpAddr {
+        self.host
+    }
+
+    /// Returns the namespace of the command, e.g. `system`.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the name of the command, e.g. `set_relay_state`.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Returns the arguments of the command, if any.
+    pub fn arg(&self) -> Option<&Value> {
+        self.arg.as_ref()
+    }
+
+    /// Returns whether the device carried out the command.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Returns why the command failed, if it did: either the request did
+    /// not get through or the device answered with an error code.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}.{}", self.host, self.target, self.command)?;
+        if let Some(arg) = &self.arg {
+            write!(f, " {}", arg)?;
+        }
+        match &self.error {
+            Some(error) => write!(f, ": failed, {}", error),
+            None => write!(f, ": ok"),
+        }
+    }
+}
+
+/// The audit sink of a device, if any.
+#[derive(Clone, Default)]
+pub(crate) struct Audit(Option<Arc<dyn AuditSink>>);
+
+impl Audit {
+    pub(crate) fn new(sink: Arc<dyn AuditSink>) -> Audit {
+        Audit(Some(sink))
+    }
+
+    /// Records the request if its command changes the state of the device.
+    pub(crate) fn record(&self, host: IpAddr, request: &Request, result: &Result<Value, Error>) {
+        if let Some(sink) = &self.0 {
+            if request.changes_state() {
+                sink.record(&AuditEntry::new(host, request, result));
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Audit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if self.0.is_some() {
+            "Some(AuditSink)"
+        } else {
+            "None"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<AuditEntry>>);
+
+    impl AuditSink for Recorder {
+        fn record(&self, entry: &AuditEntry) {
+            self.0.lock().unwrap().push(entry.clone());
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let recorder = Arc::new(Recorder::default());
+        let audit = Audit::new(recorder.clone());
+        let host = IpAddr::from([192, 168, 1, 100]);
+
+        let query = Request::new("system", "get_sysinfo", None);
+        audit.record(host, &query, &Ok(json!({ "err_code": 0 })));
+
+        let switch = Request::new("system", "set_relay_state", Some(json!({ "state": 1 })));
+        audit.record(host, &switch, &Ok(json!({ "err_code": 0 })));
+
+        let bind = Request::new(
+            "cnCloud",
+            "bind",
+            Some(json!({ "username": "me", "password": "secret" })),
+        );
+        audit.record(
+            host,
+            &bind,
+            &Ok(json!({ "err_code": -8, "err_msg": "wrong password" })),
+        );
+
+        let entries = recorder.0.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_success());
+        assert_eq!(
+            entries[0].to_string(),
+            "192.168.1.100 system.set_relay_state {\"state\":1}: ok"
+        );
+        assert_eq!(entries[1].error(), Some("error code -8: wrong password"));
+        assert_eq!(entries[1].arg().unwrap()["password"], "<redacted>");
+    }
+}
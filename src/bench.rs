@@ -0,0 +1,17 @@
+//! Internals exercised by the benchmarks under `benches/` and the fuzz
+//! targets under `fuzz/`, exposed with the `bench` and `fuzz` features.
+//! Not part of the public API.
+
+pub use crate::cache::Cache;
+#[cfg(feature = "fuzz")]
+pub use crate::discover::parse_datagram;
+
+/// Parses a response into one of the library's response types, as the
+/// device handles do.
+#[cfg(feature = "fuzz")]
+pub fn parse_response<T>(host: std::net::IpAddr, response: serde_json::Value) -> crate::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    crate::schema::parse(host, response)
+}
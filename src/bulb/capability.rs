@@ -0,0 +1,80 @@
+//! Marker types for the color capability of a [`Bulb`], for applications
+//! that know their hardware and want misuse caught at compile time.
+//!
+//! A bulb created with [`Bulb::new`] is [`Unchecked`]: every lighting
+//! method is available and unsupported ones fail at runtime with
+//! [`ErrorKind::UnsupportedOperation`]. Once [`Bulb::detect`] has asked the
+//! bulb what it supports, the returned handle is either a
+//! `Bulb<LB110, Color>` or a `Bulb<LB110, WhiteOnly>`, and calling e.g.
+//! `set_hue` on the latter does not compile.
+//!
+//! [`Bulb`]: ../struct.Bulb.html
+//! [`Bulb::new`]: ../struct.Bulb.html#method.new
+//! [`Bulb::detect`]: ../struct.Bulb.html#method.detect
+//! [`ErrorKind::UnsupportedOperation`]: ../enum.ErrorKind.html#variant.UnsupportedOperation
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tplink::DetectedBulb;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! match tplink::Bulb::new([192, 168, 1, 101]).detect()? {
+//!     DetectedBulb::Color(mut bulb) => {
+//!         bulb.set_hue(140)?;
+//!     }
+//!     DetectedBulb::WhiteOnly(mut bulb) => {
+//!         // bulb.set_hue(140)?; would not compile
+//!         bulb.set_brightness(50)?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use super::{Bulb, LB110};
+
+/// A bulb whose color capability has not been checked.
+#[derive(Debug, Clone, Copy)]
+pub enum Unchecked {}
+
+/// A bulb that supports color changes.
+#[derive(Debug, Clone, Copy)]
+pub enum Color {}
+
+/// A bulb that does not support color changes.
+///
+/// ```compile_fail
+/// use tplink::{capability::WhiteOnly, Bulb, LB110};
+///
+/// fn warm(bulb: &mut Bulb<LB110, WhiteOnly>) {
+///     bulb.set_hue(30);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub enum WhiteOnly {}
+
+/// Capabilities that allow the color (hue and saturation) of a bulb to
+/// be read and changed.
+///
+/// This trait is sealed and cannot be implemented outside the crate.
+pub trait ColorControl: private::Sealed {}
+
+impl ColorControl for Unchecked {}
+impl ColorControl for Color {}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::Unchecked {}
+    impl Sealed for super::Color {}
+}
+
+/// A bulb whose color capability has been detected, as returned by
+/// `Bulb::detect`.
+pub enum DetectedBulb {
+    /// A bulb that supports color changes.
+    Color(Bulb<LB110, Color>),
+    /// A bulb that does not support color changes.
+    WhiteOnly(Bulb<LB110, WhiteOnly>),
+}
@@ -1,36 +1,132 @@
-use super::lighting::{LightState, Lighting, HSV};
-use crate::cache::{Cache, ResponseCache};
+use super::lighting::{LightMode, LightState, Lighting, PowerEstimate, WattageSource, HSV};
+use super::ramp::{self, Easing};
+use super::schedule::{Rule, RuleList, ScheduleSettings};
+use crate::ack::Ack;
+use crate::cache::{self, Persister, ResponseCache};
 use crate::cloud::{Cloud, CloudInfo, CloudSettings};
-use crate::config::Config;
+use crate::config::{BrightnessConfig, Config};
 use crate::device::Device;
+use crate::diagnostics::{self, CallResult, Diagnostics};
 use crate::emeter::{DayStats, Emeter, EmeterStats, MonthStats, RealtimeStats};
 use crate::error::{self, Result};
-use crate::proto::{self, Proto};
+use crate::models::Model;
+use crate::proto::Proto;
+use crate::shared::Shared;
 use crate::sys::{Sys, System};
-use crate::sysinfo::{SysInfo, SystemInfo};
+use crate::sysinfo::{self, MacAddress, SysInfo, SystemInfo};
 use crate::time::{DeviceTime, DeviceTimeZone, Time, TimeSettings};
-use crate::util;
+use crate::timer::RuleId;
+use crate::validate;
 use crate::wlan::{AccessPoint, Netif, Wlan};
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
-use std::cell::RefCell;
+use std::cell::Cell;
+use std::convert::TryFrom;
 use std::fmt;
 use std::net::IpAddr;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 
 /// A TP-Link Wi-Fi LED Smart Bulb (LB110).
+#[derive(Clone)]
 pub struct LB110 {
+    proto: Rc<Proto>,
+    cache: Arc<ResponseCache>,
+    _persister: Arc<Persister>,
+    brightness_config: BrightnessConfig,
     system: System,
     lighting: Lighting,
     time_settings: TimeSettings,
+    schedule_settings: ScheduleSettings,
     cloud_settings: CloudSettings,
     netif: Netif,
     emeter: EmeterStats,
+    emeter_support: Rc<Cell<Option<bool>>>,
     sysinfo: SystemInfo<LB110Info>,
 }
 
+/// The namespaces an LB110 sends its commands to.
+///
+/// Some bulbs made for other brands speak the protocol of the LB110 under
+/// different namespaces. Overriding them lets these bulbs be controlled as
+/// an LB110. The system information is always queried with
+/// `system.get_sysinfo`, which all bulbs answer.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tplink::LB110Namespaces;
+///
+/// let namespaces = LB110Namespaces::default()
+///     .with_lighting("smartlife.iot.lightStrip")
+///     .with_emeter("smartlife.iot.common.emeter.v2");
+/// let config = tplink::Config::for_host([192, 168, 1, 101]).build();
+/// let bulb = tplink::Bulb::with_namespaces(config, &namespaces);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LB110Namespaces {
+    system: String,
+    lighting: String,
+    cloud: String,
+    emeter: String,
+    time: String,
+    schedule: String,
+}
+
+impl Default for LB110Namespaces {
+    fn default() -> LB110Namespaces {
+        LB110Namespaces {
+            system: String::from("smartlife.iot.common.system"),
+            lighting: String::from("smartlife.iot.smartbulb.lightingservice"),
+            cloud: String::from("smartlife.iot.common.cloud"),
+            emeter: String::from("smartlife.iot.common.emeter"),
+            time: String::from("smartlife.iot.common.timesetting"),
+            schedule: String::from("smartlife.iot.common.schedule"),
+        }
+    }
+}
+
+impl LB110Namespaces {
+    /// Sets the namespace of the system commands, such as `reboot`.
+    pub fn with_system(mut self, namespace: &str) -> LB110Namespaces {
+        self.system = String::from(namespace);
+        self
+    }
+
+    /// Sets the namespace of the light state commands.
+    pub fn with_lighting(mut self, namespace: &str) -> LB110Namespaces {
+        self.lighting = String::from(namespace);
+        self
+    }
+
+    /// Sets the namespace of the cloud commands.
+    pub fn with_cloud(mut self, namespace: &str) -> LB110Namespaces {
+        self.cloud = String::from(namespace);
+        self
+    }
+
+    /// Sets the namespace of the energy meter commands.
+    pub fn with_emeter(mut self, namespace: &str) -> LB110Namespaces {
+        self.emeter = String::from(namespace);
+        self
+    }
+
+    /// Sets the namespace of the time commands.
+    pub fn with_time(mut self, namespace: &str) -> LB110Namespaces {
+        self.time = String::from(namespace);
+        self
+    }
+
+    /// Sets the namespace of the schedule commands.
+    pub fn with_schedule(mut self, namespace: &str) -> LB110Namespaces {
+        self.schedule = String::from(namespace);
+        self
+    }
+}
+
 impl LB110 {
     pub(super) fn new<A>(host: A) -> LB110
     where
@@ -40,127 +136,174 @@ impl LB110 {
     }
 
     pub(super) fn with_config(config: Config) -> LB110 {
-        let addr = config.addr;
-        let read_timeout = config.read_timeout;
-        let write_timeout = config.write_timeout;
-        let buffer_size = config.buffer_size;
-
-        let proto = proto::Builder::new(addr)
-            .read_timeout(read_timeout)
-            .write_timeout(write_timeout)
-            .buffer_size(buffer_size)
-            .build();
-
-        let cache_config = config.cache_config;
-        let cache = if cache_config.enable_cache {
-            let ttl = cache_config.ttl.unwrap();
-            let cache = cache_config.initial_capacity.map_or_else(
-                || Cache::with_ttl(ttl),
-                |capacity| Cache::with_ttl_and_capacity(ttl, capacity),
-            );
-            Some(RefCell::new(cache))
-        } else {
-            None
+        LB110::with_namespaces(config, &LB110Namespaces::default())
+    }
+
+    pub(super) fn with_namespaces(config: Config, namespaces: &LB110Namespaces) -> LB110 {
+        let Shared {
+            proto,
+            cache,
+            persister,
+        } = Shared::new(&config);
+
+        let mut device = LB110 {
+            system: System::new(&namespaces.system, proto.clone(), cache.clone()),
+            lighting: Lighting::new(&namespaces.lighting, proto.clone(), cache.clone()),
+            cloud_settings: CloudSettings::new(&namespaces.cloud, proto.clone(), cache.clone()),
+            emeter: EmeterStats::new(&namespaces.emeter, proto.clone(), cache.clone()),
+            emeter_support: Rc::new(Cell::new(None)),
+            time_settings: TimeSettings::new(&namespaces.time, proto.clone()),
+            schedule_settings: ScheduleSettings::new(
+                &namespaces.schedule,
+                proto.clone(),
+                cache.clone(),
+            ),
+            netif: Netif::new(proto.clone()),
+            sysinfo: SystemInfo::new(proto.clone(), cache.clone()),
+            _persister: persister,
+            proto,
+            cache,
+            brightness_config: config.brightness_config,
         };
+        device
+            .system
+            .set_reset_confirmation(config.reset_confirmation);
 
-        LB110::with(proto, cache)
+        device
     }
 
-    fn with(proto: Proto, cache: ResponseCache) -> LB110 {
-        let proto = Rc::new(proto);
-        let cache = Rc::new(cache);
+    pub(super) fn from_sysinfo(config: Config, sysinfo: Value) -> Result<LB110> {
+        let response = sysinfo::into_response(sysinfo);
+        LB110Info::deserialize(&response["system"]["get_sysinfo"]).map_err(error::json)?;
 
-        LB110 {
-            system: System::new("smartlife.iot.common.system", proto.clone(), cache.clone()),
-            lighting: Lighting::new(
-                "smartlife.iot.smartbulb.lightingservice",
-                proto.clone(),
-                cache.clone(),
-            ),
-            cloud_settings: CloudSettings::new(
-                "smartlife.iot.common.cloud",
-                proto.clone(),
-                cache.clone(),
-            ),
-            emeter: EmeterStats::new("smartlife.iot.common.emeter", proto.clone(), cache.clone()),
-            time_settings: TimeSettings::new("smartlife.iot.common.timesetting", proto.clone()),
-            netif: Netif::new(proto.clone()),
-            sysinfo: SystemInfo::new(proto, cache),
+        let device = LB110::with_config(config);
+        cache::seed(&device.cache, &response);
+
+        // A discovery response also tells whether the bulb has an energy
+        // meter, which would otherwise be probed on first use.
+        let emeter = &response["smartlife.iot.common.emeter"]["get_realtime"];
+        if !emeter.is_null() {
+            device.emeter_support.set(Some(
+                emeter.is_object() && emeter["err_code"].as_i64().unwrap_or(0) == 0,
+            ));
         }
+
+        Ok(device)
     }
 
-    pub(super) fn sw_ver(&mut self) -> Result<String> {
+    pub(super) fn with_meta<R, F>(&self, call: F) -> Result<CallResult<R>>
+    where
+        F: FnOnce(&LB110) -> Result<R>,
+    {
+        diagnostics::measure(&self.proto, || call(self))
+    }
+
+    pub(super) fn diagnostics(&self) -> Diagnostics {
+        Diagnostics::collect(
+            &self.proto,
+            || self.cloud_settings.get_info(),
+            || self.time_settings.get_time(),
+        )
+    }
+
+    pub(super) fn sw_ver(&self) -> Result<String> {
         self.sysinfo().map(|sysinfo| sysinfo.sw_ver)
     }
 
-    pub(super) fn hw_ver(&mut self) -> Result<String> {
+    pub(super) fn hw_ver(&self) -> Result<String> {
         self.sysinfo().map(|sysinfo| sysinfo.hw_ver)
     }
 
-    pub(super) fn model(&mut self) -> Result<String> {
+    pub(super) fn model(&self) -> Result<String> {
         self.sysinfo().map(|sysinfo| sysinfo.model)
     }
 
-    pub(super) fn alias(&mut self) -> Result<String> {
+    pub(super) fn alias(&self) -> Result<String> {
         self.sysinfo().map(|sysinfo| sysinfo.alias)
     }
 
-    pub(super) fn mac_address(&mut self) -> Result<String> {
-        self.sysinfo().map(|sysinfo| sysinfo.mic_mac)
+    pub(super) fn mac_address(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.mic_mac.to_string())
     }
 
-    pub(super) fn rssi(&mut self) -> Result<i64> {
+    pub(super) fn rssi(&self) -> Result<i64> {
         self.sysinfo().map(|sysinfo| sysinfo.rssi)
     }
 
-    pub(super) fn is_dimmable(&mut self) -> Result<bool> {
+    pub(super) fn is_dimmable(&self) -> Result<bool> {
         self.sysinfo().map(|sysinfo| sysinfo.is_dimmable())
     }
 
-    pub(super) fn is_color(&mut self) -> Result<bool> {
+    pub(super) fn is_color(&self) -> Result<bool> {
         self.sysinfo().map(|sysinfo| sysinfo.is_color())
     }
 
-    pub(super) fn is_variable_color_temp(&mut self) -> Result<bool> {
+    pub(super) fn is_variable_color_temp(&self) -> Result<bool> {
         self.sysinfo()
             .map(|sysinfo| sysinfo.is_variable_color_temp())
     }
 
-    pub(super) fn is_on(&self) -> Result<bool> {
-        self.lighting
-            .get_light_state()
-            .map(|light_state| light_state.is_on())
-    }
+    pub(super) fn has_emeter(&self) -> Result<bool> {
+        if let Some(supported) = self.emeter_support.get() {
+            return Ok(supported);
+        }
 
-    pub(super) fn has_emeter(&mut self) -> Result<bool> {
-        Ok(true)
+        let supported = self.emeter.probe()?;
+        self.emeter_support.set(Some(supported));
+        Ok(supported)
     }
 
-    pub(super) fn hsv(&mut self) -> Result<HSV> {
+    pub(super) fn hsv(&self) -> Result<HSV> {
         self.sysinfo().and_then(|sysinfo| sysinfo.hsv())
     }
 
-    pub(super) fn set_hsv(&mut self, hue: u32, saturation: u32, value: u32) -> Result<()> {
+    pub(super) fn light_state(&self) -> Result<LightState> {
+        self.lighting.get_light_state()
+    }
+
+    pub(super) fn estimated_power(&self) -> Result<PowerEstimate> {
+        let details = self
+            .lighting
+            .get_light_details()
+            .map_err(|e| log::debug!("failed to get light details: {}", e))
+            .ok()
+            .and_then(|details| details.wattage());
+
+        let (rated_wattage, source) = match details {
+            Some(wattage) => (wattage, WattageSource::LightDetails),
+            None => {
+                let model = self.model()?;
+                let wattage = Model::from_name(&model)
+                    .and_then(Model::rated_wattage)
+                    .ok_or_else(|| {
+                        error::unsupported_operation(&format!(
+                            "{} estimated_power: unknown rated wattage",
+                            model
+                        ))
+                    })?;
+                (wattage, WattageSource::ModelTable)
+            }
+        };
+
+        let light_state = self.lighting.get_light_state()?;
+        Ok(PowerEstimate::new(&light_state, rated_wattage, source))
+    }
+
+    pub(super) fn set_hsv(&mut self, hue: u32, saturation: u32, value: u32) -> Result<Ack> {
         let (is_color, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_color(), sysinfo.model))?;
         if is_color {
-            if util::u32_in_range(hue, 0, 360)
-                && util::u32_in_range(saturation, 0, 100)
-                && util::u32_in_range(value, 0, 100)
-            {
-                self.lighting.set_light_state(Some(json!({
-                    "hue": hue,
-                    "saturation": saturation,
-                    "value": value,
-                    "color_temp": 0,
-                })))
-            } else {
-                Err(error::invalid_parameter(&format!(
-                    "{} set_hsv: ({}°, {}%, {}%) (valid range: hue(0-360°), saturation(0-100%), value(0-100%))",
-                    model, hue, saturation, value
-                )))
-            }
+            let op = format!("{} set_hsv", model);
+            validate::hue(&op, hue)?;
+            validate::percent(&op, "saturation", saturation)?;
+            validate::percent(&op, "value", value)?;
+            self.lighting.set_light_state(Some(json!({
+                "hue": hue,
+                "saturation": saturation,
+                "value": value,
+                "color_temp": 0,
+            })))
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} set_hsv: ({}°, {}%, {}%)",
@@ -169,20 +312,77 @@ impl LB110 {
         }
     }
 
-    pub(super) fn set_hue(&mut self, hue: u32) -> Result<()> {
+    pub(super) fn turn_on_with(
+        &mut self,
+        brightness: Option<u32>,
+        color: Option<HSV>,
+        transition: Option<Duration>,
+    ) -> Result<Ack> {
+        let sysinfo = self.sysinfo()?;
+        let model = &sysinfo.model;
+        let op = format!("{} turn_on_with", model);
+        let mut state = json!({ "on_off": 1, "ignore_default": 1 });
+
+        if let Some(color) = color {
+            if color.color_temp() > 0 {
+                if !sysinfo.is_variable_color_temp() {
+                    return Err(error::unsupported_operation(&format!(
+                        "{} turn_on_with: {}K",
+                        model,
+                        color.color_temp()
+                    )));
+                }
+                validate::color_temp(&op, model, color.color_temp())?;
+                state["color_temp"] = json!(color.color_temp());
+            } else {
+                if !sysinfo.is_color() {
+                    return Err(error::unsupported_operation(&format!(
+                        "{} turn_on_with: ({}°, {}%)",
+                        model,
+                        color.hue(),
+                        color.saturation()
+                    )));
+                }
+                validate::hue(&op, color.hue())?;
+                validate::percent(&op, "saturation", color.saturation())?;
+                state["hue"] = json!(color.hue());
+                state["saturation"] = json!(color.saturation());
+                state["color_temp"] = json!(0);
+            }
+            state["brightness"] = json!(color.value());
+        }
+
+        if let Some(brightness) = brightness {
+            state["brightness"] = json!(brightness);
+        }
+
+        if let Some(brightness) = state.get("brightness").and_then(Value::as_u64) {
+            if !sysinfo.is_dimmable() {
+                return Err(error::unsupported_operation(&format!(
+                    "{} turn_on_with: {}%",
+                    model, brightness
+                )));
+            }
+            let brightness = u32::try_from(brightness).unwrap_or(u32::MAX);
+            validate::percent(&op, "brightness", brightness)?;
+            state["brightness"] = json!(self.brightness_config.apply(brightness));
+        }
+
+        if let Some(transition) = transition {
+            state["transition_period"] = json!(transition.as_millis() as u64);
+        }
+
+        self.lighting.set_light_state(Some(state))
+    }
+
+    pub(super) fn set_hue(&mut self, hue: u32) -> Result<Ack> {
         let (is_color, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_color(), sysinfo.model))?;
         if is_color {
-            if util::u32_in_range(hue, 0, 360) {
-                self.lighting
-                    .set_light_state(Some(json!({ "hue": hue, "color_temp": 0 })))
-            } else {
-                Err(error::invalid_parameter(&format!(
-                    "{} set_hue: {}° (valid range: 0-360°)",
-                    model, hue
-                )))
-            }
+            validate::hue(&format!("{} set_hue", model), hue)?;
+            self.lighting
+                .set_light_state(Some(json!({ "hue": hue, "color_temp": 0 })))
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} set_hue: {}°",
@@ -191,7 +391,7 @@ impl LB110 {
         }
     }
 
-    pub(super) fn hue(&mut self) -> Result<u32> {
+    pub(super) fn hue(&self) -> Result<u32> {
         let (is_color, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_color(), sysinfo.model))?;
@@ -204,20 +404,18 @@ impl LB110 {
         }
     }
 
-    pub(super) fn set_saturation(&mut self, saturation: u32) -> Result<()> {
+    pub(super) fn set_saturation(&mut self, saturation: u32) -> Result<Ack> {
         let (is_color, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_color(), sysinfo.model))?;
         if is_color {
-            if util::u32_in_range(saturation, 0, 100) {
-                self.lighting
-                    .set_light_state(Some(json!({ "saturation": saturation, "color_temp": 0 })))
-            } else {
-                Err(error::invalid_parameter(&format!(
-                    "{} set_saturation: {}% (valid range: 0-100%)",
-                    model, saturation
-                )))
-            }
+            validate::percent(
+                &format!("{} set_saturation", model),
+                "saturation",
+                saturation,
+            )?;
+            self.lighting
+                .set_light_state(Some(json!({ "saturation": saturation, "color_temp": 0 })))
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} set_saturation: {}%",
@@ -226,7 +424,7 @@ impl LB110 {
         }
     }
 
-    pub(super) fn saturation(&mut self) -> Result<u32> {
+    pub(super) fn saturation(&self) -> Result<u32> {
         let (is_color, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_color(), sysinfo.model))?;
@@ -242,20 +440,19 @@ impl LB110 {
         }
     }
 
-    pub(super) fn set_brightness(&mut self, brightness: u32) -> Result<()> {
+    pub(super) fn set_brightness(&mut self, brightness: u32) -> Result<Ack> {
         let (is_dimmable, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_dimmable(), sysinfo.model))?;
         if is_dimmable {
-            if util::u32_in_range(brightness, 0, 100) {
-                self.lighting
-                    .set_light_state(Some(json!({ "brightness": brightness })))
-            } else {
-                Err(error::invalid_parameter(&format!(
-                    "{} set_brightness: {}% (valid range: 0-100%)",
-                    model, brightness
-                )))
-            }
+            validate::percent(
+                &format!("{} set_brightness", model),
+                "brightness",
+                brightness,
+            )?;
+            let brightness = self.brightness_config.apply(brightness);
+            self.lighting
+                .set_light_state(Some(json!({ "brightness": brightness })))
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} set_brightness: {}%",
@@ -264,7 +461,67 @@ impl LB110 {
         }
     }
 
-    pub(super) fn brightness(&mut self) -> Result<u32> {
+    pub(super) fn ramp_brightness(
+        &mut self,
+        from: u32,
+        to: u32,
+        duration: Duration,
+        easing: Easing,
+    ) -> Result<Ack> {
+        let (is_dimmable, model) = self
+            .sysinfo()
+            .map(|sysinfo| (sysinfo.is_dimmable(), sysinfo.model))?;
+        if !is_dimmable {
+            return Err(error::unsupported_operation(&format!(
+                "{} ramp_brightness: {}-{}%",
+                model, from, to
+            )));
+        }
+        let op = format!("{} ramp_brightness", model);
+        validate::percent(&op, "from", from)?;
+        validate::percent(&op, "to", to)?;
+
+        let from = self.brightness_config.apply(from);
+        let mut ack = self.lighting.set_light_state(Some(json!({
+            "on_off": 1,
+            "ignore_default": 1,
+            "brightness": from,
+        })))?;
+        for (brightness, interval) in
+            ramp::steps(from, self.brightness_config.apply(to), duration, easing)
+        {
+            ack = self.lighting.set_light_state(Some(json!({
+                "on_off": 1,
+                "ignore_default": 1,
+                "brightness": brightness,
+                "transition_period": interval.as_millis() as u64,
+            })))?;
+            thread::sleep(interval);
+        }
+        Ok(ack)
+    }
+
+    pub(super) fn get_schedule_rules(&self) -> Result<RuleList> {
+        self.schedule_settings.get_rules()
+    }
+
+    pub(super) fn add_schedule_rule(&mut self, rule: Rule) -> Result<RuleId> {
+        self.schedule_settings.add_rule(rule)
+    }
+
+    pub(super) fn edit_schedule_rule(&mut self, id: &RuleId, rule: Rule) -> Result<Ack> {
+        self.schedule_settings.edit_rule(id, rule)
+    }
+
+    pub(super) fn delete_schedule_rule_with_id(&mut self, id: &RuleId) -> Result<Ack> {
+        self.schedule_settings.delete_rule_with_id(id)
+    }
+
+    pub(super) fn delete_all_schedule_rules(&mut self) -> Result<Ack> {
+        self.schedule_settings.delete_all_rules()
+    }
+
+    pub(super) fn brightness(&self) -> Result<u32> {
         let (is_dimmable, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_dimmable(), sysinfo.model))?;
@@ -280,21 +537,33 @@ impl LB110 {
         }
     }
 
-    pub(super) fn set_color_temp(&mut self, color_temp: u32) -> Result<()> {
+    pub(super) fn light_mode(&self) -> Result<Option<LightMode>> {
+        self.lighting
+            .get_light_state()
+            .map(|light_state| light_state.mode())
+    }
+
+    pub(super) fn set_light_mode(&mut self, mode: LightMode) -> Result<Ack> {
+        let (is_variable_color_temp, model) = self
+            .sysinfo()
+            .map(|sysinfo| (sysinfo.is_variable_color_temp(), sysinfo.model))?;
+        if mode == LightMode::Circadian && !is_variable_color_temp {
+            return Err(error::unsupported_operation(&format!(
+                "{} set_light_mode: circadian",
+                model
+            )));
+        }
+        self.lighting.set_light_state(Some(json!({ "mode": mode })))
+    }
+
+    pub(super) fn set_color_temp(&mut self, color_temp: u32) -> Result<Ack> {
         let (is_variable_color_temp, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_variable_color_temp(), sysinfo.model))?;
         if is_variable_color_temp {
-            let range = util::valid_color_temp_range(&model);
-            if util::u32_in_range(color_temp, range.0, range.1) {
-                self.lighting
-                    .set_light_state(Some(json!({ "color_temp": color_temp })))
-            } else {
-                Err(error::invalid_parameter(&format!(
-                    "{} set_color_temp: {} (valid range: {}-{}K)",
-                    model, color_temp, range.0, range.1
-                )))
-            }
+            validate::color_temp(&format!("{} set_color_temp", model), &model, color_temp)?;
+            self.lighting
+                .set_light_state(Some(json!({ "color_temp": color_temp })))
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} set_color_temp: {}K",
@@ -303,7 +572,7 @@ impl LB110 {
         }
     }
 
-    pub(super) fn color_temp(&mut self) -> Result<u32> {
+    pub(super) fn color_temp(&self) -> Result<u32> {
         let (is_variable_color_temp, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.is_variable_color_temp(), sysinfo.model))?;
@@ -321,131 +590,125 @@ impl LB110 {
 }
 
 impl Device for LB110 {
-    fn turn_on(&mut self) -> Result<()> {
+    fn turn_on(&mut self) -> Result<Ack> {
         self.lighting.set_light_state(Some(json!({ "on_off": 1 })))
     }
 
-    fn turn_off(&mut self) -> Result<()> {
+    fn turn_off(&mut self) -> Result<Ack> {
         self.lighting.set_light_state(Some(json!({ "on_off": 0 })))
     }
+
+    fn is_on(&self) -> Result<bool> {
+        self.lighting
+            .get_light_state()
+            .map(|light_state| light_state.is_on())
+    }
+
+    fn is_on_fresh(&self) -> Result<bool> {
+        self.lighting
+            .get_light_state_fresh()
+            .map(|light_state| light_state.is_on())
+    }
 }
 
 impl Sys for LB110 {
-    fn reboot(&mut self, delay: Option<Duration>) -> Result<()> {
+    fn reboot(&mut self, delay: Option<Duration>) -> Result<Ack> {
         self.system.reboot(delay)
     }
 
-    fn factory_reset(&mut self, delay: Option<Duration>) -> Result<()> {
+    fn factory_reset(&mut self, delay: Option<Duration>) -> Result<Ack> {
         self.system.reset(delay)
     }
+
+    fn factory_reset_confirmed(
+        &mut self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack> {
+        self.system.reset_confirmed(confirmation, delay)
+    }
 }
 
 impl Time for LB110 {
-    fn time(&mut self) -> Result<DeviceTime> {
+    fn time(&self) -> Result<DeviceTime> {
         self.time_settings.get_time()
     }
 
-    fn timezone(&mut self) -> Result<DeviceTimeZone> {
+    fn timezone(&self) -> Result<DeviceTimeZone> {
         self.time_settings.get_timezone()
     }
 }
 
 impl Cloud for LB110 {
-    fn get_cloud_info(&mut self) -> Result<CloudInfo> {
+    fn get_cloud_info(&self) -> Result<CloudInfo> {
         self.cloud_settings.get_info()
     }
 
-    fn bind(&mut self, username: &str, password: &str) -> Result<()> {
+    fn bind(&mut self, username: &str, password: &str) -> Result<Ack> {
         self.cloud_settings.bind(username, password)
     }
 
-    fn unbind(&mut self) -> Result<()> {
+    fn unbind(&mut self) -> Result<Ack> {
         self.cloud_settings.unbind()
     }
 
-    fn get_firmware_list(&mut self) -> Result<Vec<String>> {
+    fn get_firmware_list(&self) -> Result<Vec<String>> {
         self.cloud_settings.get_firmware_list()
     }
 
-    fn set_server_url(&mut self, url: &str) -> Result<()> {
+    fn set_server_url(&mut self, url: &str) -> Result<Ack> {
         self.cloud_settings.set_server_url(url)
     }
 }
 
 impl Wlan for LB110 {
-    fn get_scan_info(
-        &mut self,
-        refresh: bool,
-        timeout: Option<Duration>,
-    ) -> Result<Vec<AccessPoint>> {
+    fn get_scan_info(&self, refresh: bool, timeout: Option<Duration>) -> Result<Vec<AccessPoint>> {
         self.netif.get_scan_info(refresh, timeout)
     }
 }
 
 impl Emeter for LB110 {
-    fn get_emeter_realtime(&mut self) -> Result<RealtimeStats> {
-        let (has_emeter, model) = self
-            .sysinfo()
-            .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
-
-        if has_emeter {
+    fn get_emeter_realtime(&self) -> Result<RealtimeStats> {
+        if self.has_emeter()? {
             self.emeter.get_realtime()
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} get_emeter_realtime",
-                model
+                self.model()?
             )))
         }
     }
 
-    fn get_emeter_month_stats(&mut self, year: u32) -> Result<MonthStats> {
-        let (has_emeter, model) = self
-            .sysinfo()
-            .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
-
-        if has_emeter {
+    fn get_emeter_month_stats(&self, year: u32) -> Result<MonthStats> {
+        if self.has_emeter()? {
             self.emeter.get_month_stats(year)
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} get_emeter_month_stats",
-                model
+                self.model()?
             )))
         }
     }
 
-    fn get_emeter_day_stats(&mut self, month: u32, year: u32) -> Result<DayStats> {
-        let (has_emeter, model) = self
-            .sysinfo()
-            .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
-
-        if has_emeter {
-            if util::u32_in_range(month, 1, 12) {
-                self.emeter.get_day_stats(month, year)
-            } else {
-                Err(error::invalid_parameter(&format!(
-                    "{} get_emeter_day_stats: month={} (valid range: 1-12)",
-                    model, month
-                )))
-            }
+    fn get_emeter_day_stats(&self, month: u32, year: u32) -> Result<DayStats> {
+        if self.has_emeter()? {
+            validate::month(&format!("{} get_emeter_day_stats", self.model()?), month)?;
+            self.emeter.get_day_stats(month, year)
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} get_emeter_day_stats",
-                model
+                self.model()?
             )))
         }
     }
 
-    fn erase_emeter_stats(&mut self) -> Result<()> {
-        let (has_emeter, model) = self
-            .sysinfo()
-            .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
-
-        if has_emeter {
+    fn erase_emeter_stats(&mut self) -> Result<Ack> {
+        if self.has_emeter()? {
             self.emeter.erase_stats()
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} erase_emeter_stats",
-                model
+                self.model()?
             )))
         }
     }
@@ -454,9 +717,13 @@ impl Emeter for LB110 {
 impl SysInfo for LB110 {
     type Info = LB110Info;
 
-    fn sysinfo(&mut self) -> Result<Self::Info> {
+    fn sysinfo(&self) -> Result<Self::Info> {
         self.sysinfo.get_sysinfo()
     }
+
+    fn sysinfo_fresh(&self) -> Result<Self::Info> {
+        self.sysinfo.get_sysinfo_fresh()
+    }
 }
 
 /// The system information of TP-Link Smart Wi-Fi LED Bulb (LB110).
@@ -468,7 +735,8 @@ pub struct LB110Info {
     description: Option<String>,
     alias: String,
     mic_type: String,
-    mic_mac: String,
+    #[serde(alias = "mac")]
+    mic_mac: MacAddress,
     is_dimmable: u64,
     is_color: u64,
     is_variable_color_temp: u64,
@@ -499,9 +767,10 @@ impl LB110Info {
         &self.alias
     }
 
-    /// Returns the mac address of the device.
+    /// Returns the mac address of the device, formatted as
+    /// `AA:BB:CC:DD:EE:FF`.
     pub fn mac_address(&self) -> &str {
-        &self.mic_mac
+        self.mic_mac.as_str()
     }
 
     /// Returns whether the bulb supports brightness changes.
@@ -524,10 +793,19 @@ impl LB110Info {
         self.rssi
     }
 
+    #[deprecated(
+        note = "the system information does not tell whether the bulb has an energy \
+                meter; use `Bulb::has_emeter`, which asks the bulb"
+    )]
     pub fn has_emeter(&self) -> bool {
         true
     }
 
+    /// Returns the system information as JSON, as reported by the device.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(error::json)
+    }
+
     /// Returns the current HSV (Hue, Saturation, Value) state of the bulb.
     pub fn hsv(&self) -> Result<HSV> {
         if self.is_color == 1 {
@@ -540,6 +818,81 @@ impl LB110Info {
 
 impl fmt::Display for LB110Info {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", serde_json::to_string(&self).unwrap())
+        let capabilities = [
+            (self.is_dimmable(), "dimmable"),
+            (self.is_color(), "color"),
+            (self.is_variable_color_temp(), "variable color temp"),
+        ]
+        .iter()
+        .filter(|(supported, _)| *supported)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(", ");
+        let hsv = self
+            .light_state
+            .current()
+            .or_else(|| self.light_state.default_on());
+        let light = match hsv {
+            Some(hsv) if hsv.color_temp() > 0 => {
+                format!("{}% at {}K", hsv.value(), hsv.color_temp())
+            }
+            Some(hsv) => format!("{}% at {}° / {}%", hsv.value(), hsv.hue(), hsv.saturation()),
+            None => String::from("unknown"),
+        };
+
+        sysinfo::write_fields(
+            f,
+            &[
+                ("alias", &self.alias),
+                ("model", &self.model),
+                ("hw_ver", &self.hw_ver),
+                ("sw_ver", &self.sw_ver),
+                ("mac", &self.mic_mac),
+                ("state", &sysinfo::on_off(self.light_state.is_on())),
+                ("light", &light),
+                ("supports", &capabilities),
+                ("rssi", &format!("{} dBm", self.rssi)),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bulb::Bulb;
+    use crate::crypto;
+    use std::net::UdpSocket;
+
+    #[test]
+    fn test_namespaces_are_used() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = Config::for_host([127, 0, 0, 1])
+            .with_port(device.local_addr().unwrap().port())
+            .build();
+        let handle = thread::spawn(move || {
+            for (target, command) in [
+                ("smartlife.iot.lightStrip", "transition_light_state"),
+                ("smartlife.iot.lightStrip.system", "reboot"),
+            ] {
+                let mut buf = [0; 1024];
+                let (recv, from) = device.recv_from(&mut buf).unwrap();
+                let request: Value =
+                    serde_json::from_slice(&crypto::decrypt(&buf[..recv])).unwrap();
+                assert!(request[target][command].is_object(), "{}", request);
+
+                let response = json!({ target: { command: { "err_code": 0 } } });
+                let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+                device.send_to(&response, from).unwrap();
+            }
+        });
+
+        let namespaces = LB110Namespaces::default()
+            .with_lighting("smartlife.iot.lightStrip")
+            .with_system("smartlife.iot.lightStrip.system");
+        let mut bulb = Bulb::with_namespaces(config, &namespaces);
+        bulb.turn_on().unwrap();
+        bulb.reboot(None).unwrap();
+        handle.join().unwrap();
     }
 }
@@ -1,19 +1,23 @@
-use crate::cache::ResponseCache;
+use crate::ack::Ack;
+use crate::cache::{self, ResponseCache};
 use crate::error::Result;
 use crate::proto::{Proto, Request};
+use crate::schema;
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::rc::Rc;
+use std::sync::Arc;
 
+#[derive(Clone)]
 pub(super) struct Lighting {
     ns: String,
     proto: Rc<Proto>,
-    cache: Rc<ResponseCache>,
+    cache: Arc<ResponseCache>,
 }
 
 impl Lighting {
-    pub(super) fn new(ns: &str, proto: Rc<Proto>, cache: Rc<ResponseCache>) -> Lighting {
+    pub(super) fn new(ns: &str, proto: Rc<Proto>, cache: Arc<ResponseCache>) -> Lighting {
         Lighting {
             ns: String::from(ns),
             cache,
@@ -24,51 +28,70 @@ impl Lighting {
     pub(super) fn get_light_state(&self) -> Result<LightState> {
         let request = Request::new(&self.ns, "get_light_state", None);
 
-        let response = if let Some(cache) = self.cache.as_ref() {
-            cache
-                .borrow_mut()
-                .try_get_or_insert_with(request, |r| self.proto.send_request(r))?
-        } else {
-            self.proto.send_request(&request)?
-        };
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
+
+        self.parse(response)
+    }
+
+    pub(super) fn get_light_state_fresh(&self) -> Result<LightState> {
+        let request = Request::new(&self.ns, "get_light_state", None);
+
+        let response = cache::send_and_cache(&self.cache, &self.proto, request)?;
+
+        self.parse(response)
+    }
 
+    fn parse(&self, response: Value) -> Result<LightState> {
         log::trace!("({}) {:?}", self.ns, response);
 
-        Ok(serde_json::from_value(response).unwrap_or_else(|err| {
-            panic!(
-                "invalid response from host with address {}: {}",
-                self.proto.host(),
-                err
-            )
-        }))
+        schema::parse(self.proto.host(), response)
+    }
+
+    pub(super) fn get_light_details(&self) -> Result<LightDetails> {
+        let request = Request::new(&self.ns, "get_light_details", None);
+
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        schema::parse(self.proto.host(), response)
     }
 
-    pub(super) fn set_light_state(&self, arg: Option<Value>) -> Result<()> {
+    pub(super) fn set_light_state(&self, arg: Option<Value>) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns)
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
         }
 
-        let response = self
-            .proto
-            .send_request(&Request::new(&self.ns, "transition_light_state", arg))
-            .map(|response| {
-                serde_json::from_value::<LightState>(response).unwrap_or_else(|err| {
-                    panic!(
-                        "invalid response from host with address {}: {}",
-                        self.proto.host(),
-                        err
-                    )
-                })
-            })?;
+        let response =
+            self.proto
+                .send_request(&Request::new(&self.ns, "transition_light_state", arg))?;
 
         log::trace!("({}) {:?}", self.ns, response);
 
-        Ok(())
+        Ack::from_response(response)
+    }
+}
+
+/// The lamp details of a bulb. Only the wattage is of interest here;
+/// bulbs that do not report it leave it unset.
+#[derive(Debug, Deserialize)]
+pub(super) struct LightDetails {
+    wattage: Option<f64>,
+}
+
+impl LightDetails {
+    pub(super) fn wattage(&self) -> Option<f64> {
+        self.wattage
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub(super) struct LightState {
+/// The light state of a bulb.
+///
+/// While the bulb is on, it reports its live state. While it is off, it
+/// instead reports the default-on state, i.e. the state it will restore
+/// when switched back on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightState {
     on_off: u64,
     #[serde(flatten)]
     hsv: Option<HSV>,
@@ -76,10 +99,33 @@ pub(super) struct LightState {
 }
 
 impl LightState {
-    pub(super) fn is_on(&self) -> bool {
+    /// Returns whether the bulb is on.
+    pub fn is_on(&self) -> bool {
         self.on_off == 1
     }
 
+    /// Returns the live state of the bulb, if it is on.
+    pub fn current(&self) -> Option<&HSV> {
+        self.hsv.as_ref().filter(|_| self.is_on())
+    }
+
+    /// Returns the state the bulb will restore when switched on, if it
+    /// is off.
+    pub fn default_on(&self) -> Option<&HSV> {
+        self.dft_on_state.as_ref()
+    }
+
+    /// Returns the mode of the live state of the bulb if it is on, or
+    /// of its default-on state if it is off, if the bulb reports one.
+    pub fn mode(&self) -> Option<LightMode> {
+        let hsv = if self.is_on() {
+            self.hsv.as_ref()
+        } else {
+            self.dft_on_state.as_ref()
+        };
+        hsv.and_then(HSV::mode)
+    }
+
     pub(super) fn hsv(&self) -> HSV {
         if self.on_off == 1 {
             self.hsv.as_ref().unwrap().clone()
@@ -90,6 +136,7 @@ impl LightState {
 }
 
 /// The HSV (Hue, Saturation, Value) state of the bulb.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HSV {
     hue: u32,
@@ -100,6 +147,30 @@ pub struct HSV {
 }
 
 impl HSV {
+    /// Creates a new color from the given `hue` (0-360 degrees),
+    /// `saturation` (0-100 percent) and `value` (0-100 percent).
+    pub fn new(hue: u32, saturation: u32, value: u32) -> HSV {
+        HSV {
+            hue,
+            saturation,
+            brightness: value,
+            color_temp: 0,
+            mode: None,
+        }
+    }
+
+    /// Creates a new white of the given color temperature (in kelvin)
+    /// and `value` (0-100 percent).
+    pub fn white(color_temp: u32, value: u32) -> HSV {
+        HSV {
+            hue: 0,
+            saturation: 0,
+            brightness: value,
+            color_temp,
+            mode: None,
+        }
+    }
+
     /// Returns the `hue` (color portion) of the HSV model, expressed
     /// as a number from 0 to 360 degrees.
     pub fn hue(&self) -> u32 {
@@ -118,7 +189,126 @@ impl HSV {
         self.brightness
     }
 
+    /// Returns the color temperature in kelvin, or 0 when the
+    /// color is given by its hue and saturation.
     pub fn color_temp(&self) -> u32 {
         self.color_temp
     }
+
+    /// Returns the mode of the light, if the bulb reports one it knows.
+    pub fn mode(&self) -> Option<LightMode> {
+        match self.mode.as_deref() {
+            Some("normal") => Some(LightMode::Normal),
+            Some("circadian") => Some(LightMode::Circadian),
+            _ => None,
+        }
+    }
+}
+
+/// The mode of the light of a bulb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightMode {
+    /// The light keeps the color and brightness it was set to.
+    Normal,
+    /// The light follows the time of day, warmer and dimmer in the
+    /// morning and evening, on bulbs with a variable color temperature.
+    Circadian,
+}
+
+/// An estimate of the power drawn by a bulb, derived from its brightness
+/// and rated wattage rather than measured by an energy meter.
+///
+/// The draw is taken to scale linearly with brightness and the standby
+/// draw of a switched-off bulb is not accounted for.
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerEstimate {
+    watts: f64,
+    rated_wattage: f64,
+    source: WattageSource,
+}
+
+/// Where the rated wattage of a bulb used in a [`PowerEstimate`] comes from.
+///
+/// [`PowerEstimate`]: struct.PowerEstimate.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum WattageSource {
+    /// The wattage reported by the bulb in its lamp details.
+    LightDetails,
+    /// The nominal wattage of the bulb's model, see
+    /// [`Model::rated_wattage`](models/enum.Model.html#method.rated_wattage).
+    ModelTable,
+}
+
+impl PowerEstimate {
+    pub(super) fn new(
+        light_state: &LightState,
+        rated_wattage: f64,
+        source: WattageSource,
+    ) -> PowerEstimate {
+        let watts = light_state.current().map_or(0.0, |hsv| {
+            rated_wattage * f64::from(hsv.value().clamp(1, 100)) / 100.0
+        });
+        PowerEstimate {
+            watts,
+            rated_wattage,
+            source,
+        }
+    }
+
+    /// Returns the estimated power draw in watts.
+    pub fn watts(&self) -> f64 {
+        self.watts
+    }
+
+    /// Returns the rated wattage of the bulb at full brightness.
+    pub fn rated_wattage(&self) -> f64 {
+        self.rated_wattage
+    }
+
+    /// Returns where the rated wattage comes from.
+    pub fn source(&self) -> WattageSource {
+        self.source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_power_estimate() {
+        let on: LightState = serde_json::from_value(json!({
+            "on_off": 1, "hue": 0, "saturation": 0, "brightness": 40, "color_temp": 2700, "mode": "normal"
+        }))
+        .unwrap();
+        let estimate = PowerEstimate::new(&on, 10.0, WattageSource::LightDetails);
+        assert!((estimate.watts() - 4.0).abs() < 1e-9);
+
+        let off: LightState = serde_json::from_value(json!({
+            "on_off": 0,
+            "dft_on_state": { "hue": 0, "saturation": 0, "brightness": 40, "color_temp": 2700, "mode": "normal" }
+        }))
+        .unwrap();
+        let estimate = PowerEstimate::new(&off, 10.0, WattageSource::ModelTable);
+        assert_eq!(estimate.watts(), 0.0);
+        assert_eq!(off.mode(), Some(LightMode::Normal));
+    }
+
+    #[test]
+    fn test_light_mode() {
+        let circadian: LightState = serde_json::from_value(json!({
+            "on_off": 1, "hue": 0, "saturation": 0, "brightness": 40, "color_temp": 2700, "mode": "circadian"
+        }))
+        .unwrap();
+        assert_eq!(circadian.mode(), Some(LightMode::Circadian));
+
+        let unknown: LightState = serde_json::from_value(json!({
+            "on_off": 1, "hue": 0, "saturation": 0, "brightness": 40, "color_temp": 2700, "mode": "party"
+        }))
+        .unwrap();
+        assert_eq!(unknown.mode(), None);
+        assert_eq!(json!(LightMode::Circadian), json!("circadian"));
+    }
 }
@@ -1,19 +1,31 @@
+pub mod capability;
 mod lb110;
 mod lighting;
+mod ramp;
+pub mod schedule;
 
-pub use self::lb110::LB110;
-use crate::bulb::lighting::HSV;
-use crate::cloud::{Cloud, CloudInfo};
+pub use self::capability::DetectedBulb;
+use self::capability::{Color, ColorControl, Unchecked};
+pub use self::lb110::{LB110Info, LB110Namespaces, LB110};
+pub use self::lighting::{LightMode, LightState, PowerEstimate, WattageSource, HSV};
+pub use self::ramp::Easing;
+use crate::ack::Ack;
+use crate::cloud::{self, Cloud, CloudInfo, FirmwareInfo};
 use crate::config::Config;
 use crate::device::Device;
+use crate::diagnostics::{CallResult, Diagnostics};
 use crate::emeter::{DayStats, Emeter, MonthStats, RealtimeStats};
-use crate::error::Result;
+use crate::error::{self, Result};
+use crate::models::ModelInfo;
 use crate::sys::Sys;
 use crate::sysinfo::SysInfo;
-use crate::time::{DeviceTime, DeviceTimeZone, Time};
-use crate::wlan::{AccessPoint, Wlan};
+use crate::time::{ClockDrift, DeviceTime, DeviceTimeZone, Time};
+use crate::timer::RuleId;
+use crate::wlan::{AccessPoint, ScannedAccessPoint, Wlan};
 
+use serde_json::Value;
 use std::fmt;
+use std::marker::PhantomData;
 use std::net::IpAddr;
 use std::time::Duration;
 
@@ -34,11 +46,29 @@ use std::time::Duration;
 ///     Ok(())
 /// }
 /// ```
-pub struct Bulb<T> {
+///
+/// Cloning a bulb is cheap: the clone is another handle to the same device,
+/// sharing its connection settings and response cache.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+/// let mut handle = bulb.clone();
+///
+/// handle.turn_on()?;
+/// assert!(bulb.is_on()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Bulb<T, C = Unchecked> {
     device: T,
+    capability: PhantomData<C>,
 }
 
-impl<T: Device> Bulb<T> {
+impl_device_traits!(Bulb, C);
+
+impl<T: Device, C> Bulb<T, C> {
     /// Turns on the bulb.
     ///
     /// # Examples
@@ -51,7 +81,7 @@ impl<T: Device> Bulb<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn turn_on(&mut self) -> Result<()> {
+    pub fn turn_on(&mut self) -> Result<Ack> {
         self.device.turn_on()
     }
 
@@ -67,12 +97,65 @@ impl<T: Device> Bulb<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn turn_off(&mut self) -> Result<()> {
+    pub fn turn_off(&mut self) -> Result<Ack> {
         self.device.turn_off()
     }
+
+    /// Returns whether the device is currently switched on.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let is_on = bulb.is_on()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_on(&self) -> Result<bool> {
+        self.device.is_on()
+    }
+
+    /// Returns whether the device is currently switched on, always querying
+    /// the device rather than a cached response. The fresh response replaces
+    /// the cached one, if caching is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let is_on = bulb.is_on_fresh()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_on_fresh(&self) -> Result<bool> {
+        self.device.is_on_fresh()
+    }
+
+    /// Toggles the bulb and returns the power state it was switched to.
+    ///
+    /// The current state is read and then inverted; see [`Device::toggle`]
+    /// for how concurrent changes by other clients are handled.
+    ///
+    /// [`Device::toggle`]: device/trait.Device.html#method.toggle
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let is_on = bulb.toggle()?;
+    /// println!("bulb is now {}", if is_on { "on" } else { "off" });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn toggle(&mut self) -> Result<bool> {
+        self.device.toggle()
+    }
 }
 
-impl<T: Sys> Bulb<T> {
+impl<T: Sys, C> Bulb<T, C> {
     /// Reboots the bulb after the given duration. In case when
     /// the delay duration is not provided, the bulb is set to
     /// reboot after a default delay of 1 second.
@@ -98,7 +181,7 @@ impl<T: Sys> Bulb<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn reboot(&mut self, delay: Option<Duration>) -> Result<()> {
+    pub fn reboot(&mut self, delay: Option<Duration>) -> Result<Ack> {
         self.device.reboot(delay)
     }
 
@@ -127,12 +210,37 @@ impl<T: Sys> Bulb<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn factory_reset(&mut self, delay: Option<Duration>) -> Result<()> {
+    pub fn factory_reset(&mut self, delay: Option<Duration>) -> Result<Ack> {
         self.device.factory_reset(delay)
     }
+
+    /// Factory resets the bulb after the given duration, once the given
+    /// confirmation matches either the alias or the id of the bulb. This is
+    /// the only way to reset a bulb whose config requires reset confirmation.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let config = tplink::Config::for_host([192, 168, 1, 101])
+    ///     .with_reset_confirmation(true)
+    ///     .build();
+    /// let mut bulb = tplink::Bulb::with_config(config);
+    /// assert!(bulb.factory_reset(None).is_err());
+    /// bulb.factory_reset_confirmed("Living Room", None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn factory_reset_confirmed(
+        &mut self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack> {
+        self.device.factory_reset_confirmed(confirmation, delay)
+    }
 }
 
-impl<T: Time> Bulb<T> {
+impl<T: Time, C> Bulb<T, C> {
     /// Returns the current date and time of the device without the timezone.
     /// To get the device timezone, use [`timezone`] method.
     ///
@@ -140,7 +248,7 @@ impl<T: Time> Bulb<T> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let device_time = bulb.time()?;
     /// println!("{}", device_time); // e.g. `2020-04-08 22:29:07`
     /// # Ok(())
@@ -148,7 +256,7 @@ impl<T: Time> Bulb<T> {
     /// ```
     ///
     /// [`timezone`]: #method.timezone
-    pub fn time(&mut self) -> Result<DeviceTime> {
+    pub fn time(&self) -> Result<DeviceTime> {
         self.device.time()
     }
 
@@ -158,81 +266,125 @@ impl<T: Time> Bulb<T> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// bulb.timezone()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn timezone(&mut self) -> Result<DeviceTimeZone> {
+    pub fn timezone(&self) -> Result<DeviceTimeZone> {
         self.device.timezone()
     }
+
+    /// Measures how far the clock of the bulb is ahead of or behind the
+    /// host's clock, see [`Time::clock_drift`].
+    ///
+    /// [`Time::clock_drift`]: time/trait.Time.html#method.clock_drift
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let drift = bulb.clock_drift()?;
+    /// println!("clock drift: {:+.1}s", drift.as_secs_f64());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clock_drift(&self) -> Result<ClockDrift> {
+        self.device.clock_drift()
+    }
 }
 
-impl<T: Cloud> Bulb<T> {
-    pub fn get_cloud_info(&mut self) -> Result<CloudInfo> {
+impl<T: Cloud, C> Bulb<T, C> {
+    pub fn get_cloud_info(&self) -> Result<CloudInfo> {
         self.device.get_cloud_info()
     }
 
-    pub fn bind(&mut self, username: &str, password: &str) -> Result<()> {
+    pub fn bind(&mut self, username: &str, password: &str) -> Result<Ack> {
         self.device.bind(username, password)
     }
 
-    pub fn unbind(&mut self) -> Result<()> {
+    pub fn unbind(&mut self) -> Result<Ack> {
         self.device.unbind()
     }
 
-    pub fn get_firmware_list(&mut self) -> Result<Vec<String>> {
+    pub fn get_firmware_list(&self) -> Result<Vec<String>> {
         self.device.get_firmware_list()
     }
 
-    pub fn set_server_url(&mut self, url: &str) -> Result<()> {
+    pub fn set_server_url(&mut self, url: &str) -> Result<Ack> {
         self.device.set_server_url(url)
     }
 }
 
-impl<T: Wlan> Bulb<T> {
+impl<T: Wlan, C> Bulb<T, C> {
     pub fn get_scan_info(
-        &mut self,
+        &self,
         refresh: bool,
         timeout: Option<Duration>,
     ) -> Result<Vec<AccessPoint>> {
         self.device.get_scan_info(refresh, timeout)
     }
+
+    pub fn scan_aggregate(
+        &self,
+        attempts: u32,
+        delay: Duration,
+    ) -> Result<Vec<ScannedAccessPoint>> {
+        self.device.scan_aggregate(attempts, delay)
+    }
 }
 
-impl<T: Emeter> Bulb<T> {
-    pub fn get_emeter_realtime(&mut self) -> Result<RealtimeStats> {
+impl<T: Emeter, C> Bulb<T, C> {
+    pub fn get_emeter_realtime(&self) -> Result<RealtimeStats> {
         self.device.get_emeter_realtime()
     }
 
-    pub fn get_emeter_month_stats(&mut self, year: u32) -> Result<MonthStats> {
+    pub fn get_emeter_month_stats(&self, year: u32) -> Result<MonthStats> {
         self.device.get_emeter_month_stats(year)
     }
 
-    pub fn get_emeter_day_stats(&mut self, month: u32, year: u32) -> Result<DayStats> {
+    pub fn get_emeter_day_stats(&self, month: u32, year: u32) -> Result<DayStats> {
         self.device.get_emeter_day_stats(month, year)
     }
 
-    pub fn erase_emeter_stats(&mut self) -> Result<()> {
+    pub fn erase_emeter_stats(&mut self) -> Result<Ack> {
         self.device.erase_emeter_stats()
     }
 }
 
-impl<T: SysInfo> Bulb<T> {
+impl<T: SysInfo, C> Bulb<T, C> {
     /// Returns the bulb's system information.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let sysinfo = bulb.sysinfo()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn sysinfo(&mut self) -> Result<T::Info> {
+    pub fn sysinfo(&self) -> Result<T::Info> {
         self.device.sysinfo()
     }
+
+    /// Returns the bulb's system information, always querying the device
+    /// rather than a cached response. The fresh response replaces the cached
+    /// one, if caching is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let sysinfo = bulb.sysinfo_fresh()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sysinfo_fresh(&self) -> Result<T::Info> {
+        self.device.sysinfo_fresh()
+    }
 }
 
 impl Bulb<LB110> {
@@ -249,42 +401,231 @@ impl Bulb<LB110> {
     {
         Bulb {
             device: LB110::new(host),
+            capability: PhantomData,
         }
     }
 
     pub fn with_config(config: Config) -> Bulb<LB110> {
         Bulb {
             device: LB110::with_config(config),
+            capability: PhantomData,
         }
     }
 
+    /// Creates a new Bulb instance with the given config, sending its
+    /// commands to the given namespaces rather than those of the LB110.
+    ///
+    /// See [`LB110Namespaces`] for details.
+    ///
+    /// [`LB110Namespaces`]: struct.LB110Namespaces.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tplink::LB110Namespaces;
+    ///
+    /// let namespaces = LB110Namespaces::default().with_lighting("smartlife.iot.lightStrip");
+    /// let config = tplink::Config::for_host([192, 168, 1, 101]).build();
+    /// let bulb = tplink::Bulb::with_namespaces(config, &namespaces);
+    /// ```
+    pub fn with_namespaces(config: Config, namespaces: &LB110Namespaces) -> Bulb<LB110> {
+        Bulb {
+            device: LB110::with_namespaces(config, namespaces),
+            capability: PhantomData,
+        }
+    }
+
+    /// Creates a new Bulb instance from the given local address and the
+    /// system information already fetched from the device, e.g. a discovery
+    /// response or its `get_sysinfo` object.
+    ///
+    /// If caching is enabled, the responses contained in the information
+    /// are cached, so that they are not fetched again right away. A discovery response
+    /// also tells whether the bulb has an energy meter.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Json` error if the system information is not that of a
+    /// bulb.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use serde_json::json;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let sysinfo = json!({});
+    /// let bulb = tplink::Bulb::from_sysinfo([192, 168, 1, 101], sysinfo)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_sysinfo<A>(host: A, sysinfo: Value) -> Result<Bulb<LB110>>
+    where
+        A: Into<IpAddr>,
+    {
+        Bulb::from_sysinfo_with_config(Config::for_host(host).build(), sysinfo)
+    }
+
+    /// Creates a new Bulb instance with the given configuration and the
+    /// system information already fetched from the device. See
+    /// [`from_sysinfo`](#method.from_sysinfo).
+    pub fn from_sysinfo_with_config(config: Config, sysinfo: Value) -> Result<Bulb<LB110>> {
+        LB110::from_sysinfo(config, sysinfo).map(|device| Bulb {
+            device,
+            capability: PhantomData,
+        })
+    }
+
+    /// Asks the bulb whether it supports color changes and returns a
+    /// handle whose type records the answer, so that color methods can
+    /// only be called on bulbs that support them.
+    ///
+    /// See the [`capability`](capability/index.html) module for details.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tplink::DetectedBulb;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// if let DetectedBulb::Color(mut bulb) = bulb.detect()? {
+    ///     bulb.set_hue(140)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn detect(self) -> Result<DetectedBulb> {
+        if self.is_color()? {
+            Ok(DetectedBulb::Color(self.with_capability()))
+        } else {
+            Ok(DetectedBulb::WhiteOnly(self.with_capability()))
+        }
+    }
+
+    /// Returns a handle to the bulb on which color methods are checked at
+    /// compile time, after checking that the bulb supports color changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `UnsupportedOperation` error if the bulb does not support
+    /// color changes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]).into_color()?;
+    /// bulb.set_hue(140)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_color(self) -> Result<Bulb<LB110, Color>> {
+        match self.detect()? {
+            DetectedBulb::Color(bulb) => Ok(bulb),
+            DetectedBulb::WhiteOnly(bulb) => Err(error::unsupported_operation(&format!(
+                "{} color",
+                bulb.model()?
+            ))),
+        }
+    }
+
+    fn with_capability<D>(self) -> Bulb<LB110, D> {
+        Bulb {
+            device: self.device,
+            capability: PhantomData,
+        }
+    }
+}
+
+impl<C> Bulb<LB110, C> {
     /// Returns the software version of the device.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let sw_ver = bulb.sw_ver()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn sw_ver(&mut self) -> Result<String> {
+    pub fn sw_ver(&self) -> Result<String> {
         self.device.sw_ver()
     }
 
+    /// Runs a self-test of the device and returns a report of its
+    /// reachability, latency, signal strength, cloud binding, clock drift,
+    /// firmware version and transport counters.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// println!("{}", bulb.diagnostics());
+    /// ```
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.device.diagnostics()
+    }
+
+    /// Returns the system information of the bulb, like [`sysinfo`], along
+    /// with whether it was served from the cache and how long it took.
+    ///
+    /// [`sysinfo`]: #method.sysinfo
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let call = bulb.sysinfo_with_meta()?;
+    /// println!("{:?} (cached: {})", call.latency(), call.served_from_cache());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sysinfo_with_meta(&self) -> Result<CallResult<<LB110 as SysInfo>::Info>> {
+        self.device.with_meta(|device| device.sysinfo())
+    }
+
+    /// Returns the light state, like [`light_state`], along with whether it was served
+    /// from the cache and how long it took.
+    ///
+    /// [`light_state`]: #method.light_state
+    pub fn light_state_with_meta(&self) -> Result<CallResult<LightState>> {
+        self.device.with_meta(|device| device.light_state())
+    }
+
+    /// Returns the newest firmware update available for the device, if
+    /// any, by comparing its software version against the firmware list
+    /// reported by the cloud service.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// if let Some(update) = bulb.update_available()? {
+    ///     println!("update available: {}", update);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_available(&self) -> Result<Option<FirmwareInfo>> {
+        cloud::update_available(&self.sw_ver()?, &self.get_firmware_list()?)
+    }
+
     /// Returns the hardware version of the device.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let hw_ver = bulb.hw_ver()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn hw_ver(&mut self) -> Result<String> {
+    pub fn hw_ver(&self) -> Result<String> {
         self.device.hw_ver()
     }
 
@@ -294,27 +635,44 @@ impl Bulb<LB110> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let model = bulb.model()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn model(&mut self) -> Result<String> {
+    pub fn model(&self) -> Result<String> {
         self.device.model()
     }
 
+    /// Returns the detected model of the device along with the level of
+    /// support the library offers for it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let model_info = bulb.model_info()?;
+    /// println!("{}: {:?}", model_info.name(), model_info.support_level());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn model_info(&self) -> Result<ModelInfo> {
+        self.model().map(|model| ModelInfo::detect(&model))
+    }
+
     /// Returns the name (alias) of the device.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let alias = bulb.alias()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn alias(&mut self) -> Result<String> {
+    pub fn alias(&self) -> Result<String> {
         self.device.alias()
     }
 
@@ -324,12 +682,12 @@ impl Bulb<LB110> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let mac_address = bulb.mac_address()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn mac_address(&mut self) -> Result<String> {
+    pub fn mac_address(&self) -> Result<String> {
         self.device.mac_address()
     }
 
@@ -339,12 +697,12 @@ impl Bulb<LB110> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let is_dimmable = bulb.is_dimmable()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn is_dimmable(&mut self) -> Result<bool> {
+    pub fn is_dimmable(&self) -> Result<bool> {
         self.device.is_dimmable()
     }
 
@@ -354,12 +712,12 @@ impl Bulb<LB110> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let is_color = bulb.is_color()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn is_color(&mut self) -> Result<bool> {
+    pub fn is_color(&self) -> Result<bool> {
         self.device.is_color()
     }
 
@@ -369,12 +727,12 @@ impl Bulb<LB110> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let is_variable_color_temp = bulb.is_variable_color_temp()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn is_variable_color_temp(&mut self) -> Result<bool> {
+    pub fn is_variable_color_temp(&self) -> Result<bool> {
         self.device.is_variable_color_temp()
     }
 
@@ -384,213 +742,384 @@ impl Bulb<LB110> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let rssi = bulb.rssi()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn rssi(&mut self) -> Result<i64> {
+    pub fn rssi(&self) -> Result<i64> {
         self.device.rssi()
     }
 
-    /// Returns whether the device is currently switched on.
+    /// Turns on the bulb directly at the given brightness and/or color,
+    /// optionally fading in over the given transition period.
+    ///
+    /// The state is applied in a single request, so the bulb does not
+    /// briefly come on at its previous state first. When both are given,
+    /// `brightness` takes precedence over the value of `color`.
     ///
     /// # Examples
     ///
     /// ```no_run
+    /// # use std::time::Duration;
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// let is_on = bulb.is_on()?;
+    /// let warm_white = tplink::HSV::white(2700, 10);
+    /// bulb.turn_on_with(None, Some(warm_white), Some(Duration::from_secs(1)))?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn is_on(&mut self) -> Result<bool> {
-        self.device.is_on()
+    pub fn turn_on_with(
+        &mut self,
+        brightness: Option<u32>,
+        color: Option<HSV>,
+        transition: Option<Duration>,
+    ) -> Result<Ack> {
+        self.device.turn_on_with(brightness, color, transition)
     }
 
-    /// Returns the current HSV (Hue, Saturation, Value) state of the bulb.
+    /// Returns the light state of the bulb, which separates the live state
+    /// of the bulb from the state it restores when switched on.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// let hsv = bulb.hsv()?;
-    ///
-    /// let hue = hsv.hue();                // degrees (0-360)
-    /// let saturation = hsv.saturation();  // % (0-100)
-    /// let brightness = hsv.value();       // % (0-100)
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let light_state = bulb.light_state()?;
+    /// if let Some(hsv) = light_state.default_on() {
+    ///     println!("bulb will come on at {}% brightness", hsv.value());
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn hsv(&mut self) -> Result<HSV> {
-        self.device.hsv()
+    pub fn light_state(&self) -> Result<LightState> {
+        self.device.light_state()
     }
 
-    /// Sets HSV (Hue, Saturation, Value) state of the bulb.
+    /// Returns an estimate of the power currently drawn by the bulb, for
+    /// bulbs without an energy meter.
+    ///
+    /// The estimate scales the bulb's rated wattage by its brightness. The
+    /// rated wattage is taken from the lamp details reported by the bulb,
+    /// or else from the nominal wattage of its model.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// if let Err(e) = bulb.set_hsv(270, 55, 90) {
-    ///     eprintln!("error setting hsv: {}", e);
-    /// }
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let estimate = bulb.estimated_power()?;
+    /// println!("~{:.1} W (estimated)", estimate.watts());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set_hsv(&mut self, hue: u32, saturation: u32, value: u32) -> Result<()> {
-        self.device.set_hsv(hue, saturation, value)
+    pub fn estimated_power(&self) -> Result<PowerEstimate> {
+        self.device.estimated_power()
     }
 
     /// Returns whether the device supports `emeter` stats.
     ///
+    /// Not every bulb has an energy meter, so the bulb is asked for a
+    /// reading the first time this is called and the answer is remembered
+    /// for the lifetime of the handle (and its clones).
+    ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
     /// let has_emeter = bulb.has_emeter()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn has_emeter(&mut self) -> Result<bool> {
+    pub fn has_emeter(&self) -> Result<bool> {
         self.device.has_emeter()
     }
 
-    /// Sets the hue of the bulb, if the bulb supports color changes.
-    /// Hue is color portion of the HSV model which is expressed as a
-    /// number from 0 to 360 degrees.
+    /// Sets the % brightness of the bulb, if the bulb supports brightness changes.
+    /// Brightness determines the intensity of the color and is expressed
+    /// as a number from 0 to 100 percent.
+    ///
+    /// The brightness is mapped through the dimming curve and minimum
+    /// brightness set on the bulb's [`Config`], if any.
+    ///
+    /// [`Config`]: struct.Config.html
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// bulb.set_hue(140)?;
+    /// bulb.set_brightness(30)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set_hue(&mut self, hue: u32) -> Result<()> {
-        self.device.set_hue(hue)
+    pub fn set_brightness(&mut self, brightness: u32) -> Result<Ack> {
+        self.device.set_brightness(brightness)
     }
 
-    /// Returns the hue value (expressed as a number from 0 to 360 degrees)
-    /// of the bulb, if the bulb supports color changes.
+    /// Switches the bulb on and gradually changes its % brightness from
+    /// `from` to `to` over the given duration, following the given easing
+    /// curve. This blocks until the ramp is complete.
+    ///
+    /// The ramp is made of intermediate brightness changes sent at most
+    /// once per second, each smoothed by the bulb's own transition, so
+    /// that it can last longer than the bulb's firmware transitions allow,
+    /// e.g. for a wake-up light.
     ///
     /// # Examples
     ///
     /// ```no_run
+    /// # use std::time::Duration;
+    /// use tplink::Easing;
+    ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// println!("hue: {}", bulb.hue()?);
+    /// bulb.ramp_brightness(1, 100, Duration::from_secs(30 * 60), Easing::EaseInOut)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn hue(&mut self) -> Result<u32> {
-        self.device.hue()
+    pub fn ramp_brightness(
+        &mut self,
+        from: u32,
+        to: u32,
+        duration: Duration,
+        easing: Easing,
+    ) -> Result<Ack> {
+        self.device.ramp_brightness(from, to, duration, easing)
     }
 
-    /// Sets the % saturation of the bulb, if the bulb supports color changes.
-    /// Saturation determines the amount of gray in a particular color and is
-    /// expressed as a number from 0 to 100 percent.
+    /// Returns the current % brightness of the bulb, if the bulb supports
+    /// brightness changes.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// bulb.set_saturation(70)?;
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// println!("% brightness: {}", bulb.brightness()?);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set_saturation(&mut self, saturation: u32) -> Result<()> {
-        self.device.set_saturation(saturation)
+    pub fn brightness(&self) -> Result<u32> {
+        self.device.brightness()
     }
 
-    /// Returns the current % saturation of the bulb, if the bulb supports
-    /// color changes.
+    /// Sets the color temperature of the bulb, if the bulb supports color
+    /// changes.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// println!("% saturation: {}", bulb.saturation()?);
+    /// bulb.set_color_temp(2400)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn saturation(&mut self) -> Result<u32> {
-        self.device.saturation()
+    pub fn set_color_temp(&mut self, color_temp: u32) -> Result<Ack> {
+        self.device.set_color_temp(color_temp)
     }
 
-    /// Sets the % brightness of the bulb, if the bulb supports brightness changes.
-    /// Brightness determines the intensity of the color and is expressed
-    /// as a number from 0 to 100 percent.
+    /// Returns the current color temperature of the bulb.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// println!("color temperature: {}", bulb.color_temp()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn color_temp(&self) -> Result<u32> {
+        self.device.color_temp()
+    }
+
+    /// Returns the mode of the light of the bulb, if the bulb reports one.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tplink::LightMode;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// if bulb.light_mode()? == Some(LightMode::Circadian) {
+    ///     println!("following the time of day");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn light_mode(&self) -> Result<Option<LightMode>> {
+        self.device.light_mode()
+    }
+
+    /// Sets the mode of the light of the bulb. Only bulbs with a variable
+    /// color temperature support the circadian mode.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tplink::LightMode;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// bulb.set_brightness(30)?;
+    /// bulb.set_light_mode(LightMode::Circadian)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set_brightness(&mut self, brightness: u32) -> Result<()> {
-        self.device.set_brightness(brightness)
+    pub fn set_light_mode(&mut self, mode: LightMode) -> Result<Ack> {
+        self.device.set_light_mode(mode)
     }
 
-    /// Returns the current % brightness of the bulb, if the bulb supports
-    /// brightness changes.
+    /// Returns the schedule rules stored on the bulb.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// for rule in bulb.get_schedule_rules()?.iter() {
+    ///     println!("{} at {:?}", rule.name(), rule.start());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_schedule_rules(&self) -> Result<schedule::RuleList> {
+        self.device.get_schedule_rules()
+    }
+
+    /// Stores the given schedule rule on the bulb and returns its id. See
+    /// the [`schedule`] module for how to build a rule.
+    ///
+    /// [`schedule`]: schedule/index.html
+    pub fn add_schedule_rule(&mut self, rule: schedule::Rule) -> Result<RuleId> {
+        self.device.add_schedule_rule(rule)
+    }
+
+    /// Replaces the schedule rule with the given id.
+    pub fn edit_schedule_rule(&mut self, id: &RuleId, rule: schedule::Rule) -> Result<Ack> {
+        self.device.edit_schedule_rule(id, rule)
+    }
+
+    /// Deletes the schedule rule with the given id.
+    pub fn delete_schedule_rule_with_id(&mut self, id: &RuleId) -> Result<Ack> {
+        self.device.delete_schedule_rule_with_id(id)
+    }
+
+    /// Deletes all the schedule rules stored on the bulb.
+    pub fn delete_all_schedule_rules(&mut self) -> Result<Ack> {
+        self.device.delete_all_schedule_rules()
+    }
+}
+
+impl<C: ColorControl> Bulb<LB110, C> {
+    /// Returns the current HSV (Hue, Saturation, Value) state of the bulb.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// let hsv = bulb.hsv()?;
+    ///
+    /// let hue = hsv.hue();                // degrees (0-360)
+    /// let saturation = hsv.saturation();  // % (0-100)
+    /// let brightness = hsv.value();       // % (0-100)
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hsv(&self) -> Result<HSV> {
+        self.device.hsv()
+    }
+
+    /// Sets HSV (Hue, Saturation, Value) state of the bulb.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// println!("% brightness: {}", bulb.brightness()?);
+    /// if let Err(e) = bulb.set_hsv(270, 55, 90) {
+    ///     eprintln!("error setting hsv: {}", e);
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn brightness(&mut self) -> Result<u32> {
-        self.device.brightness()
+    pub fn set_hsv(&mut self, hue: u32, saturation: u32, value: u32) -> Result<Ack> {
+        self.device.set_hsv(hue, saturation, value)
     }
 
-    /// Sets the color temperature of the bulb, if the bulb supports color
-    /// changes.
+    /// Sets the hue of the bulb, if the bulb supports color changes.
+    /// Hue is color portion of the HSV model which is expressed as a
+    /// number from 0 to 360 degrees.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// bulb.set_color_temp(2400)?;
+    /// bulb.set_hue(140)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set_color_temp(&mut self, color_temp: u32) -> Result<()> {
-        self.device.set_color_temp(color_temp)
+    pub fn set_hue(&mut self, hue: u32) -> Result<Ack> {
+        self.device.set_hue(hue)
     }
 
-    /// Returns the current color temperature of the bulb.
+    /// Returns the hue value (expressed as a number from 0 to 360 degrees)
+    /// of the bulb, if the bulb supports color changes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// println!("hue: {}", bulb.hue()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hue(&self) -> Result<u32> {
+        self.device.hue()
+    }
+
+    /// Sets the % saturation of the bulb, if the bulb supports color changes.
+    /// Saturation determines the amount of gray in a particular color and is
+    /// expressed as a number from 0 to 100 percent.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
-    /// println!("color temperature: {}", bulb.color_temp()?);
+    /// bulb.set_saturation(70)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn color_temp(&mut self) -> Result<u32> {
-        self.device.color_temp()
+    pub fn set_saturation(&mut self, saturation: u32) -> Result<Ack> {
+        self.device.set_saturation(saturation)
+    }
+
+    /// Returns the current % saturation of the bulb, if the bulb supports
+    /// color changes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let bulb = tplink::Bulb::new([192, 168, 1, 101]);
+    /// println!("% saturation: {}", bulb.saturation()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn saturation(&self) -> Result<u32> {
+        self.device.saturation()
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for Bulb<T> {
+impl<T: fmt::Debug, C> fmt::Debug for Bulb<T, C> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.device.fmt(f)
     }
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// The shortest interval between two steps of a brightness ramp, so that a
+/// long ramp does not flood the bulb with requests.
+const MIN_STEP: Duration = Duration::from_secs(1);
+
+/// An easing curve, describing how the brightness of a bulb progresses
+/// over the course of a [`Bulb::ramp_brightness`].
+///
+/// [`Bulb::ramp_brightness`]: struct.Bulb.html#method.ramp_brightness
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    /// Changes the brightness at a constant rate.
+    Linear,
+    /// Changes the brightness slowly at first, faster midway and slowly
+    /// again towards the end.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Returns the progress of the ramp, from 0 to 1, at the given fraction
+    /// of its duration, from 0 to 1.
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Returns the steps of a ramp from `from` to `to` % brightness over the
+/// given duration, as the brightness to transition to and the duration of
+/// the transition.
+///
+/// There is at most one step per % of brightness change and per
+/// `MIN_STEP`, and always at least one.
+pub(super) fn steps(
+    from: u32,
+    to: u32,
+    duration: Duration,
+    easing: Easing,
+) -> Vec<(u32, Duration)> {
+    let by_brightness = from.max(to) - from.min(to);
+    let by_time = u32::try_from(duration.as_millis() / MIN_STEP.as_millis()).unwrap_or(u32::MAX);
+    let count = by_brightness.min(by_time).max(1);
+    let interval = duration / count;
+
+    (1..=count)
+        .map(|step| {
+            let progress = easing.apply(f64::from(step) / f64::from(count));
+            let brightness = f64::from(from) + (f64::from(to) - f64::from(from)) * progress;
+            (brightness.round() as u32, interval)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps() {
+        let steps = steps(0, 100, Duration::from_secs(10), Easing::Linear);
+        assert_eq!(steps.len(), 10);
+        assert_eq!(steps[0], (10, Duration::from_secs(1)));
+        assert_eq!(steps[9], (100, Duration::from_secs(1)));
+
+        let steps = super::steps(80, 20, Duration::from_secs(600), Easing::EaseInOut);
+        assert_eq!(steps.len(), 60);
+        assert_eq!(steps[29].0, 50);
+        assert_eq!(steps[59].0, 20);
+        assert!(steps[0].0 - steps[1].0 < steps[29].0 - steps[30].0);
+        assert!(steps.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+
+        let steps = super::steps(40, 40, Duration::from_millis(200), Easing::Linear);
+        assert_eq!(steps, [(40, Duration::from_millis(200))]);
+    }
+}
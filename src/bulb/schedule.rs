@@ -0,0 +1,459 @@
+//! Schedule rules stored on a bulb, which switch it on or off or change
+//! its light at given times of the day without a running controller.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tplink::schedule::{Action, Rule, Weekday};
+//! use tplink::HSV;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+//! let rule = Rule::builder()
+//!     .name("evening")
+//!     .at(19, 30)
+//!     .on_days(&[Weekday::Friday, Weekday::Saturday])
+//!     .action(Action::SetLight(HSV::white(2700, 60)))
+//!     .build()?;
+//! let id = bulb.add_schedule_rule(rule)?;
+//! println!("added schedule rule {}", id);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::lighting::HSV;
+use crate::ack::Ack;
+use crate::cache::{self, ResponseCache};
+use crate::error::{self, Result};
+use crate::proto::{Proto, Request};
+use crate::schema;
+use crate::timer::RuleId;
+use crate::validate;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::rc::Rc;
+use std::sync::Arc;
+
+// The actions a rule takes at its start time.
+const ACT_OFF: i32 = 0;
+const ACT_ON: i32 = 1;
+const ACT_LIGHT: i32 = 2;
+
+#[derive(Clone)]
+pub(crate) struct ScheduleSettings {
+    ns: String,
+    proto: Rc<Proto>,
+    cache: Arc<ResponseCache>,
+}
+
+impl ScheduleSettings {
+    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Arc<ResponseCache>) -> ScheduleSettings {
+        ScheduleSettings {
+            ns: String::from(ns),
+            proto,
+            cache,
+        }
+    }
+
+    pub(crate) fn get_rules(&self) -> Result<RuleList> {
+        let request = Request::new(&self.ns, "get_rules", None);
+
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        schema::parse(self.proto.host(), response)
+    }
+
+    pub(crate) fn add_rule(&self, rule: Rule) -> Result<RuleId> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
+        }
+
+        let mut arg = serde_json::to_value(rule).map_err(error::json)?;
+        if let Some(arg) = arg.as_object_mut() {
+            arg.remove("id");
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::new(&self.ns, "add_rule", Some(arg)))?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        serde_json::from_value(response["id"].clone()).map_err(error::json)
+    }
+
+    pub(crate) fn edit_rule(&self, id: &RuleId, rule: Rule) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
+        }
+
+        let mut arg = serde_json::to_value(rule).map_err(error::json)?;
+        arg["id"] = json!(id);
+
+        let response = self
+            .proto
+            .send_request(&Request::new(&self.ns, "edit_rule", Some(arg)))?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        Ack::from_response(response)
+    }
+
+    pub(crate) fn delete_rule_with_id(&self, id: &RuleId) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
+        }
+
+        let response = self.proto.send_request(&Request::new(
+            &self.ns,
+            "delete_rule",
+            Some(json!({ "id": id })),
+        ))?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        Ack::from_response(response)
+    }
+
+    pub(crate) fn delete_all_rules(&self) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns);
+        }
+
+        let response =
+            self.proto
+                .send_request(&Request::new(&self.ns, "delete_all_rules", None))?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        Ack::from_response(response)
+    }
+}
+
+/// The schedule rules stored on a bulb.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuleList {
+    rule_list: Vec<Rule>,
+    #[serde(default)]
+    enable: Option<u32>,
+    #[serde(default)]
+    version: Option<u32>,
+}
+
+impl RuleList {
+    pub fn len(&self) -> usize {
+        self.rule_list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rule_list.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Rule> {
+        self.rule_list.iter()
+    }
+}
+
+/// A day of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Sunday,
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+    ];
+}
+
+/// What a schedule rule does to the bulb at its start time.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Switches the bulb off.
+    TurnOff,
+    /// Switches the bulb on at its default-on state.
+    TurnOn,
+    /// Switches the bulb on at the given color and brightness.
+    SetLight(HSV),
+}
+
+/// The light state a rule switches the bulb on with, as stored on the bulb.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RuleLight {
+    on_off: u32,
+    hue: u32,
+    saturation: u32,
+    brightness: u32,
+    color_temp: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(default)]
+    transition_period: u64,
+}
+
+/// A schedule rule stored on a bulb.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rule {
+    // rule id (skip serializing if empty)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<RuleId>,
+    // name of the rule
+    name: String,
+    // enable the rule
+    enable: u32,
+    // days of the week the rule runs on, from Sunday
+    wday: [u32; 7],
+    // repeat weekly rather than run once
+    repeat: u32,
+    // start time option: 0 for a time of day
+    stime_opt: i32,
+    // start time in minutes after midnight
+    smin: u32,
+    // start action
+    sact: i32,
+    // light state of the start action
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    s_light: Option<RuleLight>,
+    // end time option, action and minutes (-1 when unused)
+    #[serde(default = "unused")]
+    etime_opt: i32,
+    #[serde(default)]
+    emin: u32,
+    #[serde(default = "unused")]
+    eact: i32,
+    // date of a rule that runs once (0 when unused)
+    #[serde(default)]
+    year: i32,
+    #[serde(default)]
+    month: u32,
+    #[serde(default)]
+    day: u32,
+    #[serde(default)]
+    longitude: i64,
+    #[serde(default)]
+    latitude: i64,
+    #[serde(default)]
+    force: u32,
+}
+
+fn unused() -> i32 {
+    -1
+}
+
+impl Rule {
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    pub fn id(&self) -> Option<&RuleId> {
+        self.id.as_ref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enable == 1
+    }
+
+    /// Returns whether the rule runs every week, rather than once.
+    pub fn repeats(&self) -> bool {
+        self.repeat == 1
+    }
+
+    /// Returns the time of day the rule runs at, as an hour and a minute.
+    pub fn start(&self) -> (u32, u32) {
+        (self.smin / 60, self.smin % 60)
+    }
+
+    /// Returns the days of the week the rule runs on.
+    pub fn days(&self) -> Vec<Weekday> {
+        Weekday::ALL
+            .iter()
+            .zip(self.wday.iter())
+            .filter(|(_, &set)| set == 1)
+            .map(|(&day, _)| day)
+            .collect()
+    }
+
+    pub fn action(&self) -> Action {
+        match (self.sact, self.s_light.as_ref()) {
+            (ACT_OFF, _) => Action::TurnOff,
+            (ACT_LIGHT, Some(light)) if light.on_off == 1 => {
+                let hsv = if light.color_temp > 0 {
+                    HSV::white(light.color_temp, light.brightness)
+                } else {
+                    HSV::new(light.hue, light.saturation, light.brightness)
+                };
+                Action::SetLight(hsv)
+            }
+            (ACT_LIGHT, Some(_)) => Action::TurnOff,
+            _ => Action::TurnOn,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enable: bool) {
+        self.enable = if enable { 1 } else { 0 };
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = String::from(name);
+    }
+}
+
+pub struct Builder {
+    name: String,
+    enable_rule: bool,
+    hour: u32,
+    minute: u32,
+    days: [u32; 7],
+    repeat: bool,
+    action: Action,
+}
+
+impl Builder {
+    fn new() -> Builder {
+        Builder {
+            name: String::from("schedule"),
+            enable_rule: true,
+            hour: 0,
+            minute: 0,
+            days: [1; 7],
+            repeat: true,
+            action: Action::TurnOn,
+        }
+    }
+
+    pub fn name(&mut self, name: &str) -> &mut Builder {
+        self.name = String::from(name);
+        self
+    }
+
+    pub fn enable(&mut self, enable_rule: bool) -> &mut Builder {
+        self.enable_rule = enable_rule;
+        self
+    }
+
+    /// Sets the time of day the rule runs at, in the bulb's local time.
+    /// Midnight by default.
+    pub fn at(&mut self, hour: u32, minute: u32) -> &mut Builder {
+        self.hour = hour;
+        self.minute = minute;
+        self
+    }
+
+    /// Sets the days of the week the rule runs on. Every day by default.
+    pub fn on_days(&mut self, days: &[Weekday]) -> &mut Builder {
+        for (set, day) in self.days.iter_mut().zip(Weekday::ALL.iter()) {
+            *set = if days.contains(day) { 1 } else { 0 };
+        }
+        self
+    }
+
+    /// Sets whether the rule runs every week, rather than once. Enabled by
+    /// default.
+    pub fn repeat(&mut self, repeat: bool) -> &mut Builder {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Sets what the rule does. Switches the bulb on by default.
+    pub fn action(&mut self, action: Action) -> &mut Builder {
+        self.action = action;
+        self
+    }
+
+    /// Builds the rule, failing if its time of day or light is invalid.
+    pub fn build(&mut self) -> Result<Rule> {
+        let op = format!("schedule rule {}", self.name);
+        validate::time_of_day(&op, self.hour, self.minute)?;
+
+        let (sact, s_light) = match &self.action {
+            Action::TurnOff => (ACT_OFF, None),
+            Action::TurnOn => (ACT_ON, None),
+            Action::SetLight(hsv) => {
+                validate::hue(&op, hsv.hue())?;
+                validate::percent(&op, "saturation", hsv.saturation())?;
+                validate::percent(&op, "brightness", hsv.value())?;
+                let light = RuleLight {
+                    on_off: 1,
+                    hue: hsv.hue(),
+                    saturation: hsv.saturation(),
+                    brightness: hsv.value(),
+                    color_temp: hsv.color_temp(),
+                    mode: Some(String::from("customize_preset")),
+                    transition_period: 0,
+                };
+                (ACT_LIGHT, Some(light))
+            }
+        };
+
+        Ok(Rule {
+            id: None,
+            name: self.name.clone(),
+            enable: if self.enable_rule { 1 } else { 0 },
+            wday: self.days,
+            repeat: if self.repeat { 1 } else { 0 },
+            stime_opt: 0,
+            smin: self.hour * 60 + self.minute,
+            sact,
+            s_light,
+            etime_opt: unused(),
+            emin: 0,
+            eact: unused(),
+            year: 0,
+            month: 0,
+            day: 0,
+            longitude: 0,
+            latitude: 0,
+            force: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_round_trip() {
+        let rule = Rule::builder()
+            .name("evening")
+            .at(19, 30)
+            .on_days(&[Weekday::Friday, Weekday::Saturday])
+            .action(Action::SetLight(HSV::white(2700, 60)))
+            .build()
+            .unwrap();
+        let value = serde_json::to_value(&rule).unwrap();
+        assert_eq!(value["smin"], json!(1170));
+        assert_eq!(value["wday"], json!([0, 0, 0, 0, 0, 1, 1]));
+        assert_eq!(value["sact"], json!(2));
+        assert_eq!(value["s_light"]["color_temp"], json!(2700));
+
+        let rule: Rule = serde_json::from_value(value).unwrap();
+        assert_eq!(rule.start(), (19, 30));
+        assert_eq!(rule.days(), [Weekday::Friday, Weekday::Saturday]);
+        match rule.action() {
+            Action::SetLight(hsv) => assert_eq!((hsv.color_temp(), hsv.value()), (2700, 60)),
+            action => panic!("unexpected action: {:?}", action),
+        }
+
+        assert!(Rule::builder().at(7, 60).build().is_err());
+    }
+}
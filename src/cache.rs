@@ -1,15 +1,24 @@
-use crate::error::Result;
-use crate::proto::Request;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{ErrorKind, Result};
+use crate::proto::{Proto, Request};
 
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::borrow::Borrow;
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
+use std::fs;
+use std::fs::File;
 use std::hash::Hash;
-use std::time::{Duration, Instant};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-pub type ResponseCache = Option<RefCell<Cache<Request, Value>>>;
+pub type ResponseCache = Option<Mutex<Cache<Request, Value>>>;
 
 enum Status {
     NotFound,
@@ -17,41 +26,103 @@ enum Status {
     Expired,
 }
 
+struct Entry<V> {
+    inserted: Instant,
+    accessed: bool,
+    value: V,
+}
+
+struct Failure {
+    inserted: Instant,
+    kind: io::ErrorKind,
+    message: String,
+}
+
 pub struct Cache<K, V> {
-    store: HashMap<K, (Instant, V)>,
+    store: HashMap<K, Entry<V>>,
+    failures: HashMap<K, Failure>,
     ttl: Duration,
+    negative_ttl: Option<Duration>,
+    file: Option<PathBuf>,
     hits: u32,
     misses: u32,
+    generation: u64,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedCache<K, V> {
+    host: IpAddr,
+    saved_at_ms: u64,
+    entries: Vec<SavedEntry<K, V>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedEntry<K, V> {
+    key: K,
+    value: V,
+    age_ms: u64,
 }
 
 impl<K: Hash + Eq, V> Cache<K, V> {
     pub fn with_ttl(duration: Duration) -> Cache<K, V> {
         Cache {
             store: HashMap::new(),
+            failures: HashMap::new(),
             ttl: duration,
+            negative_ttl: None,
+            file: None,
             hits: 0,
             misses: 0,
+            generation: 0,
+            clock: Arc::new(SystemClock),
         }
     }
 
     pub fn with_ttl_and_capacity(duration: Duration, capacity: usize) -> Cache<K, V> {
         Cache {
             store: HashMap::with_capacity(capacity),
+            failures: HashMap::new(),
             ttl: duration,
+            negative_ttl: None,
+            file: None,
             hits: 0,
             misses: 0,
+            generation: 0,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets the duration for which I/O failures are remembered. While a
+    /// failure is remembered, lookups of the same key fail immediately
+    /// instead of hitting the device again.
+    pub fn set_negative_ttl(&mut self, ttl: Option<Duration>) {
+        self.negative_ttl = ttl;
+        if ttl.is_none() {
+            self.failures.clear();
         }
     }
 
-    pub fn get<Q: ?Sized>(&mut self, key: &Q) -> Option<&V>
+    /// Sets the clock entries expire by, e.g. a clock tests can move
+    /// forward.
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Returns the time elapsed since the given instant.
+    fn elapsed(&self, since: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(since)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
         let status = {
             let val = self.store.get(key);
-            if let Some(&(instant, _)) = val {
-                if instant.elapsed() < self.ttl {
+            if let Some(entry) = val {
+                if self.elapsed(entry.inserted) < self.ttl {
                     Status::Found
                 } else {
                     Status::Expired
@@ -67,7 +138,10 @@ impl<K: Hash + Eq, V> Cache<K, V> {
             }
             Status::Found => {
                 self.hits += 1;
-                self.store.get(key).map(|(_, value)| value)
+                self.store.get_mut(key).map(|entry| {
+                    entry.accessed = true;
+                    &entry.value
+                })
             }
             Status::Expired => {
                 self.misses += 1;
@@ -78,30 +152,50 @@ impl<K: Hash + Eq, V> Cache<K, V> {
     }
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        self.store
-            .insert(key, (Instant::now(), value))
-            .map(|(_, value)| value)
+        let entry = Entry {
+            inserted: self.clock.now(),
+            accessed: false,
+            value,
+        };
+        self.store.insert(key, entry).map(|entry| entry.value)
     }
 
-    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
-        self.store.remove(key).map(|(_, value)| value)
+        self.store.remove(key).map(|entry| entry.value)
     }
 
+    /// Retains only the entries specified by the predicate. Since the
+    /// callers use this to invalidate entries before sending a command to
+    /// the device, all remembered failures are forgotten as well.
     pub fn retain<F>(&mut self, mut f: F)
     where
         F: FnMut(&K, &mut V) -> bool,
     {
-        self.store.retain(|k, v| f(k, &mut v.1))
+        self.generation += 1;
+        self.failures.clear();
+        self.store.retain(|k, entry| f(k, &mut entry.value))
     }
 
     pub fn clear(&mut self) {
+        self.generation += 1;
+        self.failures.clear();
         self.store.clear();
     }
 
+    /// Returns the number of times the cache has been invalidated with
+    /// [`retain`] or [`clear`], so that a value read from the device before
+    /// an invalidation can be told apart and left out.
+    ///
+    /// [`retain`]: #method.retain
+    /// [`clear`]: #method.clear
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub fn hits(&self) -> Option<u32> {
         Some(self.hits)
     }
@@ -114,9 +208,43 @@ impl<K: Hash + Eq, V> Cache<K, V> {
         Some(self.ttl)
     }
 
+    pub fn negative_ttl(&self) -> Option<Duration> {
+        self.negative_ttl
+    }
+
+    /// Sets the file the cache is saved to, with [`save`], when the last
+    /// [`Persister`] of the device is dropped.
+    ///
+    /// [`save`]: #method.save
+    /// [`Persister`]: struct.Persister.html
+    pub fn set_file(&mut self, path: Option<PathBuf>) {
+        self.file = path;
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
     pub fn len(&self) -> usize {
         self.store.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// Returns the keys of the entries that have been read since they were
+    /// inserted and that expire within the given duration.
+    pub fn expiring_within(&self, lead: Duration) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.store
+            .iter()
+            .filter(|(_, entry)| entry.accessed && self.elapsed(entry.inserted) + lead >= self.ttl)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
 }
 
 impl<K, V> Cache<K, V>
@@ -124,28 +252,499 @@ where
     K: Hash + Eq,
     V: Clone,
 {
+    /// Returns the value for the given key, computing and inserting it
+    /// with `f` if the key is not present or has expired.
+    ///
+    /// If negative caching is enabled, I/O errors returned by `f` are
+    /// remembered for the negative ttl, and the same error is returned
+    /// for the key without calling `f` until it expires.
     pub fn try_get_or_insert_with<F>(&mut self, key: K, f: F) -> Result<V>
     where
-        F: Fn(&K) -> Result<V>,
-        V: Clone,
+        F: FnOnce(&K) -> Result<V>,
     {
-        match self.get(&key) {
-            Some(value) => Ok(value.to_owned()),
-            None => {
-                let value = f(&key)?;
+        if let Some(value) = self.get(&key) {
+            return Ok(value.to_owned());
+        }
+
+        if let Some(failure) = self.get_failure(&key) {
+            return Err(io::Error::new(failure.kind, failure.message.as_str()).into());
+        }
+
+        match f(&key) {
+            Ok(value) => {
+                self.failures.remove(&key);
                 self.insert(key, value.to_owned());
                 Ok(value)
             }
+            Err(err) => {
+                if let (Some(_), ErrorKind::Io(e)) = (self.negative_ttl, err.kind()) {
+                    let failure = Failure {
+                        inserted: self.clock.now(),
+                        kind: e.kind(),
+                        message: e.to_string(),
+                    };
+                    self.failures.insert(key, failure);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn get_failure(&mut self, key: &K) -> Option<&Failure> {
+        let negative_ttl = self.negative_ttl?;
+        let expired = self
+            .failures
+            .get(key)
+            .map(|failure| self.elapsed(failure.inserted) >= negative_ttl)?;
+        if expired {
+            self.failures.remove(key);
+            None
+        } else {
+            self.failures.get(key)
+        }
+    }
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Hash + Eq + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    /// Saves the unexpired entries of the cache, along with their age and
+    /// the address of the device they were read from, to the file at the
+    /// given path.
+    ///
+    /// The file is written atomically: the entries are written to a
+    /// temporary file next to it, which then replaces it.
+    pub fn save<P: AsRef<Path>>(&self, host: IpAddr, path: P) -> io::Result<()> {
+        let entries = self
+            .store
+            .iter()
+            .filter(|(_, entry)| self.elapsed(entry.inserted) < self.ttl)
+            .map(|(key, entry)| SavedEntry {
+                key,
+                value: &entry.value,
+                age_ms: self.elapsed(entry.inserted).as_millis() as u64,
+            })
+            .collect();
+        let saved = SavedCache {
+            host,
+            saved_at_ms: unix_time_ms(),
+            entries,
+        };
+
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, &saved)?;
+        writer.flush()?;
+        fs::rename(tmp, path)
+    }
+
+    /// Loads the entries saved by [`save`] from the file at the given path.
+    /// Entries that have expired in the meantime are discarded, and the
+    /// remaining ones expire as if they had never left the cache.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `io::ErrorKind::InvalidData` if the entries were read
+    /// from a device at another address, in which case none are loaded.
+    ///
+    /// [`save`]: #method.save
+    pub fn load<P: AsRef<Path>>(&mut self, host: IpAddr, path: P) -> io::Result<()> {
+        let reader = BufReader::new(File::open(path)?);
+        let saved: SavedCache<K, V> = serde_json::from_reader(reader)?;
+        if saved.host != host {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("cache was saved for {}", saved.host),
+            ));
+        }
+        let offline_ms = unix_time_ms().saturating_sub(saved.saved_at_ms);
+
+        for SavedEntry { key, value, age_ms } in saved.entries {
+            let age = Duration::from_millis(age_ms.saturating_add(offline_ms));
+            if age >= self.ttl {
+                continue;
+            }
+            if let Some(inserted) = self.clock.now().checked_sub(age) {
+                let entry = Entry {
+                    inserted,
+                    accessed: false,
+                    value,
+                };
+                self.store.insert(key, entry);
+            }
         }
+
+        Ok(())
     }
 }
 
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
 impl<K, V> Debug for Cache<K, V> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Cache")
             .field("ttl", &self.ttl)
+            .field("negative_ttl", &self.negative_ttl)
+            .field("file", &self.file)
             .field("hits", &self.hits)
             .field("misses", &self.misses)
             .finish()
     }
 }
+
+/// Returns the response to the given request from the cache, if caching is
+/// enabled and the response is present, and sends the request to the device
+/// otherwise.
+///
+/// Cache keys ignore the arguments of requests, so a request carrying an
+/// argument, such as the stats of a given year, is always sent to the
+/// device and never cached.
+pub(crate) fn get_or_send(cache: &ResponseCache, proto: &Proto, request: Request) -> Result<Value> {
+    match cache.as_ref().filter(|_| request.arg.is_none()) {
+        Some(cache) => cache
+            .lock()
+            .unwrap()
+            .try_get_or_insert_with(request, |r| proto.send_request(r)),
+        None => proto.send_request(&request),
+    }
+}
+
+/// Sends the request to the device, bypassing any cached response, and
+/// caches the new response for subsequent reads.
+pub(crate) fn send_and_cache(
+    cache: &ResponseCache,
+    proto: &Proto,
+    request: Request,
+) -> Result<Value> {
+    let response = proto.send_request(&request)?;
+    if let Some(cache) = cache.as_ref() {
+        cache.lock().unwrap().insert(request, response.clone());
+    }
+    Ok(response)
+}
+
+/// Inserts the responses contained in a combined response to several
+/// commands, such as a discovery response, into the cache, if caching is
+/// enabled. Responses reporting an error are left out.
+pub(crate) fn seed(cache: &ResponseCache, response: &Value) {
+    if let Some(cache) = cache.as_ref() {
+        let mut cache = cache.lock().unwrap();
+        for (target, commands) in response.as_object().into_iter().flatten() {
+            for (command, value) in commands.as_object().into_iter().flatten() {
+                if value.is_object() && value["err_code"].as_i64().unwrap_or(0) == 0 {
+                    cache.insert(Request::new(target, command, None), value.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Saves a response cache to its file, if one is set, when dropped.
+///
+/// A device handle holds its persister behind an `Arc` shared with its
+/// clones, so the file is written once, when the last of them is dropped.
+pub(crate) struct Persister {
+    cache: Arc<ResponseCache>,
+    host: IpAddr,
+}
+
+impl Persister {
+    pub(crate) fn new(cache: &Arc<ResponseCache>, host: IpAddr) -> Arc<Persister> {
+        Arc::new(Persister {
+            cache: cache.clone(),
+            host,
+        })
+    }
+}
+
+impl Drop for Persister {
+    /// Errors are logged rather than returned, since this is called while
+    /// the device is being dropped.
+    fn drop(&mut self) {
+        if let Some(cache) = self.cache.as_ref() {
+            let cache = cache.lock().unwrap();
+            if let Some(path) = cache.file() {
+                if let Err(e) = cache.save(self.host, path) {
+                    log::warn!("failed to save cache to {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+/// Spawns a background thread that refreshes the hot entries of the given
+/// response cache shortly before they expire, so that reads keep being served
+/// from the cache. An entry is considered hot if it has been read at least
+/// once since it was last inserted.
+///
+/// The thread only holds a weak reference to the cache and exits as soon as
+/// the device owning the cache is dropped.
+pub(crate) fn spawn_prefetcher(cache: &Arc<ResponseCache>, proto: &Proto, lead: Duration) {
+    let ttl = match cache.as_ref() {
+        Some(cache) => cache.lock().unwrap().ttl,
+        None => return,
+    };
+    let lead = lead.min(ttl);
+    let interval = (lead / 2).max(Duration::from_millis(10));
+    let cache = Arc::downgrade(cache);
+    let proto = proto.clone();
+
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        if !prefetch(&cache, &proto, lead) {
+            break;
+        }
+    });
+}
+
+/// Refreshes the hot entries of the given response cache that expire within
+/// `lead`, and returns false if the cache has been dropped.
+///
+/// Responses are only inserted if the cache has not been invalidated since
+/// the entries were collected: otherwise, a response read before a command
+/// changed the state of the device would overwrite the invalidation.
+fn prefetch(cache: &Weak<ResponseCache>, proto: &Proto, lead: Duration) -> bool {
+    let (generation, requests) = match with_cache(cache, |cache| {
+        (cache.generation(), cache.expiring_within(lead))
+    }) {
+        Some(expiring) => expiring,
+        None => return false,
+    };
+
+    for request in requests {
+        match proto.send_request(&request) {
+            Ok(response) => {
+                let inserted = with_cache(cache, |cache| {
+                    let current = cache.generation() == generation;
+                    if current {
+                        cache.insert(request.clone(), response.clone());
+                    }
+                    current
+                });
+                match inserted {
+                    Some(true) => log::trace!("prefetched {}: {:?}", request, response),
+                    Some(false) => log::trace!("dropped stale prefetch of {}", request),
+                    None => return false,
+                }
+            }
+            Err(e) => log::debug!("failed to prefetch {}: {}", request, e),
+        }
+    }
+    true
+}
+
+fn with_cache<F, T>(cache: &Weak<ResponseCache>, f: F) -> Option<T>
+where
+    F: FnOnce(&mut Cache<Request, Value>) -> T,
+{
+    let cache = cache.upgrade()?;
+    let mut cache = cache.as_ref().as_ref()?.lock().unwrap();
+    Some(f(&mut cache))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::{crypto, proto};
+    use serde_json::json;
+    use std::cell::Cell;
+    use std::net::UdpSocket;
+
+    fn timeout() -> crate::error::Error {
+        io::Error::new(io::ErrorKind::WouldBlock, "timed out").into()
+    }
+
+    #[test]
+    fn test_try_get_or_insert_with() {
+        let mut cache = Cache::with_ttl(Duration::from_secs(60));
+        assert_eq!(cache.try_get_or_insert_with("key", |_| Ok(1)).unwrap(), 1);
+        assert_eq!(cache.try_get_or_insert_with("key", |_| Ok(2)).unwrap(), 1);
+        assert_eq!(cache.hits(), Some(1));
+    }
+
+    #[test]
+    fn test_ttl() {
+        let clock = Arc::new(ManualClock::new());
+        let mut cache = Cache::with_ttl(Duration::from_secs(60));
+        cache.set_clock(clock.clone());
+        cache.insert("key", 1);
+
+        clock.advance(Duration::from_secs(50));
+        assert_eq!(cache.get("key"), Some(&1));
+        assert!(cache.expiring_within(Duration::from_secs(5)).is_empty());
+        assert_eq!(cache.expiring_within(Duration::from_secs(10)), ["key"]);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.get("key"), None);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_expiring_within() {
+        let clock = Arc::new(ManualClock::new());
+        let mut cache = Cache::with_ttl(Duration::from_secs(60));
+        cache.set_clock(clock.clone());
+        cache.insert("hot", 1);
+        cache.insert("cold", 2);
+        assert_eq!(cache.get("hot"), Some(&1));
+
+        // Only the entries read since they were inserted are worth refreshing.
+        clock.advance(Duration::from_secs(55));
+        assert_eq!(cache.expiring_within(Duration::from_secs(5)), ["hot"]);
+
+        cache.insert("hot", 3);
+        assert!(cache.expiring_within(Duration::from_secs(5)).is_empty());
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let proto = proto::Builder::new(device.local_addr().unwrap())
+            .read_timeout(Duration::from_secs(3))
+            .build();
+        let request = Request::new("system", "get_sysinfo", None);
+
+        let mut cache = Cache::with_ttl(Duration::from_secs(60));
+        cache.insert(request.clone(), json!({ "relay_state": 0 }));
+        cache.get(&request);
+        let cache = Arc::new(Some(Mutex::new(cache)));
+        let weak = Arc::downgrade(&cache);
+
+        let invalidating = cache.clone();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            for relay_state in [1, 2] {
+                let (_, from) = device.recv_from(&mut buf).unwrap();
+                if relay_state == 1 {
+                    // A command changes the state while the prefetch is in flight.
+                    let mut cache = invalidating.as_ref().as_ref().unwrap().lock().unwrap();
+                    cache.retain(|_, _| true);
+                }
+                let response =
+                    json!({ "system": { "get_sysinfo": { "relay_state": relay_state } } });
+                let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+                device.send_to(&response, from).unwrap();
+            }
+        });
+
+        let lead = Duration::from_secs(60);
+        assert!(prefetch(&weak, &proto, lead));
+        let value = with_cache(&weak, |cache| cache.get(&request).cloned());
+        assert_eq!(value, Some(Some(json!({ "relay_state": 0 }))));
+
+        assert!(prefetch(&weak, &proto, lead));
+        handle.join().unwrap();
+        let value = with_cache(&weak, |cache| cache.get(&request).cloned());
+        assert_eq!(value, Some(Some(json!({ "relay_state": 2 }))));
+
+        drop(cache);
+        assert!(!prefetch(&weak, &proto, lead));
+    }
+
+    #[test]
+    fn test_try_get_or_insert_with_does_not_cache_errors_by_default() {
+        let mut cache = Cache::<&str, u32>::with_ttl(Duration::from_secs(60));
+        let calls = Cell::new(0);
+        for _ in 0..2 {
+            let result = cache.try_get_or_insert_with("key", |_| {
+                calls.set(calls.get() + 1);
+                Err(timeout())
+            });
+            assert!(result.is_err());
+        }
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_try_get_or_insert_with_negative_caching() {
+        let mut cache = Cache::<&str, u32>::with_ttl(Duration::from_secs(60));
+        cache.set_negative_ttl(Some(Duration::from_secs(60)));
+        let calls = Cell::new(0);
+        for _ in 0..2 {
+            let result = cache.try_get_or_insert_with("key", |_| {
+                calls.set(calls.get() + 1);
+                Err(timeout())
+            });
+            match result.unwrap_err().kind() {
+                ErrorKind::Io(e) => assert_eq!(e.kind(), io::ErrorKind::WouldBlock),
+                kind => panic!("unexpected error kind: {:?}", kind),
+            }
+        }
+        assert_eq!(calls.get(), 1);
+
+        cache.retain(|_, _| true);
+        assert_eq!(cache.try_get_or_insert_with("key", |_| Ok(3)).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_negative_ttl_expires() {
+        let clock = Arc::new(ManualClock::new());
+        let mut cache = Cache::<&str, u32>::with_ttl(Duration::from_secs(60));
+        cache.set_clock(clock.clone());
+        cache.set_negative_ttl(Some(Duration::from_secs(5)));
+
+        assert!(cache
+            .try_get_or_insert_with("key", |_| Err(timeout()))
+            .is_err());
+        assert!(cache.try_get_or_insert_with("key", |_| Ok(1)).is_err());
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.try_get_or_insert_with("key", |_| Ok(1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("tplink-cache-{}.json", std::process::id()));
+        let host = IpAddr::from([192, 0, 2, 1]);
+
+        let mut cache = Cache::with_ttl(Duration::from_secs(60));
+        cache.insert(String::from("key"), 1);
+        cache.save(host, &path).unwrap();
+
+        let mut cache = Cache::<String, u32>::with_ttl(Duration::from_secs(60));
+        cache.load(host, &path).unwrap();
+        assert_eq!(cache.get("key"), Some(&1));
+
+        let mut cache = Cache::<String, u32>::with_ttl(Duration::from_secs(60));
+        let err = cache.load([192, 0, 2, 2].into(), &path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(cache.len(), 0);
+
+        let mut cache = Cache::<String, u32>::with_ttl(Duration::from_millis(0));
+        cache.load(host, &path).unwrap();
+        assert_eq!(cache.len(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_persister() {
+        let path = std::env::temp_dir().join(format!("tplink-persist-{}.json", std::process::id()));
+        let host = IpAddr::from([192, 0, 2, 1]);
+
+        let mut cache = Cache::with_ttl(Duration::from_secs(60));
+        cache.insert(Request::new("system", "get_sysinfo", None), Value::Null);
+        cache.set_file(Some(path.clone()));
+        let cache = Arc::new(Some(Mutex::new(cache)));
+
+        let persister = Persister::new(&cache, host);
+        let clone = persister.clone();
+        drop(persister);
+        assert!(!path.exists());
+        drop(clone);
+
+        let mut cache = Cache::<Request, Value>::with_ttl(Duration::from_secs(60));
+        cache.load(host, &path).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+//! The source of the current time for time-based behavior, such as the
+//! expiry of cache entries, so tests can move time forward at will rather
+//! than sleep.
+
+use std::fmt::Debug;
+use std::time::Instant;
+#[cfg(test)]
+use std::{sync::Mutex, time::Duration};
+
+/// A source of the current time.
+pub(crate) trait Clock: Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// The clock of the system, used outside of tests.
+#[derive(Debug, Default)]
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that stands still until moved forward.
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct ManualClock {
+    now: Mutex<Instant>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub(crate) fn new() -> ManualClock {
+        ManualClock {
+            now: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub(crate) fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
@@ -1,28 +1,33 @@
-use crate::cache::ResponseCache;
-use crate::error::Result;
+use crate::ack::Ack;
+use crate::cache::{self, ResponseCache};
+use crate::error::{self, Error, Result};
 use crate::proto::{Proto, Request};
+use crate::schema;
 
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::fmt;
 use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
 
 pub trait Cloud {
-    fn get_cloud_info(&mut self) -> Result<CloudInfo>;
-    fn bind(&mut self, username: &str, password: &str) -> Result<()>;
-    fn unbind(&mut self) -> Result<()>;
-    fn get_firmware_list(&mut self) -> Result<Vec<String>>;
-    fn set_server_url(&mut self, url: &str) -> Result<()>;
+    fn get_cloud_info(&self) -> Result<CloudInfo>;
+    fn bind(&mut self, username: &str, password: &str) -> Result<Ack>;
+    fn unbind(&mut self) -> Result<Ack>;
+    fn get_firmware_list(&self) -> Result<Vec<String>>;
+    fn set_server_url(&mut self, url: &str) -> Result<Ack>;
 }
 
+#[derive(Clone)]
 pub(crate) struct CloudSettings {
     ns: String,
     proto: Rc<Proto>,
-    cache: Rc<ResponseCache>,
+    cache: Arc<ResponseCache>,
 }
 
 impl CloudSettings {
-    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Rc<ResponseCache>) -> CloudSettings {
+    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Arc<ResponseCache>) -> CloudSettings {
         CloudSettings {
             ns: String::from(ns),
             proto,
@@ -33,28 +38,16 @@ impl CloudSettings {
     pub(crate) fn get_info(&self) -> Result<CloudInfo> {
         let request = Request::new(&self.ns, "get_info", None);
 
-        let response = if let Some(cache) = self.cache.as_ref() {
-            cache
-                .borrow_mut()
-                .try_get_or_insert_with(request, |r| self.proto.send_request(r))?
-        } else {
-            self.proto.send_request(&request)?
-        };
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
 
         log::trace!("{:?}", response);
 
-        Ok(serde_json::from_value(response).unwrap_or_else(|err| {
-            panic!(
-                "invalid response from host with address {}: {}",
-                self.proto.host(),
-                err
-            )
-        }))
+        schema::parse(self.proto.host(), response)
     }
 
-    pub(crate) fn bind(&self, username: &str, password: &str) -> Result<()> {
+    pub(crate) fn bind(&self, username: &str, password: &str) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns)
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
         }
 
         let response = self.proto.send_request(&Request::new(
@@ -65,12 +58,12 @@ impl CloudSettings {
 
         log::trace!("{:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 
-    pub(crate) fn unbind(&self) -> Result<()> {
+    pub(crate) fn unbind(&self) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns)
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
         }
 
         let response = self
@@ -79,38 +72,24 @@ impl CloudSettings {
 
         log::trace!("{:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 
     pub(crate) fn get_firmware_list(&self) -> Result<Vec<String>> {
         let request = Request::new(&self.ns, "get_intl_fw_list", None);
 
-        let response = if let Some(cache) = self.cache.as_ref() {
-            cache
-                .borrow_mut()
-                .try_get_or_insert_with(request, |r| self.proto.send_request(r))?
-        } else {
-            self.proto.send_request(&request)?
-        };
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
 
         log::trace!("{:?}", response);
 
-        let fw_list = serde_json::from_value::<FirmwareList>(response)
-            .map(|response| response.fw_list)
-            .unwrap_or_else(|err| {
-                panic!(
-                    "invalid response from host with address {}: {}",
-                    self.proto.host(),
-                    err
-                )
-            });
+        let fw_list = schema::parse::<FirmwareList>(self.proto.host(), response)?.fw_list;
 
         Ok(fw_list)
     }
 
-    pub(crate) fn set_server_url(&self, url: &str) -> Result<()> {
+    pub(crate) fn set_server_url(&self, url: &str) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns)
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
         }
 
         let response = self.proto.send_request(&Request::new(
@@ -121,7 +100,7 @@ impl CloudSettings {
 
         log::trace!("{:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 }
 
@@ -166,6 +145,179 @@ impl CloudInfo {
 
 impl fmt::Display for CloudInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", serde_json::to_string(&self).unwrap())
+        let json = serde_json::to_string(&self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+/// A parsed TP-Link firmware version, such as `1.2.5 Build 171213 Rel.101523`.
+///
+/// Versions are ordered by their dotted release number first, then by
+/// build date and release number when present.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    release: Vec<u32>,
+    build: Option<u32>,
+    rel: Option<u32>,
+}
+
+impl FirmwareVersion {
+    /// Returns the dotted release number of the version, e.g. `[1, 2, 5]`.
+    pub fn release(&self) -> &[u32] {
+        &self.release
+    }
+
+    /// Returns the build number (date) of the version, if any.
+    pub fn build(&self) -> Option<u32> {
+        self.build
+    }
+
+    /// Returns the `Rel.` number of the version, if any.
+    pub fn rel(&self) -> Option<u32> {
+        self.rel
+    }
+
+    /// Finds the first version in the given text, ignoring any surrounding words.
+    fn find(text: &str) -> Option<FirmwareVersion> {
+        let mut tokens = text
+            .split(|c: char| c.is_whitespace() || c == ',' || c == '"')
+            .filter(|token| !token.is_empty())
+            .skip_while(|token| parse_release(token).is_none());
+
+        let release = parse_release(tokens.next()?)?;
+        let mut version = FirmwareVersion {
+            release,
+            build: None,
+            rel: None,
+        };
+
+        while let Some(token) = tokens.next() {
+            if token.eq_ignore_ascii_case("build") {
+                version.build = tokens.next().and_then(|build| build.parse().ok());
+            } else if let Some(rel) = token.strip_prefix("Rel.") {
+                version.rel = rel.parse().ok();
+            } else {
+                break;
+            }
+        }
+
+        Some(version)
+    }
+}
+
+fn parse_release(token: &str) -> Option<Vec<u32>> {
+    if !token.contains('.') {
+        return None;
+    }
+    token.split('.').map(|part| part.parse().ok()).collect()
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<FirmwareVersion> {
+        FirmwareVersion::find(s)
+            .ok_or_else(|| error::invalid_parameter(&format!("firmware version: {}", s)))
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let release = self
+            .release
+            .iter()
+            .map(|part| part.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", release.join("."))?;
+        if let Some(build) = self.build {
+            write!(f, " Build {}", build)?;
+        }
+        if let Some(rel) = self.rel {
+            write!(f, " Rel.{}", rel)?;
+        }
+        Ok(())
+    }
+}
+
+/// A firmware update available for a device.
+#[derive(Debug, Clone)]
+pub struct FirmwareInfo {
+    current: FirmwareVersion,
+    available: FirmwareVersion,
+    entry: String,
+}
+
+impl FirmwareInfo {
+    /// Returns the firmware version currently installed on the device.
+    pub fn current(&self) -> &FirmwareVersion {
+        &self.current
+    }
+
+    /// Returns the newer firmware version available for the device.
+    pub fn available(&self) -> &FirmwareVersion {
+        &self.available
+    }
+
+    /// Returns the firmware list entry, as reported by the device,
+    /// that describes the available update.
+    pub fn entry(&self) -> &str {
+        &self.entry
+    }
+}
+
+impl fmt::Display for FirmwareInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.current, self.available)
+    }
+}
+
+/// Returns the newest firmware in `fw_list` if it is newer than `sw_ver`.
+pub(crate) fn update_available(sw_ver: &str, fw_list: &[String]) -> Result<Option<FirmwareInfo>> {
+    let current = sw_ver.parse::<FirmwareVersion>()?;
+
+    Ok(fw_list
+        .iter()
+        .filter_map(|entry| FirmwareVersion::find(entry).map(|version| (version, entry)))
+        .filter(|(version, _)| *version > current)
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(available, entry)| FirmwareInfo {
+            current: current.clone(),
+            available,
+            entry: entry.clone(),
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_firmware_version() {
+        let version = "1.2.5 Build 171213 Rel.101523"
+            .parse::<FirmwareVersion>()
+            .unwrap();
+        assert_eq!(version.release(), &[1, 2, 5]);
+        assert_eq!(version.build(), Some(171213));
+        assert_eq!(version.rel(), Some(101523));
+        assert_eq!(version.to_string(), "1.2.5 Build 171213 Rel.101523");
+
+        assert!("not a version".parse::<FirmwareVersion>().is_err());
+    }
+
+    #[test]
+    fn test_update_available() {
+        let fw_list = vec![
+            String::from("1.2.5 Build 171213 Rel.101523"),
+            String::from("Firmware 1.5.4 Build 180815 Rel.121440 is available"),
+            String::from("1.2.6 Build 180101 Rel.100000"),
+        ];
+
+        let update = update_available("1.2.5 Build 171213 Rel.101523", &fw_list)
+            .unwrap()
+            .unwrap();
+        assert_eq!(update.available().release(), &[1, 5, 4]);
+
+        let update = update_available("1.5.4 Build 180815 Rel.121440", &fw_list).unwrap();
+        assert!(update.is_none());
     }
 }
@@ -1,11 +1,77 @@
+use crate::ack::Ack;
 use crate::error::Result;
 
 /// The `Device` trait represents devices that are capable of
-/// performing basic device commands.
+/// performing basic device commands and reporting their power state.
 pub trait Device {
     /// Turns on the device.
-    fn turn_on(&mut self) -> Result<()>;
+    fn turn_on(&mut self) -> Result<Ack>;
 
     /// Turns off the device.
-    fn turn_off(&mut self) -> Result<()>;
+    fn turn_off(&mut self) -> Result<Ack>;
+
+    /// Returns whether the device is currently switched on.
+    fn is_on(&self) -> Result<bool>;
+
+    /// Returns whether the device is currently switched on, bypassing
+    /// any cached response.
+    fn is_on_fresh(&self) -> Result<bool>;
+
+    /// Toggles the device and returns the power state it was switched to.
+    ///
+    /// The current state is read first and the opposite state is written
+    /// afterwards. The two steps are not atomic: if another client changes
+    /// the state in between, the write wins and the returned state is what
+    /// the device was last told to be. The current state is always read
+    /// from the device, bypassing any cached response, so that a change made
+    /// by another client since the last read is not undone.
+    fn toggle(&mut self) -> Result<bool> {
+        if self.is_on_fresh()? {
+            self.turn_off().map(|_| false)
+        } else {
+            self.turn_on().map(|_| true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A device whose cached state went stale after another client
+    /// switched it on.
+    struct Stale {
+        cached: bool,
+        on: bool,
+    }
+
+    impl Device for Stale {
+        fn turn_on(&mut self) -> Result<Ack> {
+            self.on = true;
+            Ok(Ack::default())
+        }
+
+        fn turn_off(&mut self) -> Result<Ack> {
+            self.on = false;
+            Ok(Ack::default())
+        }
+
+        fn is_on(&self) -> Result<bool> {
+            Ok(self.cached)
+        }
+
+        fn is_on_fresh(&self) -> Result<bool> {
+            Ok(self.on)
+        }
+    }
+
+    #[test]
+    fn test_toggle_reads_fresh_state() {
+        let mut device = Stale {
+            cached: false,
+            on: true,
+        };
+        assert!(!device.toggle().unwrap());
+        assert!(!device.on);
+    }
 }
@@ -1,115 +1,159 @@
-use crate::cache::ResponseCache;
-use crate::error::Result;
+use crate::ack::Ack;
+use crate::cache::{self, ResponseCache};
+use crate::energy::Tariff;
+use crate::error::{self, ErrorKind, Result};
 use crate::proto::{Proto, Request};
+use crate::schema;
+use crate::util;
+use crate::validate;
 
+#[cfg(feature = "chrono")]
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
+#[cfg(feature = "chrono")]
+use std::collections::BTreeMap;
+#[cfg(feature = "chrono")]
+use std::convert::TryFrom;
 use std::rc::Rc;
+use std::sync::Arc;
 
 pub trait Emeter {
-    fn get_emeter_realtime(&mut self) -> Result<RealtimeStats>;
-    fn get_emeter_month_stats(&mut self, year: u32) -> Result<MonthStats>;
-    fn get_emeter_day_stats(&mut self, month: u32, year: u32) -> Result<DayStats>;
-    fn erase_emeter_stats(&mut self) -> Result<()>;
+    fn get_emeter_realtime(&self) -> Result<RealtimeStats>;
+    fn get_emeter_month_stats(&self, year: u32) -> Result<MonthStats>;
+    fn get_emeter_day_stats(&self, month: u32, year: u32) -> Result<DayStats>;
+    fn erase_emeter_stats(&mut self) -> Result<Ack>;
+
+    /// Returns the monthly energy usage from the month `from` up to and
+    /// including the month `to`, both given as `(year, month)`, merged into
+    /// a single series in chronological order.
+    ///
+    /// The device reports monthly usage one year at a time, so a single
+    /// `get_monthstat` request is issued per calendar year in the range.
+    fn get_emeter_stats_range(&self, from: (u32, u32), to: (u32, u32)) -> Result<MonthStats> {
+        for &(_, month) in &[from, to] {
+            validate::month("get_emeter_stats_range", month)?;
+        }
+        if from > to {
+            return Err(error::invalid_parameter(
+                "get_emeter_stats_range: `from` is after `to`",
+            ));
+        }
+
+        let mut month_list = Vec::new();
+        for year in from.0..=to.0 {
+            let stats = self.get_emeter_month_stats(year)?;
+            month_list.extend(
+                stats.month_list.into_iter().filter(|stat| {
+                    (stat.year, stat.month) >= from && (stat.year, stat.month) <= to
+                }),
+            );
+        }
+        month_list.sort_by_key(|stat| (stat.year, stat.month));
+
+        Ok(MonthStats { month_list })
+    }
 }
 
+/// The energy meter of a device, within the given namespace. The meter of
+/// a child of the device, such as an outlet of a power strip, is addressed
+/// through the same namespace with the id of the child as context.
+#[derive(Clone)]
 pub(crate) struct EmeterStats {
     ns: String,
+    child_id: Option<String>,
     proto: Rc<Proto>,
-    cache: Rc<ResponseCache>,
+    cache: Arc<ResponseCache>,
 }
 
 impl EmeterStats {
-    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Rc<ResponseCache>) -> EmeterStats {
+    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Arc<ResponseCache>) -> EmeterStats {
         EmeterStats {
             ns: String::from(ns),
+            child_id: None,
             proto,
             cache,
         }
     }
 
+    /// Returns the energy meter of the child of the device with the given
+    /// id, sharing the connection and cache of the device.
+    pub(crate) fn for_child(&self, child_id: &str) -> EmeterStats {
+        EmeterStats {
+            child_id: Some(String::from(child_id)),
+            ..self.clone()
+        }
+    }
+
+    fn request(&self, command: &str, arg: Option<Value>) -> Request {
+        let request = Request::new(&self.ns, command, arg);
+        match &self.child_id {
+            Some(child_id) => request.with_child(child_id),
+            None => request,
+        }
+    }
+
     pub(crate) fn get_realtime(&self) -> Result<RealtimeStats> {
-        let request = Request::new(&self.ns, "get_realtime", None);
+        let request = self.request("get_realtime", None);
 
-        let response = if let Some(cache) = self.cache.as_ref() {
-            cache
-                .borrow_mut()
-                .try_get_or_insert_with(request, |r| self.proto.send_request(r))?
-        } else {
-            self.proto.send_request(&request)?
-        };
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
 
         log::trace!("({}) {:?}", self.ns, response);
 
-        Ok(serde_json::from_value(response).unwrap_or_else(|err| {
-            panic!(
-                "invalid response from host with address {}: {}",
-                self.proto.host(),
-                err
-            )
-        }))
+        schema::parse(self.proto.host(), response)
     }
 
     pub(crate) fn get_day_stats(&self, month: u32, year: u32) -> Result<DayStats> {
-        let request = Request::new(
-            &self.ns,
-            "get_daystat",
-            Some(json!({ "month": month , "year": year})),
-        );
+        let request = self.request("get_daystat", Some(json!({ "month": month , "year": year})));
 
-        let response = if let Some(cache) = self.cache.as_ref() {
-            cache
-                .borrow_mut()
-                .try_get_or_insert_with(request, |r| self.proto.send_request(r))?
-        } else {
-            self.proto.send_request(&request)?
-        };
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
 
         log::trace!("({}) {:?}", self.ns, response);
 
-        Ok(serde_json::from_value(response).unwrap_or_else(|err| {
-            panic!(
-                "invalid response from host with address {}: {}",
-                self.proto.host(),
-                err
-            )
-        }))
+        schema::parse(self.proto.host(), response)
     }
 
     pub(crate) fn get_month_stats(&self, year: u32) -> Result<MonthStats> {
-        let request = Request::new(&self.ns, "get_monthstat", Some(json!({ "year": year })));
+        let request = self.request("get_monthstat", Some(json!({ "year": year })));
 
-        let response = if let Some(cache) = self.cache.as_ref() {
-            cache
-                .borrow_mut()
-                .try_get_or_insert_with(request, |r| self.proto.send_request(r))?
-        } else {
-            self.proto.send_request(&request)?
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        schema::parse(self.proto.host(), response)
+    }
+
+    /// Returns whether the device implements the emeter namespace, by
+    /// asking for a realtime reading. Devices without an energy meter
+    /// respond with an error in place of the reading.
+    pub(crate) fn probe(&self) -> Result<bool> {
+        let response = match self.proto.send_request(&self.request("get_realtime", None)) {
+            Err(e) if matches!(e.kind(), ErrorKind::UnsupportedNamespace { .. }) => {
+                return Ok(false)
+            }
+            response => response?,
         };
 
         log::trace!("({}) {:?}", self.ns, response);
 
-        Ok(serde_json::from_value(response).unwrap_or_else(|err| {
-            panic!(
-                "invalid response from host with address {}: {}",
-                self.proto.host(),
-                err
-            )
-        }))
+        Ok(response.is_object() && response["err_code"].as_i64().unwrap_or(0) == 0)
     }
 
-    pub(crate) fn erase_stats(&self) -> Result<()> {
+    pub(crate) fn erase_stats(&self) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns)
+            cache
+                .lock()
+                .unwrap()
+                .retain(|k, _| k.target != self.ns || k.child_id != self.child_id)
         }
 
-        let response =
-            self.proto
-                .send_request(&Request::new(&self.ns, "erase_emeter_stat", None))?;
+        let response = self
+            .proto
+            .send_request(&self.request("erase_emeter_stat", None))?;
 
         log::debug!("{:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 }
 
@@ -119,27 +163,542 @@ pub struct RealtimeStats {
     stats: Map<String, Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl RealtimeStats {
+    /// Returns the power draw, in W.
+    ///
+    /// Firmware reports it either in W (`power`) or in mW (`power_mw`).
+    pub fn power_w(&self) -> Option<f64> {
+        self.stats.get("power").and_then(Value::as_f64).or_else(|| {
+            self.stats
+                .get("power_mw")
+                .and_then(Value::as_f64)
+                .map(|mw| mw / 1000.0)
+        })
+    }
+
+    /// Returns the energy counted by the meter since it was last reset, in
+    /// Wh.
+    ///
+    /// Firmware reports it either in kWh (`total`) or in Wh (`total_wh`).
+    pub fn total_wh(&self) -> Option<f64> {
+        self.stats
+            .get("total_wh")
+            .and_then(Value::as_f64)
+            .or_else(|| {
+                self.stats
+                    .get("total")
+                    .and_then(Value::as_f64)
+                    .map(|kwh| kwh * 1000.0)
+            })
+    }
+
+    /// Returns the energy used between the `previous` reading and this one.
+    ///
+    /// The energy counter restarts from zero when the stats are erased, and
+    /// on some firmware when the device reboots. A counter lower than the
+    /// previous one is taken to have restarted, and all the energy it
+    /// counted since to have been used after the previous reading.
+    ///
+    /// Returns `None` if either reading lacks the energy counter, as some
+    /// firmware omits it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let previous = plug.get_emeter_realtime()?;
+    /// thread::sleep(Duration::from_secs(60));
+    /// if let Some(delta) = plug.get_emeter_realtime()?.delta(&previous) {
+    ///     println!("used {:.1} Wh in the last minute", delta.energy_wh());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delta(&self, previous: &RealtimeStats) -> Option<EnergyDelta> {
+        let total = self.total_wh()?;
+        let previous = previous.total_wh()?;
+        if total >= previous {
+            Some(EnergyDelta {
+                energy_wh: total - previous,
+                reset: false,
+            })
+        } else {
+            Some(EnergyDelta {
+                energy_wh: total,
+                reset: true,
+            })
+        }
+    }
+}
+
+/// The energy used between two realtime readings, see
+/// [`RealtimeStats::delta`].
+///
+/// [`RealtimeStats::delta`]: struct.RealtimeStats.html#method.delta
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct EnergyDelta {
+    energy_wh: f64,
+    reset: bool,
+}
+
+impl EnergyDelta {
+    /// Returns the energy used, in Wh.
+    pub fn energy_wh(&self) -> f64 {
+        self.energy_wh
+    }
+
+    /// Returns whether the energy counter restarted between the readings,
+    /// in which case the energy used before the restart is not accounted
+    /// for.
+    pub fn is_reset(&self) -> bool {
+        self.reset
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DayStats {
     day_list: Vec<DayStat>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct DayStat {
+impl DayStats {
+    pub fn len(&self) -> usize {
+        self.day_list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.day_list.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, DayStat> {
+        self.day_list.iter()
+    }
+
+    /// Returns the total energy usage over all the days, in Wh.
+    pub fn energy_wh(&self) -> u64 {
+        self.day_list
+            .iter()
+            .map(|stat| u64::from(stat.energy_wh))
+            .sum()
+    }
+
+    /// Returns the cost of the energy used over all the days.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::UnsupportedOperation` for a time-of-use
+    /// tariff, since the stats do not say at what time of day the energy
+    /// was used.
+    pub fn cost(&self, tariff: &Tariff) -> Result<f64> {
+        tariff.cost(self.energy_wh())
+    }
+
+    /// Returns the stats in chronological order, with a 0 Wh entry for
+    /// every day between the first and the last one reported that the
+    /// device left out. Devices omit the days they used no energy on.
+    pub fn with_gaps_filled(mut self) -> DayStats {
+        self.day_list
+            .sort_by_key(|stat| (stat.year, stat.month, stat.day));
+        let mut day_list: Vec<DayStat> = Vec::with_capacity(self.day_list.len());
+        for stat in self.day_list {
+            while let Some(next) = day_list.last().map(DayStat::next) {
+                if (next.year, next.month, next.day) >= (stat.year, stat.month, stat.day) {
+                    break;
+                }
+                day_list.push(next);
+            }
+            day_list.push(stat);
+        }
+        DayStats { day_list }
+    }
+
+    /// Returns the energy usage of each day, in Wh, by date, with the days
+    /// the device left out filled in as in [`with_gaps_filled`].
+    ///
+    /// [`with_gaps_filled`]: #method.with_gaps_filled
+    #[cfg(feature = "chrono")]
+    pub fn by_date(&self) -> BTreeMap<NaiveDate, u32> {
+        self.clone()
+            .with_gaps_filled()
+            .day_list
+            .into_iter()
+            .filter_map(|stat| stat.date().map(|date| (date, stat.energy_wh)))
+            .collect()
+    }
+}
+
+/// The energy usage of a single day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayStat {
     energy_wh: u32,
     day: u32,
     month: u32,
     year: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl DayStat {
+    pub fn energy_wh(&self) -> u32 {
+        self.energy_wh
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn year(&self) -> u32 {
+        self.year
+    }
+
+    /// Returns the date of the day, if it is a valid one.
+    #[cfg(feature = "chrono")]
+    pub fn date(&self) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(i32::try_from(self.year).ok()?, self.month, self.day)
+    }
+
+    /// Returns an empty entry for the day after this one.
+    fn next(&self) -> DayStat {
+        let (year, month, day) = if self.day < util::days_in_month(self.year as i32, self.month) {
+            (self.year, self.month, self.day + 1)
+        } else if self.month < 12 {
+            (self.year, self.month + 1, 1)
+        } else {
+            (self.year + 1, 1, 1)
+        };
+        DayStat {
+            energy_wh: 0,
+            day,
+            month,
+            year,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MonthStats {
     month_list: Vec<MonthStat>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MonthStat {
+impl MonthStats {
+    pub fn len(&self) -> usize {
+        self.month_list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.month_list.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, MonthStat> {
+        self.month_list.iter()
+    }
+
+    /// Returns the total energy usage over all the months, in Wh.
+    pub fn energy_wh(&self) -> u64 {
+        self.month_list
+            .iter()
+            .map(|stat| u64::from(stat.energy_wh))
+            .sum()
+    }
+
+    /// Returns the cost of the energy used over all the months.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::UnsupportedOperation` for a time-of-use
+    /// tariff, since the stats do not say at what time of day the energy
+    /// was used.
+    pub fn cost(&self, tariff: &Tariff) -> Result<f64> {
+        tariff.cost(self.energy_wh())
+    }
+
+    /// Returns the stats in chronological order, with a 0 Wh entry for
+    /// every month between the first and the last one reported that the
+    /// device left out. Devices omit the months they used no energy in.
+    pub fn with_gaps_filled(mut self) -> MonthStats {
+        self.month_list.sort_by_key(|stat| (stat.year, stat.month));
+        let mut month_list: Vec<MonthStat> = Vec::with_capacity(self.month_list.len());
+        for stat in self.month_list {
+            while let Some(next) = month_list.last().map(MonthStat::next) {
+                if (next.year, next.month) >= (stat.year, stat.month) {
+                    break;
+                }
+                month_list.push(next);
+            }
+            month_list.push(stat);
+        }
+        MonthStats { month_list }
+    }
+
+    /// Returns the energy usage of each month, in Wh, by the date of its
+    /// first day, with the months the device left out filled in as in
+    /// [`with_gaps_filled`].
+    ///
+    /// [`with_gaps_filled`]: #method.with_gaps_filled
+    #[cfg(feature = "chrono")]
+    pub fn by_date(&self) -> BTreeMap<NaiveDate, u32> {
+        self.clone()
+            .with_gaps_filled()
+            .month_list
+            .into_iter()
+            .filter_map(|stat| stat.date().map(|date| (date, stat.energy_wh)))
+            .collect()
+    }
+}
+
+/// The energy usage of a single month.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonthStat {
     energy_wh: u32,
     month: u32,
     year: u32,
 }
+
+impl MonthStat {
+    pub fn energy_wh(&self) -> u32 {
+        self.energy_wh
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn year(&self) -> u32 {
+        self.year
+    }
+
+    /// Returns the date of the first day of the month, if it is a valid
+    /// one.
+    #[cfg(feature = "chrono")]
+    pub fn date(&self) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(i32::try_from(self.year).ok()?, self.month, 1)
+    }
+
+    /// Returns an empty entry for the month after this one.
+    fn next(&self) -> MonthStat {
+        let (year, month) = if self.month < 12 {
+            (self.year, self.month + 1)
+        } else {
+            (self.year + 1, 1)
+        };
+        MonthStat {
+            energy_wh: 0,
+            month,
+            year,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use crate::crypto;
+    use std::net::UdpSocket;
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    struct Meter;
+
+    impl Emeter for Meter {
+        fn get_emeter_realtime(&self) -> Result<RealtimeStats> {
+            unreachable!("not used by this test")
+        }
+
+        fn get_emeter_month_stats(&self, year: u32) -> Result<MonthStats> {
+            let month_list = (1..=12)
+                .rev()
+                .map(|month| MonthStat {
+                    energy_wh: year * 100 + month,
+                    month,
+                    year,
+                })
+                .collect();
+            Ok(MonthStats { month_list })
+        }
+
+        fn get_emeter_day_stats(&self, _month: u32, _year: u32) -> Result<DayStats> {
+            unreachable!("not used by this test")
+        }
+
+        fn erase_emeter_stats(&mut self) -> Result<Ack> {
+            unreachable!("not used by this test")
+        }
+    }
+
+    #[test]
+    fn test_stats_range_across_years() {
+        let stats = Meter.get_emeter_stats_range((2019, 11), (2020, 2)).unwrap();
+        let months = stats
+            .iter()
+            .map(|stat| (stat.year(), stat.month()))
+            .collect::<Vec<_>>();
+        assert_eq!(months, [(2019, 11), (2019, 12), (2020, 1), (2020, 2)]);
+        assert_eq!(stats.energy_wh(), 201_911 + 201_912 + 202_001 + 202_002);
+    }
+
+    #[test]
+    fn test_stats_range_invalid() {
+        assert!(Meter.get_emeter_stats_range((2020, 2), (2019, 11)).is_err());
+        assert!(Meter.get_emeter_stats_range((2020, 0), (2020, 2)).is_err());
+    }
+
+    #[test]
+    fn test_child_context() {
+        let proto = Rc::new(crate::proto::Builder::new(([127, 0, 0, 1], 9999)).build());
+        let emeter = EmeterStats::new("emeter", proto, Arc::new(None));
+        let outlet = emeter.for_child("8006...01");
+
+        assert_eq!(emeter.request("get_realtime", None).child_id, None);
+        let request = outlet.request("get_realtime", None);
+        assert_eq!(request.child_id.as_deref(), Some("8006...01"));
+        assert_ne!(request, emeter.request("get_realtime", None));
+    }
+
+    fn day(year: u32, month: u32, day: u32, energy_wh: u32) -> DayStat {
+        DayStat {
+            energy_wh,
+            day,
+            month,
+            year,
+        }
+    }
+
+    #[test]
+    fn test_gaps_filled() {
+        let days = DayStats {
+            day_list: vec![day(2021, 3, 2, 5), day(2021, 2, 27, 7)],
+        }
+        .with_gaps_filled();
+        let dates: Vec<_> = days
+            .iter()
+            .map(|stat| (stat.month(), stat.day(), stat.energy_wh()))
+            .collect();
+        assert_eq!(dates, [(2, 27, 7), (2, 28, 0), (3, 1, 0), (3, 2, 5)]);
+
+        let months = MonthStats {
+            month_list: vec![
+                MonthStat {
+                    energy_wh: 3,
+                    month: 2,
+                    year: 2021,
+                },
+                MonthStat {
+                    energy_wh: 4,
+                    month: 11,
+                    year: 2020,
+                },
+            ],
+        }
+        .with_gaps_filled();
+        let dates: Vec<_> = months
+            .iter()
+            .map(|stat| (stat.year(), stat.month(), stat.energy_wh()))
+            .collect();
+        assert_eq!(
+            dates,
+            [(2020, 11, 4), (2020, 12, 0), (2021, 1, 0), (2021, 2, 3)]
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_by_date() {
+        let days = DayStats {
+            day_list: vec![day(2020, 12, 31, 5), day(2021, 1, 2, 7)],
+        };
+        let by_date = days.by_date();
+        assert_eq!(by_date.len(), 3);
+        assert_eq!(by_date[&NaiveDate::from_ymd_opt(2021, 1, 1).unwrap()], 0);
+        assert_eq!(by_date[&NaiveDate::from_ymd_opt(2021, 1, 2).unwrap()], 7);
+    }
+
+    #[test]
+    fn test_delta() {
+        let reading = |stats| serde_json::from_value::<RealtimeStats>(stats).unwrap();
+        let v1 = reading(json!({ "power": 12.5, "total": 1.25, "err_code": 0 }));
+        let v2 = reading(json!({ "power_mw": 12500, "total_wh": 1300, "err_code": 0 }));
+        assert_eq!(v1.power_w(), Some(12.5));
+        assert_eq!(v2.power_w(), Some(12.5));
+        assert_eq!(v1.total_wh(), Some(1250.0));
+
+        let delta = v2.delta(&v1).unwrap();
+        assert_eq!(delta.energy_wh(), 50.0);
+        assert!(!delta.is_reset());
+
+        let delta = reading(json!({ "total_wh": 20 })).delta(&v2).unwrap();
+        assert_eq!(delta.energy_wh(), 20.0);
+        assert!(delta.is_reset());
+
+        let v3 = reading(json!({ "power_mw": 12500, "err_code": 0 }));
+        assert_eq!(v3.delta(&v2), None);
+        assert_eq!(v2.delta(&v3), None);
+    }
+
+    #[test]
+    fn test_stats_range_through_cache() {
+        struct Device(EmeterStats);
+
+        impl Emeter for Device {
+            fn get_emeter_realtime(&self) -> Result<RealtimeStats> {
+                unreachable!("not used by this test")
+            }
+
+            fn get_emeter_month_stats(&self, year: u32) -> Result<MonthStats> {
+                self.0.get_month_stats(year)
+            }
+
+            fn get_emeter_day_stats(&self, _month: u32, _year: u32) -> Result<DayStats> {
+                unreachable!("not used by this test")
+            }
+
+            fn erase_emeter_stats(&mut self) -> Result<Ack> {
+                unreachable!("not used by this test")
+            }
+        }
+
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        device
+            .set_read_timeout(Some(Duration::from_secs(3)))
+            .unwrap();
+        let addr = device.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            for _ in 0..2 {
+                let (recv, from) = device.recv_from(&mut buf).unwrap();
+                let request: Value =
+                    serde_json::from_slice(&crypto::decrypt(&buf[..recv])).unwrap();
+                let year = request["emeter"]["get_monthstat"]["year"].as_u64().unwrap();
+                let month_list: Vec<_> = (1..=12)
+                    .map(|month| json!({ "year": year, "month": month, "energy_wh": month }))
+                    .collect();
+                let response =
+                    json!({ "emeter": { "get_monthstat": { "month_list": month_list } } });
+                let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+                device.send_to(&response, from).unwrap();
+            }
+        });
+
+        let proto = Rc::new(
+            crate::proto::Builder::new(addr)
+                .read_timeout(Duration::from_secs(3))
+                .build(),
+        );
+        let cache = Arc::new(Some(Mutex::new(Cache::with_ttl(Duration::from_secs(60)))));
+        let meter = Device(EmeterStats::new("emeter", proto, cache));
+
+        let stats = meter.get_emeter_stats_range((2019, 11), (2020, 2)).unwrap();
+        handle.join().unwrap();
+        let months: Vec<_> = stats
+            .iter()
+            .map(|stat| (stat.year(), stat.month()))
+            .collect();
+        assert_eq!(months, [(2019, 11), (2019, 12), (2020, 1), (2020, 2)]);
+    }
+}
@@ -5,3 +5,180 @@ pub mod sys;
 pub mod sysinfo;
 pub mod time;
 pub mod wlan;
+
+/// Implements the device traits for a device wrapper (e.g. `Plug<T>`) by
+/// delegating to its `device`, so the wrappers can be used in code that is
+/// generic over the traits. Type parameters of the wrapper after the device
+/// (e.g. `C` in `Bulb<T, C>`) are passed after its name.
+macro_rules! impl_device_traits {
+    ($wrapper:ident $(, $param:ident)*) => {
+        impl<T: crate::device::Device $(, $param)*> crate::device::Device for $wrapper<T $(, $param)*> {
+            fn turn_on(&mut self) -> crate::error::Result<crate::ack::Ack> {
+                self.device.turn_on()
+            }
+
+            fn turn_off(&mut self) -> crate::error::Result<crate::ack::Ack> {
+                self.device.turn_off()
+            }
+
+            fn is_on(&self) -> crate::error::Result<bool> {
+                self.device.is_on()
+            }
+
+            fn is_on_fresh(&self) -> crate::error::Result<bool> {
+                self.device.is_on_fresh()
+            }
+
+            fn toggle(&mut self) -> crate::error::Result<bool> {
+                self.device.toggle()
+            }
+        }
+
+        impl<T: crate::sys::Sys $(, $param)*> crate::sys::Sys for $wrapper<T $(, $param)*> {
+            fn reboot(
+                &mut self,
+                delay: Option<std::time::Duration>,
+            ) -> crate::error::Result<crate::ack::Ack> {
+                self.device.reboot(delay)
+            }
+
+            fn factory_reset(
+                &mut self,
+                delay: Option<std::time::Duration>,
+            ) -> crate::error::Result<crate::ack::Ack> {
+                self.device.factory_reset(delay)
+            }
+
+            fn factory_reset_confirmed(
+                &mut self,
+                confirmation: &str,
+                delay: Option<std::time::Duration>,
+            ) -> crate::error::Result<crate::ack::Ack> {
+                self.device.factory_reset_confirmed(confirmation, delay)
+            }
+        }
+
+        impl<T: crate::sysinfo::SysInfo $(, $param)*> crate::sysinfo::SysInfo for $wrapper<T $(, $param)*> {
+            type Info = T::Info;
+
+            fn sysinfo(&self) -> crate::error::Result<Self::Info> {
+                self.device.sysinfo()
+            }
+
+            fn sysinfo_fresh(&self) -> crate::error::Result<Self::Info> {
+                self.device.sysinfo_fresh()
+            }
+        }
+
+        impl<T: crate::time::Time $(, $param)*> crate::time::Time for $wrapper<T $(, $param)*> {
+            fn time(&self) -> crate::error::Result<crate::time::DeviceTime> {
+                self.device.time()
+            }
+
+            fn timezone(&self) -> crate::error::Result<crate::time::DeviceTimeZone> {
+                self.device.timezone()
+            }
+        }
+
+        impl<T: crate::cloud::Cloud $(, $param)*> crate::cloud::Cloud for $wrapper<T $(, $param)*> {
+            fn get_cloud_info(&self) -> crate::error::Result<crate::cloud::CloudInfo> {
+                self.device.get_cloud_info()
+            }
+
+            fn bind(
+                &mut self,
+                username: &str,
+                password: &str,
+            ) -> crate::error::Result<crate::ack::Ack> {
+                self.device.bind(username, password)
+            }
+
+            fn unbind(&mut self) -> crate::error::Result<crate::ack::Ack> {
+                self.device.unbind()
+            }
+
+            fn get_firmware_list(&self) -> crate::error::Result<Vec<String>> {
+                self.device.get_firmware_list()
+            }
+
+            fn set_server_url(&mut self, url: &str) -> crate::error::Result<crate::ack::Ack> {
+                self.device.set_server_url(url)
+            }
+        }
+
+        impl<T: crate::wlan::Wlan $(, $param)*> crate::wlan::Wlan for $wrapper<T $(, $param)*> {
+            fn get_scan_info(
+                &self,
+                refresh: bool,
+                timeout: Option<std::time::Duration>,
+            ) -> crate::error::Result<Vec<crate::wlan::AccessPoint>> {
+                self.device.get_scan_info(refresh, timeout)
+            }
+        }
+
+        impl<T: crate::emeter::Emeter $(, $param)*> crate::emeter::Emeter for $wrapper<T $(, $param)*> {
+            fn get_emeter_realtime(&self) -> crate::error::Result<crate::emeter::RealtimeStats> {
+                self.device.get_emeter_realtime()
+            }
+
+            fn get_emeter_month_stats(
+                &self,
+                year: u32,
+            ) -> crate::error::Result<crate::emeter::MonthStats> {
+                self.device.get_emeter_month_stats(year)
+            }
+
+            fn get_emeter_day_stats(
+                &self,
+                month: u32,
+                year: u32,
+            ) -> crate::error::Result<crate::emeter::DayStats> {
+                self.device.get_emeter_day_stats(month, year)
+            }
+
+            fn erase_emeter_stats(&mut self) -> crate::error::Result<crate::ack::Ack> {
+                self.device.erase_emeter_stats()
+            }
+
+            fn get_emeter_stats_range(
+                &self,
+                from: (u32, u32),
+                to: (u32, u32),
+            ) -> crate::error::Result<crate::emeter::MonthStats> {
+                self.device.get_emeter_stats_range(from, to)
+            }
+        }
+
+        impl<T: crate::timer::Timer $(, $param)*> crate::timer::Timer for $wrapper<T $(, $param)*> {
+            fn get_timer_rules(&self) -> crate::error::Result<crate::timer::RuleList> {
+                self.device.get_timer_rules()
+            }
+
+            fn add_timer_rule(
+                &mut self,
+                rule: crate::timer::Rule,
+            ) -> crate::error::Result<crate::timer::RuleId> {
+                self.device.add_timer_rule(rule)
+            }
+
+            fn edit_timer_rule(
+                &mut self,
+                id: &crate::timer::RuleId,
+                rule: crate::timer::Rule,
+            ) -> crate::error::Result<crate::ack::Ack> {
+                self.device.edit_timer_rule(id, rule)
+            }
+
+            fn delete_timer_rule_with_id(
+                &mut self,
+                id: &crate::timer::RuleId,
+            ) -> crate::error::Result<crate::ack::Ack> {
+                self.device.delete_timer_rule_with_id(id)
+            }
+
+            fn delete_all_timer_rules(&mut self) -> crate::error::Result<crate::ack::Ack> {
+                self.device.delete_all_timer_rules()
+            }
+        }
+    };
+}
@@ -1,9 +1,11 @@
-use crate::cache::ResponseCache;
-use crate::error::Result;
-use crate::proto::{Proto, Request};
+use crate::ack::Ack;
+use crate::cache::{self, ResponseCache};
+use crate::error::{self, Result};
+use crate::proto::{Command, Proto, Request};
 
 use serde_json::json;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// The `Sys` trait represents devices that are capable of performing
@@ -12,33 +14,54 @@ pub trait Sys {
     /// Reboots the device after the given duration. In case when the duration
     /// isn't provided, the device is set to reboot after a default duration
     /// of 1 second.
-    fn reboot(&mut self, delay: Option<Duration>) -> Result<()>;
+    fn reboot(&mut self, delay: Option<Duration>) -> Result<Ack>;
 
     /// Factory resets the device after the given duration. In case when the
     /// duration isn't provided, the device is set to reset after a default duration
     /// of 1 second.
-    fn factory_reset(&mut self, delay: Option<Duration>) -> Result<()>;
+    fn factory_reset(&mut self, delay: Option<Duration>) -> Result<Ack>;
+
+    /// Factory resets the device after the given duration, like
+    /// `factory_reset`, once the given confirmation matches either the
+    /// alias or the id of the device.
+    ///
+    /// This is the only way to reset a device whose config requires reset
+    /// confirmation, see [`ConfigBuilder::with_reset_confirmation`].
+    ///
+    /// [`ConfigBuilder::with_reset_confirmation`]: ../struct.ConfigBuilder.html#method.with_reset_confirmation
+    fn factory_reset_confirmed(
+        &mut self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack>;
 }
 
+#[derive(Clone)]
 pub(crate) struct System {
     ns: String,
     proto: Rc<Proto>,
-    cache: Rc<ResponseCache>,
+    cache: Arc<ResponseCache>,
+    reset_confirmation: bool,
 }
 
 impl System {
-    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Rc<ResponseCache>) -> System {
+    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Arc<ResponseCache>) -> System {
         System {
             ns: String::from(ns),
             proto,
             cache,
+            reset_confirmation: false,
         }
     }
 
-    pub(crate) fn reboot(&self, delay: Option<Duration>) -> Result<()> {
+    pub(crate) fn set_reset_confirmation(&mut self, required: bool) {
+        self.reset_confirmation = required;
+    }
+
+    pub(crate) fn reboot(&self, delay: Option<Duration>) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
             log::trace!("({}) {:?}", self.ns, cache);
-            cache.borrow_mut().clear();
+            cache.lock().unwrap().clear();
         }
 
         let delay_in_secs = delay.map_or(1, |duration| duration.as_secs());
@@ -51,13 +74,45 @@ impl System {
 
         log::trace!("({}) {:?}", self.ns, response);
 
-        Ok(())
+        Ack::from_response(response)
+    }
+
+    pub(crate) fn reset(&self, delay: Option<Duration>) -> Result<Ack> {
+        if self.reset_confirmation {
+            return Err(error::confirmation_required(&format!(
+                "factory_reset of device at {}",
+                self.proto.host()
+            )));
+        }
+        self.send_reset(delay)
+    }
+
+    pub(crate) fn reset_confirmed(
+        &self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack> {
+        // Read the identity afresh, since the alias may have been changed
+        // since it was cached.
+        let sysinfo =
+            cache::send_and_cache(&self.cache, &self.proto, Request::from(Command::GetSysinfo))?;
+        let matches = ["alias", "deviceId"]
+            .iter()
+            .any(|key| sysinfo[*key].as_str() == Some(confirmation));
+        if !matches {
+            return Err(error::confirmation_required(&format!(
+                "factory_reset of device at {}: {:?} matches neither its alias nor its id",
+                self.proto.host(),
+                confirmation
+            )));
+        }
+        self.send_reset(delay)
     }
 
-    pub(crate) fn reset(&self, delay: Option<Duration>) -> Result<()> {
+    fn send_reset(&self, delay: Option<Duration>) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
             log::trace!("({}) {:?}", self.ns, cache);
-            cache.borrow_mut().clear();
+            cache.lock().unwrap().clear();
         }
 
         let delay_in_secs = delay.map_or(1, |duration| duration.as_secs());
@@ -70,6 +125,6 @@ impl System {
 
         log::trace!("({}) {:?}", self.ns, response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 }
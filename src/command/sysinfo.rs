@@ -1,10 +1,17 @@
-use crate::cache::ResponseCache;
-use crate::error::Result;
-use crate::proto::{Proto, Request};
+use crate::cache::{self, ResponseCache};
+use crate::error::{self, Error, Result};
+use crate::proto::{Command, Proto, Request};
+use crate::schema;
 
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+use std::fmt;
 use std::marker::PhantomData;
+use std::ops::BitOr;
 use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
 
 /// The `SysInfo` trait represents devices that are capable of
 /// returning their system information.
@@ -13,17 +20,32 @@ pub trait SysInfo {
     type Info;
 
     /// Attempts to fetch the system information from the device.
-    fn sysinfo(&mut self) -> Result<Self::Info>;
+    fn sysinfo(&self) -> Result<Self::Info>;
+
+    /// Attempts to fetch the system information from the device,
+    /// bypassing any cached response.
+    fn sysinfo_fresh(&self) -> Result<Self::Info>;
 }
 
 pub(crate) struct SystemInfo<T> {
     proto: Rc<Proto>,
-    cache: Rc<ResponseCache>,
+    cache: Arc<ResponseCache>,
     _ghost: PhantomData<T>,
 }
 
+// Implemented manually, since deriving would require `T: Clone`.
+impl<T> Clone for SystemInfo<T> {
+    fn clone(&self) -> Self {
+        SystemInfo {
+            proto: self.proto.clone(),
+            cache: self.cache.clone(),
+            _ghost: PhantomData,
+        }
+    }
+}
+
 impl<T> SystemInfo<T> {
-    pub(crate) fn new(proto: Rc<Proto>, cache: Rc<ResponseCache>) -> SystemInfo<T> {
+    pub(crate) fn new(proto: Rc<Proto>, cache: Arc<ResponseCache>) -> SystemInfo<T> {
         SystemInfo {
             proto,
             cache,
@@ -34,24 +56,510 @@ impl<T> SystemInfo<T> {
 
 impl<T: DeserializeOwned> SystemInfo<T> {
     pub(crate) fn get_sysinfo(&self) -> Result<T> {
-        let request = Request::new("system", "get_sysinfo", None);
+        let request = Request::from(Command::GetSysinfo);
 
-        let response = if let Some(cache) = self.cache.as_ref() {
-            cache
-                .borrow_mut()
-                .try_get_or_insert_with(request, |r| self.proto.send_request(r))?
-        } else {
-            self.proto.send_request(&request)?
-        };
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
+
+        self.parse(response)
+    }
 
+    pub(crate) fn get_sysinfo_fresh(&self) -> Result<T> {
+        let request = Request::from(Command::GetSysinfo);
+
+        let response = cache::send_and_cache(&self.cache, &self.proto, request)?;
+
+        self.parse(response)
+    }
+
+    fn parse(&self, response: Value) -> Result<T> {
         log::trace!("(system) {:?}", response);
 
-        Ok(serde_json::from_value(response).unwrap_or_else(|err| {
-            panic!(
-                "invalid response from host with address {}: {}",
-                self.proto.host(),
-                err
+        schema::parse(self.proto.host(), response)
+    }
+}
+
+/// Writes the given fields of a system information, one per line, with
+/// their values aligned.
+pub(crate) fn write_fields(
+    f: &mut fmt::Formatter,
+    fields: &[(&str, &dyn fmt::Display)],
+) -> fmt::Result {
+    let width = fields
+        .iter()
+        .map(|(key, _)| key.len() + 1)
+        .max()
+        .unwrap_or(0);
+    for (i, (key, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "{:width$} {}", format!("{}:", key), value, width = width)?;
+    }
+    Ok(())
+}
+
+/// Returns "on" or "off".
+pub(crate) fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// The set of features a device advertises in the `feature` field of its
+/// system information, e.g. `"TIM:ENE"`.
+///
+/// # Examples
+///
+/// ```
+/// use tplink::sysinfo::FeatureFlags;
+///
+/// let features = FeatureFlags::parse("TIM:ENE");
+/// assert!(features.contains(FeatureFlags::TIMER | FeatureFlags::ENERGY));
+/// assert_eq!(features.to_string(), "TIM:ENE");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FeatureFlags(u32);
+
+impl FeatureFlags {
+    /// Countdown timer rules (`TIM`).
+    pub const TIMER: FeatureFlags = FeatureFlags(1);
+    /// Energy monitoring (`ENE`).
+    pub const ENERGY: FeatureFlags = FeatureFlags(1 << 1);
+
+    const CODES: &'static [(&'static str, FeatureFlags)] =
+        &[("TIM", FeatureFlags::TIMER), ("ENE", FeatureFlags::ENERGY)];
+
+    /// Returns an empty set of features.
+    pub fn empty() -> FeatureFlags {
+        FeatureFlags(0)
+    }
+
+    /// Parses a colon-separated feature string. Unknown feature codes are
+    /// ignored.
+    pub fn parse(feature: &str) -> FeatureFlags {
+        feature.split(':').filter(|code| !code.is_empty()).fold(
+            FeatureFlags::empty(),
+            |flags, code| match FeatureFlags::CODES.iter().find(|(c, _)| *c == code) {
+                Some((_, flag)) => flags | *flag,
+                None => {
+                    log::debug!("ignoring unknown feature code {}", code);
+                    flags
+                }
+            },
+        )
+    }
+
+    /// Returns whether all the features in `other` are set.
+    pub fn contains(self, other: FeatureFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether no features are set.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for FeatureFlags {
+    type Output = FeatureFlags;
+
+    fn bitor(self, rhs: FeatureFlags) -> FeatureFlags {
+        FeatureFlags(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for FeatureFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let codes = FeatureFlags::CODES
+            .iter()
+            .filter(|(_, flag)| self.contains(*flag))
+            .map(|(code, _)| *code)
+            .collect::<Vec<_>>();
+        f.write_str(&codes.join(":"))
+    }
+}
+
+impl Serialize for FeatureFlags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FeatureFlags {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|feature| FeatureFlags::parse(&feature))
+    }
+}
+
+/// The mac address of a device, formatted as `AA:BB:CC:DD:EE:FF`.
+///
+/// Devices report mac addresses in different formats, e.g. bulbs report
+/// `50C7BF000006` where plugs report `50:C7:BF:00:00:01`. Either is parsed
+/// into the same mac address.
+///
+/// # Examples
+///
+/// ```
+/// use tplink::sysinfo::MacAddress;
+///
+/// let mac = "50c7bf000006".parse::<MacAddress>()?;
+/// assert_eq!(mac.to_string(), "50:C7:BF:00:00:06");
+/// assert_eq!(mac, "50-C7-BF-00-00-06".parse()?);
+/// assert!("50:C7:BF".parse::<MacAddress>().is_err());
+/// # Ok::<(), tplink::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MacAddress(String);
+
+impl MacAddress {
+    /// Returns the mac address as `AA:BB:CC:DD:EE:FF`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub(crate) fn normalize(mac: &str) -> Option<MacAddress> {
+        let digits = mac
+            .chars()
+            .filter(|c| !matches!(c, ':' | '-' | '.'))
+            .map(|c| c.to_ascii_uppercase())
+            .collect::<Vec<_>>();
+        if digits.len() != 12 || !digits.iter().all(char::is_ascii_hexdigit) {
+            return None;
+        }
+        let pairs = digits
+            .chunks(2)
+            .map(|pair| pair.iter().collect::<String>())
+            .collect::<Vec<_>>();
+        Some(MacAddress(pairs.join(":")))
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<MacAddress> {
+        MacAddress::normalize(s)
+            .ok_or_else(|| error::invalid_parameter(&format!("mac address: {}", s)))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for MacAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// Deserializing is lenient: a mac address the device reports in an
+/// unknown format is kept as reported, rather than failing to parse the
+/// whole system information.
+impl<'de> Deserialize<'de> for MacAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)
+            .map(|mac| MacAddress::normalize(&mac).unwrap_or(MacAddress(mac)))
+    }
+}
+
+/// The system information common to all TP-Link devices, as parsed by
+/// [`parse_sysinfo`].
+///
+/// [`parse_sysinfo`]: fn.parse_sysinfo.html
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceInfo {
+    device_type: String,
+    model: String,
+    alias: String,
+    mac: MacAddress,
+    sw_ver: String,
+    hw_ver: String,
+    rssi: Option<i64>,
+    has_relay: bool,
+    has_led: bool,
+    is_dimmable: bool,
+    is_color: bool,
+    is_variable_color_temp: bool,
+    features: FeatureFlags,
+    children: usize,
+}
+
+impl DeviceInfo {
+    /// Returns the device type, e.g. `IOT.SMARTPLUGSWITCH` or `IOT.SMARTBULB`.
+    pub fn device_type(&self) -> &str {
+        &self.device_type
+    }
+
+    /// Returns the model of the device.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Returns the name (alias) of the device.
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    /// Returns the mac address of the device, formatted as
+    /// `AA:BB:CC:DD:EE:FF` regardless of how the device reports it.
+    pub fn mac_address(&self) -> &str {
+        self.mac.as_str()
+    }
+
+    /// Returns the software version of the device.
+    pub fn sw_ver(&self) -> &str {
+        &self.sw_ver
+    }
+
+    /// Returns the hardware version of the device.
+    pub fn hw_ver(&self) -> &str {
+        &self.hw_ver
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device, if reported.
+    pub fn rssi(&self) -> Option<i64> {
+        self.rssi
+    }
+
+    /// Returns whether the device switches a relay (plugs and switches).
+    pub fn has_relay(&self) -> bool {
+        self.has_relay
+    }
+
+    /// Returns whether the device has a controllable LED.
+    pub fn has_led(&self) -> bool {
+        self.has_led
+    }
+
+    /// Returns whether the device supports brightness changes.
+    pub fn is_dimmable(&self) -> bool {
+        self.is_dimmable
+    }
+
+    /// Returns whether the device supports color changes.
+    pub fn is_color(&self) -> bool {
+        self.is_color
+    }
+
+    /// Returns whether the device supports color temperature changes.
+    pub fn is_variable_color_temp(&self) -> bool {
+        self.is_variable_color_temp
+    }
+
+    /// Returns the features advertised by the device. Bulbs do not
+    /// advertise any.
+    pub fn features(&self) -> FeatureFlags {
+        self.features
+    }
+
+    /// Returns the number of child outlets of the device (power strips).
+    pub fn children(&self) -> usize {
+        self.children
+    }
+}
+
+#[derive(Deserialize)]
+struct RawDeviceInfo {
+    #[serde(rename = "type", alias = "mic_type")]
+    device_type: String,
+    model: String,
+    alias: String,
+    #[serde(alias = "mic_mac")]
+    mac: MacAddress,
+    sw_ver: String,
+    hw_ver: String,
+    rssi: Option<i64>,
+    relay_state: Option<u64>,
+    led_off: Option<u64>,
+    #[serde(default)]
+    is_dimmable: u64,
+    #[serde(default)]
+    is_color: u64,
+    #[serde(default)]
+    is_variable_color_temp: u64,
+    #[serde(default)]
+    feature: FeatureFlags,
+    #[serde(default)]
+    children: Vec<Value>,
+}
+
+/// Parses the system information of any TP-Link device.
+///
+/// Accepts either the `get_sysinfo` object itself, or a full response
+/// containing it under `system.get_sysinfo`. Differences between device
+/// families, such as `mic_mac` on bulbs in place of `mac` on plugs, are
+/// normalised.
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+///
+/// let info = tplink::sysinfo::parse_sysinfo(json!({
+///     "mic_type": "IOT.SMARTBULB",
+///     "model": "LB110(US)",
+///     "alias": "Hallway",
+///     "mic_mac": "50C7BF000006",
+///     "sw_ver": "1.8.11 Build 191113 Rel.105336",
+///     "hw_ver": "1.0",
+///     "is_dimmable": 1,
+/// })).unwrap();
+/// assert_eq!(info.mac_address(), "50:C7:BF:00:00:06");
+/// assert!(info.is_dimmable());
+/// ```
+pub fn parse_sysinfo(mut value: Value) -> Result<DeviceInfo> {
+    if value.get("system").is_some() {
+        value = value["system"]["get_sysinfo"].take();
+    }
+
+    let raw = serde_json::from_value::<RawDeviceInfo>(value).map_err(error::json)?;
+
+    Ok(DeviceInfo {
+        device_type: raw.device_type,
+        model: raw.model,
+        alias: raw.alias,
+        mac: raw.mac,
+        sw_ver: raw.sw_ver,
+        hw_ver: raw.hw_ver,
+        rssi: raw.rssi,
+        has_relay: raw.relay_state.is_some(),
+        has_led: raw.led_off.is_some(),
+        is_dimmable: raw.is_dimmable == 1,
+        is_color: raw.is_color == 1,
+        is_variable_color_temp: raw.is_variable_color_temp == 1,
+        features: raw.feature,
+        children: raw.children.len(),
+    })
+}
+
+/// Wraps a bare `get_sysinfo` object into a full response, leaving full
+/// responses (e.g. discovery responses) as they are.
+pub(crate) fn into_response(value: Value) -> Value {
+    if value.get("system").is_some() {
+        value
+    } else {
+        json!({ "system": { "get_sysinfo": value } })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> DeviceInfo {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name);
+        let value = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+        parse_sysinfo(value).unwrap_or_else(|e| panic!("{}: {}", name, e))
+    }
+
+    #[test]
+    fn test_parse_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        for entry in fs::read_dir(dir).unwrap() {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            let info = fixture(&name);
+
+            let model = name.split('_').next().unwrap().to_uppercase();
+            assert!(info.model().starts_with(&model), "{}", name);
+            assert_eq!(info.mac_address().len(), 17, "{}", name);
+            assert!(info
+                .sw_ver()
+                .parse::<crate::cloud::FirmwareVersion>()
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_parse_fixture_capabilities() {
+        let plug = fixture("hs110_hw2.json");
+        assert!(plug.has_relay() && plug.has_led());
+        assert!(plug.features().contains(FeatureFlags::ENERGY));
+        assert!(!fixture("hs105_hw1.json")
+            .features()
+            .contains(FeatureFlags::ENERGY));
+        assert!(!plug.is_dimmable());
+
+        let bulb = fixture("lb130_hw1.json");
+        assert_eq!(bulb.mac_address(), "50:C7:BF:00:00:08");
+        assert!(bulb.is_dimmable() && bulb.is_color() && bulb.is_variable_color_temp());
+        assert!(!bulb.has_relay());
+
+        assert!(bulb.features().is_empty());
+
+        let strip = fixture("hs300_hw1.json");
+        assert_eq!(strip.children(), 3);
+    }
+
+    #[test]
+    fn test_parse_typed_fixtures() {
+        fn parse<T: serde::de::DeserializeOwned>(name: &str) -> T {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures")
+                .join(name);
+            let mut value: Value = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+            crate::schema::parse([127, 0, 0, 1].into(), value["system"]["get_sysinfo"].take())
+                .unwrap_or_else(|e| panic!("{}: {}", name, e))
+        }
+
+        for name in [
+            "hs100_hw1.json",
+            "hs100_hw2.json",
+            "hs105_hw1.json",
+            "hs110_hw1.json",
+            "hs110_hw2.json",
+        ] {
+            let info: crate::HS100Info = parse(name);
+            let expected = fixture(name);
+            assert_eq!(info.model(), expected.model(), "{}", name);
+            assert_eq!(info.mac_address(), expected.mac_address(), "{}", name);
+            assert_eq!(info.has_emeter(), name.starts_with("hs110"), "{}", name);
+        }
+
+        for name in [
+            "lb110_hw1.json",
+            "lb120_hw1.json",
+            "lb130_hw1.json",
+            "kl130_hw1.json",
+        ] {
+            let info: crate::LB110Info = parse(name);
+            let expected = fixture(name);
+            assert_eq!(info.model(), expected.model(), "{}", name);
+            assert_eq!(info.mac_address(), expected.mac_address(), "{}", name);
+            assert_eq!(info.is_color(), expected.is_color(), "{}", name);
+            assert!(info.is_dimmable(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_mac_address() {
+        let mut value: Value = serde_json::from_slice(
+            &fs::read(
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("fixtures")
+                    .join("lb130_hw1.json"),
             )
-        }))
+            .unwrap(),
+        )
+        .unwrap();
+        let sysinfo = value["system"]["get_sysinfo"].as_object_mut().unwrap();
+        let mac = sysinfo.remove("mic_mac").unwrap();
+        sysinfo.insert("mac".into(), mac);
+
+        let info: crate::LB110Info =
+            serde_json::from_value(value["system"]["get_sysinfo"].take()).unwrap();
+        assert_eq!(info.mac_address(), "50:C7:BF:00:00:08");
+
+        let mac: MacAddress = serde_json::from_value(json!("not a mac")).unwrap();
+        assert_eq!(mac.as_str(), "not a mac");
+        assert!("50:C7:BF:00:00:0G".parse::<MacAddress>().is_err());
     }
 }
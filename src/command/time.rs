@@ -1,22 +1,47 @@
+use crate::ack::Ack;
 use crate::error::Result;
 use crate::proto::{Proto, Request};
+use crate::schema;
+use crate::util;
 
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::fmt;
 use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Every timezone offset is a whole multiple of 15 minutes.
+const OFFSET_STEP_MS: i64 = 15 * 60 * 1000;
 
 /// The `Time` trait represents devices that are capable of maintaining
 /// and providing their time and timezone.
 pub trait Time {
     /// Attempts to fetch the device's time. Returns the current
     /// date and time of the device without the timezone.
-    fn time(&mut self) -> Result<DeviceTime>;
+    fn time(&self) -> Result<DeviceTime>;
 
     /// Attempts to fetch the device's timezone. Returns the current
     /// timezone of the device.
-    fn timezone(&mut self) -> Result<DeviceTimeZone>;
+    fn timezone(&self) -> Result<DeviceTimeZone>;
+
+    /// Measures how far the device's clock is ahead of or behind the
+    /// host's clock.
+    ///
+    /// The device's time is compared with the host's time halfway through
+    /// the request, which compensates for the round trip. Since the device
+    /// reports local time without its UTC offset, the offset is taken to
+    /// be the nearest multiple of 15 minutes, so drifts of more than 7.5
+    /// minutes cannot be told apart from a different timezone. The device
+    /// reports whole seconds, so the drift is accurate to about a second.
+    fn clock_drift(&self) -> Result<ClockDrift> {
+        let sent = SystemTime::now();
+        let time = self.time()?;
+        let received = SystemTime::now();
+        Ok(ClockDrift::measure(&time, sent, received))
+    }
 }
 
+#[derive(Clone)]
 pub(crate) struct TimeSettings {
     ns: String,
     proto: Rc<Proto>,
@@ -34,15 +59,7 @@ impl TimeSettings {
         let response = self
             .proto
             .send_request(&Request::new(&self.ns, "get_time", None))
-            .map(|response| {
-                serde_json::from_value(response).unwrap_or_else(|err| {
-                    panic!(
-                        "invalid response from host with address {}: {}",
-                        self.proto.host(),
-                        err
-                    )
-                })
-            })?;
+            .and_then(|response| schema::parse(self.proto.host(), response))?;
 
         log::trace!("({}) {:?}", self.ns, response);
 
@@ -53,20 +70,35 @@ impl TimeSettings {
         let response = self
             .proto
             .send_request(&Request::new(&self.ns, "get_timezone", None))
-            .map(|response| {
-                serde_json::from_value(response).unwrap_or_else(|err| {
-                    panic!(
-                        "invalid response from host with address {}: {}",
-                        self.proto.host(),
-                        err
-                    )
-                })
-            })?;
+            .and_then(|response| schema::parse(self.proto.host(), response))?;
 
         log::trace!("({}) {:?}", self.ns, response);
 
         Ok(response)
     }
+
+    /// Sets the timezone of the device to the one with the given index,
+    /// keeping its current local time.
+    pub(crate) fn set_timezone(&self, index: i32) -> Result<Ack> {
+        let time = self.get_time()?;
+        let response = self.proto.send_request(&Request::new(
+            &self.ns,
+            "set_timezone",
+            Some(json!({
+                "year": time.year,
+                "month": time.month,
+                "mday": time.day,
+                "hour": time.hour,
+                "min": time.min,
+                "sec": time.sec,
+                "index": index,
+            })),
+        ))?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        Ack::from_response(response)
+    }
 }
 
 /// The device's time without the timezone.
@@ -75,7 +107,7 @@ impl TimeSettings {
 ///
 /// ```no_run
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-/// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+/// let plug = tplink::Plug::new([192, 168, 1, 100]);
 ///
 /// let device_time = plug.time()?;
 /// println!("{}", device_time);        // e.g. `2020-04-08 22:29:07`
@@ -130,6 +162,13 @@ impl DeviceTime {
     pub fn second(&self) -> u32 {
         self.sec
     }
+
+    /// Returns the seconds since the Unix epoch of this time, as if it
+    /// were in UTC.
+    pub(crate) fn naive_unix_secs(&self) -> i64 {
+        util::days_from_civil(self.year, self.month, self.day) * 86_400
+            + i64::from(self.hour * 3600 + self.min * 60 + self.sec)
+    }
 }
 
 impl fmt::Display for DeviceTime {
@@ -142,6 +181,68 @@ impl fmt::Display for DeviceTime {
     }
 }
 
+/// The difference between the device's clock and the host's clock, as
+/// measured by [`Time::clock_drift`].
+///
+/// [`Time::clock_drift`]: trait.Time.html#method.clock_drift
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ClockDrift {
+    drift_ms: i64,
+    round_trip: Duration,
+}
+
+impl ClockDrift {
+    pub(crate) fn measure(time: &DeviceTime, sent: SystemTime, received: SystemTime) -> ClockDrift {
+        let round_trip = received.duration_since(sent).unwrap_or_default();
+        let host_ms = unix_ms(sent) + round_trip.as_millis() as i64 / 2;
+        // The device truncates its time to the second, so it is on average
+        // half a second later than reported.
+        let device_ms = time.naive_unix_secs() * 1000 + 500;
+
+        ClockDrift {
+            drift_ms: strip_utc_offset(device_ms - host_ms),
+            round_trip,
+        }
+    }
+
+    /// Returns whether the device's clock is ahead of the host's clock.
+    pub fn is_ahead(&self) -> bool {
+        self.drift_ms > 0
+    }
+
+    /// Returns the size of the drift, regardless of its direction.
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.drift_ms.unsigned_abs())
+    }
+
+    /// Returns the drift in seconds, positive if the device's clock is
+    /// ahead of the host's clock and negative if it is behind.
+    pub fn as_secs_f64(&self) -> f64 {
+        self.drift_ms as f64 / 1000.0
+    }
+
+    /// Returns the round-trip time of the request the drift was measured
+    /// with.
+    pub fn round_trip(&self) -> Duration {
+        self.round_trip
+    }
+}
+
+/// Returns the remainder of a difference between a local time and a UTC
+/// time, in ms, once the UTC offset, taken to be the nearest multiple of 15
+/// minutes, is removed.
+pub(crate) fn strip_utc_offset(diff_ms: i64) -> i64 {
+    let offset_ms = (diff_ms as f64 / OFFSET_STEP_MS as f64).round() as i64 * OFFSET_STEP_MS;
+    diff_ms - offset_ms
+}
+
+fn unix_ms(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
 /// The device's timezone.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeviceTimeZone {
@@ -153,3 +254,32 @@ impl DeviceTimeZone {
         self.index
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_clock_drift() {
+        // 2020-04-08 22:29:07 in a UTC+02:00 timezone.
+        let time: DeviceTime = serde_json::from_value(json!({
+            "year": 2020, "month": 4, "mday": 8, "hour": 22, "min": 29, "sec": 7,
+        }))
+        .unwrap();
+        let utc = UNIX_EPOCH + Duration::from_secs(1_586_377_747);
+
+        let drift = ClockDrift::measure(&time, utc, utc + Duration::from_millis(200));
+        assert!(drift.is_ahead());
+        assert_eq!(drift.duration(), Duration::from_millis(400));
+        assert_eq!(drift.round_trip(), Duration::from_millis(200));
+
+        let drift = ClockDrift::measure(
+            &time,
+            utc + Duration::from_secs(3),
+            utc + Duration::from_secs(3),
+        );
+        assert!(!drift.is_ahead());
+        assert_eq!(drift.as_secs_f64(), -2.5);
+    }
+}
@@ -1,19 +1,46 @@
+use crate::ack::Ack;
 use crate::error::Result;
 use crate::proto::{Proto, Request};
+use crate::schema;
 
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::rc::Rc;
+use std::thread;
 use std::time::Duration;
 
 pub trait Wlan {
-    fn get_scan_info(
-        &mut self,
-        refresh: bool,
-        timeout: Option<Duration>,
-    ) -> Result<Vec<AccessPoint>>;
+    fn get_scan_info(&self, refresh: bool, timeout: Option<Duration>) -> Result<Vec<AccessPoint>>;
+
+    /// Scans for access points the given number of times, waiting the
+    /// given delay between scans, and merges the results, since a single
+    /// scan frequently misses some access points.
+    ///
+    /// The device reports neither the BSSID nor the signal strength of an
+    /// access point, so access points are told apart by their SSID and key
+    /// type, and each is marked with the attempts whose scan saw it.
+    fn scan_aggregate(&self, attempts: u32, delay: Duration) -> Result<Vec<ScannedAccessPoint>> {
+        let attempts = attempts.max(1);
+        let mut merged = Vec::new();
+        for attempt in 0..attempts {
+            if attempt > 0 {
+                thread::sleep(delay);
+            }
+            for ap in self.get_scan_info(true, None)? {
+                ScannedAccessPoint::merge(&mut merged, ap, attempt, attempts);
+            }
+        }
+        Ok(merged)
+    }
 }
 
+/// The scan timeout used when neither the caller nor the proto sets one.
+const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How much longer than the scan itself to wait for the scan results.
+const SCAN_MARGIN: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
 pub(crate) struct Netif {
     ns: String,
     proto: Rc<Proto>,
@@ -33,30 +60,39 @@ impl Netif {
         timeout: Option<Duration>,
     ) -> Result<Vec<AccessPoint>> {
         let refresh = if refresh { 1 } else { 0 };
-        // Note: If scan timeout is greater than proto's read timeout,
-        // the method returns with an ErrorKind::WouldBlock error.
-        let timeout = timeout.map_or(
-            self.proto.read_timeout().map_or(3, |to| to.as_secs()),
-            |duration| duration.as_secs(),
-        );
+        let read_timeout = self.proto.read_timeout();
+        let timeout = timeout.or(read_timeout).unwrap_or(DEFAULT_SCAN_TIMEOUT);
+
+        // The device only responds once the scan is complete, so wait for
+        // the response for at least as long as the scan takes, rather than
+        // failing with a timeout error when the scan outlasts the proto's
+        // read timeout.
+        let read_timeout = read_timeout.map(|read_timeout| read_timeout.max(timeout + SCAN_MARGIN));
+
+        let response = self.proto.send_request_with_timeout(
+            &Request::new(
+                &self.ns,
+                "get_scaninfo",
+                Some(json!({ "refresh": refresh, "timeout": timeout.as_secs() })),
+            ),
+            read_timeout,
+        )?;
+
+        log::trace!("{:?}", response);
+
+        Ok(schema::parse::<AccessPointList>(self.proto.host(), response)?.ap_list)
+    }
 
+    /// Asks the device to join the given wireless network. The device
+    /// leaves its current network, or its own access point, to do so.
+    pub(crate) fn set_stainfo(&self, ssid: &str, password: &str, key_type: u32) -> Result<Ack> {
         let response = self.proto.send_request(&Request::new(
             &self.ns,
-            "get_scaninfo",
-            Some(json!({ "refresh": refresh, "timeout": timeout })),
+            "set_stainfo",
+            Some(json!({ "ssid": ssid, "password": password, "key_type": key_type })),
         ))?;
 
-        log::trace!("{:?}", response);
-
-        Ok(serde_json::from_value::<AccessPointList>(response)
-            .map(|response| response.ap_list)
-            .unwrap_or_else(|err| {
-                panic!(
-                    "invalid response from host with address {}: {}",
-                    self.proto.host(),
-                    err
-                )
-            }))
+        Ack::from_response(response)
     }
 }
 
@@ -80,3 +116,131 @@ impl AccessPoint {
         self.key_type
     }
 }
+
+/// An access point found by [`Wlan::scan_aggregate`], along with the scan
+/// attempts that saw it.
+///
+/// [`Wlan::scan_aggregate`]: trait.Wlan.html#method.scan_aggregate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedAccessPoint {
+    ssid: String,
+    key_type: u32,
+    seen: Vec<bool>,
+}
+
+impl ScannedAccessPoint {
+    fn merge(merged: &mut Vec<ScannedAccessPoint>, ap: AccessPoint, attempt: u32, attempts: u32) {
+        let index = match merged
+            .iter()
+            .position(|seen| seen.ssid == ap.ssid && seen.key_type == ap.key_type)
+        {
+            Some(index) => index,
+            None => {
+                merged.push(ScannedAccessPoint {
+                    ssid: ap.ssid,
+                    key_type: ap.key_type,
+                    seen: vec![false; attempts as usize],
+                });
+                merged.len() - 1
+            }
+        };
+        merged[index].seen[attempt as usize] = true;
+    }
+
+    pub fn ssid(&self) -> &str {
+        &self.ssid
+    }
+
+    pub fn key_type(&self) -> u32 {
+        self.key_type
+    }
+
+    /// Returns whether the scan of the given attempt, counting from 0, saw
+    /// the access point.
+    pub fn seen_in(&self, attempt: u32) -> bool {
+        self.seen.get(attempt as usize).copied().unwrap_or(false)
+    }
+
+    /// Returns the number of scans that saw the access point.
+    pub fn times_seen(&self) -> usize {
+        self.seen.iter().filter(|&&seen| seen).count()
+    }
+
+    /// Returns the fraction, from 0 to 1, of scans that saw the access
+    /// point, as a rough measure of how reliably it can be reached.
+    pub fn seen_ratio(&self) -> f64 {
+        self.times_seen() as f64 / self.seen.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+    use crate::proto::Builder;
+    use serde_json::Value;
+    use std::net::UdpSocket;
+    use std::thread;
+
+    #[test]
+    fn test_scan_outlasts_read_timeout() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = device.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            let (recv, from) = device.recv_from(&mut buf).unwrap();
+            let request: Value = serde_json::from_slice(&crypto::decrypt(&buf[..recv])).unwrap();
+            assert_eq!(
+                request,
+                json!({ "netif": { "get_scaninfo": { "refresh": 1, "timeout": 1 } } })
+            );
+
+            thread::sleep(Duration::from_millis(500));
+            let response = json!({ "netif": { "get_scaninfo": {
+                "ap_list": [{ "ssid": "home", "key_type": 3 }],
+            } } });
+            let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+            device.send_to(&response, from).unwrap();
+        });
+
+        let proto = Builder::new(addr)
+            .read_timeout(Duration::from_millis(200))
+            .build();
+        let netif = Netif::new(Rc::new(proto));
+        let ap_list = netif
+            .get_scan_info(true, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert_eq!(ap_list.len(), 1);
+        assert_eq!(ap_list[0].ssid(), "home");
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_merge_scans() {
+        let ap = |ssid: &str, key_type: u32| AccessPoint {
+            ssid: ssid.to_string(),
+            key_type,
+        };
+        let scans = vec![
+            vec![ap("home", 3), ap("guest", 0)],
+            vec![ap("home", 3)],
+            vec![ap("home", 3), ap("neighbour", 3), ap("guest", 0)],
+        ];
+
+        let mut merged = Vec::new();
+        for (attempt, scan) in scans.into_iter().enumerate() {
+            for ap in scan {
+                ScannedAccessPoint::merge(&mut merged, ap, attempt as u32, 3);
+            }
+        }
+
+        let ssids: Vec<_> = merged.iter().map(|ap| ap.ssid()).collect();
+        assert_eq!(ssids, ["home", "guest", "neighbour"]);
+        assert_eq!(merged[0].times_seen(), 3);
+        assert!(merged[1].seen_in(0) && !merged[1].seen_in(1) && merged[1].seen_in(2));
+        assert!(!merged[2].seen_in(0) && merged[2].seen_in(2));
+        assert!(!merged[2].seen_in(3));
+        assert!((merged[2].seen_ratio() - 1.0 / 3.0).abs() < f64::EPSILON);
+    }
+}
@@ -1,4 +1,9 @@
+use crate::audit::{Audit, AuditSink};
+use crate::proto::{self, SocketOptions};
+
 use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Configuration options used to configure a TP-Link device.
@@ -32,6 +37,14 @@ pub struct Config {
     pub(crate) write_timeout: Duration,
     pub(crate) cache_config: CacheConfig,
     pub(crate) buffer_size: usize,
+    pub(crate) offline_tolerance: u32,
+    pub(crate) retry_attempts: u32,
+    pub(crate) max_concurrent_requests: usize,
+    pub(crate) socket_options: SocketOptions,
+    pub(crate) brightness_config: BrightnessConfig,
+    pub(crate) reset_confirmation: bool,
+    pub(crate) audit: Audit,
+    pub(crate) lease_holder: Option<String>,
 }
 
 impl Config {
@@ -163,6 +176,61 @@ impl Config {
         self.cache_config.initial_capacity
     }
 
+    /// Returns how long before expiry the cached responses are refreshed in
+    /// the background, if cache prefetching is enabled, and `None` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_cache_enabled(Duration::from_secs(3), None)
+    ///     .with_cache_prefetch(Duration::from_millis(500))
+    ///     .build();
+    /// assert_eq!(config.cache_prefetch(), Some(Duration::from_millis(500)));
+    /// ```
+    pub fn cache_prefetch(&self) -> Option<Duration> {
+        self.cache_config.prefetch
+    }
+
+    /// Returns the duration for which failed requests are remembered, if
+    /// negative caching is enabled, and `None` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_cache_enabled(Duration::from_secs(3), None)
+    ///     .with_negative_cache(Duration::from_secs(10))
+    ///     .build();
+    /// assert_eq!(config.negative_cache_ttl(), Some(Duration::from_secs(10)));
+    /// ```
+    pub fn negative_cache_ttl(&self) -> Option<Duration> {
+        self.cache_config.negative_ttl
+    }
+
+    /// Returns the file the response cache is persisted to, if cache
+    /// persistence is enabled, and `None` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    ///
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_cache_enabled(Duration::from_secs(30), None)
+    ///     .with_cache_file("/tmp/tplink-cache.json")
+    ///     .build();
+    /// assert_eq!(config.cache_file(), Some(Path::new("/tmp/tplink-cache.json")));
+    /// ```
+    pub fn cache_file(&self) -> Option<&Path> {
+        self.cache_config.file.as_deref()
+    }
+
     /// Returns the configured response buffer size for the device.
     ///
     /// # Examples
@@ -177,22 +245,175 @@ impl Config {
     pub fn buffer_size(&self) -> usize {
         self.buffer_size
     }
+
+    /// Returns the configured number of copies of each request sent to
+    /// the device.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_offline_tolerance(3)
+    ///     .build();
+    /// assert_eq!(config.offline_tolerance(), 3);
+    /// ```
+    pub fn offline_tolerance(&self) -> u32 {
+        self.offline_tolerance
+    }
+
+    /// Returns the configured number of times a request is sent before
+    /// giving up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_retry(3)
+    ///     .build();
+    /// assert_eq!(config.retry_attempts(), 3);
+    /// ```
+    pub fn retry_attempts(&self) -> u32 {
+        self.retry_attempts
+    }
+
+    /// Returns the configured number of requests that may be in flight to
+    /// the device at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_max_concurrent_requests(2)
+    ///     .build();
+    /// assert_eq!(config.max_concurrent_requests(), 2);
+    /// ```
+    pub fn max_concurrent_requests(&self) -> usize {
+        self.max_concurrent_requests
+    }
+
+    /// Returns the configured IP time-to-live of the requests sent to the
+    /// device, if set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_ip_ttl(4)
+    ///     .build();
+    /// assert_eq!(config.ip_ttl(), Some(4));
+    /// ```
+    pub fn ip_ttl(&self) -> Option<u32> {
+        self.socket_options.ttl
+    }
+
+    /// Returns the configured IP type of service (DSCP/ECN byte) of the
+    /// requests sent to the device, if set.
+    ///
+    /// It is only applied with the `sockopt` feature on Unix, see
+    /// [`ConfigBuilder::with_tos`].
+    ///
+    /// [`ConfigBuilder::with_tos`]: struct.ConfigBuilder.html#method.with_tos
+    pub fn tos(&self) -> Option<u8> {
+        self.socket_options.tos
+    }
+
+    /// Returns whether `SO_REUSEADDR` is requested on the sockets used to
+    /// talk to the device.
+    ///
+    /// It is only applied with the `sockopt` feature on Unix, see
+    /// [`ConfigBuilder::with_reuse_address`].
+    ///
+    /// [`ConfigBuilder::with_reuse_address`]: struct.ConfigBuilder.html#method.with_reuse_address
+    pub fn reuse_address(&self) -> bool {
+        self.socket_options.reuse_address
+    }
+
+    /// Returns the configured minimum brightness that `set_brightness`
+    /// clamps to, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 101])
+    ///     .with_min_brightness(5)
+    ///     .build();
+    /// assert_eq!(config.min_brightness(), Some(5));
+    /// ```
+    pub fn min_brightness(&self) -> Option<u32> {
+        self.brightness_config.min
+    }
+
+    /// Returns the configured gamma of the dimming curve applied by
+    /// `set_brightness`, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 101])
+    ///     .with_dimming_curve(2.2)
+    ///     .build();
+    /// assert_eq!(config.dimming_curve(), Some(2.2));
+    /// ```
+    pub fn dimming_curve(&self) -> Option<f64> {
+        self.brightness_config.gamma
+    }
+
+    /// Returns whether factory resets of the device must be confirmed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_reset_confirmation(true)
+    ///     .build();
+    /// assert!(config.reset_confirmation_required());
+    /// ```
+    pub fn reset_confirmation_required(&self) -> bool {
+        self.reset_confirmation
+    }
+
+    /// Returns the configured name under which the device holds leases, if
+    /// any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 101])
+    ///     .with_lease_holder("wake-up")
+    ///     .build();
+    /// assert_eq!(config.lease_holder(), Some("wake-up"));
+    /// ```
+    pub fn lease_holder(&self) -> Option<&str> {
+        self.lease_holder.as_deref()
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Default, Clone)]
 pub(crate) struct CacheConfig {
     pub(crate) enable_cache: bool,
     pub(crate) ttl: Option<Duration>,
     pub(crate) initial_capacity: Option<usize>,
+    pub(crate) prefetch: Option<Duration>,
+    pub(crate) negative_ttl: Option<Duration>,
+    pub(crate) file: Option<PathBuf>,
 }
 
-impl Default for CacheConfig {
-    fn default() -> Self {
-        CacheConfig {
-            enable_cache: false,
-            ttl: None,
-            initial_capacity: None,
-        }
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct BrightnessConfig {
+    pub(crate) min: Option<u32>,
+    pub(crate) gamma: Option<f64>,
+}
+
+impl BrightnessConfig {
+    /// Maps the requested % brightness through the dimming curve, then
+    /// clamps it between the minimum brightness and 100.
+    pub(crate) fn apply(&self, brightness: u32) -> u32 {
+        let brightness = brightness.min(100);
+        let brightness = match self.gamma {
+            Some(gamma) => (100.0 * (f64::from(brightness) / 100.0).powf(gamma)).round() as u32,
+            None => brightness,
+        };
+        brightness.clamp(self.min.unwrap_or(0), 100)
     }
 }
 
@@ -235,6 +456,14 @@ pub struct ConfigBuilder {
     write_timeout: Option<Duration>,
     cache_config: CacheConfig,
     buffer_size: Option<usize>,
+    offline_tolerance: Option<u32>,
+    retry_attempts: Option<u32>,
+    max_concurrent_requests: Option<usize>,
+    socket_options: SocketOptions,
+    brightness_config: BrightnessConfig,
+    reset_confirmation: bool,
+    audit: Audit,
+    lease_holder: Option<String>,
 }
 
 impl ConfigBuilder {
@@ -246,11 +475,19 @@ impl ConfigBuilder {
     {
         ConfigBuilder {
             host: addr.into(),
-            port: 9999,
+            port: proto::DEFAULT_PORT,
             read_timeout: None,
             write_timeout: None,
             cache_config: Default::default(),
             buffer_size: None,
+            offline_tolerance: None,
+            retry_attempts: None,
+            max_concurrent_requests: None,
+            socket_options: Default::default(),
+            brightness_config: Default::default(),
+            reset_confirmation: false,
+            audit: Audit::default(),
+            lease_holder: None,
         }
     }
 
@@ -329,10 +566,105 @@ impl ConfigBuilder {
             enable_cache: true,
             ttl: Some(ttl),
             initial_capacity,
+            prefetch: self.cache_config.prefetch,
+            negative_ttl: self.cache_config.negative_ttl,
+            file: self.cache_config.file.take(),
         };
         self
     }
 
+    /// Enables refreshing the frequently read cached responses (e.g. system
+    /// information or light state) on a background thread, the given duration
+    /// before they expire. Reads are then served from the cache without
+    /// waiting on the device, while the data is never older than the cache ttl.
+    ///
+    /// Only responses that were read since they were last fetched are refreshed,
+    /// so the background traffic stops for data that is no longer in use. The
+    /// background thread exits when the device is dropped.
+    ///
+    /// Prefetching has no effect unless caching is enabled with
+    /// [`with_cache_enabled`]. By default, prefetching is disabled.
+    ///
+    /// [`with_cache_enabled`]: #method.with_cache_enabled
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_cache_enabled(Duration::from_secs(3), None)
+    ///     .with_cache_prefetch(Duration::from_millis(500))
+    ///     .build();
+    /// assert_eq!(config.cache_prefetch(), Some(Duration::from_millis(500)));
+    /// ```
+    pub fn with_cache_prefetch(&mut self, lead: Duration) -> &mut ConfigBuilder {
+        self.cache_config.prefetch = Some(lead);
+        self
+    }
+
+    /// Enables caching of failed requests for the given ttl (time-to-live).
+    ///
+    /// When a cached request fails with an I/O error (e.g. the device is
+    /// offline and the request times out), the failure is remembered and
+    /// the same request fails immediately with the same error until the ttl
+    /// expires, instead of waiting for the full timeout on every call. Any
+    /// command that changes the device state forgets the remembered failures.
+    ///
+    /// Negative caching has no effect unless caching is enabled with
+    /// [`with_cache_enabled`]. By default, negative caching is disabled.
+    ///
+    /// [`with_cache_enabled`]: #method.with_cache_enabled
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_cache_enabled(Duration::from_secs(3), None)
+    ///     .with_negative_cache(Duration::from_secs(10))
+    ///     .build();
+    /// assert_eq!(config.negative_cache_ttl(), Some(Duration::from_secs(10)));
+    /// ```
+    pub fn with_negative_cache(&mut self, ttl: Duration) -> &mut ConfigBuilder {
+        self.cache_config.negative_ttl = Some(ttl);
+        self
+    }
+
+    /// Persists the response cache to the file at the given path, so that
+    /// short-lived processes run in quick succession can reuse each other's
+    /// cached responses.
+    ///
+    /// The cache is loaded from the file when the device is created, and saved
+    /// back (along with the age of every entry and the address of the device)
+    /// when the device and all its clones are dropped. Entries whose ttl has
+    /// expired in the meantime are discarded on load. A missing or unreadable
+    /// file, or one saved for a device at another address, simply results in
+    /// an empty cache.
+    ///
+    /// Persistence has no effect unless caching is enabled with
+    /// [`with_cache_enabled`]. By default, the cache is not persisted.
+    ///
+    /// [`with_cache_enabled`]: #method.with_cache_enabled
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    ///
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_cache_enabled(Duration::from_secs(30), None)
+    ///     .with_cache_file("/tmp/tplink-cache.json")
+    ///     .build();
+    /// assert_eq!(config.cache_file(), Some(Path::new("/tmp/tplink-cache.json")));
+    /// ```
+    pub fn with_cache_file<P: Into<PathBuf>>(&mut self, path: P) -> &mut ConfigBuilder {
+        self.cache_config.file = Some(path.into());
+        self
+    }
+
     /// Sets the device's response buffer size.
     ///
     /// The buffer size should be large enough to hold device's response bytes. If the
@@ -357,6 +689,247 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets how many copies of each request datagram are sent to the
+    /// device at once. Requests are sent over UDP, so sending more than one
+    /// copy makes them more likely to reach devices on a lossy network, at
+    /// the cost of the device possibly acting on a request more than once,
+    /// even when the first copy got through.
+    ///
+    /// For requests to a single device, prefer [`with_retry`], which only
+    /// sends a request again when the device does not respond. Duplicate
+    /// copies remain for broadcasts, such as [`discover_with_config`],
+    /// which have no single response to wait for before sending again.
+    /// When both are set, each attempt sends this many copies. If not set,
+    /// or set to 0, a single copy is sent.
+    ///
+    /// [`discover_with_config`]: fn.discover_with_config.html
+    ///
+    /// [`with_retry`]: #method.with_retry
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_offline_tolerance(3)
+    ///     .build();
+    /// ```
+    pub fn with_offline_tolerance(&mut self, offline_tolerance: u32) -> &mut ConfigBuilder {
+        self.offline_tolerance = Some(offline_tolerance);
+        self
+    }
+
+    /// Sets how many times a request is sent to the device before giving
+    /// up.
+    ///
+    /// The read timeout is split evenly between the attempts: a request is
+    /// only sent again when the device has not responded within its share
+    /// of the timeout, and a late response to an earlier attempt is still
+    /// accepted. The whole request never takes longer than the read
+    /// timeout. A device may still act twice on a request whose response,
+    /// rather than the request itself, got lost.
+    ///
+    /// If not set, or set to 0, a request is sent once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// // Sends the request again after 1 and 2 seconds without a response.
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_read_timeout(Duration::from_secs(3))
+    ///     .with_retry(3)
+    ///     .build();
+    /// ```
+    pub fn with_retry(&mut self, attempts: u32) -> &mut ConfigBuilder {
+        self.retry_attempts = Some(attempts);
+        self
+    }
+
+    /// Sets how many requests may be in flight to the device at a time.
+    ///
+    /// Requests to the device are serialized across all handles to it in
+    /// the process and admitted in the order they are made. Handles
+    /// cannot be sent between threads, so a device used from several
+    /// threads has a handle built on each of them; the limit holds across
+    /// all of these handles, not just within one. Devices handle few
+    /// requests at a time, so by default a single request is in flight at
+    /// a time. A limit of 0 is treated as 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_max_concurrent_requests(2)
+    ///     .build();
+    /// ```
+    pub fn with_max_concurrent_requests(&mut self, limit: usize) -> &mut ConfigBuilder {
+        self.max_concurrent_requests = Some(limit);
+        self
+    }
+
+    /// Sets the IP time-to-live of the requests sent to the device, so
+    /// that they can be routed across network segments, e.g. to reach
+    /// devices on another VLAN.
+    ///
+    /// This is unrelated to the time-to-live of cached responses, see
+    /// [`with_cache_enabled`]. If not set, the operating system default is
+    /// used.
+    ///
+    /// [`with_cache_enabled`]: #method.with_cache_enabled
+    pub fn with_ip_ttl(&mut self, ttl: u32) -> &mut ConfigBuilder {
+        self.socket_options.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the IP type of service (DSCP/ECN byte) of the requests sent
+    /// to the device.
+    ///
+    /// If not set, the operating system default is used. The type of
+    /// service is only applied with the `sockopt` feature on Unix; without
+    /// it, the setting is kept in the config but has no effect.
+    pub fn with_tos(&mut self, tos: u8) -> &mut ConfigBuilder {
+        self.socket_options.tos = Some(tos);
+        self
+    }
+
+    /// Sets whether `SO_REUSEADDR` is set on the sockets used to talk to
+    /// the device.
+    ///
+    /// Disabled by default. The option is only applied with the `sockopt`
+    /// feature on Unix; without it, the setting is kept in the config but
+    /// has no effect.
+    pub fn with_reuse_address(&mut self, reuse_address: bool) -> &mut ConfigBuilder {
+        self.socket_options.reuse_address = reuse_address;
+        self
+    }
+
+    /// Sets the minimum % brightness of the device. Brightness values below
+    /// the minimum passed to `set_brightness` are raised to it, which avoids
+    /// the flicker some bulbs show at very low brightness.
+    ///
+    /// The minimum is applied after the dimming curve, if any. By default,
+    /// brightness values are not clamped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `brightness` is above 100.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 101])
+    ///     .with_min_brightness(5)
+    ///     .build();
+    /// assert_eq!(config.min_brightness(), Some(5));
+    /// ```
+    pub fn with_min_brightness(&mut self, brightness: u32) -> &mut ConfigBuilder {
+        assert!(
+            brightness <= 100,
+            "min brightness must be at most 100, got {}",
+            brightness
+        );
+        self.brightness_config.min = Some(brightness);
+        self
+    }
+
+    /// Sets the gamma of the dimming curve that maps the % brightness passed
+    /// to `set_brightness` to the % brightness sent to the device, as
+    /// `100 * (brightness / 100) ^ gamma`.
+    ///
+    /// A gamma above 1 (e.g. 2.2) makes evenly spaced slider positions
+    /// appear evenly spaced in perceived light output. By default, no
+    /// curve is applied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `gamma` is not a finite number above 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 101])
+    ///     .with_dimming_curve(2.2)
+    ///     .build();
+    /// assert_eq!(config.dimming_curve(), Some(2.2));
+    /// ```
+    pub fn with_dimming_curve(&mut self, gamma: f64) -> &mut ConfigBuilder {
+        assert!(
+            gamma.is_finite() && gamma > 0.0,
+            "dimming curve gamma must be finite and above 0, got {}",
+            gamma
+        );
+        self.brightness_config.gamma = Some(gamma);
+        self
+    }
+
+    /// Sets whether factory resets of the device must be confirmed.
+    ///
+    /// When set, `factory_reset` fails with
+    /// [`ErrorKind::ConfirmationRequired`] and the device can only be
+    /// reset with `factory_reset_confirmed`, passing the alias or the id
+    /// of the device, which protects scripts managing many devices from
+    /// resetting the wrong one. Disabled by default.
+    ///
+    /// [`ErrorKind::ConfirmationRequired`]: enum.ErrorKind.html#variant.ConfirmationRequired
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_reset_confirmation(true)
+    ///     .build();
+    /// assert!(config.reset_confirmation_required());
+    /// ```
+    pub fn with_reset_confirmation(&mut self, required: bool) -> &mut ConfigBuilder {
+        self.reset_confirmation = required;
+        self
+    }
+
+    /// Sets the sink recording every state-changing command sent to the
+    /// device, along with its result. None is set by default.
+    ///
+    /// See the [`audit`] module for details.
+    ///
+    /// [`audit`]: audit/index.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use tplink::audit::LogSink;
+    ///
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_audit_sink(Arc::new(LogSink))
+    ///     .build();
+    /// ```
+    pub fn with_audit_sink(&mut self, sink: Arc<dyn AuditSink>) -> &mut ConfigBuilder {
+        self.audit = Audit::new(sink);
+        self
+    }
+
+    /// Sets the name of the holder of the [leases] the device is controlled
+    /// under.
+    ///
+    /// State-changing commands sent to a device leased to another holder
+    /// fail with [`ErrorKind::LeaseHeld`]. Devices without a holder are
+    /// blocked by any lease. None is set by default.
+    ///
+    /// [leases]: lease/index.html
+    /// [`ErrorKind::LeaseHeld`]: enum.ErrorKind.html#variant.LeaseHeld
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = tplink::Config::for_host([192, 168, 1, 101])
+    ///     .with_lease_holder("wake-up")
+    ///     .build();
+    /// ```
+    pub fn with_lease_holder(&mut self, holder: &str) -> &mut ConfigBuilder {
+        self.lease_holder = Some(String::from(holder));
+        self
+    }
+
     /// Creates a new configured [`Config`] instance.
     ///
     /// [`Config`]: struct.Config.html
@@ -372,7 +945,7 @@ impl ConfigBuilder {
     /// ```
     pub fn build(&mut self) -> Config {
         let addr = SocketAddr::new(self.host, self.port);
-        let cache_config = self.cache_config;
+        let cache_config = self.cache_config.clone();
 
         // Set the default read timeout to 3 seconds
         let read_timeout = self.read_timeout.unwrap_or(Duration::from_secs(3));
@@ -383,12 +956,88 @@ impl ConfigBuilder {
         // Set the default buffer size to 4 * 1024
         let buffer_size = self.buffer_size.unwrap_or(4 * 1024);
 
+        // Send a single copy of each request by default, and at least one
+        let offline_tolerance = self.offline_tolerance.unwrap_or(1).max(1);
+
+        // Send each request once, without retrying, by default
+        let retry_attempts = self.retry_attempts.unwrap_or(1).max(1);
+
+        // Serialize the requests to the device by default
+        let max_concurrent_requests = self.max_concurrent_requests.unwrap_or(1).max(1);
+
         Config {
             addr,
             read_timeout,
             write_timeout,
             cache_config,
             buffer_size,
+            offline_tolerance,
+            retry_attempts,
+            max_concurrent_requests,
+            socket_options: self.socket_options,
+            brightness_config: self.brightness_config,
+            reset_confirmation: self.reset_confirmation,
+            audit: self.audit.clone(),
+            lease_holder: self.lease_holder.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brightness_config() {
+        let config = BrightnessConfig::default();
+        assert_eq!(config.apply(0), 0);
+        assert_eq!(config.apply(50), 50);
+
+        let config = BrightnessConfig {
+            min: Some(5),
+            gamma: Some(2.0),
+        };
+        assert_eq!(config.apply(0), 5);
+        assert_eq!(config.apply(50), 25);
+        assert_eq!(config.apply(100), 100);
+        assert_eq!(config.apply(150), 100);
+
+        let config = BrightnessConfig {
+            min: None,
+            gamma: Some(0.5),
+        };
+        assert_eq!(config.apply(200), 100);
+    }
+
+    #[test]
+    fn test_offline_tolerance() {
+        let config = Config::for_host([192, 168, 1, 100]).build();
+        assert_eq!(config.offline_tolerance(), 1);
+
+        let config = Config::for_host([192, 168, 1, 100])
+            .with_offline_tolerance(3)
+            .build();
+        assert_eq!(config.offline_tolerance(), 3);
+
+        let config = Config::for_host([192, 168, 1, 100])
+            .with_offline_tolerance(0)
+            .build();
+        assert_eq!(config.offline_tolerance(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 100")]
+    fn test_min_brightness_above_100() {
+        Config::for_host([192, 168, 1, 101]).with_min_brightness(101);
+    }
+
+    #[test]
+    fn test_invalid_dimming_curve() {
+        for gamma in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let result = std::panic::catch_unwind(|| {
+                Config::for_host([192, 168, 1, 101]).with_dimming_curve(gamma);
+            });
+            assert!(result.is_err(), "{}", gamma);
         }
     }
 }
@@ -0,0 +1,131 @@
+//! Checking of device responses against the JSON schemas of known commands,
+//! enabled with the `schema-check` feature.
+//!
+//! Unlike the `strict` feature, a response that diverges from its schema
+//! is not rejected: the divergences are logged as warnings along with the
+//! model and firmware of the device, so that fields silently drifting
+//! across firmware versions can be reported.
+//!
+//! The schemas under `schemas/` are embedded at build time. Only the
+//! subset of JSON Schema they use is understood: `type`, `required`,
+//! `properties`, `additionalProperties: false` and `items`.
+
+use serde_json::Value;
+
+/// The schemas of the responses to known commands, by command. A command
+/// answers alike whatever the namespace it is sent to.
+const SCHEMAS: &[(&str, &str)] = &[
+    ("get_sysinfo", include_str!("../schemas/get_sysinfo.json")),
+    ("get_realtime", include_str!("../schemas/get_realtime.json")),
+    ("get_time", include_str!("../schemas/get_time.json")),
+    ("get_info", include_str!("../schemas/get_info.json")),
+    (
+        "get_light_state",
+        include_str!("../schemas/get_light_state.json"),
+    ),
+];
+
+/// Returns the ways in which the response to the given command diverges
+/// from the schema of the command, if the command is known.
+pub(crate) fn check(command: &str, response: &Value) -> Vec<String> {
+    let mut divergences = Vec::new();
+    if let Some((_, schema)) = SCHEMAS.iter().find(|(known, _)| *known == command) {
+        let schema = serde_json::from_str(schema).expect("invalid embedded schema");
+        check_value(&schema, response, "$", &mut divergences);
+    }
+    divergences
+}
+
+fn check_value(schema: &Value, value: &Value, path: &str, divergences: &mut Vec<String>) {
+    if let Some(expected) = schema["type"].as_str() {
+        if !is_of_type(value, expected) {
+            divergences.push(format!("{}: expected {}, found {}", path, expected, value));
+            return;
+        }
+    }
+
+    if let Value::Object(fields) = value {
+        let properties = schema["properties"].as_object();
+        let required = schema["required"].as_array().into_iter().flatten();
+        for field in required.filter_map(Value::as_str) {
+            if !fields.contains_key(field) {
+                divergences.push(format!("{}: missing field `{}`", path, field));
+            }
+        }
+        for (field, value) in fields {
+            let path = format!("{}.{}", path, field);
+            match properties.and_then(|properties| properties.get(field)) {
+                Some(schema) => check_value(schema, value, &path, divergences),
+                None if schema["additionalProperties"] == Value::Bool(false) => {
+                    divergences.push(format!("{}: unknown field", path))
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            check_value(schema, item, &format!("{}[{}]", path, i), divergences);
+        }
+    }
+}
+
+fn is_of_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_embedded_schemas() {
+        for (command, schema) in SCHEMAS {
+            assert!(serde_json::from_str::<Value>(schema).is_ok(), "{}", command);
+        }
+        for fixture in [
+            "hs100_hw1",
+            "hs110_hw2",
+            "hs300_hw1",
+            "lb110_hw1",
+            "kl130_hw1",
+        ] {
+            let path = format!("{}/fixtures/{}.json", env!("CARGO_MANIFEST_DIR"), fixture);
+            let device: Value =
+                serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+            let sysinfo = &device["system"]["get_sysinfo"];
+            assert_eq!(
+                check("get_sysinfo", sysinfo),
+                Vec::<String>::new(),
+                "{}",
+                fixture
+            );
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let time = json!({ "year": 2024, "month": "3", "hour": 7, "min": 0, "sec": 0, "tz": 1 });
+        assert_eq!(
+            check("get_time", &time),
+            [
+                "$: missing field `mday`",
+                "$.month: expected integer, found \"3\"",
+                "$.tz: unknown field",
+            ]
+        );
+        assert!(check("get_time", &json!([])).len() == 1);
+        assert!(check("set_relay_state", &json!({ "anything": 1 })).is_empty());
+    }
+}
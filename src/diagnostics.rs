@@ -0,0 +1,248 @@
+pub use crate::proto::TransportStats;
+
+use crate::cloud::CloudInfo;
+use crate::error::Result;
+use crate::proto::{Command, Proto, Request};
+use crate::sysinfo;
+use crate::time::{self, DeviceTime};
+
+use serde::Serialize;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A self-test report of a device, suitable for a support bundle.
+///
+/// Every probe is independent: a probe that fails is reported as `None`
+/// rather than failing the whole report. When the device is unreachable,
+/// the remaining probes are skipped.
+///
+/// # Examples
+///
+/// ```no_run
+/// let plug = tplink::Plug::new([192, 168, 1, 100]);
+/// let report = plug.diagnostics();
+/// if !report.is_reachable() {
+///     println!("{} is offline", report.host());
+/// }
+/// println!("{}", report);
+/// ```
+#[derive(Debug, Serialize)]
+pub struct Diagnostics {
+    host: IpAddr,
+    latency: Option<Duration>,
+    sw_ver: Option<String>,
+    rssi: Option<i64>,
+    cloud_bound: Option<bool>,
+    device_time: Option<DeviceTime>,
+    time_drift: Option<i64>,
+    transport: TransportStats,
+}
+
+impl Diagnostics {
+    pub(crate) fn collect<C, T>(proto: &Proto, cloud_info: C, time: T) -> Diagnostics
+    where
+        C: FnOnce() -> Result<CloudInfo>,
+        T: FnOnce() -> Result<DeviceTime>,
+    {
+        let mut report = Diagnostics {
+            host: proto.host(),
+            latency: None,
+            sw_ver: None,
+            rssi: None,
+            cloud_bound: None,
+            device_time: None,
+            time_drift: None,
+            transport: TransportStats::default(),
+        };
+
+        let start = Instant::now();
+        match proto.send_request(&Request::from(Command::GetSysinfo)) {
+            Ok(response) => {
+                report.latency = Some(start.elapsed());
+                match sysinfo::parse_sysinfo(response) {
+                    Ok(info) => {
+                        report.sw_ver = Some(String::from(info.sw_ver()));
+                        report.rssi = info.rssi();
+                    }
+                    Err(e) => log::debug!("diagnostics: invalid sysinfo: {}", e),
+                }
+
+                report.cloud_bound = cloud_info()
+                    .map(|info| info.bounded())
+                    .map_err(|e| log::debug!("diagnostics: cloud info: {}", e))
+                    .ok();
+
+                if let Ok(device_time) =
+                    time().map_err(|e| log::debug!("diagnostics: device time: {}", e))
+                {
+                    report.time_drift = time_drift(&device_time, SystemTime::now());
+                    report.device_time = Some(device_time);
+                }
+            }
+            Err(e) => log::debug!("diagnostics: {} is unreachable: {}", proto.host(), e),
+        }
+
+        report.transport = proto.stats();
+        report
+    }
+
+    /// Returns the address of the device.
+    pub fn host(&self) -> IpAddr {
+        self.host
+    }
+
+    /// Returns whether the device responded to the reachability probe.
+    pub fn is_reachable(&self) -> bool {
+        self.latency.is_some()
+    }
+
+    /// Returns the round-trip time of the reachability probe.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    /// Returns the firmware version of the device.
+    pub fn sw_ver(&self) -> Option<&str> {
+        self.sw_ver.as_deref()
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device.
+    pub fn rssi(&self) -> Option<i64> {
+        self.rssi
+    }
+
+    /// Returns whether the device is bound to a cloud account.
+    pub fn is_cloud_bound(&self) -> Option<bool> {
+        self.cloud_bound
+    }
+
+    /// Returns the local time reported by the device.
+    pub fn device_time(&self) -> Option<&DeviceTime> {
+        self.device_time.as_ref()
+    }
+
+    /// Returns how many seconds the device clock is ahead of the host
+    /// clock (negative when behind).
+    ///
+    /// The device reports its local time without a UTC offset, so the
+    /// offset is taken to be the difference rounded to the nearest quarter
+    /// hour and the remainder is reported as the drift. Drifts of more than
+    /// 7.5 minutes therefore cannot be told apart from a timezone offset.
+    pub fn time_drift(&self) -> Option<i64> {
+        self.time_drift
+    }
+
+    /// Returns the transport counters of the connection to the device,
+    /// including the requests sent by the probes themselves.
+    pub fn transport(&self) -> TransportStats {
+        self.transport
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_string(&self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+/// The value returned by a call, along with how it was served, as returned
+/// by the `*_with_meta` variants of device methods.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = tplink::Config::for_host([192, 168, 1, 100])
+///     .with_cache_enabled(Duration::from_secs(5), None)
+///     .build();
+/// let plug = tplink::Plug::with_config(config);
+/// for _ in 0..2 {
+///     let call = plug.sysinfo_with_meta()?;
+///     println!(
+///         "served from cache: {}, in {:?}",
+///         call.served_from_cache(),
+///         call.latency()
+///     );
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CallResult<T> {
+    value: T,
+    served_from_cache: bool,
+    latency: Duration,
+}
+
+impl<T> CallResult<T> {
+    /// Returns the value returned by the call.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns the value returned by the call, consuming the result.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    /// Returns whether the call was served without sending any request
+    /// to the device.
+    pub fn served_from_cache(&self) -> bool {
+        self.served_from_cache
+    }
+
+    /// Returns how long the call took.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+}
+
+/// Makes the given call, recording whether it sent any request to the
+/// device and how long it took.
+pub(crate) fn measure<T, F>(proto: &Proto, call: F) -> Result<CallResult<T>>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let requests = proto.stats().requests();
+    let start = Instant::now();
+    let value = call()?;
+    Ok(CallResult {
+        value,
+        served_from_cache: proto.stats().requests() == requests,
+        latency: start.elapsed(),
+    })
+}
+
+fn time_drift(device_time: &DeviceTime, now: SystemTime) -> Option<i64> {
+    let host = now.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    let diff = device_time.naive_unix_secs() - host;
+    Some(time::strip_utc_offset(diff * 1000) / 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_time_drift() {
+        // 2020-04-08 22:29:07 UTC
+        let now = UNIX_EPOCH + Duration::from_secs(1_586_384_947);
+        let time = |day, hour, min, sec| -> DeviceTime {
+            serde_json::from_value(json!({
+                "year": 2020, "month": 4, "mday": day, "hour": hour, "min": min, "sec": sec
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(time_drift(&time(8, 22, 29, 7), now), Some(0));
+        // UTC-4, 12 seconds fast
+        assert_eq!(time_drift(&time(8, 18, 29, 19), now), Some(12));
+        // UTC+5:30, 3 seconds slow
+        assert_eq!(time_drift(&time(9, 3, 59, 4), now), Some(-3));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::ack::Ack;
+use crate::cache::{self, ResponseCache};
+use crate::error::Result;
+use crate::proto::{Proto, Request};
+use crate::schema;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone)]
+pub(super) struct Dimming {
+    ns: String,
+    proto: Rc<Proto>,
+    cache: Arc<ResponseCache>,
+}
+
+impl Dimming {
+    pub(super) fn new(ns: &str, proto: Rc<Proto>, cache: Arc<ResponseCache>) -> Dimming {
+        Dimming {
+            ns: String::from(ns),
+            proto,
+            cache,
+        }
+    }
+
+    pub(super) fn get_parameters(&self) -> Result<DimmerParameters> {
+        let request = Request::new(&self.ns, "get_dimmer_parameters", None);
+
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        schema::parse(self.proto.host(), response)
+    }
+
+    pub(super) fn set_brightness(&self, brightness: u32) -> Result<Ack> {
+        self.send_command("set_brightness", json!({ "brightness": brightness }))
+    }
+
+    pub(super) fn set_gentle_on_time(&self, duration: Duration) -> Result<Ack> {
+        self.send_command(
+            "set_gentle_on_time",
+            json!({ "duration": duration.as_millis() as u64 }),
+        )
+    }
+
+    pub(super) fn set_gentle_off_time(&self, duration: Duration) -> Result<Ack> {
+        self.send_command(
+            "set_gentle_off_time",
+            json!({ "duration": duration.as_millis() as u64 }),
+        )
+    }
+
+    pub(super) fn transition(&self, brightness: u32, duration: Duration) -> Result<Ack> {
+        self.send_command(
+            "set_dimmer_transition",
+            json!({ "brightness": brightness, "duration": duration.as_millis() as u64 }),
+        )
+    }
+
+    fn send_command(&self, command: &str, arg: serde_json::Value) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache
+                .lock()
+                .unwrap()
+                .retain(|k, _| k.target != self.ns && k.target != "system")
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::new(&self.ns, command, Some(arg)))?;
+
+        log::trace!("({}) {:?}", self.ns, response);
+
+        Ack::from_response(response)
+    }
+}
+
+/// The dimming parameters of a dimmer-capable device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DimmerParameters {
+    #[serde(alias = "minThreshold")]
+    min_threshold: u32,
+    #[serde(alias = "fadeOnTime")]
+    fade_on_time: u64,
+    #[serde(alias = "fadeOffTime")]
+    fade_off_time: u64,
+    #[serde(alias = "gentleOnTime")]
+    gentle_on_time: u64,
+    #[serde(alias = "gentleOffTime")]
+    gentle_off_time: u64,
+    #[serde(alias = "rampRate")]
+    ramp_rate: u32,
+}
+
+impl DimmerParameters {
+    /// Returns the minimum brightness threshold of the dimmer.
+    pub fn min_threshold(&self) -> u32 {
+        self.min_threshold
+    }
+
+    /// Returns the duration over which the light fades in when switched on.
+    pub fn fade_on_time(&self) -> Duration {
+        Duration::from_millis(self.fade_on_time)
+    }
+
+    /// Returns the duration over which the light fades out when switched off.
+    pub fn fade_off_time(&self) -> Duration {
+        Duration::from_millis(self.fade_off_time)
+    }
+
+    /// Returns the duration over which the light ramps up on a gentle on.
+    pub fn gentle_on_time(&self) -> Duration {
+        Duration::from_millis(self.gentle_on_time)
+    }
+
+    /// Returns the duration over which the light ramps down on a gentle off.
+    pub fn gentle_off_time(&self) -> Duration {
+        Duration::from_millis(self.gentle_off_time)
+    }
+
+    /// Returns the ramp rate of the dimmer.
+    pub fn ramp_rate(&self) -> u32 {
+        self.ramp_rate
+    }
+}
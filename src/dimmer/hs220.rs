@@ -0,0 +1,433 @@
+use super::dimming::{DimmerParameters, Dimming};
+use crate::ack::Ack;
+use crate::cache::{self, Persister, ResponseCache};
+use crate::cloud::{Cloud, CloudInfo, CloudSettings};
+use crate::config::{BrightnessConfig, Config};
+use crate::device::Device;
+use crate::diagnostics::{self, CallResult, Diagnostics};
+use crate::error::{self, Result};
+use crate::proto::{Command, Proto, Request};
+use crate::shared::Shared;
+use crate::sys::{Sys, System};
+use crate::sysinfo::{self, FeatureFlags, MacAddress, SysInfo, SystemInfo};
+use crate::time::{DeviceTime, DeviceTimeZone, Time, TimeSettings};
+use crate::timer::{Rule, RuleId, RuleList, Timer, TimerSettings};
+use crate::validate;
+use crate::wlan::{AccessPoint, Netif, Wlan};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A TP-Link Wi-Fi Smart Dimmer Switch (HS220).
+#[derive(Clone)]
+pub struct HS220 {
+    proto: Rc<Proto>,
+    cache: Arc<ResponseCache>,
+    _persister: Arc<Persister>,
+    brightness_config: BrightnessConfig,
+    system: System,
+    dimming: Dimming,
+    time_settings: TimeSettings,
+    timer_settings: TimerSettings,
+    cloud_settings: CloudSettings,
+    netif: Netif,
+    sysinfo: SystemInfo<HS220Info>,
+}
+
+impl HS220 {
+    pub(super) fn new<A>(host: A) -> HS220
+    where
+        A: Into<IpAddr>,
+    {
+        HS220::with_config(Config::for_host(host).build())
+    }
+
+    pub(super) fn with_config(config: Config) -> HS220 {
+        let Shared {
+            proto,
+            cache,
+            persister,
+        } = Shared::new(&config);
+
+        let mut device = HS220 {
+            system: System::new("system", proto.clone(), cache.clone()),
+            dimming: Dimming::new("smartlife.iot.dimmer", proto.clone(), cache.clone()),
+            time_settings: TimeSettings::new("time", proto.clone()),
+            timer_settings: TimerSettings::new("count_down", proto.clone(), cache.clone()),
+            cloud_settings: CloudSettings::new("cnCloud", proto.clone(), cache.clone()),
+            netif: Netif::new(proto.clone()),
+            sysinfo: SystemInfo::new(proto.clone(), cache.clone()),
+            _persister: persister,
+            proto,
+            cache,
+            brightness_config: config.brightness_config,
+        };
+        device
+            .system
+            .set_reset_confirmation(config.reset_confirmation);
+
+        device
+    }
+
+    pub(super) fn from_sysinfo(config: Config, sysinfo: Value) -> Result<HS220> {
+        let response = sysinfo::into_response(sysinfo);
+        HS220Info::deserialize(&response["system"]["get_sysinfo"]).map_err(error::json)?;
+
+        let device = HS220::with_config(config);
+        cache::seed(&device.cache, &response);
+        Ok(device)
+    }
+
+    pub(super) fn with_meta<R, F>(&self, call: F) -> Result<CallResult<R>>
+    where
+        F: FnOnce(&HS220) -> Result<R>,
+    {
+        diagnostics::measure(&self.proto, || call(self))
+    }
+
+    pub(super) fn diagnostics(&self) -> Diagnostics {
+        Diagnostics::collect(
+            &self.proto,
+            || self.cloud_settings.get_info(),
+            || self.time_settings.get_time(),
+        )
+    }
+
+    pub(super) fn sw_ver(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.sw_ver)
+    }
+
+    pub(super) fn hw_ver(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.hw_ver)
+    }
+
+    pub(super) fn model(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.model)
+    }
+
+    pub(super) fn alias(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.alias)
+    }
+
+    pub(super) fn mac_address(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.mac.to_string())
+    }
+
+    pub(super) fn rssi(&self) -> Result<i64> {
+        self.sysinfo().map(|sysinfo| sysinfo.rssi)
+    }
+
+    pub(super) fn brightness(&self) -> Result<u32> {
+        self.sysinfo().map(|sysinfo| sysinfo.brightness)
+    }
+
+    pub(super) fn set_brightness(&mut self, brightness: u32) -> Result<Ack> {
+        // The model is only looked up to describe an invalid brightness.
+        if brightness > 100 {
+            let op = format!("{} set_brightness", self.model()?);
+            validate::percent(&op, "brightness", brightness)?;
+        }
+        self.dimming
+            .set_brightness(self.brightness_config.apply(brightness))
+    }
+
+    pub(super) fn dimmer_parameters(&self) -> Result<DimmerParameters> {
+        self.dimming.get_parameters()
+    }
+
+    pub(super) fn set_gentle_on_time(&mut self, duration: Duration) -> Result<Ack> {
+        self.dimming.set_gentle_on_time(duration)
+    }
+
+    pub(super) fn set_gentle_off_time(&mut self, duration: Duration) -> Result<Ack> {
+        self.dimming.set_gentle_off_time(duration)
+    }
+
+    pub(super) fn gentle_on(&mut self) -> Result<Ack> {
+        let brightness = self.brightness()?;
+        let duration = self.dimmer_parameters()?.gentle_on_time();
+        self.dimming.transition(brightness, duration)
+    }
+
+    pub(super) fn gentle_off(&mut self) -> Result<Ack> {
+        let duration = self.dimmer_parameters()?.gentle_off_time();
+        self.dimming.transition(0, duration)
+    }
+}
+
+impl Device for HS220 {
+    fn turn_on(&mut self) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetRelayState { on: true }))?;
+
+        log::trace!("(system) {:?}", response);
+
+        Ack::from_response(response)
+    }
+
+    fn turn_off(&mut self) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetRelayState { on: false }))?;
+
+        log::trace!("(system) {:?}", response);
+
+        Ack::from_response(response)
+    }
+
+    fn is_on(&self) -> Result<bool> {
+        self.sysinfo().map(|sysinfo| sysinfo.is_on())
+    }
+
+    fn is_on_fresh(&self) -> Result<bool> {
+        self.sysinfo_fresh().map(|sysinfo| sysinfo.is_on())
+    }
+}
+
+impl Sys for HS220 {
+    fn reboot(&mut self, delay: Option<Duration>) -> Result<Ack> {
+        self.system.reboot(delay)
+    }
+
+    fn factory_reset(&mut self, delay: Option<Duration>) -> Result<Ack> {
+        self.system.reset(delay)
+    }
+
+    fn factory_reset_confirmed(
+        &mut self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack> {
+        self.system.reset_confirmed(confirmation, delay)
+    }
+}
+
+impl Time for HS220 {
+    fn time(&self) -> Result<DeviceTime> {
+        self.time_settings.get_time()
+    }
+
+    fn timezone(&self) -> Result<DeviceTimeZone> {
+        self.time_settings.get_timezone()
+    }
+}
+
+impl Timer for HS220 {
+    fn get_timer_rules(&self) -> Result<RuleList> {
+        self.timer_settings.get_rules()
+    }
+
+    fn add_timer_rule(&mut self, rule: Rule) -> Result<RuleId> {
+        let is_table_empty = self.get_timer_rules().map(|list| list.is_empty())?;
+        if is_table_empty {
+            self.timer_settings.add_rule(rule)
+        } else {
+            Err(error::unsupported_operation(
+                "add_timer_rule: table is full",
+            ))
+        }
+    }
+
+    fn edit_timer_rule(&mut self, id: &RuleId, rule: Rule) -> Result<Ack> {
+        self.timer_settings.edit_rule(id, rule)
+    }
+
+    fn delete_timer_rule_with_id(&mut self, id: &RuleId) -> Result<Ack> {
+        self.timer_settings.delete_rule_with_id(id)
+    }
+
+    fn delete_all_timer_rules(&mut self) -> Result<Ack> {
+        self.timer_settings.delete_all_rules()
+    }
+}
+
+impl Cloud for HS220 {
+    fn get_cloud_info(&self) -> Result<CloudInfo> {
+        self.cloud_settings.get_info()
+    }
+
+    fn bind(&mut self, username: &str, password: &str) -> Result<Ack> {
+        self.cloud_settings.bind(username, password)
+    }
+
+    fn unbind(&mut self) -> Result<Ack> {
+        self.cloud_settings.unbind()
+    }
+
+    fn get_firmware_list(&self) -> Result<Vec<String>> {
+        self.cloud_settings.get_firmware_list()
+    }
+
+    fn set_server_url(&mut self, url: &str) -> Result<Ack> {
+        self.cloud_settings.set_server_url(url)
+    }
+}
+
+impl Wlan for HS220 {
+    fn get_scan_info(&self, refresh: bool, timeout: Option<Duration>) -> Result<Vec<AccessPoint>> {
+        self.netif.get_scan_info(refresh, timeout)
+    }
+}
+
+impl SysInfo for HS220 {
+    type Info = HS220Info;
+
+    fn sysinfo(&self) -> Result<Self::Info> {
+        self.sysinfo.get_sysinfo()
+    }
+
+    fn sysinfo_fresh(&self) -> Result<Self::Info> {
+        self.sysinfo.get_sysinfo_fresh()
+    }
+}
+
+/// The system information of TP-Link Wi-Fi Smart Dimmer Switch (HS220).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HS220Info {
+    sw_ver: String,
+    hw_ver: String,
+    model: String,
+    #[serde(rename = "type")]
+    device_type: String,
+    mac: MacAddress,
+    alias: String,
+    relay_state: u64,
+    brightness: u32,
+    rssi: i64,
+    led_off: u64,
+    #[serde(default)]
+    feature: FeatureFlags,
+    #[serde(flatten)]
+    other: Map<String, Value>,
+}
+
+impl HS220Info {
+    /// Returns the software version of the device.
+    pub fn sw_ver(&self) -> &str {
+        &self.sw_ver
+    }
+
+    /// Returns the hardware version of the device.
+    pub fn hw_ver(&self) -> &str {
+        &self.hw_ver
+    }
+
+    /// Returns the model of the device.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Returns the name (alias) of the device.
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    /// Returns the mac address of the device, formatted as
+    /// `AA:BB:CC:DD:EE:FF`.
+    pub fn mac_address(&self) -> &str {
+        self.mac.as_str()
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device.
+    pub fn rssi(&self) -> i64 {
+        self.rssi
+    }
+
+    /// Returns the current % brightness of the dimmer.
+    pub fn brightness(&self) -> u32 {
+        self.brightness
+    }
+
+    /// Returns the features advertised by the device.
+    pub fn features(&self) -> FeatureFlags {
+        self.feature
+    }
+
+    /// Returns the system information as JSON, as reported by the device.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(error::json)
+    }
+
+    /// Returns whether the device is on.
+    fn is_on(&self) -> bool {
+        self.relay_state == 1
+    }
+}
+
+impl fmt::Display for HS220Info {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        sysinfo::write_fields(
+            f,
+            &[
+                ("alias", &self.alias),
+                ("model", &self.model),
+                ("hw_ver", &self.hw_ver),
+                ("sw_ver", &self.sw_ver),
+                ("mac", &self.mac),
+                ("state", &sysinfo::on_off(self.is_on())),
+                ("brightness", &format!("{}%", self.brightness)),
+                ("led", &sysinfo::on_off(self.led_off == 0)),
+                ("rssi", &format!("{} dBm", self.rssi)),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> Value {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name);
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sysinfo() {
+        let mut value = fixture("hs220_hw1.json");
+        let info: HS220Info =
+            crate::schema::parse([127, 0, 0, 1].into(), value["system"]["get_sysinfo"].take())
+                .unwrap();
+        assert_eq!(info.alias(), "Living Room");
+        assert_eq!(info.model(), "HS220(US)");
+        assert_eq!(info.mac_address(), "B0:BE:76:00:00:04");
+        assert_eq!(info.brightness(), 60);
+        assert_eq!(info.rssi(), -47);
+    }
+
+    #[test]
+    fn test_from_sysinfo_seeds_cache() {
+        // Nothing listens on the address, so reads must be served from the cache.
+        let config = Config::for_host([127, 0, 0, 1])
+            .with_cache_enabled(Duration::from_secs(60), None)
+            .build();
+
+        let dimmer = HS220::from_sysinfo(config, fixture("hs220_hw1.json")).unwrap();
+        assert_eq!(dimmer.sysinfo().unwrap().alias(), "Living Room");
+
+        let params = dimmer.dimmer_parameters().unwrap();
+        assert_eq!(params.min_threshold(), 11);
+        assert_eq!(params.fade_on_time(), Duration::from_secs(1));
+        assert_eq!(params.gentle_on_time(), Duration::from_secs(3));
+        assert_eq!(params.gentle_off_time(), Duration::from_secs(10));
+        assert_eq!(params.ramp_rate(), 30);
+    }
+}
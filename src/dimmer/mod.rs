@@ -0,0 +1,625 @@
+mod dimming;
+mod hs220;
+
+pub use self::dimming::DimmerParameters;
+pub use self::hs220::{HS220Info, HS220};
+use crate::ack::Ack;
+use crate::cloud::{self, Cloud, CloudInfo, FirmwareInfo};
+use crate::config::Config;
+use crate::device::Device;
+use crate::diagnostics::{CallResult, Diagnostics};
+use crate::error::Result;
+use crate::models::ModelInfo;
+use crate::sys::Sys;
+use crate::sysinfo::SysInfo;
+use crate::time::{ClockDrift, DeviceTime, DeviceTimeZone, Time};
+use crate::timer::{Rule, RuleId, RuleList, RulePatch, Timer};
+use crate::wlan::{AccessPoint, ScannedAccessPoint, Wlan};
+
+use serde_json::Value;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// A TP-Link Smart Dimmer Switch.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+///
+///     dimmer.set_gentle_on_time(Duration::from_secs(3))?;
+///     dimmer.gentle_on()?;
+///
+///     dimmer.set_gentle_off_time(Duration::from_secs(10))?;
+///     dimmer.gentle_off()?;
+///
+///     Ok(())
+/// }
+/// ```
+///
+/// Cloning a dimmer is cheap: the clone is another handle to the same device,
+/// sharing its connection settings and response cache.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+/// let mut handle = dimmer.clone();
+///
+/// handle.turn_on()?;
+/// assert!(dimmer.is_on()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Dimmer<T> {
+    device: T,
+}
+
+impl_device_traits!(Dimmer);
+
+impl<T: Device> Dimmer<T> {
+    /// Turns on the dimmer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// dimmer.turn_on()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn turn_on(&mut self) -> Result<Ack> {
+        self.device.turn_on()
+    }
+
+    /// Turns off the dimmer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// dimmer.turn_off()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn turn_off(&mut self) -> Result<Ack> {
+        self.device.turn_off()
+    }
+
+    /// Returns whether the device is currently switched on.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let is_on = dimmer.is_on()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_on(&self) -> Result<bool> {
+        self.device.is_on()
+    }
+
+    /// Returns whether the device is currently switched on, always querying
+    /// the device rather than a cached response. The fresh response replaces
+    /// the cached one, if caching is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let is_on = dimmer.is_on_fresh()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_on_fresh(&self) -> Result<bool> {
+        self.device.is_on_fresh()
+    }
+
+    /// Toggles the dimmer and returns the power state it was switched to.
+    ///
+    /// The current state is read and then inverted; see [`Device::toggle`]
+    /// for how concurrent changes by other clients are handled.
+    ///
+    /// [`Device::toggle`]: device/trait.Device.html#method.toggle
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let is_on = dimmer.toggle()?;
+    /// println!("dimmer is now {}", if is_on { "on" } else { "off" });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn toggle(&mut self) -> Result<bool> {
+        self.device.toggle()
+    }
+}
+
+impl<T: Sys> Dimmer<T> {
+    /// Reboots the dimmer after the given duration. In case when the delay
+    /// duration is not provided, the dimmer is set to reboot after a default
+    /// delay of 1 second.
+    pub fn reboot(&mut self, delay: Option<Duration>) -> Result<Ack> {
+        self.device.reboot(delay)
+    }
+
+    /// Factory resets the dimmer after the given duration. In case when the
+    /// delay duration is not provided, the dimmer is set to reset after a
+    /// default delay of 1 second.
+    pub fn factory_reset(&mut self, delay: Option<Duration>) -> Result<Ack> {
+        self.device.factory_reset(delay)
+    }
+
+    /// Factory resets the dimmer after the given duration, once the given
+    /// confirmation matches either the alias or the id of the dimmer. This is
+    /// the only way to reset a dimmer whose config requires reset confirmation.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let config = tplink::Config::for_host([192, 168, 1, 102])
+    ///     .with_reset_confirmation(true)
+    ///     .build();
+    /// let mut dimmer = tplink::Dimmer::with_config(config);
+    /// assert!(dimmer.factory_reset(None).is_err());
+    /// dimmer.factory_reset_confirmed("Living Room", None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn factory_reset_confirmed(
+        &mut self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack> {
+        self.device.factory_reset_confirmed(confirmation, delay)
+    }
+}
+
+impl<T: Time> Dimmer<T> {
+    /// Returns the current date and time of the device without the timezone.
+    pub fn time(&self) -> Result<DeviceTime> {
+        self.device.time()
+    }
+
+    /// Returns the current timezone of the device.
+    pub fn timezone(&self) -> Result<DeviceTimeZone> {
+        self.device.timezone()
+    }
+
+    /// Measures how far the clock of the dimmer is ahead of or behind the
+    /// host's clock, see [`Time::clock_drift`].
+    ///
+    /// [`Time::clock_drift`]: time/trait.Time.html#method.clock_drift
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let drift = dimmer.clock_drift()?;
+    /// println!("clock drift: {:+.1}s", drift.as_secs_f64());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clock_drift(&self) -> Result<ClockDrift> {
+        self.device.clock_drift()
+    }
+}
+
+impl<T: Timer> Dimmer<T> {
+    pub fn get_timer_rules(&self) -> Result<RuleList> {
+        self.device.get_timer_rules()
+    }
+
+    pub fn add_timer_rule(&mut self, rule: Rule) -> Result<RuleId> {
+        self.device.add_timer_rule(rule)
+    }
+
+    pub fn edit_timer_rule(&mut self, id: &RuleId, rule: Rule) -> Result<Ack> {
+        self.device.edit_timer_rule(id, rule)
+    }
+
+    pub fn patch_timer_rule(&mut self, id: &RuleId, patch: &RulePatch) -> Result<Ack> {
+        self.device.patch_timer_rule(id, patch)
+    }
+
+    pub fn update_timer_rule<F>(&mut self, id: &RuleId, f: F) -> Result<Ack>
+    where
+        F: FnOnce(&mut Rule),
+    {
+        self.device.update_timer_rule(id, f)
+    }
+
+    pub fn delete_timer_rule_with_id(&mut self, id: &RuleId) -> Result<Ack> {
+        self.device.delete_timer_rule_with_id(id)
+    }
+
+    pub fn delete_all_timer_rules(&mut self) -> Result<Ack> {
+        self.device.delete_all_timer_rules()
+    }
+}
+
+impl<T: Cloud> Dimmer<T> {
+    pub fn get_cloud_info(&self) -> Result<CloudInfo> {
+        self.device.get_cloud_info()
+    }
+
+    pub fn bind(&mut self, username: &str, password: &str) -> Result<Ack> {
+        self.device.bind(username, password)
+    }
+
+    pub fn unbind(&mut self) -> Result<Ack> {
+        self.device.unbind()
+    }
+
+    pub fn get_firmware_list(&self) -> Result<Vec<String>> {
+        self.device.get_firmware_list()
+    }
+
+    pub fn set_server_url(&mut self, url: &str) -> Result<Ack> {
+        self.device.set_server_url(url)
+    }
+}
+
+impl<T: Wlan> Dimmer<T> {
+    pub fn get_scan_info(
+        &self,
+        refresh: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<AccessPoint>> {
+        self.device.get_scan_info(refresh, timeout)
+    }
+
+    pub fn scan_aggregate(
+        &self,
+        attempts: u32,
+        delay: Duration,
+    ) -> Result<Vec<ScannedAccessPoint>> {
+        self.device.scan_aggregate(attempts, delay)
+    }
+}
+
+impl<T: SysInfo> Dimmer<T> {
+    /// Returns the dimmer's system information.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let sysinfo = dimmer.sysinfo()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sysinfo(&self) -> Result<T::Info> {
+        self.device.sysinfo()
+    }
+
+    /// Returns the dimmer's system information, always querying the device
+    /// rather than a cached response. The fresh response replaces the cached
+    /// one, if caching is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let sysinfo = dimmer.sysinfo_fresh()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sysinfo_fresh(&self) -> Result<T::Info> {
+        self.device.sysinfo_fresh()
+    }
+}
+
+impl Dimmer<HS220> {
+    /// Creates a new Dimmer instance from the given local address.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// ```
+    pub fn new<A>(host: A) -> Dimmer<HS220>
+    where
+        A: Into<IpAddr>,
+    {
+        Dimmer {
+            device: HS220::new(host),
+        }
+    }
+
+    pub fn with_config(config: Config) -> Dimmer<HS220> {
+        Dimmer {
+            device: HS220::with_config(config),
+        }
+    }
+
+    /// Creates a new Dimmer instance from the given local address and the
+    /// system information already fetched from the device, e.g. a discovery
+    /// response or its `get_sysinfo` object.
+    ///
+    /// If caching is enabled, the responses contained in the information
+    /// are cached, so that they are not fetched again right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Json` error if the system information is not that of a
+    /// dimmer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use serde_json::json;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let sysinfo = json!({});
+    /// let dimmer = tplink::Dimmer::from_sysinfo([192, 168, 1, 102], sysinfo)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_sysinfo<A>(host: A, sysinfo: Value) -> Result<Dimmer<HS220>>
+    where
+        A: Into<IpAddr>,
+    {
+        Dimmer::from_sysinfo_with_config(Config::for_host(host).build(), sysinfo)
+    }
+
+    /// Creates a new Dimmer instance with the given configuration and the
+    /// system information already fetched from the device. See
+    /// [`from_sysinfo`](#method.from_sysinfo).
+    pub fn from_sysinfo_with_config(config: Config, sysinfo: Value) -> Result<Dimmer<HS220>> {
+        HS220::from_sysinfo(config, sysinfo).map(|device| Dimmer { device })
+    }
+
+    /// Returns the software version of the device.
+    pub fn sw_ver(&self) -> Result<String> {
+        self.device.sw_ver()
+    }
+
+    /// Runs a self-test of the device and returns a report of its
+    /// reachability, latency, signal strength, cloud binding, clock drift,
+    /// firmware version and transport counters.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// println!("{}", dimmer.diagnostics());
+    /// ```
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.device.diagnostics()
+    }
+
+    /// Returns the system information of the dimmer, like [`sysinfo`], along
+    /// with whether it was served from the cache and how long it took.
+    ///
+    /// [`sysinfo`]: #method.sysinfo
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let call = dimmer.sysinfo_with_meta()?;
+    /// println!("{:?} (cached: {})", call.latency(), call.served_from_cache());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sysinfo_with_meta(&self) -> Result<CallResult<<HS220 as SysInfo>::Info>> {
+        self.device.with_meta(|device| device.sysinfo())
+    }
+
+    /// Returns the newest firmware update available for the device, if
+    /// any, by comparing its software version against the firmware list
+    /// reported by the cloud service.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// if let Some(update) = dimmer.update_available()? {
+    ///     println!("update available: {}", update);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_available(&self) -> Result<Option<FirmwareInfo>> {
+        cloud::update_available(&self.sw_ver()?, &self.get_firmware_list()?)
+    }
+
+    /// Returns the hardware version of the device.
+    pub fn hw_ver(&self) -> Result<String> {
+        self.device.hw_ver()
+    }
+
+    /// Returns the model of the device.
+    pub fn model(&self) -> Result<String> {
+        self.device.model()
+    }
+
+    /// Returns the detected model of the device along with the level of
+    /// support the library offers for it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let model_info = dimmer.model_info()?;
+    /// println!("{}: {:?}", model_info.name(), model_info.support_level());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn model_info(&self) -> Result<ModelInfo> {
+        self.model().map(|model| ModelInfo::detect(&model))
+    }
+
+    /// Returns the name (alias) of the device.
+    pub fn alias(&self) -> Result<String> {
+        self.device.alias()
+    }
+
+    /// Returns the mac address of the device.
+    pub fn mac_address(&self) -> Result<String> {
+        self.device.mac_address()
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device.
+    pub fn rssi(&self) -> Result<i64> {
+        self.device.rssi()
+    }
+
+    /// Returns the current % brightness of the dimmer.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// println!("% brightness: {}", dimmer.brightness()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn brightness(&self) -> Result<u32> {
+        self.device.brightness()
+    }
+
+    /// Sets the % brightness of the dimmer, expressed as a number from
+    /// 0 to 100 percent.
+    ///
+    /// The brightness is mapped through the dimming curve and minimum
+    /// brightness set on the dimmer's [`Config`], if any.
+    ///
+    /// [`Config`]: struct.Config.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// dimmer.set_brightness(40)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_brightness(&mut self, brightness: u32) -> Result<Ack> {
+        self.device.set_brightness(brightness)
+    }
+
+    /// Returns the dimming parameters of the device, including the
+    /// configured gentle on/off times.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// let params = dimmer.dimmer_parameters()?;
+    /// println!("gentle on time: {:?}", params.gentle_on_time());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dimmer_parameters(&self) -> Result<DimmerParameters> {
+        self.device.dimmer_parameters()
+    }
+
+    /// Returns the duration over which the light ramps up on a gentle on.
+    pub fn gentle_on_time(&self) -> Result<Duration> {
+        self.device
+            .dimmer_parameters()
+            .map(|params| params.gentle_on_time())
+    }
+
+    /// Returns the duration over which the light ramps down on a gentle off.
+    pub fn gentle_off_time(&self) -> Result<Duration> {
+        self.device
+            .dimmer_parameters()
+            .map(|params| params.gentle_off_time())
+    }
+
+    /// Sets the duration over which the light ramps up on a gentle on.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// dimmer.set_gentle_on_time(Duration::from_secs(3))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_gentle_on_time(&mut self, duration: Duration) -> Result<Ack> {
+        self.device.set_gentle_on_time(duration)
+    }
+
+    /// Sets the duration over which the light ramps down on a gentle off.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// dimmer.set_gentle_off_time(Duration::from_secs(10))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_gentle_off_time(&mut self, duration: Duration) -> Result<Ack> {
+        self.device.set_gentle_off_time(duration)
+    }
+
+    /// Ramps the light up to its current brightness over the configured
+    /// gentle on time, instead of switching it on instantly.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// dimmer.gentle_on()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn gentle_on(&mut self) -> Result<Ack> {
+        self.device.gentle_on()
+    }
+
+    /// Ramps the light down to off over the configured gentle off time,
+    /// instead of switching it off instantly.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut dimmer = tplink::Dimmer::new([192, 168, 1, 102]);
+    /// dimmer.gentle_off()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn gentle_off(&mut self) -> Result<Ack> {
+        self.device.gentle_off()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Dimmer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.device.fmt(f)
+    }
+}
@@ -1,12 +1,23 @@
-use crate::bulb::LB110;
-use crate::error::Result;
-use crate::plug::HS100;
-use crate::{proto, Bulb, Plug};
+use crate::ack::Ack;
+use crate::bulb::{LB110Info, LB110};
+use crate::config::CacheConfig;
+use crate::dimmer::{HS220Info, HS220};
+use crate::error::{self, Result};
+use crate::json;
+use crate::lease;
+use crate::plug::{HS100Info, HS100};
+use crate::proto::{self, SocketOptions};
+use crate::switch::{HS200Info, HS200};
+use crate::sysinfo::MacAddress;
+use crate::{Bulb, Config, Dimmer, Plug, Switch};
 
+use serde::Serialize;
 use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::net::IpAddr;
-use std::time::Duration;
+use std::collections::{hash_map, HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
 
 /// Types of TP-Link Wi-Fi Smart Home Devices.
 pub enum DeviceKind {
@@ -14,6 +25,10 @@ pub enum DeviceKind {
     Plug(Box<Plug<HS100>>),
     /// TP-Link Smart Wi-Fi Bulb.
     Bulb(Box<Bulb<LB110>>),
+    /// TP-Link Smart Wi-Fi Dimmer Switch.
+    Dimmer(Box<Dimmer<HS220>>),
+    /// TP-Link Smart Wi-Fi Light Switch.
+    Switch(Box<Switch<HS200>>),
     /// TP-Link Smart Wi-Fi Power Strip
     Strip,
     /// Encompasses any other TP-Link devices that
@@ -21,8 +36,109 @@ pub enum DeviceKind {
     Unknown,
 }
 
+impl DeviceKind {
+    /// Returns the system information of the device, whatever its kind.
+    ///
+    /// Fails with `ErrorKind::UnsupportedOperation` for power strips and
+    /// unrecognised devices, which have no typed system information.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     for (ip, device) in tplink::discover()?.iter() {
+    ///         if let Ok(info) = device.info() {
+    ///             println!("{}: {} ({})", ip, info.alias(), info.model());
+    ///         }
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn info(&self) -> Result<AnyDeviceInfo> {
+        match self {
+            DeviceKind::Plug(plug) => plug.sysinfo().map(AnyDeviceInfo::Plug),
+            DeviceKind::Bulb(bulb) => bulb.sysinfo().map(AnyDeviceInfo::Bulb),
+            DeviceKind::Dimmer(dimmer) => dimmer.sysinfo().map(AnyDeviceInfo::Dimmer),
+            DeviceKind::Switch(switch) => switch.sysinfo().map(AnyDeviceInfo::Switch),
+            DeviceKind::Strip => Err(error::unsupported_operation("strip sysinfo")),
+            DeviceKind::Unknown => Err(error::unsupported_operation("unknown device sysinfo")),
+        }
+    }
+}
+
+/// The system information of a device of any kind, with accessors for the
+/// fields common to all of them.
+#[derive(Debug)]
+pub enum AnyDeviceInfo {
+    /// The system information of a plug.
+    Plug(HS100Info),
+    /// The system information of a bulb.
+    Bulb(LB110Info),
+    /// The system information of a dimmer switch.
+    Dimmer(HS220Info),
+    /// The system information of a light switch.
+    Switch(HS200Info),
+}
+
+macro_rules! common {
+    ($self:ident, $info:ident => $field:expr) => {
+        match $self {
+            AnyDeviceInfo::Plug($info) => $field,
+            AnyDeviceInfo::Bulb($info) => $field,
+            AnyDeviceInfo::Dimmer($info) => $field,
+            AnyDeviceInfo::Switch($info) => $field,
+        }
+    };
+}
+
+impl AnyDeviceInfo {
+    /// Returns the name (alias) of the device.
+    pub fn alias(&self) -> &str {
+        common!(self, info => info.alias())
+    }
+
+    /// Returns the model of the device.
+    pub fn model(&self) -> &str {
+        common!(self, info => info.model())
+    }
+
+    /// Returns the mac address of the device.
+    pub fn mac_address(&self) -> &str {
+        common!(self, info => info.mac_address())
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device.
+    pub fn rssi(&self) -> i64 {
+        common!(self, info => info.rssi())
+    }
+
+    /// Returns the software version of the device.
+    pub fn sw_ver(&self) -> &str {
+        common!(self, info => info.sw_ver())
+    }
+
+    /// Returns the hardware version of the device.
+    pub fn hw_ver(&self) -> &str {
+        common!(self, info => info.hw_ver())
+    }
+}
+
+impl fmt::Display for AnyDeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        common!(self, info => info.fmt(f))
+    }
+}
+
 /// Discover existing TP-Link Smart Home devices on the network.
 ///
+/// The handles of the devices found are created with the default
+/// configuration, so caching is disabled and the information in the
+/// discovery responses is not kept. To have the handles cache their
+/// responses, starting with those of the discovery, enable caching in the
+/// configuration passed to [`discover_with_config`].
+///
+/// [`discover_with_config`]: fn.discover_with_config.html
+///
 /// # Examples
 ///
 /// ```no_run
@@ -41,7 +157,99 @@ pub enum DeviceKind {
 ///     Ok(())
 /// }
 /// ```
-pub fn discover() -> Result<HashMap<IpAddr, DeviceKind>> {
+pub fn discover() -> Result<DiscoveryResult> {
+    discover_filtered(|_| true)
+}
+
+/// Discover the TP-Link Smart Home devices on the network for which the
+/// given predicate returns `true`.
+///
+/// The predicate is given the information each device reports in its
+/// discovery response, and devices it rejects are left out of the result
+/// without a handle ever being constructed for them.
+///
+/// # Examples
+///
+/// Discovers the energy monitoring plugs on the network.
+///
+/// ```no_run
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let plugs = tplink::discover_filtered(|info| {
+///         info.kind() == "plug" && info.capabilities().iter().any(|c| c == "emeter")
+///     })?;
+///     for (ip, _) in &plugs {
+///         println!("found energy monitoring plug: {}", ip);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn discover_filtered<F>(filter: F) -> Result<DiscoveryResult>
+where
+    F: FnMut(&DiscoveryInfo) -> bool,
+{
+    discover_with(&default_config(), filter)
+}
+
+/// Discover the TP-Link Smart Home devices on the network with the given
+/// configuration.
+///
+/// The configured host is used as the broadcast address, which allows
+/// discovering the devices on another network segment through its
+/// directed broadcast address. The timeouts, buffer size, offline
+/// tolerance and socket options of the configuration apply to the
+/// discovery request; [`discover`] uses a tolerance of 3.
+///
+/// The cache settings of the configuration apply to the handles of the
+/// devices found instead, except for the cache file, which is never set.
+/// With caching enabled, each handle starts with the responses of its
+/// device to the discovery in its cache.
+///
+/// [`discover`]: fn.discover.html
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let config = tplink::Config::for_host([192, 168, 2, 255])
+///         .with_ip_ttl(4)
+///         .with_offline_tolerance(3)
+///         .with_cache_enabled(Duration::from_secs(30), None)
+///         .build();
+///     for (ip, _) in tplink::discover_with_config(config)? {
+///         println!("found device: {}", ip);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn discover_with_config(config: Config) -> Result<DiscoveryResult> {
+    discover_with(&config, |_| true)
+}
+
+fn default_config() -> Config {
+    Config::for_host([255, 255, 255, 255])
+        .with_offline_tolerance(3)
+        .build()
+}
+
+fn discover_with<F>(config: &Config, mut filter: F) -> Result<DiscoveryResult>
+where
+    F: FnMut(&DiscoveryInfo) -> bool,
+{
+    let mut result = DiscoveryResult {
+        broadcast: Some(Broadcast::from(config)),
+        cache_config: device_cache_config(config),
+        ..Default::default()
+    };
+    for (ip, value) in broadcast(config)? {
+        result.insert(ip, &value, &mut filter);
+    }
+
+    Ok(result)
+}
+
+fn broadcast(config: &Config) -> Result<Vec<(IpAddr, Value)>> {
     let query = json!({
         "system": {"get_sysinfo": {}},
         "emeter": {"get_realtime": {}},
@@ -50,25 +258,651 @@ pub fn discover() -> Result<HashMap<IpAddr, DeviceKind>> {
         "smartlife.iot.smartbulb.lightingservice": {"get_light_state": {}},
     });
     let request = serde_json::to_vec(&query).unwrap();
-    let proto = proto::Builder::new(([255, 255, 255, 255], 9999))
+    let proto = proto::Builder::new(config.addr)
         .broadcast(true)
-        .read_timeout(Duration::from_secs(3))
-        .write_timeout(Duration::from_secs(3))
-        .tolerance(3)
+        .read_timeout(config.read_timeout)
+        .write_timeout(config.write_timeout)
+        .buffer_size(config.buffer_size)
+        .offline_tolerance(config.offline_tolerance)
+        .socket_options(config.socket_options)
         .build();
     let responses = proto.discover(&request)?;
 
-    let mut devices = HashMap::new();
-    for (ip, response) in responses {
-        let value = serde_json::from_slice::<Value>(&response).unwrap();
-        let device = device_from(ip, &value)?;
-        devices.entry(ip).or_insert(device);
+    Ok(responses
+        .into_iter()
+        .filter_map(|(ip, response)| parse_response(ip, &response).map(|value| (ip, value)))
+        .collect())
+}
+
+/// The settings of the discovery broadcast a result was found with, which
+/// commands broadcast to the devices of the result reuse.
+#[derive(Debug, Clone, Copy)]
+struct Broadcast {
+    addr: SocketAddr,
+    write_timeout: Duration,
+    buffer_size: usize,
+    offline_tolerance: u32,
+    socket_options: SocketOptions,
+}
+
+impl Broadcast {
+    fn from(config: &Config) -> Broadcast {
+        Broadcast {
+            addr: config.addr,
+            write_timeout: config.write_timeout,
+            buffer_size: config.buffer_size,
+            offline_tolerance: config.offline_tolerance,
+            socket_options: config.socket_options,
+        }
     }
 
-    Ok(devices)
+    /// Broadcasts `set_relay_state`, waiting briefly for the
+    /// acknowledgements, which are not needed.
+    fn set_relay_state(&self, on: bool) -> Result<()> {
+        let command = json!({ "system": { "set_relay_state": { "state": u8::from(on) } } });
+        let request = serde_json::to_vec(&command).unwrap();
+        let proto = proto::Builder::new(self.addr)
+            .broadcast(true)
+            .read_timeout(Duration::from_millis(200))
+            .write_timeout(self.write_timeout)
+            .buffer_size(self.buffer_size)
+            .offline_tolerance(self.offline_tolerance)
+            .socket_options(self.socket_options)
+            .build();
+        proto.discover(&request).map(|acks| {
+            log::debug!(
+                "set_relay_state broadcast acknowledged by {} hosts",
+                acks.len()
+            )
+        })
+    }
+}
+
+/// Parses a decrypted response to a discovery broadcast. Any host on the
+/// network can answer the broadcast, so responses that are not valid JSON
+/// are skipped rather than trusted.
+fn parse_response(host: IpAddr, response: &[u8]) -> Option<Value> {
+    match json::from_slice(response) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::debug!("ignoring invalid discovery response from {}: {}", host, e);
+            None
+        }
+    }
 }
 
-fn device_from(host: IpAddr, value: &Value) -> Result<DeviceKind> {
+/// Runs a datagram answering a discovery broadcast through the same
+/// decryption and parsing as [`discover`], for the fuzz targets.
+///
+/// [`discover`]: fn.discover.html
+#[cfg(feature = "fuzz")]
+pub fn parse_datagram(datagram: &[u8]) -> DiscoveryResult {
+    let host = IpAddr::from([127, 0, 0, 1]);
+    let mut result = DiscoveryResult::default();
+    if let Some(value) = parse_response(host, &crate::crypto::decrypt(datagram)) {
+        result.insert(host, &value, &mut |_| true);
+    }
+    result
+}
+
+/// The number of consecutive refreshes a device may fail to answer before
+/// a [`DiscoveryCache`] forgets it. A single broadcast can get lost, so a
+/// device is only dropped once it has missed more than one.
+const MAX_MISSED_REFRESHES: u32 = 1;
+
+/// A cache of discovery results, so that repeated discoveries within a
+/// short window reuse the devices found by a recent broadcast instead of
+/// broadcasting again.
+///
+/// A new broadcast is sent when the results are older than the cache's
+/// time-to-live, or on [`force_refresh`]. Devices that stop answering the
+/// broadcasts are dropped from the cache after missing two in a row.
+///
+/// [`force_refresh`]: #method.force_refresh
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut cache = tplink::DiscoveryCache::new(Duration::from_secs(30));
+///
+///     // Broadcasts and waits for the devices to answer.
+///     let devices = cache.discover()?;
+///     // Reuses the devices found above.
+///     let devices = cache.discover()?;
+///     // Broadcasts again, e.g. after a new device was set up.
+///     let devices = cache.force_refresh()?;
+///     Ok(())
+/// }
+/// ```
+pub struct DiscoveryCache {
+    config: Config,
+    ttl: Duration,
+    refreshed_at: Option<Instant>,
+    entries: HashMap<IpAddr, CachedResponse>,
+}
+
+struct CachedResponse {
+    value: Value,
+    missed: u32,
+}
+
+impl DiscoveryCache {
+    /// Creates an empty cache whose results are reused for the given
+    /// time-to-live, discovering with the same settings as [`discover`].
+    ///
+    /// [`discover`]: fn.discover.html
+    pub fn new(ttl: Duration) -> DiscoveryCache {
+        DiscoveryCache::with_config(default_config(), ttl)
+    }
+
+    /// Creates an empty cache whose results are reused for the given
+    /// time-to-live, discovering with the given configuration as
+    /// [`discover_with_config`] does.
+    ///
+    /// [`discover_with_config`]: fn.discover_with_config.html
+    pub fn with_config(config: Config, ttl: Duration) -> DiscoveryCache {
+        DiscoveryCache {
+            config,
+            ttl,
+            refreshed_at: None,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the devices on the network, broadcasting a discovery
+    /// request only if the cached results have expired.
+    pub fn discover(&mut self) -> Result<DiscoveryResult> {
+        self.discover_filtered(|_| true)
+    }
+
+    /// Returns the devices on the network for which the given predicate
+    /// returns `true`, broadcasting a discovery request only if the cached
+    /// results have expired. See [`discover_filtered`].
+    ///
+    /// [`discover_filtered`]: fn.discover_filtered.html
+    pub fn discover_filtered<F>(&mut self, filter: F) -> Result<DiscoveryResult>
+    where
+        F: FnMut(&DiscoveryInfo) -> bool,
+    {
+        if !self.is_fresh(Instant::now()) {
+            self.update(broadcast(&self.config)?, Instant::now());
+        }
+        Ok(self.result(filter))
+    }
+
+    /// Broadcasts a discovery request regardless of the age of the cached
+    /// results, and returns the devices on the network.
+    pub fn force_refresh(&mut self) -> Result<DiscoveryResult> {
+        self.update(broadcast(&self.config)?, Instant::now());
+        Ok(self.result(|_| true))
+    }
+
+    fn is_fresh(&self, now: Instant) -> bool {
+        self.refreshed_at
+            .is_some_and(|refreshed_at| now.duration_since(refreshed_at) < self.ttl)
+    }
+
+    fn update(&mut self, responses: Vec<(IpAddr, Value)>, now: Instant) {
+        for entry in self.entries.values_mut() {
+            entry.missed += 1;
+        }
+        for (ip, value) in responses {
+            self.entries.insert(ip, CachedResponse { value, missed: 0 });
+        }
+        self.entries
+            .retain(|_, entry| entry.missed <= MAX_MISSED_REFRESHES);
+        self.refreshed_at = Some(now);
+    }
+
+    fn result<F>(&self, mut filter: F) -> DiscoveryResult
+    where
+        F: FnMut(&DiscoveryInfo) -> bool,
+    {
+        let mut result = DiscoveryResult {
+            broadcast: Some(Broadcast::from(&self.config)),
+            cache_config: device_cache_config(&self.config),
+            ..Default::default()
+        };
+        for (ip, entry) in &self.entries {
+            result.insert(*ip, &entry.value, &mut filter);
+        }
+        result
+    }
+}
+
+/// How a plug was switched by [`DiscoveryResult::switch_plugs`].
+///
+/// [`DiscoveryResult::switch_plugs`]: struct.DiscoveryResult.html#method.switch_plugs
+#[derive(Debug, Clone)]
+pub enum Switched {
+    /// The plug was switched by the broadcast.
+    Broadcast,
+    /// The broadcast did not reach the plug, which was then switched over
+    /// its own connection, with the given acknowledgement.
+    Unicast(Ack),
+}
+
+/// The devices found on the network by [`discover`].
+///
+/// Besides a handle to every device, the result keeps the information
+/// reported by each device in its discovery response, which can be
+/// exported as an inventory of the network.
+///
+/// [`discover`]: fn.discover.html
+#[derive(Default)]
+pub struct DiscoveryResult {
+    devices: HashMap<IpAddr, DeviceKind>,
+    info: HashMap<IpAddr, DiscoveryInfo>,
+    /// Every host that answered the discovery, including those filtered out.
+    responders: HashSet<IpAddr>,
+    broadcast: Option<Broadcast>,
+    /// The cache settings of the handles of the devices.
+    cache_config: CacheConfig,
+}
+
+impl DiscoveryResult {
+    pub(crate) fn insert<F>(&mut self, host: IpAddr, value: &Value, filter: &mut F)
+    where
+        F: FnMut(&DiscoveryInfo) -> bool,
+    {
+        self.responders.insert(host);
+        if let hash_map::Entry::Vacant(entry) = self.devices.entry(host) {
+            let info = DiscoveryInfo::from(host, value);
+            if filter(&info) {
+                entry.insert(device_from(host, info.kind, value, &self.cache_config));
+                self.info.insert(host, info);
+            }
+        }
+    }
+
+    /// Returns the number of devices found on the network.
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns whether no devices were found on the network.
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Returns the device with the given address, if any.
+    pub fn get(&self, host: &IpAddr) -> Option<&DeviceKind> {
+        self.devices.get(host)
+    }
+
+    /// Returns a mutable reference to the device with the given address, if any.
+    pub fn get_mut(&mut self, host: &IpAddr) -> Option<&mut DeviceKind> {
+        self.devices.get_mut(host)
+    }
+
+    /// Returns the discovery information of the device with the given address, if any.
+    pub fn info(&self, host: &IpAddr) -> Option<&DiscoveryInfo> {
+        self.info.get(host)
+    }
+
+    /// Returns an iterator over the addresses and devices found on the network.
+    pub fn iter(&self) -> hash_map::Iter<'_, IpAddr, DeviceKind> {
+        self.devices.iter()
+    }
+
+    /// Switches all the plugs found on the network on or off at once, and
+    /// returns how each plug was switched, ordered by address.
+    ///
+    /// When it is safe to, the plugs are first sent `set_relay_state` in a
+    /// single broadcast with the settings they were discovered with, so
+    /// that they switch at the same time. Each plug is then queried for its
+    /// state and switched over its own connection if it is not yet in the
+    /// requested state.
+    ///
+    /// Switches, dimmers and power strips answer the broadcast as well, so
+    /// it is only sent if every device that answered the discovery is a
+    /// plug of this result, i.e. none was filtered out, and none of them is
+    /// [leased](lease/index.html). Otherwise the plugs are only switched
+    /// one by one, which checks the leases. Devices that did not answer the
+    /// discovery cannot be accounted for, so the broadcast is best effort.
+    ///
+    /// The broadcast is not passed to audit sinks, the unicast commands
+    /// are.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tplink::Switched;
+    ///
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut devices = tplink::discover()?;
+    ///     for (host, result) in devices.switch_plugs(false) {
+    ///         match result {
+    ///             Ok(Switched::Broadcast) => println!("{}: off", host),
+    ///             Ok(Switched::Unicast(ack)) => println!("{}: off, retried ({})", host, ack),
+    ///             Err(e) => println!("{}: failed, {}", host, e),
+    ///         }
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn switch_plugs(&mut self, on: bool) -> Vec<(IpAddr, Result<Switched>)> {
+        let broadcast = self.broadcast.filter(|_| self.can_broadcast_to_plugs());
+        let mut sent = false;
+        if let Some(broadcast) = broadcast {
+            match broadcast.set_relay_state(on) {
+                Ok(()) => sent = true,
+                Err(e) => log::debug!("failed to broadcast set_relay_state: {}", e),
+            }
+        }
+
+        let mut plugs: Vec<_> = self
+            .devices
+            .iter_mut()
+            .filter_map(|(host, device)| match device {
+                DeviceKind::Plug(plug) => Some((*host, plug)),
+                _ => None,
+            })
+            .collect();
+        plugs.sort_by_key(|(host, _)| *host);
+        plugs
+            .into_iter()
+            .map(|(host, plug)| {
+                let result = plug.is_on_fresh().and_then(|is_on| {
+                    if is_on == on && sent {
+                        Ok(Switched::Broadcast)
+                    } else if on {
+                        plug.turn_on().map(Switched::Unicast)
+                    } else {
+                        plug.turn_off().map(Switched::Unicast)
+                    }
+                });
+                (host, result)
+            })
+            .collect()
+    }
+
+    /// Returns whether a broadcast command would only reach the plugs of
+    /// the result, none of which is leased.
+    fn can_broadcast_to_plugs(&self) -> bool {
+        !self.responders.is_empty()
+            && self.responders.iter().all(|host| {
+                matches!(self.devices.get(host), Some(DeviceKind::Plug(_)))
+                    && lease::check(*host, None).is_ok()
+            })
+    }
+
+    /// Returns the address of the device with the given name (alias),
+    /// ignoring case, along with the device, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let devices = tplink::discover()?;
+    ///     if let Some((host, _)) = devices.get_by_alias("kitchen") {
+    ///         println!("Kitchen is at {}", host);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn get_by_alias(&self, alias: &str) -> Option<(&IpAddr, &DeviceKind)> {
+        self.find_by_alias(alias, AliasMatch::Exact)
+    }
+
+    /// Returns the address of the only device whose name (alias) matches
+    /// the given one, ignoring case, along with the device. Returns `None`
+    /// if no device matches, or if several do, as a prefix may.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tplink::AliasMatch;
+    ///
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let devices = tplink::discover()?;
+    ///     // Matches "Kitchen lamp", unless there is also a "Kitchen fan".
+    ///     if let Some((host, _)) = devices.find_by_alias("kit", AliasMatch::Prefix) {
+    ///         println!("found at {}", host);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_by_alias(
+        &self,
+        alias: &str,
+        matching: AliasMatch,
+    ) -> Option<(&IpAddr, &DeviceKind)> {
+        let alias = alias.to_lowercase();
+        let mut found = self.info.values().filter(|info| {
+            let candidate = info.alias.to_lowercase();
+            match matching {
+                AliasMatch::Exact => candidate == alias,
+                AliasMatch::Prefix => candidate.starts_with(&alias),
+            }
+        });
+        match (found.next(), found.next()) {
+            (Some(info), None) => self.devices.get_key_value(&info.host),
+            _ => None,
+        }
+    }
+
+    /// Returns the address of the device with the given mac address, in any
+    /// of the formats devices report them in, along with the device, if
+    /// any.
+    pub fn get_by_mac(&self, mac: &str) -> Option<(&IpAddr, &DeviceKind)> {
+        let same_mac =
+            |other: &str| match (MacAddress::normalize(mac), MacAddress::normalize(other)) {
+                (Some(mac), Some(other)) => mac == other,
+                _ => mac.eq_ignore_ascii_case(other),
+            };
+        self.info
+            .values()
+            .find(|info| same_mac(&info.mac))
+            .and_then(|info| self.devices.get_key_value(&info.host))
+    }
+
+    /// Writes the inventory of the discovered devices as CSV, one row per
+    /// device ordered by address, with the columns `ip`, `mac`, `model`,
+    /// `alias`, `sw_ver`, `rssi` and `capabilities` (separated by `;`).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let devices = tplink::discover()?;
+    ///     devices.export_csv(std::io::stdout())?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn export_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "ip,mac,model,alias,sw_ver,rssi,capabilities")?;
+        for info in self.inventory() {
+            let rssi = info.rssi.map(|rssi| rssi.to_string()).unwrap_or_default();
+            let row = [
+                info.host.to_string(),
+                info.mac.clone(),
+                info.model.clone(),
+                info.alias.clone(),
+                info.sw_ver.clone(),
+                rssi,
+                info.capabilities.join(";"),
+            ];
+            let row = row.iter().map(|field| csv_field(field)).collect::<Vec<_>>();
+            writeln!(writer, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the inventory of the discovered devices as a JSON array, one
+    /// object per device ordered by address.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let devices = tplink::discover()?;
+    ///     devices.export_json(std::io::stdout())?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn export_json<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, &self.inventory()).map_err(error::json)
+    }
+
+    fn inventory(&self) -> Vec<&DiscoveryInfo> {
+        let mut inventory = self.info.values().collect::<Vec<_>>();
+        inventory.sort_by_key(|info| info.host);
+        inventory
+    }
+}
+
+impl IntoIterator for DiscoveryResult {
+    type Item = (IpAddr, DeviceKind);
+    type IntoIter = hash_map::IntoIter<IpAddr, DeviceKind>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.devices.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a DiscoveryResult {
+    type Item = (&'a IpAddr, &'a DeviceKind);
+    type IntoIter = hash_map::Iter<'a, IpAddr, DeviceKind>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.devices.iter()
+    }
+}
+
+/// How [`DiscoveryResult::find_by_alias`] matches the names of devices,
+/// always ignoring case.
+///
+/// [`DiscoveryResult::find_by_alias`]: struct.DiscoveryResult.html#method.find_by_alias
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasMatch {
+    /// The name is the given one.
+    Exact,
+    /// The name starts with the given one.
+    Prefix,
+}
+
+/// The information reported by a device in its discovery response.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveryInfo {
+    #[serde(rename = "ip")]
+    host: IpAddr,
+    kind: &'static str,
+    mac: String,
+    model: String,
+    alias: String,
+    sw_ver: String,
+    rssi: Option<i64>,
+    capabilities: Vec<String>,
+}
+
+impl DiscoveryInfo {
+    fn from(host: IpAddr, value: &Value) -> DiscoveryInfo {
+        let sysinfo = &value["system"]["get_sysinfo"];
+        let string = |key: &str| sysinfo[key].as_str().unwrap_or_default().to_string();
+        let flag = |key: &str| sysinfo[key].as_u64() == Some(1);
+        let supports = |target: &str, command: &str| {
+            let response = &value[target][command];
+            response.is_object() && response["err_code"].as_i64().unwrap_or(0) == 0
+        };
+
+        let mut capabilities = Vec::new();
+        if sysinfo.get("relay_state").is_some() {
+            capabilities.push("relay");
+        }
+        if sysinfo.get("led_off").is_some() {
+            capabilities.push("led");
+        }
+        if supports("emeter", "get_realtime")
+            || supports("smartlife.iot.common.emeter", "get_realtime")
+        {
+            capabilities.push("emeter");
+        }
+        if supports("smartlife.iot.dimmer", "get_dimmer_parameters") || flag("is_dimmable") {
+            capabilities.push("dimmable");
+        }
+        if flag("is_color") {
+            capabilities.push("color");
+        }
+        if flag("is_variable_color_temp") {
+            capabilities.push("color_temp");
+        }
+
+        DiscoveryInfo {
+            host,
+            kind: kind_of(value),
+            mac: sysinfo
+                .get("mac")
+                .or_else(|| sysinfo.get("mic_mac"))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            model: string("model"),
+            alias: string("alias"),
+            sw_ver: string("sw_ver"),
+            rssi: sysinfo["rssi"].as_i64(),
+            capabilities: capabilities.into_iter().map(String::from).collect(),
+        }
+    }
+
+    /// Returns the address of the device.
+    pub fn host(&self) -> IpAddr {
+        self.host
+    }
+
+    /// Returns the kind of the device, one of `"plug"`, `"bulb"`,
+    /// `"dimmer"`, `"switch"`, `"strip"` or `"unknown"`, matching the
+    /// variants of [`DeviceKind`].
+    ///
+    /// [`DeviceKind`]: enum.DeviceKind.html
+    pub fn kind(&self) -> &str {
+        self.kind
+    }
+
+    /// Returns the mac address of the device.
+    pub fn mac_address(&self) -> &str {
+        &self.mac
+    }
+
+    /// Returns the model of the device.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Returns the name (alias) of the device.
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    /// Returns the software version of the device.
+    pub fn sw_ver(&self) -> &str {
+        &self.sw_ver
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device, if reported.
+    pub fn rssi(&self) -> Option<i64> {
+        self.rssi
+    }
+
+    /// Returns the capabilities of the device, such as `"relay"`, `"led"`,
+    /// `"emeter"`, `"dimmable"`, `"color"` and `"color_temp"`.
+    pub fn capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn kind_of(value: &Value) -> &'static str {
     let (device_type, sysinfo) = {
         if value.get("system").is_some() && value["system"].get("get_sysinfo").is_some() {
             let sysinfo = &value["system"]["get_sysinfo"];
@@ -84,13 +918,326 @@ fn device_from(host: IpAddr, value: &Value) -> Result<DeviceKind> {
         }
     };
 
+    let dev_name = sysinfo
+        .get("dev_name")
+        .map(|name| name.to_string().to_lowercase())
+        .unwrap_or_default();
+
     if device_type.contains("plug") && sysinfo.get("children").is_some() {
-        Ok(DeviceKind::Strip)
+        "strip"
+    } else if device_type.contains("plug") && dev_name.contains("dimmer") {
+        "dimmer"
+    } else if device_type.contains("plug") && dev_name.contains("switch") {
+        "switch"
     } else if device_type.contains("plug") {
-        Ok(DeviceKind::Plug(Box::from(Plug::new(host))))
+        "plug"
     } else if device_type.contains("bulb") {
-        Ok(DeviceKind::Bulb(Box::from(Bulb::new(host))))
+        "bulb"
     } else {
-        Ok(DeviceKind::Unknown)
+        "unknown"
+    }
+}
+
+/// Returns the cache settings of the handles of the devices discovered
+/// with the given configuration. The handles do not share a cache file.
+fn device_cache_config(config: &Config) -> CacheConfig {
+    CacheConfig {
+        file: None,
+        ..config.cache_config.clone()
+    }
+}
+
+fn device_from(host: IpAddr, kind: &str, value: &Value, cache_config: &CacheConfig) -> DeviceKind {
+    let config = || {
+        let mut config = Config::for_host(host).build();
+        config.cache_config = cache_config.clone();
+        config
+    };
+    let value = || value.clone();
+
+    match kind {
+        "strip" => DeviceKind::Strip,
+        "dimmer" => DeviceKind::Dimmer(Box::from(
+            Dimmer::from_sysinfo_with_config(config(), value())
+                .unwrap_or_else(|_| Dimmer::with_config(config())),
+        )),
+        "switch" => DeviceKind::Switch(Box::from(
+            Switch::from_sysinfo_with_config(config(), value())
+                .unwrap_or_else(|_| Switch::with_config(config())),
+        )),
+        "plug" => DeviceKind::Plug(Box::from(
+            Plug::from_sysinfo_with_config(config(), value())
+                .unwrap_or_else(|_| Plug::with_config(config())),
+        )),
+        "bulb" => DeviceKind::Bulb(Box::from(
+            Bulb::from_sysinfo_with_config(config(), value())
+                .unwrap_or_else(|_| Bulb::with_config(config())),
+        )),
+        _ => DeviceKind::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_info() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("hs110_hw2.json");
+        let value: Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        // Nothing listens on the address, so the info must come from the cache.
+        let config = Config::for_host([127, 0, 0, 1])
+            .with_cache_enabled(Duration::from_secs(60), None)
+            .build();
+        let plug = DeviceKind::Plug(Box::new(
+            Plug::from_sysinfo_with_config(config, value).unwrap(),
+        ));
+
+        let info = plug.info().unwrap();
+        assert_eq!(info.alias(), "Fridge");
+        assert!(info.model().starts_with("HS110"));
+        assert!(matches!(info, AnyDeviceInfo::Plug(_)));
+        assert!(DeviceKind::Strip.info().is_err());
+    }
+
+    #[test]
+    fn test_kind_mapping() {
+        let host = IpAddr::from([127, 0, 0, 1]);
+        for (fixture, kind) in [("hs200_hw2.json", "switch"), ("hs220_hw1.json", "dimmer")] {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures")
+                .join(fixture);
+            let value: Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+            assert_eq!(kind_of(&value), kind);
+
+            let mut result = DiscoveryResult::default();
+            result.insert(host, &value, &mut |_| true);
+            assert_eq!(result.info(&host).unwrap().kind(), kind);
+            match (result.get(&host), kind) {
+                (Some(DeviceKind::Switch(_)), "switch")
+                | (Some(DeviceKind::Dimmer(_)), "dimmer") => {}
+                _ => panic!("{} not discovered as a {}", fixture, kind),
+            }
+        }
+    }
+
+    #[test]
+    fn test_handles_are_seeded() {
+        let host = IpAddr::from([127, 0, 0, 1]);
+        let value = json!({
+            "system": {"get_sysinfo": {
+                "sw_ver": "1.1.2 Build 200103 Rel.120214",
+                "hw_ver": "2.0",
+                "model": "HS220(US)",
+                "type": "IOT.SMARTPLUGSWITCH",
+                "dev_name": "Smart Wi-Fi Dimmer",
+                "mac": "50:C7:BF:00:00:02",
+                "alias": "Hallway",
+                "relay_state": 1,
+                "brightness": 40,
+                "rssi": -61,
+                "led_off": 0,
+            }},
+        });
+        let config = Config::for_host([255, 255, 255, 255])
+            .with_cache_enabled(Duration::from_secs(60), None)
+            .with_cache_file("/nonexistent/tplink-cache.json")
+            .build();
+
+        let mut result = DiscoveryResult {
+            cache_config: device_cache_config(&config),
+            ..Default::default()
+        };
+        assert_eq!(result.cache_config.file, None);
+        result.insert(host, &value, &mut |_| true);
+
+        // Nothing listens on the address, so the info must come from the cache.
+        let info = result.get(&host).unwrap().info().unwrap();
+        assert!(matches!(info, AnyDeviceInfo::Dimmer(_)));
+        assert_eq!(info.alias(), "Hallway");
+
+        let mut result = DiscoveryResult::default();
+        result.insert(host, &value, &mut |_| true);
+        assert!(matches!(result.get(&host), Some(DeviceKind::Dimmer(_))));
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let host = IpAddr::from([192, 168, 1, 100]);
+        let value = json!({
+            "system": {"get_sysinfo": {
+                "type": "IOT.SMARTPLUGSWITCH",
+                "model": "HS110(US)",
+                "alias": "Kitchen, left",
+                "mac": "50:C7:BF:00:00:01",
+                "sw_ver": "1.2.5 Build 171213 Rel.101523",
+                "rssi": -52,
+                "relay_state": 1,
+                "led_off": 0,
+            }},
+            "emeter": {"get_realtime": {"err_code": 0, "power": 1.5}},
+            "smartlife.iot.dimmer": {"get_dimmer_parameters": {"err_code": -1}},
+        });
+
+        let mut result = DiscoveryResult::default();
+        result.insert(host, &value, &mut |_| true);
+
+        let mut csv = Vec::new();
+        result.export_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "ip,mac,model,alias,sw_ver,rssi,capabilities\n\
+             192.168.1.100,50:C7:BF:00:00:01,HS110(US),\"Kitchen, left\",\
+             1.2.5 Build 171213 Rel.101523,-52,relay;led;emeter\n"
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut result = DiscoveryResult::default();
+        for (last, alias, mac) in &[
+            (100, "Kitchen lamp", "50:C7:BF:00:00:01"),
+            (101, "Kitchen fan", "50C7BF000002"),
+            (102, "Bedroom", "50:C7:BF:00:00:03"),
+        ] {
+            let value = json!({
+                "system": {"get_sysinfo": {
+                    "type": "IOT.SMARTPLUGSWITCH",
+                    "alias": alias,
+                    "mac": mac,
+                    "relay_state": 0,
+                }},
+            });
+            result.insert(IpAddr::from([192, 168, 1, *last]), &value, &mut |_| true);
+        }
+        let host = |found: Option<(&IpAddr, &DeviceKind)>| found.map(|(host, _)| *host);
+
+        let bedroom = Some(IpAddr::from([192, 168, 1, 102]));
+        assert_eq!(host(result.get_by_alias("BEDROOM")), bedroom);
+        assert_eq!(host(result.get_by_alias("Bed")), None);
+        assert_eq!(
+            host(result.find_by_alias("bed", AliasMatch::Prefix)),
+            bedroom
+        );
+        assert_eq!(
+            host(result.find_by_alias("kitchen", AliasMatch::Prefix)),
+            None
+        );
+        assert_eq!(
+            host(result.find_by_alias("kitchen f", AliasMatch::Prefix)),
+            Some(IpAddr::from([192, 168, 1, 101]))
+        );
+
+        assert_eq!(
+            host(result.get_by_mac("50-c7-bf-00-00-02")),
+            Some(IpAddr::from([192, 168, 1, 101]))
+        );
+        assert_eq!(host(result.get_by_mac("50C7BF000003")), bedroom);
+        assert_eq!(host(result.get_by_mac("50:C7:BF:00:00:04")), None);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let host = IpAddr::from([127, 0, 0, 1]);
+        assert!(parse_response(host, b"{\"system\":{}}").is_some());
+        assert!(parse_response(host, b"{\"system\":").is_none());
+        assert!(parse_response(host, &[0xff, 0x00]).is_none());
+    }
+
+    #[test]
+    fn test_filter() {
+        let host = IpAddr::from([192, 168, 1, 101]);
+        let value = json!({
+            "system": {"get_sysinfo": {
+                "mic_type": "IOT.SMARTBULB",
+                "model": "LB110(US)",
+                "alias": "Hallway",
+                "mic_mac": "50C7BF000006",
+                "sw_ver": "1.8.11 Build 191113 Rel.105336",
+                "is_dimmable": 1,
+            }},
+        });
+
+        let mut result = DiscoveryResult::default();
+        result.insert(host, &value, &mut |info| info.kind() == "plug");
+        assert!(result.is_empty());
+
+        result.insert(host, &value, &mut |info| info.kind() == "bulb");
+        assert!(matches!(result.get(&host), Some(DeviceKind::Bulb(_))));
+    }
+
+    #[test]
+    fn test_can_broadcast_to_plugs() {
+        let plug =
+            json!({"system": {"get_sysinfo": {"type": "IOT.SMARTPLUGSWITCH", "relay_state": 0}}});
+        let switch = json!({"system": {"get_sysinfo": {
+            "type": "IOT.SMARTPLUGSWITCH",
+            "dev_name": "Wi-Fi Smart Light Switch",
+            "relay_state": 0,
+        }}});
+        let first = IpAddr::from([192, 0, 2, 80]);
+        let second = IpAddr::from([192, 0, 2, 81]);
+
+        let mut result = DiscoveryResult::default();
+        assert!(!result.can_broadcast_to_plugs());
+        result.insert(first, &plug, &mut |_| true);
+        assert!(result.can_broadcast_to_plugs());
+
+        // A filtered-out plug would be switched by the broadcast as well.
+        let mut filtered = DiscoveryResult::default();
+        filtered.insert(first, &plug, &mut |_| true);
+        filtered.insert(second, &plug, &mut |_| false);
+        assert!(!filtered.can_broadcast_to_plugs());
+
+        // So would a light switch.
+        result.insert(second, &switch, &mut |_| true);
+        assert!(!result.can_broadcast_to_plugs());
+
+        let mut leased = DiscoveryResult::default();
+        leased.insert(first, &plug, &mut |_| true);
+        let lease = crate::lease::Lease::acquire(first, "test", Duration::from_secs(60)).unwrap();
+        assert!(!leased.can_broadcast_to_plugs());
+        drop(lease);
+        assert!(leased.can_broadcast_to_plugs());
+    }
+
+    #[test]
+    fn test_broadcast_set_relay_state() {
+        let device = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = Config::for_host([127, 0, 0, 1])
+            .with_port(device.local_addr().unwrap().port())
+            .build();
+        let handle = std::thread::spawn(move || {
+            let mut buf = [0; 1024];
+            let (len, _) = device.recv_from(&mut buf).unwrap();
+            serde_json::from_slice::<Value>(&crate::crypto::decrypt(&buf[..len])).unwrap()
+        });
+
+        Broadcast::from(&config).set_relay_state(true).unwrap();
+        assert_eq!(
+            handle.join().unwrap(),
+            json!({ "system": { "set_relay_state": { "state": 1 } } })
+        );
+    }
+
+    #[test]
+    fn test_cache() {
+        let host = IpAddr::from([192, 168, 1, 100]);
+        let value = json!({"system": {"get_sysinfo": {"type": "IOT.SMARTPLUGSWITCH"}}});
+        let mut cache = DiscoveryCache::new(Duration::from_secs(30));
+
+        let start = Instant::now();
+        assert!(!cache.is_fresh(start));
+        cache.update(vec![(host, value)], start);
+        assert!(cache.is_fresh(start + Duration::from_secs(29)));
+        assert!(!cache.is_fresh(start + Duration::from_secs(30)));
+
+        // A device is kept through one missed refresh and dropped after two.
+        cache.update(Vec::new(), start + Duration::from_secs(30));
+        assert!(cache.result(|_| true).get(&host).is_some());
+        cache.update(Vec::new(), start + Duration::from_secs(60));
+        assert!(cache.result(|_| true).is_empty());
     }
 }
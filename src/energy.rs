@@ -0,0 +1,263 @@
+//! Energy tariffs, for turning the usage reported by a device's energy
+//! meter into a cost, and a rolling average of the power it draws.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tplink::energy::Tariff;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let plug = tplink::Plug::new([192, 168, 1, 100]);
+//! let stats = plug.get_emeter_month_stats(2020)?;
+//! println!("cost this year: {:.2}", stats.cost(&Tariff::flat(0.25))?);
+//!
+//! // 0.30 per kWh during the day, 0.12 per kWh overnight. The energy
+//! // meter only reports daily totals, so usage is priced by the hour it
+//! // was measured in, e.g. from realtime readings.
+//! let tariff = Tariff::time_of_use(&[(0..7, 0.12), (7..23, 0.30), (23..24, 0.12)])?;
+//! println!("cost of 1.5 kWh at 8pm: {:.2}", tariff.cost_at(20, 1500));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::emeter::RealtimeStats;
+use crate::error::{self, Result};
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// The price of energy, per kWh, for each hour of the day.
+///
+/// Prices are in whatever currency unit the tariff is given in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tariff {
+    hourly: [f64; 24],
+}
+
+impl Tariff {
+    /// Creates a tariff with the same price at any time of day.
+    pub fn flat(price_per_kwh: f64) -> Tariff {
+        Tariff {
+            hourly: [price_per_kwh; 24],
+        }
+    }
+
+    /// Creates a time-of-use tariff from bands of hours and their price.
+    ///
+    /// The bands must cover each hour of the day (`0..24`) exactly once.
+    pub fn time_of_use(bands: &[(Range<u32>, f64)]) -> Result<Tariff> {
+        let mut hourly = [None; 24];
+        for (hours, price) in bands {
+            if hours.start >= hours.end || hours.end > 24 {
+                return Err(error::invalid_parameter(&format!(
+                    "time_of_use: band {:?} (expected hours within 0..24)",
+                    hours
+                )));
+            }
+            for hour in hours.clone() {
+                if hourly[hour as usize].replace(*price).is_some() {
+                    return Err(error::invalid_parameter(&format!(
+                        "time_of_use: hour {} is covered by more than one band",
+                        hour
+                    )));
+                }
+            }
+        }
+
+        let mut prices = [0.0; 24];
+        for (hour, price) in hourly.iter().enumerate() {
+            prices[hour] = price.ok_or_else(|| {
+                error::invalid_parameter(&format!(
+                    "time_of_use: hour {} is not covered by any band",
+                    hour
+                ))
+            })?;
+        }
+
+        Ok(Tariff { hourly: prices })
+    }
+
+    /// Returns the price per kWh at the given hour of the day (0-23).
+    pub fn price_at(&self, hour: u32) -> f64 {
+        self.hourly[hour as usize % 24]
+    }
+
+    /// Returns the price per kWh averaged over the day.
+    pub fn average_price(&self) -> f64 {
+        self.hourly.iter().sum::<f64>() / 24.0
+    }
+
+    /// Returns whether the price is the same at any time of day.
+    pub fn is_flat(&self) -> bool {
+        self.hourly.iter().all(|price| *price == self.hourly[0])
+    }
+
+    /// Returns the cost of the given energy, in Wh, used at an unknown
+    /// time of day, such as the daily and monthly totals of the energy
+    /// meter.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::UnsupportedOperation` if the price depends
+    /// on the time of day, since the energy cannot be priced without
+    /// knowing when it was used; see [`cost_at`](#method.cost_at).
+    pub fn cost(&self, energy_wh: u64) -> Result<f64> {
+        if self.is_flat() {
+            Ok(self.cost_at(0, energy_wh))
+        } else {
+            Err(error::unsupported_operation(
+                "cost: a time-of-use tariff needs the hour the energy was used in",
+            ))
+        }
+    }
+
+    /// Returns the cost of the given energy, in Wh, used within the given
+    /// hour of the day (0-23).
+    pub fn cost_at(&self, hour: u32, energy_wh: u64) -> f64 {
+        energy_wh as f64 / 1000.0 * self.price_at(hour)
+    }
+}
+
+/// The average power drawn by a device over a rolling window of time,
+/// from the realtime readings of its energy meter.
+///
+/// Each reading is taken to hold until the next one, so readings taken
+/// at irregular intervals are weighted by how long they held.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+/// use tplink::energy::PowerAverage;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let plug = tplink::Plug::new([192, 168, 1, 100]);
+/// let mut average = PowerAverage::new(Duration::from_secs(300));
+/// loop {
+///     average.add(&plug.get_emeter_realtime()?);
+///     if let Some(watts) = average.watts() {
+///         println!("{:.1} W over the last 5 minutes", watts);
+///     }
+///     thread::sleep(Duration::from_secs(10));
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PowerAverage {
+    window: Duration,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl PowerAverage {
+    /// Creates an average over the given window of time.
+    pub fn new(window: Duration) -> PowerAverage {
+        PowerAverage {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Adds a realtime reading taken now. Readings without a power draw
+    /// are ignored.
+    pub fn add(&mut self, stats: &RealtimeStats) {
+        if let Some(watts) = stats.power_w() {
+            self.push(Instant::now(), watts);
+        }
+    }
+
+    /// Adds a power draw, in W, read at the given instant, which must not
+    /// be earlier than the previous one.
+    pub fn push(&mut self, at: Instant, watts: f64) {
+        self.samples.push_back((at, watts));
+        // Keep the last reading from before the window, which holds at its
+        // start.
+        while self.samples.len() > 1 && at.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Returns the average power draw over the window, in W, or `None`
+    /// before any reading was added.
+    pub fn watts(&self) -> Option<f64> {
+        let &(last_at, last) = self.samples.back()?;
+        let start = last_at.checked_sub(self.window);
+        let mut energy = 0.0;
+        let mut span = 0.0;
+        for (&(at, watts), &(next_at, _)) in self.samples.iter().zip(self.samples.iter().skip(1)) {
+            let from = match start {
+                Some(start) if start > at => start,
+                _ => at,
+            };
+            let held = next_at.saturating_duration_since(from).as_secs_f64();
+            energy += watts * held;
+            span += held;
+        }
+        if span > 0.0 {
+            Some(energy / span)
+        } else {
+            Some(last)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_of_use() {
+        let tariff = Tariff::time_of_use(&[(0..6, 0.10), (6..24, 0.30)]).unwrap();
+        assert_eq!(tariff.price_at(5), 0.10);
+        assert_eq!(tariff.price_at(6), 0.30);
+        assert!((tariff.average_price() - 0.25).abs() < 1e-9);
+        assert!(!tariff.is_flat());
+        assert!(tariff.cost(2000).is_err());
+        assert!((tariff.cost_at(3, 2000) - 0.2).abs() < 1e-9);
+
+        let flat = Tariff::time_of_use(&[(0..24, 0.25)]).unwrap();
+        assert_eq!(flat, Tariff::flat(0.25));
+        assert!((flat.cost(2000).unwrap() - 0.5).abs() < 1e-9);
+
+        assert!(Tariff::time_of_use(&[(0..12, 0.10)]).is_err());
+        assert!(Tariff::time_of_use(&[(0..12, 0.10), (11..24, 0.30)]).is_err());
+        assert!(Tariff::time_of_use(&[(0..25, 0.10)]).is_err());
+    }
+
+    #[test]
+    fn test_bands_change_cost() {
+        // The same usage, 1 kWh overnight and 2 kWh in the evening, costs
+        // more where the evening is the expensive band.
+        let usage = [(2, 1000), (19, 2000)];
+        let cost = |tariff: &Tariff| -> f64 {
+            usage
+                .iter()
+                .map(|&(hour, wh)| tariff.cost_at(hour, wh))
+                .sum()
+        };
+        let night_cheap = Tariff::time_of_use(&[(0..7, 0.10), (7..24, 0.30)]).unwrap();
+        let evening_cheap = Tariff::time_of_use(&[(0..17, 0.30), (17..24, 0.10)]).unwrap();
+        assert!((cost(&night_cheap) - 0.7).abs() < 1e-9);
+        assert!((cost(&evening_cheap) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_power_average() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut average = PowerAverage::new(Duration::from_secs(60));
+        assert_eq!(average.watts(), None);
+
+        average.push(at(0), 10.0);
+        assert_eq!(average.watts(), Some(10.0));
+        // 10 W for 30 s, then 40 W for 10 s.
+        average.push(at(30), 40.0);
+        average.push(at(40), 0.0);
+        assert!((average.watts().unwrap() - 17.5).abs() < 1e-9);
+
+        // Only the last 60 s count: 10 W for 10 s, 40 W for 10 s, 0 W for 40 s.
+        average.push(at(80), 0.0);
+        assert!((average.watts().unwrap() - 500.0 / 60.0).abs() < 1e-9);
+    }
+}
@@ -1,6 +1,11 @@
+use crate::pairing::PairingFailure;
+use crate::wire::DecodeError;
+
 use std::error::Error as StdError;
 use std::fmt;
 use std::io;
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
 /// A type alias for `Result<T, tplink::Error>`.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -25,6 +30,7 @@ impl Error {
 
 /// The specific type of an error.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
     /// An I/O error that occurred while interacting with a device.
     Io(io::Error),
@@ -36,10 +42,90 @@ pub enum ErrorKind {
     UnsupportedOperation(String),
     /// An error of this kind occurs when a valid operation is
     /// requested by the client with an invalid parameter.
-    InvalidParameter(String),
+    InvalidParameter(InvalidParameter),
+    /// An error of this kind occurs when a request targets a namespace
+    /// that the device's firmware does not implement, e.g. the energy
+    /// meter of a plug without one.
+    UnsupportedNamespace {
+        /// The namespace targeted by the request, e.g. `"emeter"`.
+        target: String,
+    },
+    /// An error of this kind occurs when the device rejects a request
+    /// within its namespace without running the command, e.g. with `-2`
+    /// (`member not support`) for a command the namespace does not know.
+    Device {
+        /// The error code reported by the device.
+        code: i64,
+        /// The error message reported by the device, if any.
+        msg: Option<String>,
+    },
+    /// An error of this kind occurs when a destructive operation, such as
+    /// a factory reset, is requested without the confirmation the device
+    /// is configured to require, or with one that does not match the
+    /// device.
+    ConfirmationRequired(String),
+    /// An error of this kind occurs when pairing a device with a wireless
+    /// network fails, e.g. because the network was not found.
+    PairingFailed(PairingFailure),
+    /// An error of this kind occurs when a state-changing command is sent
+    /// to a device while another controller holds a lease on it.
+    LeaseHeld {
+        /// The holder of the lease.
+        holder: String,
+        /// The time left until the lease expires, unless renewed.
+        remaining: Duration,
+    },
+}
 
-    #[doc(hidden)]
-    __NonExhaustive,
+/// The details of an invalid parameter passed to an operation.
+///
+/// Parameters checked against a range of valid values, such as the hue
+/// of a bulb, also report the name of the parameter, the value provided
+/// and the range it must be within.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tplink::ErrorKind;
+///
+/// let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+/// if let Err(e) = bulb.set_hue(400) {
+///     if let ErrorKind::InvalidParameter(param) = e.kind() {
+///         assert_eq!(param.field(), Some("hue"));
+///         assert_eq!(param.value(), Some(400));
+///         assert_eq!(param.range(), Some(&(0..=360)));
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidParameter {
+    message: String,
+    field: Option<&'static str>,
+    value: Option<i64>,
+    range: Option<RangeInclusive<i64>>,
+}
+
+impl InvalidParameter {
+    /// Returns the name of the invalid parameter, if known.
+    pub fn field(&self) -> Option<&str> {
+        self.field
+    }
+
+    /// Returns the value provided for the parameter, if it is numeric.
+    pub fn value(&self) -> Option<i64> {
+        self.value
+    }
+
+    /// Returns the range of valid values of the parameter, if it has one.
+    pub fn range(&self) -> Option<&RangeInclusive<i64>> {
+        self.range.as_ref()
+    }
+}
+
+impl fmt::Display for InvalidParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
 impl fmt::Display for Error {
@@ -49,7 +135,24 @@ impl fmt::Display for Error {
             ErrorKind::Json(ref e) => e.fmt(f),
             ErrorKind::UnsupportedOperation(ref op) => write!(f, "unsupported operation: {}", op),
             ErrorKind::InvalidParameter(ref param) => write!(f, "invalid parameter: {}", param),
-            _ => unreachable!(),
+            ErrorKind::UnsupportedNamespace { ref target } => {
+                write!(f, "unsupported namespace: {}", target)
+            }
+            ErrorKind::Device { code, ref msg } => match msg {
+                Some(msg) => write!(f, "device error {}: {}", code, msg),
+                None => write!(f, "device error {}", code),
+            },
+            ErrorKind::ConfirmationRequired(ref op) => write!(f, "confirmation required: {}", op),
+            ErrorKind::PairingFailed(ref failure) => write!(f, "pairing failed: {}", failure),
+            ErrorKind::LeaseHeld {
+                ref holder,
+                remaining,
+            } => write!(
+                f,
+                "lease held by {} for another {}s",
+                holder,
+                remaining.as_secs()
+            ),
         }
     }
 }
@@ -70,6 +173,16 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Error {
+        match e {
+            DecodeError::Json(e) => json(e),
+            DecodeError::UnsupportedNamespace(target) => unsupported_namespace(&target),
+            DecodeError::Device { code, msg, .. } => device(code, msg),
+        }
+    }
+}
+
 pub(crate) fn json(e: serde_json::Error) -> Error {
     Error::new(ErrorKind::Json(e))
 }
@@ -78,6 +191,62 @@ pub(crate) fn unsupported_operation(op: &str) -> Error {
     Error::new(ErrorKind::UnsupportedOperation(op.into()))
 }
 
+pub(crate) fn unsupported_namespace(target: &str) -> Error {
+    Error::new(ErrorKind::UnsupportedNamespace {
+        target: target.into(),
+    })
+}
+
+pub(crate) fn device(code: i64, msg: Option<String>) -> Error {
+    Error::new(ErrorKind::Device { code, msg })
+}
+
+pub(crate) fn confirmation_required(op: &str) -> Error {
+    Error::new(ErrorKind::ConfirmationRequired(op.into()))
+}
+
+pub(crate) fn pairing_failed(failure: PairingFailure) -> Error {
+    Error::new(ErrorKind::PairingFailed(failure))
+}
+
+pub(crate) fn lease_held(holder: &str, remaining: Duration) -> Error {
+    Error::new(ErrorKind::LeaseHeld {
+        holder: holder.into(),
+        remaining,
+    })
+}
+
 pub(crate) fn invalid_parameter(param: &str) -> Error {
-    Error::new(ErrorKind::InvalidParameter(param.into()))
+    Error::new(ErrorKind::InvalidParameter(InvalidParameter {
+        message: param.into(),
+        field: None,
+        value: None,
+        range: None,
+    }))
+}
+
+/// Creates an error for a parameter outside of its range of valid values,
+/// e.g. `LB110(US) set_hue: hue 400° (valid range: 0-360°)`.
+pub(crate) fn out_of_range(
+    op: &str,
+    field: &'static str,
+    value: i64,
+    range: RangeInclusive<i64>,
+    unit: &str,
+) -> Error {
+    Error::new(ErrorKind::InvalidParameter(InvalidParameter {
+        message: format!(
+            "{}: {} {}{} (valid range: {}-{}{})",
+            op,
+            field,
+            value,
+            unit,
+            range.start(),
+            range.end(),
+            unit
+        ),
+        field: Some(field),
+        value: Some(value),
+        range: Some(range),
+    }))
 }
@@ -0,0 +1,134 @@
+//! Serialization of the requests sent to a device.
+//!
+//! Devices handle few requests at a time, and may drop or mix up requests
+//! sent concurrently, e.g. by separate handles to the same device built
+//! on different threads. Every request therefore holds a [`Permit`] of
+//! the gate of its device's address for its whole round trip. Gates admit
+//! requests in the order they arrive, up to a limit at a time, and are
+//! forgotten once no request holds or waits for a permit.
+//!
+//! [`Permit`]: struct.Permit.html
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+
+/// The gates of the devices with requests in flight, by address. Gates
+/// are owned by the requests using them, so a gate is dropped with the
+/// last of its requests and its entry pruned on the next acquisition.
+static GATES: Mutex<Option<HashMap<SocketAddr, Weak<Gate>>>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Gate {
+    state: Mutex<State>,
+    admitted: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// The ticket handed to the next request to arrive.
+    next: u64,
+    /// The ticket of the next request to admit.
+    serving: u64,
+    /// The number of requests admitted and not yet complete.
+    active: usize,
+}
+
+/// A request admitted through the gate of its device, which leaves the
+/// gate when dropped.
+pub(crate) struct Permit {
+    gate: Arc<Gate>,
+}
+
+/// Waits until the gate of the device at the given address admits a new
+/// request, with at most `limit` requests admitted at a time.
+pub(crate) fn acquire(addr: SocketAddr, limit: usize) -> Permit {
+    let gate = {
+        let mut gates = GATES.lock().unwrap();
+        let gates = gates.get_or_insert_with(HashMap::new);
+        gates.retain(|_, gate| gate.strong_count() > 0);
+        match gates.get(&addr).and_then(Weak::upgrade) {
+            Some(gate) => gate,
+            None => {
+                let gate = Arc::new(Gate::default());
+                gates.insert(addr, Arc::downgrade(&gate));
+                gate
+            }
+        }
+    };
+
+    let mut state = gate.state.lock().unwrap();
+    let ticket = state.next;
+    state.next += 1;
+    while ticket != state.serving || state.active >= limit.max(1) {
+        state = gate.admitted.wait(state).unwrap();
+    }
+    state.serving += 1;
+    state.active += 1;
+    // The next request in line may be admitted as well, within the limit.
+    gate.admitted.notify_all();
+    drop(state);
+
+    Permit { gate }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.gate.state.lock().unwrap();
+        state.active -= 1;
+        self.gate.admitted.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_limit() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let (active, peak) = (active.clone(), peak.clone());
+                thread::spawn(move || {
+                    let _permit = acquire(addr, 2);
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn test_pruned() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 2));
+        let contains = |addr| {
+            GATES
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|gates| gates.contains_key(&addr))
+        };
+
+        let permit = acquire(addr, 1);
+        assert!(contains(addr));
+        drop(permit);
+
+        // The next acquisition, for any device, prunes the unused gate.
+        drop(acquire(SocketAddr::from(([127, 0, 0, 1], 3)), 1));
+        assert!(!contains(addr));
+    }
+}
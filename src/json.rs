@@ -0,0 +1,85 @@
+//! The JSON parser behind the decoding of device responses.
+//!
+//! Parsing dominates the cost of polling many devices at a high rate, so
+//! the parser can be swapped for simd-json with the `simd-json` feature.
+//! Both parse into a [`serde_json::Value`], so the rest of the crate does
+//! not depend on the backend in use.
+//!
+//! [`serde_json::Value`]: https://docs.rs/serde_json/1/serde_json/enum.Value.html
+
+use serde_json::Value;
+
+/// A JSON parser.
+pub(crate) trait Backend {
+    /// Parses a JSON document.
+    fn from_slice(json: &[u8]) -> Result<Value, serde_json::Error>;
+
+    /// Parses a JSON document the parser may use as scratch space, which
+    /// spares parsers that work in place a copy of the document.
+    fn from_mut_slice(json: &mut [u8]) -> Result<Value, serde_json::Error> {
+        Self::from_slice(json)
+    }
+}
+
+/// Parses with serde_json, the default backend.
+#[cfg_attr(feature = "simd-json", allow(dead_code))]
+pub(crate) struct SerdeJson;
+
+impl Backend for SerdeJson {
+    fn from_slice(json: &[u8]) -> Result<Value, serde_json::Error> {
+        serde_json::from_slice(json)
+    }
+}
+
+/// Parses with simd-json, which works in place.
+#[cfg(feature = "simd-json")]
+pub(crate) struct SimdJson;
+
+#[cfg(feature = "simd-json")]
+impl Backend for SimdJson {
+    fn from_slice(json: &[u8]) -> Result<Value, serde_json::Error> {
+        Self::from_mut_slice(&mut json.to_vec())
+    }
+
+    fn from_mut_slice(json: &mut [u8]) -> Result<Value, serde_json::Error> {
+        // Errors are reported as serde_json errors whatever the backend.
+        simd_json::serde::from_slice(json).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(not(feature = "simd-json"))]
+type Default = SerdeJson;
+#[cfg(feature = "simd-json")]
+type Default = SimdJson;
+
+/// Parses a JSON document with the backend enabled at build time.
+pub(crate) fn from_slice(json: &[u8]) -> Result<Value, serde_json::Error> {
+    Default::from_slice(json)
+}
+
+/// Parses a JSON document the backend may use as scratch space.
+pub(crate) fn from_mut_slice(json: &mut [u8]) -> Result<Value, serde_json::Error> {
+    Default::from_mut_slice(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check<B: Backend>() {
+        let mut json = br#"{"system":{"get_sysinfo":{"alias":"Fridge","relay_state":1}}}"#.to_vec();
+        let expected =
+            json!({ "system": { "get_sysinfo": { "alias": "Fridge", "relay_state": 1 } } });
+        assert_eq!(B::from_slice(&json).unwrap(), expected);
+        assert_eq!(B::from_mut_slice(&mut json).unwrap(), expected);
+        assert!(B::from_slice(b"{\"system\":").is_err());
+    }
+
+    #[test]
+    fn test_backends() {
+        check::<SerdeJson>();
+        #[cfg(feature = "simd-json")]
+        check::<SimdJson>();
+    }
+}
@@ -0,0 +1,176 @@
+//! Advisory leases granting a controller exclusive control of a device for
+//! a while.
+//!
+//! When several automation loops in a process control the same devices,
+//! a loop can take a [`Lease`] on a device so that the others do not fight
+//! over it. While the lease is held, state-changing commands (any command
+//! other than a `get_*` query) sent to the device by handles whose config
+//! does not name the holder with [`ConfigBuilder::with_lease_holder`] fail
+//! with [`ErrorKind::LeaseHeld`]. Queries are never blocked.
+//!
+//! Leases are held by the process, by device address, and expire after
+//! their duration unless renewed. They do not stop other processes or
+//! applications from controlling the device.
+//!
+//! [`Lease`]: struct.Lease.html
+//! [`ConfigBuilder::with_lease_holder`]: ../struct.ConfigBuilder.html#method.with_lease_holder
+//! [`ErrorKind::LeaseHeld`]: ../enum.ErrorKind.html#variant.LeaseHeld
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tplink::lease::Lease;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let lease = Lease::acquire([192, 168, 1, 101], "wake-up", Duration::from_secs(60))?;
+//!
+//! let config = tplink::Config::for_host([192, 168, 1, 101])
+//!     .with_lease_holder("wake-up")
+//!     .build();
+//! let mut bulb = tplink::Bulb::with_config(config);
+//! bulb.turn_on()?;
+//!
+//! // Meanwhile, `tplink::Bulb::new([192, 168, 1, 101]).turn_off()` fails
+//! // with `ErrorKind::LeaseHeld`.
+//! lease.release();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{self, Result};
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The leases taken in the process, by device address.
+static LEASES: Mutex<Option<HashMap<IpAddr, Held>>> = Mutex::new(None);
+
+#[derive(Debug)]
+struct Held {
+    holder: String,
+    expires: Instant,
+}
+
+/// A lease on a device, released when dropped.
+#[derive(Debug)]
+pub struct Lease {
+    host: IpAddr,
+    holder: String,
+}
+
+impl Lease {
+    /// Takes a lease on the device at the given address for the given
+    /// holder, for the given duration.
+    ///
+    /// Taking a lease the holder already holds renews it.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::LeaseHeld` if another holder holds a lease on
+    /// the device that has not expired.
+    pub fn acquire<A>(host: A, holder: &str, duration: Duration) -> Result<Lease>
+    where
+        A: Into<IpAddr>,
+    {
+        let host = host.into();
+        let mut leases = LEASES.lock().unwrap();
+        let leases = leases.get_or_insert_with(HashMap::new);
+        let now = Instant::now();
+        if let Some(held) = leases.get(&host) {
+            if held.holder != holder && held.expires > now {
+                return Err(error::lease_held(&held.holder, held.expires - now));
+            }
+        }
+
+        leases.insert(
+            host,
+            Held {
+                holder: String::from(holder),
+                expires: now + duration,
+            },
+        );
+        Ok(Lease {
+            host,
+            holder: String::from(holder),
+        })
+    }
+
+    /// Returns the address of the device.
+    pub fn host(&self) -> IpAddr {
+        self.host
+    }
+
+    /// Returns the holder of the lease.
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// Extends the lease to the given duration from now.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::LeaseHeld` if the lease expired and another
+    /// holder has taken one since.
+    pub fn renew(&mut self, duration: Duration) -> Result<()> {
+        Lease::acquire(self.host, &self.holder, duration).map(std::mem::forget)
+    }
+
+    /// Releases the lease, like dropping it.
+    pub fn release(self) {}
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if let Some(leases) = LEASES.lock().unwrap().as_mut() {
+            if leases.get(&self.host).map(|held| &held.holder) == Some(&self.holder) {
+                leases.remove(&self.host);
+            }
+        }
+    }
+}
+
+/// Checks that a state-changing command may be sent to the device at the
+/// given address by the given holder, if any.
+pub(crate) fn check(host: IpAddr, holder: Option<&str>) -> Result<()> {
+    if let Some(leases) = LEASES.lock().unwrap().as_ref() {
+        if let Some(held) = leases.get(&host) {
+            let now = Instant::now();
+            if Some(held.holder.as_str()) != holder && held.expires > now {
+                return Err(error::lease_held(&held.holder, held.expires - now));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_lease() {
+        let host = IpAddr::from([192, 0, 2, 70]);
+        let lease = Lease::acquire(host, "a", Duration::from_secs(60)).unwrap();
+        assert!(check(host, Some("a")).is_ok());
+        assert!(matches!(
+            check(host, None).unwrap_err().kind(),
+            ErrorKind::LeaseHeld { holder, .. } if holder == "a"
+        ));
+        assert!(Lease::acquire(host, "b", Duration::from_secs(60)).is_err());
+
+        lease.release();
+        assert!(check(host, None).is_ok());
+        let mut lease = Lease::acquire(host, "b", Duration::from_secs(60)).unwrap();
+
+        // An expired lease no longer blocks anyone.
+        lease.renew(Duration::ZERO).unwrap();
+        assert!(check(host, Some("a")).is_ok());
+        let _taken = Lease::acquire(host, "a", Duration::from_secs(60)).unwrap();
+        drop(lease);
+        assert!(check(host, None).is_err());
+    }
+}
@@ -1,21 +1,69 @@
 // #![deny(missing_docs)]
 
+extern crate alloc;
+
+// Declared first so that its macros are visible in the other modules.
+#[macro_use]
+mod command;
+
+mod ack;
+pub mod audit;
+#[cfg(any(feature = "bench", feature = "fuzz"))]
+#[doc(hidden)]
+pub mod bench;
 mod bulb;
 #[allow(dead_code)]
 mod cache;
-mod command;
+mod clock;
 mod config;
+#[cfg(feature = "schema-check")]
+mod conformance;
 #[allow(dead_code)]
 mod crypto;
+pub mod diagnostics;
+mod dimmer;
 mod discover;
+pub mod energy;
 mod error;
+mod gate;
+mod json;
+pub mod lease;
+pub mod models;
+pub mod pairing;
 mod plug;
+pub mod prelude;
 mod proto;
+pub mod provisioning;
+mod redact;
+mod request;
+pub mod routines;
+mod schema;
+#[cfg(feature = "serve")]
+pub mod serve;
+mod shared;
+pub mod solar;
+pub mod state;
+mod status;
+mod switch;
 mod util;
+mod validate;
+pub mod wire;
 
-pub use self::bulb::Bulb;
+pub use self::ack::Ack;
+pub use self::bulb::{
+    capability, schedule, Bulb, DetectedBulb, Easing, LB110Info, LB110Namespaces, LightMode,
+    LightState, PowerEstimate, WattageSource, HSV, LB110,
+};
 pub use self::command::{cloud, device, emeter, sys, sysinfo, time, wlan};
 pub use self::config::{Config, ConfigBuilder};
-pub use self::discover::{discover, DeviceKind};
-pub use self::error::{Error, ErrorKind, Result};
-pub use self::plug::{timer, Plug};
+pub use self::dimmer::{Dimmer, HS220Info, HS220};
+pub use self::discover::{
+    discover, discover_filtered, discover_with_config, AliasMatch, AnyDeviceInfo, DeviceKind,
+    DiscoveryCache, DiscoveryInfo, DiscoveryResult, Switched,
+};
+pub use self::error::{Error, ErrorKind, InvalidParameter, Result};
+pub use self::plug::{timer, ActiveMode, HS100Info, Plug, HS100};
+pub use self::redact::set_log_redaction;
+pub use self::request::{Command, Request};
+pub use self::status::{status, status_with_config, StatusRow};
+pub use self::switch::{HS200Info, Switch, HS200};
@@ -0,0 +1,257 @@
+//! Known TP-Link Kasa device models and their level of support.
+//!
+//! The model of a device is detected from the `model` field of its system
+//! information (e.g. `HS110(US)`), so users can tell what to expect from a
+//! device before relying on it.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Mutex;
+
+/// The model names a support warning has been logged for.
+static WARNED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// The level of support the library offers for a device model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SupportLevel {
+    /// The model has been tested against the library.
+    Supported,
+    /// The model speaks the same protocol as a supported model, but has not
+    /// been tested. Some operations may not be supported by the device.
+    BestEffort,
+    /// The model is not recognised by the library.
+    Unknown,
+}
+
+/// TP-Link Kasa device models known to the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Model {
+    /// Smart Wi-Fi Plug.
+    HS100,
+    /// Smart Wi-Fi Plug Mini.
+    HS103,
+    /// Smart Wi-Fi Plug Mini.
+    HS105,
+    /// Smart Wi-Fi Plug with Energy Monitoring.
+    HS110,
+    /// Smart Wi-Fi Plug Mini.
+    KP105,
+    /// Smart Wi-Fi Plug Mini with Energy Monitoring.
+    KP115,
+    /// Smart Outdoor Wi-Fi Plug.
+    KP125,
+    /// Smart Wi-Fi LED Bulb.
+    LB100,
+    /// Smart Wi-Fi LED Bulb with Dimmable Light.
+    LB110,
+    /// Smart Wi-Fi LED Bulb with Tunable White Light.
+    LB120,
+    /// Smart Wi-Fi LED Bulb with Color Changing.
+    LB130,
+    /// Smart Wi-Fi LED Bulb with Dimmable Light.
+    KL110,
+    /// Smart Wi-Fi LED Bulb with Tunable White Light.
+    KL120,
+    /// Smart Wi-Fi LED Bulb with Color Changing.
+    KL130,
+    /// Smart Wi-Fi Light Switch.
+    HS200,
+    /// Smart Wi-Fi 3-Way Light Switch.
+    HS210,
+    /// Smart Wi-Fi Dimmer Switch.
+    HS220,
+}
+
+const MODELS: &[(&str, Model, SupportLevel)] = &[
+    ("HS100", Model::HS100, SupportLevel::Supported),
+    ("HS103", Model::HS103, SupportLevel::BestEffort),
+    ("HS105", Model::HS105, SupportLevel::BestEffort),
+    ("HS110", Model::HS110, SupportLevel::Supported),
+    ("KP105", Model::KP105, SupportLevel::BestEffort),
+    ("KP115", Model::KP115, SupportLevel::BestEffort),
+    ("KP125", Model::KP125, SupportLevel::BestEffort),
+    ("LB100", Model::LB100, SupportLevel::BestEffort),
+    ("LB110", Model::LB110, SupportLevel::Supported),
+    ("LB120", Model::LB120, SupportLevel::BestEffort),
+    ("LB130", Model::LB130, SupportLevel::BestEffort),
+    ("KL110", Model::KL110, SupportLevel::BestEffort),
+    ("KL120", Model::KL120, SupportLevel::BestEffort),
+    ("KL130", Model::KL130, SupportLevel::BestEffort),
+    ("HS200", Model::HS200, SupportLevel::BestEffort),
+    ("HS210", Model::HS210, SupportLevel::BestEffort),
+    ("HS220", Model::HS220, SupportLevel::BestEffort),
+];
+
+impl Model {
+    /// Returns the level of support the library offers for the model.
+    pub fn support_level(self) -> SupportLevel {
+        MODELS
+            .iter()
+            .find(|(_, model, _)| *model == self)
+            .map(|(_, _, support)| *support)
+            .unwrap_or(SupportLevel::Unknown)
+    }
+
+    /// Returns the rated power draw of the model in watts at full
+    /// brightness, for bulbs.
+    pub fn rated_wattage(self) -> Option<f64> {
+        match self {
+            Model::LB100 => Some(7.0),
+            Model::LB110 => Some(10.0),
+            Model::LB120 | Model::LB130 => Some(11.0),
+            Model::KL110 | Model::KL120 | Model::KL130 => Some(10.0),
+            _ => None,
+        }
+    }
+
+    /// Returns the range of color temperatures (in kelvin) supported by
+    /// the model, for bulbs with tunable white light.
+    pub fn color_temp_range(self) -> Option<(u32, u32)> {
+        match self {
+            Model::LB120 => Some((2700, 6500)),
+            Model::LB130 | Model::KL130 => Some((2500, 9000)),
+            Model::KL120 => Some((2700, 5000)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Model> {
+        MODELS
+            .iter()
+            .find(|(prefix, _, _)| name.starts_with(prefix))
+            .map(|(_, model, _)| *model)
+    }
+
+    fn name(self) -> &'static str {
+        MODELS
+            .iter()
+            .find(|(_, model, _)| *model == self)
+            .map(|(name, _, _)| *name)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The model of a device, as detected from its system information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    name: String,
+    model: Option<Model>,
+}
+
+impl ModelInfo {
+    /// Detects the model from the `model` field of a device's system
+    /// information, e.g. `HS110(US)`.
+    ///
+    /// A warning is logged the first time a model that is not fully
+    /// supported is detected, and a debug message every time after.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tplink::models::{Model, ModelInfo, SupportLevel};
+    ///
+    /// let info = ModelInfo::detect("HS110(US)");
+    /// assert_eq!(info.model(), Some(Model::HS110));
+    /// assert_eq!(info.support_level(), SupportLevel::Supported);
+    /// ```
+    pub fn detect(name: &str) -> ModelInfo {
+        let model = Model::from_name(name);
+        let info = ModelInfo {
+            name: String::from(name),
+            model,
+        };
+
+        let level = || {
+            if first_warning(name) {
+                log::Level::Warn
+            } else {
+                log::Level::Debug
+            }
+        };
+        match info.support_level() {
+            SupportLevel::Supported => {}
+            SupportLevel::BestEffort => log::log!(
+                level(),
+                "{} has not been tested with this library, some operations may not work",
+                name
+            ),
+            SupportLevel::Unknown => {
+                log::log!(level(), "{} is not a recognised device model", name)
+            }
+        }
+
+        info
+    }
+
+    /// Returns the model name as reported by the device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the detected model, or `None` if it is not recognised.
+    pub fn model(&self) -> Option<Model> {
+        self.model
+    }
+
+    /// Returns the level of support the library offers for the device.
+    pub fn support_level(&self) -> SupportLevel {
+        self.model
+            .map(Model::support_level)
+            .unwrap_or(SupportLevel::Unknown)
+    }
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({:?})", self.name, self.support_level())
+    }
+}
+
+/// Returns whether no support warning has been logged for the model name
+/// yet, and remembers that one is.
+fn first_warning(name: &str) -> bool {
+    let mut warned = WARNED.lock().unwrap();
+    warned
+        .get_or_insert_with(HashSet::new)
+        .insert(String::from(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        for (name, model, support) in [
+            ("HS110(US)", Some(Model::HS110), SupportLevel::Supported),
+            ("HS100(UK)", Some(Model::HS100), SupportLevel::Supported),
+            ("KL130B(US)", Some(Model::KL130), SupportLevel::BestEffort),
+            ("HS220(US)", Some(Model::HS220), SupportLevel::BestEffort),
+            ("HS300(US)", None, SupportLevel::Unknown),
+            ("", None, SupportLevel::Unknown),
+        ] {
+            let info = ModelInfo::detect(name);
+            assert_eq!(info.model(), model, "{}", name);
+            assert_eq!(info.support_level(), support, "{}", name);
+            assert_eq!(info.name(), name);
+        }
+        assert_eq!(
+            ModelInfo::detect("HS220(US)").to_string(),
+            "HS220(US) (BestEffort)"
+        );
+    }
+
+    #[test]
+    fn test_first_warning() {
+        assert!(first_warning("KP125(US) test"));
+        assert!(!first_warning("KP125(US) test"));
+        assert!(first_warning("KP125(EU) test"));
+    }
+}
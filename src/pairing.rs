@@ -0,0 +1,331 @@
+//! Onboarding of new devices onto a wireless network.
+//!
+//! A new or factory reset device opens its own access point (`TP-LINK_…`)
+//! and answers on `192.168.0.1`. Pairing asks it to join a wireless
+//! network, then waits for it to come online there.
+//!
+//! A [`Pairing`] is a resumable state machine: each call to [`step`]
+//! advances it by at most one state, and a step that fails with an I/O
+//! error leaves the state unchanged, so it can simply be retried. This
+//! lets the host switch from the device's access point back to its own
+//! network once the credentials are sent.
+//!
+//! [`Pairing`]: struct.Pairing.html
+//! [`step`]: struct.Pairing.html#method.step
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tplink::pairing::{Pairing, PairingState};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut pairing = Pairing::new("home", "hunter2");
+//! pairing.on_progress(|state| println!("{}", state));
+//!
+//! // Connected to the device's access point.
+//! while *pairing.state() != PairingState::CredentialsSent {
+//!     pairing.step()?;
+//! }
+//!
+//! // Reconnect the host to the "home" network, then wait for the device.
+//! let host = pairing.run()?;
+//! println!("device online at {}", host);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::Config;
+use crate::discover;
+use crate::error::{self, Result};
+use crate::proto::{self, Command, Proto, Request};
+use crate::wlan::Netif;
+
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// The address a device answers on while it runs its own access point.
+const SOFTAP_ADDR: [u8; 4] = [192, 168, 0, 1];
+
+/// How long to wait for the device to come online on the network by
+/// default.
+const DEFAULT_JOIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The states of a [`Pairing`], in the order they are reached.
+///
+/// [`Pairing`]: struct.Pairing.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairingState {
+    /// Scanning, from the device, for the network to join.
+    Scanning,
+    /// The network was found, and the device was asked to join it. The
+    /// host can now reconnect to the network.
+    CredentialsSent,
+    /// Waiting for the device to come online on the network.
+    WaitingForJoin,
+    /// The device is online on the network, at the given address.
+    Online(IpAddr),
+    /// Pairing failed. It can be restarted with [`reset`].
+    ///
+    /// [`reset`]: struct.Pairing.html#method.reset
+    Failed(PairingFailure),
+}
+
+impl fmt::Display for PairingState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PairingState::Scanning => write!(f, "scanning"),
+            PairingState::CredentialsSent => write!(f, "credentials sent"),
+            PairingState::WaitingForJoin => write!(f, "waiting for the device to join"),
+            PairingState::Online(host) => write!(f, "online at {}", host),
+            PairingState::Failed(failure) => write!(f, "failed: {}", failure),
+        }
+    }
+}
+
+/// The reasons pairing can fail for, beyond I/O errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairingFailure {
+    /// The device could not find the network to join.
+    ApNotFound,
+    /// The device did not come online on the network, and opened its own
+    /// access point again, which it does when it cannot authenticate.
+    WrongPassword,
+    /// The device did not come online on the network in time.
+    JoinTimedOut,
+}
+
+impl fmt::Display for PairingFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PairingFailure::ApNotFound => write!(f, "network not found"),
+            PairingFailure::WrongPassword => write!(f, "wrong password"),
+            PairingFailure::JoinTimedOut => write!(f, "device did not join the network"),
+        }
+    }
+}
+
+/// A callback notified of the states a [`Pairing`] reaches.
+///
+/// [`Pairing`]: struct.Pairing.html
+type Progress = Box<dyn FnMut(&PairingState)>;
+
+/// Pairs a device running its own access point with a wireless network.
+pub struct Pairing {
+    ssid: String,
+    password: String,
+    config: Config,
+    join_timeout: Duration,
+    state: PairingState,
+    mac: String,
+    waiting_since: Option<Instant>,
+    progress: Option<Progress>,
+}
+
+impl Pairing {
+    /// Creates a pairing of the device at its default access point address
+    /// with the network of the given SSID and password.
+    pub fn new(ssid: &str, password: &str) -> Pairing {
+        Pairing::with_config(Config::for_host(SOFTAP_ADDR).build(), ssid, password)
+    }
+
+    /// Creates a pairing of the device with the given configuration, e.g.
+    /// for a device answering on another address.
+    pub fn with_config(config: Config, ssid: &str, password: &str) -> Pairing {
+        Pairing {
+            ssid: ssid.to_string(),
+            password: password.to_string(),
+            config,
+            join_timeout: DEFAULT_JOIN_TIMEOUT,
+            state: PairingState::Scanning,
+            mac: String::new(),
+            waiting_since: None,
+            progress: None,
+        }
+    }
+
+    /// Sets how long to wait for the device to come online on the network,
+    /// 60 seconds by default.
+    pub fn with_join_timeout(mut self, join_timeout: Duration) -> Pairing {
+        self.join_timeout = join_timeout;
+        self
+    }
+
+    /// Sets a callback, called with every new state the pairing reaches.
+    pub fn on_progress<F>(&mut self, progress: F)
+    where
+        F: FnMut(&PairingState) + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Returns the current state of the pairing.
+    pub fn state(&self) -> &PairingState {
+        &self.state
+    }
+
+    /// Restarts the pairing from scanning, e.g. after it failed.
+    pub fn reset(&mut self) {
+        self.waiting_since = None;
+        self.transition(PairingState::Scanning);
+    }
+
+    /// Advances the pairing by at most one state, and returns the state it
+    /// reached.
+    ///
+    /// Once the credentials are sent, the host must reconnect to the
+    /// network, since each step then broadcasts a discovery request to
+    /// find the device there. An I/O error leaves the state unchanged, and
+    /// a pairing that failed or is online stays so.
+    pub fn step(&mut self) -> Result<&PairingState> {
+        let next = match &self.state {
+            PairingState::Scanning => self.scan()?,
+            PairingState::CredentialsSent => {
+                self.waiting_since = Some(Instant::now());
+                Some(PairingState::WaitingForJoin)
+            }
+            PairingState::WaitingForJoin => self.wait_for_join()?,
+            PairingState::Online(_) | PairingState::Failed(_) => None,
+        };
+        if let Some(next) = next {
+            self.transition(next);
+        }
+        match self.state {
+            PairingState::Failed(failure) => Err(error::pairing_failed(failure)),
+            _ => Ok(&self.state),
+        }
+    }
+
+    /// Steps the pairing until the device is online, and returns its
+    /// address on the network.
+    pub fn run(&mut self) -> Result<IpAddr> {
+        loop {
+            if let PairingState::Online(host) = self.step()? {
+                return Ok(*host);
+            }
+        }
+    }
+
+    fn scan(&mut self) -> Result<Option<PairingState>> {
+        let proto = Rc::new(self.proto());
+        let sysinfo = proto.send_request(&Request::from(Command::GetSysinfo))?;
+        let netif = Netif::new(proto);
+        let ap_list = netif.get_scan_info(true, None)?;
+        let ap = match ap_list.iter().find(|ap| ap.ssid() == self.ssid) {
+            Some(ap) => ap,
+            None => return Ok(Some(PairingState::Failed(PairingFailure::ApNotFound))),
+        };
+
+        match netif.set_stainfo(&self.ssid, &self.password, ap.key_type()) {
+            Ok(_) => {}
+            // The device may leave its access point before answering.
+            Err(e) if is_timeout(&e) => {}
+            Err(e) => return Err(e),
+        }
+        self.mac = sysinfo
+            .get("mac")
+            .or_else(|| sysinfo.get("mic_mac"))
+            .and_then(|mac| mac.as_str())
+            .unwrap_or_default()
+            .to_string();
+        Ok(Some(PairingState::CredentialsSent))
+    }
+
+    fn wait_for_join(&mut self) -> Result<Option<PairingState>> {
+        let mac = &self.mac;
+        let found = discover::discover_filtered(|info| info.mac_address() == mac)?;
+        if let Some((host, _)) = found.iter().next() {
+            return Ok(Some(PairingState::Online(*host)));
+        }
+
+        let waiting_since = *self.waiting_since.get_or_insert_with(Instant::now);
+        if waiting_since.elapsed() < self.join_timeout {
+            return Ok(None);
+        }
+        // The device opens its access point again when it cannot join, but
+        // the host may no longer be able to reach it there.
+        let softap = self
+            .proto()
+            .send_request(&Request::from(Command::GetSysinfo));
+        let failure = if softap.is_ok() {
+            PairingFailure::WrongPassword
+        } else {
+            PairingFailure::JoinTimedOut
+        };
+        Ok(Some(PairingState::Failed(failure)))
+    }
+
+    fn transition(&mut self, state: PairingState) {
+        log::debug!("({}) pairing: {}", self.config.addr.ip(), state);
+        self.state = state;
+        if let Some(progress) = self.progress.as_mut() {
+            progress(&self.state);
+        }
+    }
+
+    fn proto(&self) -> Proto {
+        proto::Builder::new(self.config.addr)
+            .read_timeout(self.config.read_timeout)
+            .write_timeout(self.config.write_timeout)
+            .buffer_size(self.config.buffer_size)
+            .offline_tolerance(self.config.offline_tolerance)
+            .retry(self.config.retry_attempts)
+            .max_concurrent_requests(self.config.max_concurrent_requests)
+            .socket_options(self.config.socket_options)
+            .audit(self.config.audit.clone())
+            .lease_holder(self.config.lease_holder.clone())
+            .build()
+    }
+}
+
+impl fmt::Debug for Pairing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pairing")
+            .field("ssid", &self.ssid)
+            .field("host", &self.config.addr.ip())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+fn is_timeout(e: &error::Error) -> bool {
+    match e.kind() {
+        error::ErrorKind::Io(e) => {
+            e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::WouldBlock
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_failed_pairing_stays_failed() {
+        let mut pairing = Pairing::new("home", "hunter2");
+        let states = Rc::new(RefCell::new(Vec::new()));
+        let seen = states.clone();
+        pairing.on_progress(move |state| seen.borrow_mut().push(state.clone()));
+
+        pairing.transition(PairingState::Failed(PairingFailure::ApNotFound));
+        let err = pairing.step().unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            error::ErrorKind::PairingFailed(PairingFailure::ApNotFound)
+        ));
+
+        pairing.reset();
+        assert_eq!(pairing.state(), &PairingState::Scanning);
+        assert_eq!(
+            *states.borrow(),
+            [
+                PairingState::Failed(PairingFailure::ApNotFound),
+                PairingState::Scanning,
+            ]
+        );
+    }
+}
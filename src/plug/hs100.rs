@@ -1,29 +1,36 @@
-use super::timer::{Rule, RuleList, Timer, TimerSettings};
-use crate::cache::{Cache, ResponseCache};
+use super::timer::{Rule, RuleId, RuleList, Timer, TimerSettings};
+use crate::ack::Ack;
+use crate::cache::{self, Persister, ResponseCache};
 use crate::cloud::{Cloud, CloudInfo, CloudSettings};
 use crate::config::Config;
 use crate::device::Device;
+use crate::diagnostics::{self, CallResult, Diagnostics};
 use crate::emeter::{DayStats, Emeter, EmeterStats, MonthStats, RealtimeStats};
 use crate::error::{self, Result};
-use crate::proto::{self, Proto, Request};
+use crate::proto::{Command, Proto, Request};
+use crate::shared::Shared;
+use crate::solar::{self, SunTimes};
 use crate::sys::{Sys, System};
-use crate::sysinfo::{SysInfo, SystemInfo};
+use crate::sysinfo::{self, FeatureFlags, MacAddress, SysInfo, SystemInfo};
 use crate::time::{DeviceTime, DeviceTimeZone, Time, TimeSettings};
-use crate::util;
+use crate::validate;
 use crate::wlan::{AccessPoint, Netif, Wlan};
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
-use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::fmt;
 use std::net::IpAddr;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// A TP-Link Wi-Fi Smart Plug (HS100).
+#[derive(Clone)]
 pub struct HS100 {
     proto: Rc<Proto>,
-    cache: Rc<ResponseCache>,
+    cache: Arc<ResponseCache>,
+    _persister: Arc<Persister>,
     system: System,
     time_settings: TimeSettings,
     timer_settings: TimerSettings,
@@ -42,37 +49,13 @@ impl HS100 {
     }
 
     pub(super) fn with_config(config: Config) -> HS100 {
-        let addr = config.addr;
-        let read_timeout = config.read_timeout;
-        let write_timeout = config.write_timeout;
-        let buffer_size = config.buffer_size;
-
-        let proto = proto::Builder::new(addr)
-            .read_timeout(read_timeout)
-            .write_timeout(write_timeout)
-            .buffer_size(buffer_size)
-            .build();
-
-        let cache_config = config.cache_config;
-        let cache = if cache_config.enable_cache {
-            let ttl = cache_config.ttl.unwrap();
-            let cache = cache_config.initial_capacity.map_or_else(
-                || Cache::with_ttl(ttl),
-                |capacity| Cache::with_ttl_and_capacity(ttl, capacity),
-            );
-            Some(RefCell::new(cache))
-        } else {
-            None
-        };
-
-        HS100::with(proto, cache)
-    }
-
-    fn with(proto: Proto, cache: ResponseCache) -> HS100 {
-        let proto = Rc::new(proto);
-        let cache = Rc::new(cache);
+        let Shared {
+            proto,
+            cache,
+            persister,
+        } = Shared::new(&config);
 
-        HS100 {
+        let mut device = HS100 {
             system: System::new("system", proto.clone(), cache.clone()),
             time_settings: TimeSettings::new("time", proto.clone()),
             timer_settings: TimerSettings::new("count_down", proto.clone(), cache.clone()),
@@ -80,144 +63,257 @@ impl HS100 {
             emeter: EmeterStats::new("emeter", proto.clone(), cache.clone()),
             netif: Netif::new(proto.clone()),
             sysinfo: SystemInfo::new(proto.clone(), cache.clone()),
+            _persister: persister,
             proto,
             cache,
-        }
+        };
+        device
+            .system
+            .set_reset_confirmation(config.reset_confirmation);
+
+        device
+    }
+
+    pub(super) fn from_sysinfo(config: Config, sysinfo: Value) -> Result<HS100> {
+        let response = sysinfo::into_response(sysinfo);
+        HS100Info::deserialize(&response["system"]["get_sysinfo"]).map_err(error::json)?;
+
+        let device = HS100::with_config(config);
+        cache::seed(&device.cache, &response);
+        Ok(device)
     }
 
-    pub(super) fn sw_ver(&mut self) -> Result<String> {
+    pub(super) fn with_meta<R, F>(&self, call: F) -> Result<CallResult<R>>
+    where
+        F: FnOnce(&HS100) -> Result<R>,
+    {
+        diagnostics::measure(&self.proto, || call(self))
+    }
+
+    pub(super) fn diagnostics(&self) -> Diagnostics {
+        Diagnostics::collect(
+            &self.proto,
+            || self.cloud_settings.get_info(),
+            || self.time_settings.get_time(),
+        )
+    }
+
+    pub(super) fn sw_ver(&self) -> Result<String> {
         self.sysinfo().map(|sysinfo| sysinfo.sw_ver)
     }
 
-    pub(super) fn hw_ver(&mut self) -> Result<String> {
+    pub(super) fn hw_ver(&self) -> Result<String> {
         self.sysinfo().map(|sysinfo| sysinfo.hw_ver)
     }
 
-    pub(super) fn model(&mut self) -> Result<String> {
+    pub(super) fn model(&self) -> Result<String> {
         self.sysinfo().map(|sysinfo| sysinfo.model)
     }
 
-    pub(super) fn alias(&mut self) -> Result<String> {
+    pub(super) fn alias(&self) -> Result<String> {
         self.sysinfo().map(|sysinfo| sysinfo.alias)
     }
 
-    pub(super) fn mac_address(&mut self) -> Result<String> {
-        self.sysinfo().map(|sysinfo| sysinfo.mac)
+    pub(super) fn mac_address(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.mac.to_string())
     }
 
-    pub(super) fn rssi(&mut self) -> Result<i64> {
+    pub(super) fn rssi(&self) -> Result<i64> {
         self.sysinfo().map(|sysinfo| sysinfo.rssi)
     }
 
-    pub(super) fn location(&mut self) -> Result<Location> {
+    pub(super) fn location(&self) -> Result<Location> {
         self.sysinfo().map(|sysinfo| sysinfo.location)
     }
 
-    pub(super) fn has_emeter(&mut self) -> Result<bool> {
+    pub(super) fn has_emeter(&self) -> Result<bool> {
         self.sysinfo().map(|sysinfo| sysinfo.has_emeter())
     }
 
-    pub(super) fn is_on(&mut self) -> Result<bool> {
-        self.sysinfo().map(|sysinfo| sysinfo.is_on())
+    pub(super) fn is_led_on(&self) -> Result<bool> {
+        self.sysinfo().map(|sysinfo| sysinfo.is_led_on())
     }
 
-    pub(super) fn is_led_on(&mut self) -> Result<bool> {
-        self.sysinfo().map(|sysinfo| sysinfo.is_led_on())
+    pub(super) fn turn_on_led(&mut self) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetLedOff { off: false }))?;
+
+        log::trace!("(system) {:?}", response);
+
+        Ack::from_response(response)
+    }
+
+    pub(super) fn turn_off_led(&mut self) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetLedOff { off: true }))?;
+
+        log::trace!("(system) {:?}", response);
+
+        Ack::from_response(response)
+    }
+
+    pub(super) fn active_mode(&self) -> Result<ActiveMode> {
+        self.sysinfo()?
+            .active_mode()
+            .ok_or_else(|| error::unsupported_operation("active_mode"))
     }
 
-    pub(super) fn turn_on_led(&mut self) -> Result<()> {
+    pub(super) fn set_active_mode(&mut self, mode: ActiveMode) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != "system");
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
         }
 
         let response = self.proto.send_request(&Request::new(
             "system",
-            "set_led_off",
-            Some(json!({ "off": false })),
+            "set_mode",
+            Some(json!({ "mode": mode })),
         ))?;
 
         log::trace!("(system) {:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
+    }
+
+    pub(super) fn get_child_emeter_realtime(&self, child_id: &str) -> Result<RealtimeStats> {
+        let (has_emeter, model) = self
+            .sysinfo()
+            .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
+
+        if has_emeter {
+            self.emeter.for_child(child_id).get_realtime()
+        } else {
+            Err(error::unsupported_operation(&format!(
+                "{} get_child_emeter_realtime",
+                model
+            )))
+        }
     }
 
-    pub(super) fn turn_off_led(&mut self) -> Result<()> {
+    pub(super) fn set_alias(&mut self, alias: &str) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != "system");
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetDevAlias {
+                alias: alias.to_string(),
+            }))?;
+
+        log::trace!("(system) {:?}", response);
+
+        Ack::from_response(response)
+    }
+
+    pub(super) fn set_location(&mut self, latitude: f64, longitude: f64) -> Result<Ack> {
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(error::invalid_parameter(&format!(
+                "set_location: ({}, {}) is not a location",
+                latitude, longitude
+            )));
+        }
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
         }
 
         let response = self.proto.send_request(&Request::new(
             "system",
-            "set_led_off",
-            Some(json!({ "off": true })),
+            "set_dev_location",
+            Some(json!({ "latitude": latitude, "longitude": longitude })),
         ))?;
 
         log::trace!("(system) {:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
+    }
+
+    pub(super) fn set_timezone(&mut self, index: i32) -> Result<Ack> {
+        self.time_settings.set_timezone(index)
     }
 }
 
 impl Device for HS100 {
-    fn turn_on(&mut self) -> Result<()> {
+    fn turn_on(&mut self) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != "system");
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
         }
 
-        let response = self.proto.send_request(&Request::new(
-            "system",
-            "set_relay_state",
-            Some(json!({ "state": 1 })),
-        ))?;
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetRelayState { on: true }))?;
 
         log::trace!("(system) {:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 
-    fn turn_off(&mut self) -> Result<()> {
+    fn turn_off(&mut self) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != "system");
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
         }
 
-        let response = self.proto.send_request(&Request::new(
-            "system",
-            "set_relay_state",
-            Some(json!({ "state": 0 })),
-        ))?;
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetRelayState { on: false }))?;
 
         log::trace!("(system) {:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
+    }
+
+    fn is_on(&self) -> Result<bool> {
+        self.sysinfo().map(|sysinfo| sysinfo.is_on())
+    }
+
+    fn is_on_fresh(&self) -> Result<bool> {
+        self.sysinfo_fresh().map(|sysinfo| sysinfo.is_on())
     }
 }
 
 impl Sys for HS100 {
-    fn reboot(&mut self, delay: Option<Duration>) -> Result<()> {
+    fn reboot(&mut self, delay: Option<Duration>) -> Result<Ack> {
         self.system.reboot(delay)
     }
 
-    fn factory_reset(&mut self, delay: Option<Duration>) -> Result<()> {
+    fn factory_reset(&mut self, delay: Option<Duration>) -> Result<Ack> {
         self.system.reset(delay)
     }
+
+    fn factory_reset_confirmed(
+        &mut self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack> {
+        self.system.reset_confirmed(confirmation, delay)
+    }
 }
 
 impl Time for HS100 {
-    fn time(&mut self) -> Result<DeviceTime> {
+    fn time(&self) -> Result<DeviceTime> {
         self.time_settings.get_time()
     }
 
-    fn timezone(&mut self) -> Result<DeviceTimeZone> {
+    fn timezone(&self) -> Result<DeviceTimeZone> {
         self.time_settings.get_timezone()
     }
 }
 
 impl Timer for HS100 {
-    fn get_timer_rules(&mut self) -> Result<RuleList> {
+    fn get_timer_rules(&self) -> Result<RuleList> {
         self.timer_settings.get_rules()
     }
 
-    fn add_timer_rule(&mut self, rule: Rule) -> Result<String> {
+    fn add_timer_rule(&mut self, rule: Rule) -> Result<RuleId> {
         let is_table_empty = self.get_timer_rules().map(|list| list.is_empty())?;
         if is_table_empty {
             self.timer_settings.add_rule(rule)
@@ -228,53 +324,49 @@ impl Timer for HS100 {
         }
     }
 
-    fn edit_timer_rule(&mut self, id: &str, rule: Rule) -> Result<()> {
+    fn edit_timer_rule(&mut self, id: &RuleId, rule: Rule) -> Result<Ack> {
         self.timer_settings.edit_rule(id, rule)
     }
 
-    fn delete_timer_rule_with_id(&mut self, id: &str) -> Result<()> {
+    fn delete_timer_rule_with_id(&mut self, id: &RuleId) -> Result<Ack> {
         self.timer_settings.delete_rule_with_id(id)
     }
 
-    fn delete_all_timer_rules(&mut self) -> Result<()> {
+    fn delete_all_timer_rules(&mut self) -> Result<Ack> {
         self.timer_settings.delete_all_rules()
     }
 }
 
 impl Cloud for HS100 {
-    fn get_cloud_info(&mut self) -> Result<CloudInfo> {
+    fn get_cloud_info(&self) -> Result<CloudInfo> {
         self.cloud_settings.get_info()
     }
 
-    fn bind(&mut self, username: &str, password: &str) -> Result<()> {
+    fn bind(&mut self, username: &str, password: &str) -> Result<Ack> {
         self.cloud_settings.bind(username, password)
     }
 
-    fn unbind(&mut self) -> Result<()> {
+    fn unbind(&mut self) -> Result<Ack> {
         self.cloud_settings.unbind()
     }
 
-    fn get_firmware_list(&mut self) -> Result<Vec<String>> {
+    fn get_firmware_list(&self) -> Result<Vec<String>> {
         self.cloud_settings.get_firmware_list()
     }
 
-    fn set_server_url(&mut self, url: &str) -> Result<()> {
+    fn set_server_url(&mut self, url: &str) -> Result<Ack> {
         self.cloud_settings.set_server_url(url)
     }
 }
 
 impl Wlan for HS100 {
-    fn get_scan_info(
-        &mut self,
-        refresh: bool,
-        timeout: Option<Duration>,
-    ) -> Result<Vec<AccessPoint>> {
+    fn get_scan_info(&self, refresh: bool, timeout: Option<Duration>) -> Result<Vec<AccessPoint>> {
         self.netif.get_scan_info(refresh, timeout)
     }
 }
 
 impl Emeter for HS100 {
-    fn get_emeter_realtime(&mut self) -> Result<RealtimeStats> {
+    fn get_emeter_realtime(&self) -> Result<RealtimeStats> {
         let (has_emeter, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
@@ -289,7 +381,7 @@ impl Emeter for HS100 {
         }
     }
 
-    fn get_emeter_month_stats(&mut self, year: u32) -> Result<MonthStats> {
+    fn get_emeter_month_stats(&self, year: u32) -> Result<MonthStats> {
         let (has_emeter, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
@@ -304,20 +396,14 @@ impl Emeter for HS100 {
         }
     }
 
-    fn get_emeter_day_stats(&mut self, month: u32, year: u32) -> Result<DayStats> {
+    fn get_emeter_day_stats(&self, month: u32, year: u32) -> Result<DayStats> {
         let (has_emeter, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
 
         if has_emeter {
-            if util::u32_in_range(month, 1, 12) {
-                self.emeter.get_day_stats(month, year)
-            } else {
-                Err(error::invalid_parameter(&format!(
-                    "{} get_emeter_day_stats: month={} (valid range: 1-12)",
-                    model, month
-                )))
-            }
+            validate::month(&format!("{} get_emeter_day_stats", model), month)?;
+            self.emeter.get_day_stats(month, year)
         } else {
             Err(error::unsupported_operation(&format!(
                 "{} get_emeter_day_stats",
@@ -326,7 +412,7 @@ impl Emeter for HS100 {
         }
     }
 
-    fn erase_emeter_stats(&mut self) -> Result<()> {
+    fn erase_emeter_stats(&mut self) -> Result<Ack> {
         let (has_emeter, model) = self
             .sysinfo()
             .map(|sysinfo| (sysinfo.has_emeter(), sysinfo.model))?;
@@ -345,9 +431,13 @@ impl Emeter for HS100 {
 impl SysInfo for HS100 {
     type Info = HS100Info;
 
-    fn sysinfo(&mut self) -> Result<Self::Info> {
+    fn sysinfo(&self) -> Result<Self::Info> {
         self.sysinfo.get_sysinfo()
     }
+
+    fn sysinfo_fresh(&self) -> Result<Self::Info> {
+        self.sysinfo.get_sysinfo_fresh()
+    }
 }
 
 /// The system information of TP-Link Wi-Fi Smart Plug (HS100).
@@ -358,20 +448,38 @@ pub struct HS100Info {
     model: String,
     #[serde(rename = "type")]
     device_type: String,
-    mac: String,
+    mac: MacAddress,
     alias: String,
     relay_state: u64,
     rssi: i64,
     #[serde(flatten)]
     location: Location,
     led_off: u64,
-    feature: String,
+    feature: FeatureFlags,
     #[serde(flatten)]
     other: Map<String, Value>,
 }
 
-/// The location coordinates of the device.
+/// The automation driving the relay of a plug, reported as `active_mode`
+/// in its system information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActiveMode {
+    /// The relay is switched by the schedule rules.
+    Schedule,
+    /// The relay is switched by a countdown timer rule.
+    CountDown,
+    /// Neither schedule rules nor countdown timers switch the relay.
+    None,
+}
+
+/// The location coordinates of the device, in units of 1e-4 degrees.
+///
+/// Newer firmware reports them as `latitude_i`/`longitude_i` integers,
+/// while some older firmware reports `latitude`/`longitude` in degrees;
+/// both forms are accepted.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "RawLocation")]
 pub struct Location {
     #[serde(rename = "longitude_i")]
     pub longitude: i64,
@@ -379,9 +487,81 @@ pub struct Location {
     pub latitude: i64,
 }
 
+#[derive(Deserialize)]
+struct RawLocation {
+    longitude_i: Option<i64>,
+    latitude_i: Option<i64>,
+    longitude: Option<f64>,
+    latitude: Option<f64>,
+}
+
+impl TryFrom<RawLocation> for Location {
+    type Error = &'static str;
+
+    fn try_from(raw: RawLocation) -> std::result::Result<Location, Self::Error> {
+        let degrees = |value: f64| (value * Location::UNITS_PER_DEGREE).round() as i64;
+        Ok(Location {
+            longitude: raw
+                .longitude_i
+                .or_else(|| raw.longitude.map(degrees))
+                .ok_or("missing field `longitude_i`")?,
+            latitude: raw
+                .latitude_i
+                .or_else(|| raw.latitude.map(degrees))
+                .ok_or("missing field `latitude_i`")?,
+        })
+    }
+}
+
+impl Location {
+    /// The number of units of the coordinates per degree.
+    pub(crate) const UNITS_PER_DEGREE: f64 = 10_000.0;
+
+    /// Returns the latitude of the location in degrees, positive north of
+    /// the equator.
+    pub fn latitude(&self) -> f64 {
+        self.latitude as f64 / Location::UNITS_PER_DEGREE
+    }
+
+    /// Returns the longitude of the location in degrees, positive east of
+    /// the prime meridian.
+    pub fn longitude(&self) -> f64 {
+        self.longitude as f64 / Location::UNITS_PER_DEGREE
+    }
+
+    /// Returns the sunrise and sunset on the given day (UTC) at the
+    /// location.
+    pub fn sun_times(&self, year: i32, month: u32, day: u32) -> SunTimes {
+        solar::sun_times(self.latitude(), self.longitude(), year, month, day)
+    }
+}
+
+impl From<&Location> for (f64, f64) {
+    /// Returns the latitude and longitude of the location in degrees.
+    fn from(location: &Location) -> (f64, f64) {
+        (location.latitude(), location.longitude())
+    }
+}
+
+impl From<Location> for (f64, f64) {
+    /// Returns the latitude and longitude of the location in degrees.
+    fn from(location: Location) -> (f64, f64) {
+        (&location).into()
+    }
+}
+
+/// Formats the location as e.g. `37.3703° N, 122.0382° W`.
 impl fmt::Display for Location {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {})", self.latitude, self.longitude)
+        let (latitude, longitude) = (self.latitude(), self.longitude());
+        write!(
+            f,
+            "{:.4}° {}, {:.4}° {}",
+            latitude.abs(),
+            if latitude < 0.0 { 'S' } else { 'N' },
+            longitude.abs(),
+            if longitude < 0.0 { 'W' } else { 'E' },
+        )
     }
 }
 
@@ -406,9 +586,10 @@ impl HS100Info {
         &self.alias
     }
 
-    /// Returns the mac address of the device.
+    /// Returns the mac address of the device, formatted as
+    /// `AA:BB:CC:DD:EE:FF`.
     pub fn mac_address(&self) -> &str {
-        &self.mac
+        self.mac.as_str()
     }
 
     /// Returns the Wi-Fi signal strength (rssi) of the device.
@@ -421,9 +602,27 @@ impl HS100Info {
         &self.location
     }
 
+    /// Returns the features advertised by the device.
+    pub fn features(&self) -> FeatureFlags {
+        self.feature
+    }
+
+    /// Returns the automation driving the relay, or `None` if the device
+    /// does not report it or reports a mode the library does not know.
+    pub fn active_mode(&self) -> Option<ActiveMode> {
+        self.other
+            .get("active_mode")
+            .and_then(|mode| ActiveMode::deserialize(mode).ok())
+    }
+
     /// Returns whether the device supports emeter stats.
     pub fn has_emeter(&self) -> bool {
-        self.feature.contains("ENE")
+        self.feature.contains(FeatureFlags::ENERGY)
+    }
+
+    /// Returns the system information as JSON, as reported by the device.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(error::json)
     }
 
     /// Returns whether the device is on.
@@ -439,6 +638,115 @@ impl HS100Info {
 
 impl fmt::Display for HS100Info {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", serde_json::to_string(&self).unwrap())
+        sysinfo::write_fields(
+            f,
+            &[
+                ("alias", &self.alias),
+                ("model", &self.model),
+                ("hw_ver", &self.hw_ver),
+                ("sw_ver", &self.sw_ver),
+                ("mac", &self.mac),
+                ("state", &sysinfo::on_off(self.is_on())),
+                ("led", &sysinfo::on_off(self.is_led_on())),
+                ("rssi", &format!("{} dBm", self.rssi)),
+                ("location", &self.location),
+                ("features", &self.feature),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> HS100Info {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name);
+        let mut value: Value = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+        crate::schema::parse([127, 0, 0, 1].into(), value["system"]["get_sysinfo"].take()).unwrap()
+    }
+
+    #[test]
+    fn test_from_sysinfo_seeds_cache() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("hs110_hw2.json");
+        let value: Value = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+        // Nothing listens on the address, so reads must be served from the cache.
+        let config = Config::for_host([127, 0, 0, 1])
+            .with_cache_enabled(Duration::from_secs(60), None)
+            .build();
+
+        let plug = HS100::from_sysinfo(config, value).unwrap();
+        assert_eq!(plug.sysinfo().unwrap().alias(), "Fridge");
+        let call = plug.with_meta(|plug| plug.sysinfo()).unwrap();
+        assert!(call.served_from_cache());
+        assert_eq!(call.value().alias(), "Fridge");
+        assert!(HS100::from_sysinfo(Config::for_host([127, 0, 0, 1]).build(), json!({})).is_err());
+    }
+
+    #[test]
+    fn test_reset_confirmation() {
+        // Nothing listens on the address, so the reset must be refused
+        // before any request is sent.
+        let config = Config::for_host([127, 0, 0, 1])
+            .with_read_timeout(Duration::from_millis(100))
+            .with_reset_confirmation(true)
+            .build();
+        let mut plug = HS100::with_config(config);
+
+        let err = plug.factory_reset(None).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            error::ErrorKind::ConfirmationRequired(_)
+        ));
+        assert_eq!(plug.proto.stats().requests(), 0);
+    }
+
+    #[test]
+    fn test_active_mode() {
+        assert_eq!(
+            fixture("hs100_hw1.json").active_mode(),
+            Some(ActiveMode::Schedule)
+        );
+        assert_eq!(
+            fixture("hs110_hw2.json").active_mode(),
+            Some(ActiveMode::None)
+        );
+        assert_eq!(json!(ActiveMode::CountDown), json!("count_down"));
+    }
+
+    #[test]
+    fn test_display() {
+        let info = fixture("hs110_hw2.json");
+        let display = info.to_string();
+        assert!(display.starts_with("alias:    Fridge\nmodel:    "));
+        assert!(display.contains("\nstate:    "));
+
+        let json: Value = serde_json::from_str(&info.to_json().unwrap()).unwrap();
+        assert_eq!(json["alias"], "Fridge");
+    }
+
+    #[test]
+    fn test_location_formats() {
+        let location = fixture("hs100_hw1.json").location;
+        assert_eq!(
+            (location.latitude, location.longitude),
+            (373_703, -1_220_382)
+        );
+
+        let location = fixture("hs105_hw1.json").location;
+        assert_eq!(
+            (location.latitude, location.longitude),
+            (373_702, -1_220_382)
+        );
+
+        assert_eq!(<(f64, f64)>::from(&location), (37.3702, -122.0382));
+        assert_eq!(location.to_string(), "37.3702° N, 122.0382° W");
     }
 }
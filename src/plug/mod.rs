@@ -1,18 +1,24 @@
 mod hs100;
 pub mod timer;
 
-pub use self::hs100::{Location, HS100};
-use self::timer::{Rule, RuleList, Timer};
-use crate::cloud::{Cloud, CloudInfo};
+pub use self::hs100::{ActiveMode, HS100Info, Location, HS100};
+use self::timer::{Rule, RuleId, RuleList, RulePatch, Timer};
+use crate::ack::Ack;
+use crate::cloud::{self, Cloud, CloudInfo, FirmwareInfo};
 use crate::config::Config;
 use crate::device::Device;
+use crate::diagnostics::{CallResult, Diagnostics};
 use crate::emeter::{DayStats, Emeter, MonthStats, RealtimeStats};
 use crate::error::Result;
+use crate::models::ModelInfo;
+use crate::provisioning::{Change, Profile};
 use crate::sys::Sys;
 use crate::sysinfo::SysInfo;
-use crate::time::{DeviceTime, DeviceTimeZone, Time};
-use crate::wlan::{AccessPoint, Wlan};
+use crate::time::{ClockDrift, DeviceTime, DeviceTimeZone, Time};
+use crate::validate;
+use crate::wlan::{AccessPoint, ScannedAccessPoint, Wlan};
 
+use serde_json::Value;
 use std::fmt;
 use std::net::IpAddr;
 use std::time::Duration;
@@ -34,10 +40,27 @@ use std::time::Duration;
 ///     Ok(())
 /// }
 /// ```
+///
+/// Cloning a plug is cheap: the clone is another handle to the same device,
+/// sharing its connection settings and response cache.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let plug = tplink::Plug::new([192, 168, 1, 100]);
+/// let mut handle = plug.clone();
+///
+/// handle.turn_on()?;
+/// assert!(plug.is_on()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
 pub struct Plug<T> {
     device: T,
 }
 
+impl_device_traits!(Plug);
+
 impl<T: Device> Plug<T> {
     /// Turns on the plug.
     ///
@@ -51,7 +74,7 @@ impl<T: Device> Plug<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn turn_on(&mut self) -> Result<()> {
+    pub fn turn_on(&mut self) -> Result<Ack> {
         self.device.turn_on()
     }
 
@@ -67,9 +90,62 @@ impl<T: Device> Plug<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn turn_off(&mut self) -> Result<()> {
+    pub fn turn_off(&mut self) -> Result<Ack> {
         self.device.turn_off()
     }
+
+    /// Returns whether the device is currently switched on.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let is_on = plug.is_on()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_on(&self) -> Result<bool> {
+        self.device.is_on()
+    }
+
+    /// Returns whether the device is currently switched on, always querying
+    /// the device rather than a cached response. The fresh response replaces
+    /// the cached one, if caching is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let is_on = plug.is_on_fresh()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_on_fresh(&self) -> Result<bool> {
+        self.device.is_on_fresh()
+    }
+
+    /// Toggles the plug and returns the power state it was switched to.
+    ///
+    /// The current state is read and then inverted; see [`Device::toggle`]
+    /// for how concurrent changes by other clients are handled.
+    ///
+    /// [`Device::toggle`]: device/trait.Device.html#method.toggle
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let is_on = plug.toggle()?;
+    /// println!("plug is now {}", if is_on { "on" } else { "off" });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn toggle(&mut self) -> Result<bool> {
+        self.device.toggle()
+    }
 }
 
 impl<T: Sys> Plug<T> {
@@ -99,7 +175,7 @@ impl<T: Sys> Plug<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn reboot(&mut self, delay: Option<Duration>) -> Result<()> {
+    pub fn reboot(&mut self, delay: Option<Duration>) -> Result<Ack> {
         self.device.reboot(delay)
     }
 
@@ -129,9 +205,34 @@ impl<T: Sys> Plug<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn factory_reset(&mut self, delay: Option<Duration>) -> Result<()> {
+    pub fn factory_reset(&mut self, delay: Option<Duration>) -> Result<Ack> {
         self.device.factory_reset(delay)
     }
+
+    /// Factory resets the plug after the given duration, once the given
+    /// confirmation matches either the alias or the id of the plug. This is
+    /// the only way to reset a plug whose config requires reset confirmation.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let config = tplink::Config::for_host([192, 168, 1, 100])
+    ///     .with_reset_confirmation(true)
+    ///     .build();
+    /// let mut plug = tplink::Plug::with_config(config);
+    /// assert!(plug.factory_reset(None).is_err());
+    /// plug.factory_reset_confirmed("Living Room", None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn factory_reset_confirmed(
+        &mut self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack> {
+        self.device.factory_reset_confirmed(confirmation, delay)
+    }
 }
 
 impl<T: Time> Plug<T> {
@@ -142,14 +243,14 @@ impl<T: Time> Plug<T> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let time = plug.time()?; // e.g. `2020-04-09 22:32:01`
     /// # Ok(())
     /// # }
     /// ```
     ///
     /// [`timezone`]: #method.timezone
-    pub fn time(&mut self) -> Result<DeviceTime> {
+    pub fn time(&self) -> Result<DeviceTime> {
         self.device.time()
     }
 
@@ -159,84 +260,179 @@ impl<T: Time> Plug<T> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let timezone = plug.timezone()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn timezone(&mut self) -> Result<DeviceTimeZone> {
+    pub fn timezone(&self) -> Result<DeviceTimeZone> {
         self.device.timezone()
     }
+
+    /// Measures how far the clock of the plug is ahead of or behind the
+    /// host's clock, see [`Time::clock_drift`].
+    ///
+    /// [`Time::clock_drift`]: time/trait.Time.html#method.clock_drift
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let drift = plug.clock_drift()?;
+    /// println!("clock drift: {:+.1}s", drift.as_secs_f64());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clock_drift(&self) -> Result<ClockDrift> {
+        self.device.clock_drift()
+    }
 }
 
 impl<T: Timer> Plug<T> {
-    pub fn get_timer_rules(&mut self) -> Result<RuleList> {
+    pub fn get_timer_rules(&self) -> Result<RuleList> {
         self.device.get_timer_rules()
     }
 
-    pub fn add_timer_rule(&mut self, rule: Rule) -> Result<String> {
+    pub fn add_timer_rule(&mut self, rule: Rule) -> Result<RuleId> {
         self.device.add_timer_rule(rule)
     }
 
-    pub fn edit_timer_rule(&mut self, id: &str, rule: Rule) -> Result<()> {
+    pub fn edit_timer_rule(&mut self, id: &RuleId, rule: Rule) -> Result<Ack> {
         self.device.edit_timer_rule(id, rule)
     }
 
-    pub fn delete_timer_rule_with_id(&mut self, id: &str) -> Result<()> {
+    pub fn patch_timer_rule(&mut self, id: &RuleId, patch: &RulePatch) -> Result<Ack> {
+        self.device.patch_timer_rule(id, patch)
+    }
+
+    pub fn update_timer_rule<F>(&mut self, id: &RuleId, f: F) -> Result<Ack>
+    where
+        F: FnOnce(&mut Rule),
+    {
+        self.device.update_timer_rule(id, f)
+    }
+
+    pub fn delete_timer_rule_with_id(&mut self, id: &RuleId) -> Result<Ack> {
         self.device.delete_timer_rule_with_id(id)
     }
 
-    pub fn delete_all_timer_rules(&mut self) -> Result<()> {
+    pub fn delete_all_timer_rules(&mut self) -> Result<Ack> {
         self.device.delete_all_timer_rules()
     }
 }
 
+impl<T: Device + Timer> Plug<T> {
+    /// Power-cycles the plug: switches the relay off and back on after the
+    /// given duration, e.g. to reboot a router or camera attached to it.
+    ///
+    /// Switching back on is scheduled on the plug itself as a countdown timer
+    /// rule before the relay is switched off, so the plug comes back on even
+    /// if the client loses its connection in the meantime (for instance when
+    /// the plug powers the network it is reached through). Since the plug
+    /// holds a single countdown rule, any existing timer rules are deleted.
+    ///
+    /// Returns the id of the scheduled rule. The duration is rounded down to
+    /// whole seconds and must be at least 1 second.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// plug.power_cycle(Duration::from_secs(10))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn power_cycle(&mut self, off_duration: Duration) -> Result<RuleId> {
+        validate::delay("power_cycle", "off_duration", off_duration, 1..=u32::MAX)?;
+
+        self.device.delete_all_timer_rules()?;
+        let id = self.device.add_timer_rule(
+            Rule::builder()
+                .turn_on(true)
+                .delay(off_duration)
+                .name("power cycle")
+                .build(),
+        )?;
+        self.device.turn_off()?;
+        Ok(id)
+    }
+}
+
 impl<T: Cloud> Plug<T> {
-    pub fn get_cloud_info(&mut self) -> Result<CloudInfo> {
+    pub fn get_cloud_info(&self) -> Result<CloudInfo> {
         self.device.get_cloud_info()
     }
 
-    pub fn bind(&mut self, username: &str, password: &str) -> Result<()> {
+    pub fn bind(&mut self, username: &str, password: &str) -> Result<Ack> {
         self.device.bind(username, password)
     }
 
-    pub fn unbind(&mut self) -> Result<()> {
+    pub fn unbind(&mut self) -> Result<Ack> {
         self.device.unbind()
     }
 
-    pub fn get_firmware_list(&mut self) -> Result<Vec<String>> {
+    pub fn get_firmware_list(&self) -> Result<Vec<String>> {
         self.device.get_firmware_list()
     }
 
-    pub fn set_server_url(&mut self, url: &str) -> Result<()> {
+    pub fn set_server_url(&mut self, url: &str) -> Result<Ack> {
         self.device.set_server_url(url)
     }
 }
 
 impl<T: Wlan> Plug<T> {
     pub fn get_scan_info(
-        &mut self,
+        &self,
         refresh: bool,
         timeout: Option<Duration>,
     ) -> Result<Vec<AccessPoint>> {
         self.device.get_scan_info(refresh, timeout)
     }
+
+    pub fn scan_aggregate(
+        &self,
+        attempts: u32,
+        delay: Duration,
+    ) -> Result<Vec<ScannedAccessPoint>> {
+        self.device.scan_aggregate(attempts, delay)
+    }
 }
 
 impl<T: Emeter> Plug<T> {
-    pub fn get_emeter_realtime(&mut self) -> Result<RealtimeStats> {
+    pub fn get_emeter_realtime(&self) -> Result<RealtimeStats> {
         self.device.get_emeter_realtime()
     }
 
-    pub fn get_emeter_month_stats(&mut self, year: u32) -> Result<MonthStats> {
+    pub fn get_emeter_month_stats(&self, year: u32) -> Result<MonthStats> {
         self.device.get_emeter_month_stats(year)
     }
 
-    pub fn get_emeter_day_stats(&mut self, month: u32, year: u32) -> Result<DayStats> {
+    pub fn get_emeter_day_stats(&self, month: u32, year: u32) -> Result<DayStats> {
         self.device.get_emeter_day_stats(month, year)
     }
 
-    pub fn erase_emeter_stats(&mut self) -> Result<()> {
+    /// Returns the monthly energy usage from `from` up to and including
+    /// `to`, both given as `(year, month)`, as a single series.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let stats = plug.get_emeter_stats_range((2019, 11), (2020, 2))?;
+    /// println!("billing period usage: {} Wh", stats.energy_wh());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_emeter_stats_range(&self, from: (u32, u32), to: (u32, u32)) -> Result<MonthStats> {
+        self.device.get_emeter_stats_range(from, to)
+    }
+
+    pub fn erase_emeter_stats(&mut self) -> Result<Ack> {
         self.device.erase_emeter_stats()
     }
 }
@@ -248,14 +444,31 @@ impl<T: SysInfo> Plug<T> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let sysinfo = plug.sysinfo()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn sysinfo(&mut self) -> Result<T::Info> {
+    pub fn sysinfo(&self) -> Result<T::Info> {
         self.device.sysinfo()
     }
+
+    /// Returns the plug's system information, always querying the device
+    /// rather than a cached response. The fresh response replaces the cached
+    /// one, if caching is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let sysinfo = plug.sysinfo_fresh()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sysinfo_fresh(&self) -> Result<T::Info> {
+        self.device.sysinfo_fresh()
+    }
 }
 
 impl Plug<HS100> {
@@ -281,33 +494,130 @@ impl Plug<HS100> {
         }
     }
 
+    /// Creates a new Plug instance from the given local address and the
+    /// system information already fetched from the device, e.g. a discovery
+    /// response or its `get_sysinfo` object.
+    ///
+    /// If caching is enabled, the responses contained in the information
+    /// are cached, so that they are not fetched again right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Json` error if the system information is not that of a
+    /// plug.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use serde_json::json;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let sysinfo = json!({});
+    /// let plug = tplink::Plug::from_sysinfo([192, 168, 1, 100], sysinfo)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_sysinfo<A>(host: A, sysinfo: Value) -> Result<Plug<HS100>>
+    where
+        A: Into<IpAddr>,
+    {
+        Plug::from_sysinfo_with_config(Config::for_host(host).build(), sysinfo)
+    }
+
+    /// Creates a new Plug instance with the given configuration and the
+    /// system information already fetched from the device. See
+    /// [`from_sysinfo`](#method.from_sysinfo).
+    pub fn from_sysinfo_with_config(config: Config, sysinfo: Value) -> Result<Plug<HS100>> {
+        HS100::from_sysinfo(config, sysinfo).map(|device| Plug { device })
+    }
+
     /// Returns the software version of the device.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let sw_ver = plug.sw_ver()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn sw_ver(&mut self) -> Result<String> {
+    pub fn sw_ver(&self) -> Result<String> {
         self.device.sw_ver()
     }
 
+    /// Runs a self-test of the device and returns a report of its
+    /// reachability, latency, signal strength, cloud binding, clock drift,
+    /// firmware version and transport counters.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// println!("{}", plug.diagnostics());
+    /// ```
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.device.diagnostics()
+    }
+
+    /// Returns the system information of the plug, like [`sysinfo`], along
+    /// with whether it was served from the cache and how long it took.
+    ///
+    /// [`sysinfo`]: #method.sysinfo
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let call = plug.sysinfo_with_meta()?;
+    /// println!("{:?} (cached: {})", call.latency(), call.served_from_cache());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sysinfo_with_meta(&self) -> Result<CallResult<<HS100 as SysInfo>::Info>> {
+        self.device.with_meta(|device| device.sysinfo())
+    }
+
+    /// Returns the current real-time energy meter reading, like [`get_emeter_realtime`], along with whether it was served
+    /// from the cache and how long it took.
+    ///
+    /// [`get_emeter_realtime`]: #method.get_emeter_realtime
+    pub fn get_emeter_realtime_with_meta(&self) -> Result<CallResult<RealtimeStats>> {
+        self.device.with_meta(|device| device.get_emeter_realtime())
+    }
+
+    /// Returns the newest firmware update available for the device, if
+    /// any, by comparing its software version against the firmware list
+    /// reported by the cloud service.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// if let Some(update) = plug.update_available()? {
+    ///     println!("update available: {}", update);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_available(&self) -> Result<Option<FirmwareInfo>> {
+        cloud::update_available(&self.sw_ver()?, &self.get_firmware_list()?)
+    }
+
     /// Returns the hardware version of the device.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let hw_ver = plug.hw_ver()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn hw_ver(&mut self) -> Result<String> {
+    pub fn hw_ver(&self) -> Result<String> {
         self.device.hw_ver()
     }
 
@@ -317,27 +627,44 @@ impl Plug<HS100> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let model = plug.model()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn model(&mut self) -> Result<String> {
+    pub fn model(&self) -> Result<String> {
         self.device.model()
     }
 
+    /// Returns the detected model of the device along with the level of
+    /// support the library offers for it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let model_info = plug.model_info()?;
+    /// println!("{}: {:?}", model_info.name(), model_info.support_level());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn model_info(&self) -> Result<ModelInfo> {
+        self.model().map(|model| ModelInfo::detect(&model))
+    }
+
     /// Returns the name (alias) of the device.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let alias = plug.alias()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn alias(&mut self) -> Result<String> {
+    pub fn alias(&self) -> Result<String> {
         self.device.alias()
     }
 
@@ -347,12 +674,12 @@ impl Plug<HS100> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let mac_address = plug.mac_address()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn mac_address(&mut self) -> Result<String> {
+    pub fn mac_address(&self) -> Result<String> {
         self.device.mac_address()
     }
 
@@ -362,12 +689,12 @@ impl Plug<HS100> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let rssi = plug.rssi()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn rssi(&mut self) -> Result<i64> {
+    pub fn rssi(&self) -> Result<i64> {
         self.device.rssi()
     }
 
@@ -377,42 +704,79 @@ impl Plug<HS100> {
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let location = plug.location()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn location(&mut self) -> Result<Location> {
+    pub fn location(&self) -> Result<Location> {
         self.device.location()
     }
 
-    /// Returns whether the device is currently switched on.
+    /// Renames the device.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
-    /// let is_on = plug.is_on()?;
+    /// plug.set_alias("Fridge")?;
+    /// assert_eq!(plug.alias()?, "Fridge");
     /// # Ok(())
     /// # }
     /// ```
-    pub fn is_on(&mut self) -> Result<bool> {
-        self.device.is_on()
+    pub fn set_alias(&mut self, alias: &str) -> Result<Ack> {
+        self.device.set_alias(alias)
     }
 
-    /// Returns whether the device LED is currently switched on.
+    /// Sets the location of the device, given its latitude and longitude
+    /// in degrees. The device uses its location for sunrise and sunset
+    /// schedules.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// plug.set_location(52.52, 13.405)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_location(&mut self, latitude: f64, longitude: f64) -> Result<Ack> {
+        self.device.set_location(latitude, longitude)
+    }
+
+    /// Sets the timezone of the device to the one with the given index, as
+    /// reported by [`timezone`], keeping its current local time.
+    ///
+    /// [`timezone`]: #method.timezone
     ///
     /// # Examples
     ///
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// plug.set_timezone(39)?;
+    /// assert_eq!(plug.timezone()?.index(), 39);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_timezone(&mut self, index: i32) -> Result<Ack> {
+        self.device.set_timezone(index)
+    }
+
+    /// Returns whether the device LED is currently switched on.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
     /// let is_led_on = plug.is_led_on()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn is_led_on(&mut self) -> Result<bool> {
+    pub fn is_led_on(&self) -> Result<bool> {
         self.device.is_led_on()
     }
 
@@ -428,7 +792,7 @@ impl Plug<HS100> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn turn_on_led(&mut self) -> Result<()> {
+    pub fn turn_on_led(&mut self) -> Result<Ack> {
         self.device.turn_on_led()
     }
 
@@ -444,11 +808,110 @@ impl Plug<HS100> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn turn_off_led(&mut self) -> Result<()> {
+    pub fn turn_off_led(&mut self) -> Result<Ack> {
         self.device.turn_off_led()
     }
 
-    pub fn has_emeter(&mut self) -> Result<bool> {
+    /// Returns the automation currently driving the relay.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tplink::ActiveMode;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// if plug.active_mode()? == ActiveMode::CountDown {
+    ///     println!("a countdown timer is running");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn active_mode(&self) -> Result<ActiveMode> {
+        self.device.active_mode()
+    }
+
+    /// Switches the automation driving the relay between the schedule
+    /// rules, the countdown timer rules, or neither.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tplink::ActiveMode;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// plug.set_active_mode(ActiveMode::Schedule)?;
+    /// assert_eq!(plug.active_mode()?, ActiveMode::Schedule);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_active_mode(&mut self, mode: ActiveMode) -> Result<Ack> {
+        self.device.set_active_mode(mode)
+    }
+
+    /// Returns the current real-time energy meter reading of the outlet
+    /// of a power strip with the given child id.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let strip = tplink::Plug::new([192, 168, 1, 100]);
+    /// let stats = strip.get_child_emeter_realtime("8006AF35494E7DB13DDE9B8F40BF2D9B1963D7C700")?;
+    /// println!("{:?}", stats);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_child_emeter_realtime(&self, child_id: &str) -> Result<RealtimeStats> {
+        self.device.get_child_emeter_realtime(child_id)
+    }
+
+    /// Exports the settings of the device (alias, timezone, location, LED
+    /// state and countdown timer rules) as a [`Profile`], which can be
+    /// serialized and later pushed back with [`apply_config`], e.g. after
+    /// a factory reset or to a replacement device.
+    ///
+    /// [`Profile`]: provisioning/struct.Profile.html
+    /// [`apply_config`]: #method.apply_config
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let plug = tplink::Plug::new([192, 168, 1, 100]);
+    /// let config = plug.export_config()?;
+    /// std::fs::write("fridge.json", serde_json::to_vec(&config)?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn export_config(&self) -> Result<Profile> {
+        Profile::of(self)
+    }
+
+    /// Applies the settings of the given [`Profile`] to the device, and
+    /// returns the changes made. Settings that already match are left
+    /// untouched.
+    ///
+    /// [`Profile`]: provisioning/struct.Profile.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut plug = tplink::Plug::new([192, 168, 1, 101]);
+    /// let config = serde_json::from_slice(&std::fs::read("fridge.json")?)?;
+    /// for change in plug.apply_config(&config)? {
+    ///     println!("{}", change);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn apply_config(&mut self, profile: &Profile) -> Result<Vec<Change>> {
+        profile.apply(self)
+    }
+
+    pub fn has_emeter(&self) -> Result<bool> {
         self.device.has_emeter()
     }
 }
@@ -1,28 +1,61 @@
-use crate::cache::ResponseCache;
-use crate::error::Result;
+use crate::ack::Ack;
+use crate::cache::{self, ResponseCache};
+use crate::error::{self, Error, Result};
 use crate::proto::{Proto, Request};
+use crate::schema;
 
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::fmt;
 use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub trait Timer {
-    fn get_timer_rules(&mut self) -> Result<RuleList>;
-    fn add_timer_rule(&mut self, rule: Rule) -> Result<String>;
-    fn edit_timer_rule(&mut self, id: &str, rule: Rule) -> Result<()>;
-    fn delete_timer_rule_with_id(&mut self, id: &str) -> Result<()>;
-    fn delete_all_timer_rules(&mut self) -> Result<()>;
+    fn get_timer_rules(&self) -> Result<RuleList>;
+    fn add_timer_rule(&mut self, rule: Rule) -> Result<RuleId>;
+    fn edit_timer_rule(&mut self, id: &RuleId, rule: Rule) -> Result<Ack>;
+    fn delete_timer_rule_with_id(&mut self, id: &RuleId) -> Result<Ack>;
+    fn delete_all_timer_rules(&mut self) -> Result<Ack>;
+
+    /// Reads the rule with the given id, applies `f` to it and writes it
+    /// back, so fields left untouched by `f` keep their current values.
+    ///
+    /// Like `toggle`, the read and the write are not atomic.
+    fn update_timer_rule<F>(&mut self, id: &RuleId, f: F) -> Result<Ack>
+    where
+        F: FnOnce(&mut Rule),
+    {
+        let mut rule = self
+            .get_timer_rules()?
+            .iter()
+            .find(|rule| rule.id() == Some(id))
+            .cloned()
+            .ok_or_else(|| {
+                error::invalid_parameter(&format!("update_timer_rule: no rule with id {}", id))
+            })?;
+        f(&mut rule);
+        self.edit_timer_rule(id, rule)
+    }
+
+    /// Applies the fields set in `patch` to the rule with the given id, on
+    /// top of its current values, and writes the whole rule back with
+    /// [`update_timer_rule`](#method.update_timer_rule).
+    fn patch_timer_rule(&mut self, id: &RuleId, patch: &RulePatch) -> Result<Ack> {
+        self.update_timer_rule(id, |rule| patch.apply(rule))
+    }
 }
 
+#[derive(Clone)]
 pub(crate) struct TimerSettings {
     ns: String,
     proto: Rc<Proto>,
-    cache: Rc<ResponseCache>,
+    cache: Arc<ResponseCache>,
 }
 
 impl TimerSettings {
-    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Rc<ResponseCache>) -> TimerSettings {
+    pub(crate) fn new(ns: &str, proto: Rc<Proto>, cache: Arc<ResponseCache>) -> TimerSettings {
         TimerSettings {
             ns: String::from(ns),
             proto,
@@ -33,28 +66,16 @@ impl TimerSettings {
     pub(crate) fn get_rules(&self) -> Result<RuleList> {
         let request = Request::new(&self.ns, "get_rules", None);
 
-        let response = if let Some(cache) = self.cache.as_ref() {
-            cache
-                .borrow_mut()
-                .try_get_or_insert_with(request, |r| self.proto.send_request(r))?
-        } else {
-            self.proto.send_request(&request)?
-        };
+        let response = cache::get_or_send(&self.cache, &self.proto, request)?;
 
         log::trace!("{:?}", response);
 
-        Ok(serde_json::from_value(response).unwrap_or_else(|err| {
-            panic!(
-                "invalid response from host with address {}: {}",
-                self.proto.host(),
-                err
-            )
-        }))
+        schema::parse(self.proto.host(), response)
     }
 
-    pub(crate) fn add_rule(&self, rule: Rule) -> Result<String> {
+    pub(crate) fn add_rule(&self, rule: Rule) -> Result<RuleId> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns)
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
         }
 
         let Rule {
@@ -73,12 +94,20 @@ impl TimerSettings {
 
         log::trace!("{:?}", response);
 
-        Ok(response["id"].to_string())
+        let ack = Ack::from_response(response)?;
+        if !ack.is_success() {
+            return Err(error::device(
+                ack.err_code(),
+                ack.err_msg().map(String::from),
+            ));
+        }
+        let id = ack.get("id").cloned().unwrap_or_default();
+        serde_json::from_value(id).map_err(error::json)
     }
 
-    pub(crate) fn edit_rule(&self, id: &str, rule: Rule) -> Result<()> {
+    pub(crate) fn edit_rule(&self, id: &RuleId, rule: Rule) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns)
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
         }
 
         let Rule {
@@ -97,12 +126,12 @@ impl TimerSettings {
 
         log::trace!("{:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 
-    pub(crate) fn delete_rule_with_id(&self, id: &str) -> Result<()> {
+    pub(crate) fn delete_rule_with_id(&self, id: &RuleId) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns)
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns)
         }
 
         let response = self.proto.send_request(&Request::new(
@@ -113,12 +142,12 @@ impl TimerSettings {
 
         log::trace!("{:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 
-    pub(crate) fn delete_all_rules(&self) -> Result<()> {
+    pub(crate) fn delete_all_rules(&self) -> Result<Ack> {
         if let Some(cache) = self.cache.as_ref() {
-            cache.borrow_mut().retain(|k, _| k.target != self.ns);
+            cache.lock().unwrap().retain(|k, _| k.target != self.ns);
         }
 
         let response =
@@ -127,7 +156,7 @@ impl TimerSettings {
 
         log::trace!("{:?}", response);
 
-        Ok(())
+        Ack::from_response(response)
     }
 }
 
@@ -144,6 +173,39 @@ impl RuleList {
     pub fn is_empty(&self) -> bool {
         self.rule_list.is_empty()
     }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Rule> {
+        self.rule_list.iter()
+    }
+}
+
+/// The identifier the device assigns to a timer rule.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RuleId(String);
+
+impl RuleId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RuleId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for RuleId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<RuleId> {
+        if s.is_empty() {
+            Err(error::invalid_parameter("rule id: empty"))
+        } else {
+            Ok(RuleId(String::from(s)))
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -157,7 +219,7 @@ pub struct Rule {
     // name of the rule
     name: String,
     // rule id (skip serializing if empty)
-    id: Option<String>,
+    id: Option<RuleId>,
     // remaining time in secs (Skip serializing)
     remain: Option<i64>,
 }
@@ -166,6 +228,93 @@ impl Rule {
     pub fn builder() -> Builder {
         Builder::new()
     }
+
+    pub fn id(&self) -> Option<&RuleId> {
+        self.id.as_ref()
+    }
+
+    pub fn turns_on(&self) -> bool {
+        self.act == 1
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enable == 1
+    }
+
+    pub fn delay(&self) -> Duration {
+        Duration::from_secs(self.delay)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_turn_on(&mut self, turn_on: bool) {
+        self.act = if turn_on { 1 } else { 0 };
+    }
+
+    pub fn set_enabled(&mut self, enable: bool) {
+        self.enable = if enable { 1 } else { 0 };
+    }
+
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay.as_secs();
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = String::from(name);
+    }
+}
+
+/// A partial edit of a timer rule. Only the fields that are set are
+/// changed, so the others keep their current values.
+#[derive(Clone, Debug, Default)]
+pub struct RulePatch {
+    act: Option<u32>,
+    delay: Option<u64>,
+    enable: Option<u32>,
+    name: Option<String>,
+}
+
+impl RulePatch {
+    pub fn new() -> RulePatch {
+        RulePatch::default()
+    }
+
+    pub fn turn_on(&mut self, turn_on: bool) -> &mut RulePatch {
+        self.act = Some(if turn_on { 1 } else { 0 });
+        self
+    }
+
+    pub fn enable(&mut self, enable_rule: bool) -> &mut RulePatch {
+        self.enable = Some(if enable_rule { 1 } else { 0 });
+        self
+    }
+
+    pub fn delay(&mut self, delay: Duration) -> &mut RulePatch {
+        self.delay = Some(delay.as_secs());
+        self
+    }
+
+    pub fn name(&mut self, name: &str) -> &mut RulePatch {
+        self.name = Some(String::from(name));
+        self
+    }
+
+    fn apply(&self, rule: &mut Rule) {
+        if let Some(act) = self.act {
+            rule.act = act;
+        }
+        if let Some(delay) = self.delay {
+            rule.delay = delay;
+        }
+        if let Some(enable) = self.enable {
+            rule.enable = enable;
+        }
+        if let Some(name) = &self.name {
+            rule.name = name.clone();
+        }
+    }
 }
 
 pub struct Builder {
@@ -221,3 +370,134 @@ impl Builder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+    use crate::error::ErrorKind;
+    use crate::proto::Builder;
+    use serde_json::Value;
+    use std::net::UdpSocket;
+    use std::thread;
+
+    #[test]
+    fn test_rule_id() {
+        let response = json!({ "id": "8AA75A2B0A8F4C3A9C2B8B2E1F4D5C6B", "err_code": 0 });
+        let id = serde_json::from_value::<RuleId>(response["id"].clone()).unwrap();
+        assert_eq!(id.to_string(), "8AA75A2B0A8F4C3A9C2B8B2E1F4D5C6B");
+        assert_eq!(id, "8AA75A2B0A8F4C3A9C2B8B2E1F4D5C6B".parse().unwrap());
+        assert_eq!(
+            json!({ "id": id }),
+            json!({ "id": "8AA75A2B0A8F4C3A9C2B8B2E1F4D5C6B" })
+        );
+    }
+
+    #[test]
+    fn test_rule_patch_apply() {
+        let mut rule = Rule::builder()
+            .turn_on(false)
+            .delay(Duration::from_secs(60))
+            .name("porch")
+            .build();
+        RulePatch::new()
+            .enable(false)
+            .name("garden")
+            .apply(&mut rule);
+        assert!(!rule.is_enabled());
+        assert_eq!(rule.name(), "garden");
+        assert!(!rule.turns_on());
+        assert_eq!(rule.delay(), Duration::from_secs(60));
+    }
+
+    struct Device(TimerSettings);
+
+    impl Timer for Device {
+        fn get_timer_rules(&self) -> Result<RuleList> {
+            self.0.get_rules()
+        }
+
+        fn add_timer_rule(&mut self, rule: Rule) -> Result<RuleId> {
+            self.0.add_rule(rule)
+        }
+
+        fn edit_timer_rule(&mut self, id: &RuleId, rule: Rule) -> Result<Ack> {
+            self.0.edit_rule(id, rule)
+        }
+
+        fn delete_timer_rule_with_id(&mut self, _id: &RuleId) -> Result<Ack> {
+            unreachable!("not used by this test")
+        }
+
+        fn delete_all_timer_rules(&mut self) -> Result<Ack> {
+            unreachable!("not used by this test")
+        }
+    }
+
+    /// Answers each request with the next response, passing the request
+    /// to `check` first.
+    fn device(
+        responses: Vec<Value>,
+        check: impl Fn(usize, &Value) + Send + 'static,
+    ) -> (Device, thread::JoinHandle<()>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            for (i, response) in responses.into_iter().enumerate() {
+                let (recv, from) = socket.recv_from(&mut buf).unwrap();
+                let request: Value =
+                    serde_json::from_slice(&crypto::decrypt(&buf[..recv])).unwrap();
+                check(i, &request);
+                let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+                socket.send_to(&response, from).unwrap();
+            }
+        });
+        let proto = Builder::new(addr)
+            .read_timeout(Duration::from_secs(3))
+            .build();
+        let timer = TimerSettings::new("count_down", Rc::new(proto), Arc::new(None));
+        (Device(timer), handle)
+    }
+
+    #[test]
+    fn test_patch_sends_merged_rule() {
+        let rule = json!({
+            "id": "A1", "name": "porch", "enable": 1, "act": 1, "delay": 60, "remain": 60,
+        });
+        let responses = vec![
+            json!({ "count_down": { "get_rules": { "rule_list": [rule], "err_code": 0 } } }),
+            json!({ "count_down": { "edit_rule": { "err_code": 0 } } }),
+        ];
+        let (mut timer, handle) = device(responses, |i, request| {
+            if i == 1 {
+                assert_eq!(
+                    request["count_down"]["edit_rule"],
+                    json!({ "id": "A1", "name": "garden", "enable": 0, "act": 1, "delay": 60 })
+                );
+            }
+        });
+
+        let id: RuleId = "A1".parse().unwrap();
+        let ack = timer
+            .patch_timer_rule(&id, RulePatch::new().enable(false).name("garden"))
+            .unwrap();
+        assert!(ack.is_success());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_add_rule_error() {
+        let responses = vec![json!({ "count_down": { "add_rule": {
+            "err_code": -10, "err_msg": "table is full",
+        } } })];
+        let (mut timer, handle) = device(responses, |_, _| {});
+
+        let err = timer.add_timer_rule(Rule::builder().build()).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::Device { code: -10, msg: Some(msg) } if msg == "table is full"
+        ));
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,36 @@
+//! The traits and types most programs need, for glob importing.
+//!
+//! The device wrappers ([`Plug`], [`Bulb`], [`Dimmer`] and [`Switch`])
+//! implement the traits for whatever their device supports, so code can
+//! be written once for any device with a given capability.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tplink::prelude::*;
+//!
+//! fn report<T: Device + Emeter>(device: &mut T) -> Result<()> {
+//!     device.turn_on()?;
+//!     println!("{:?}", device.get_emeter_realtime()?);
+//!     Ok(())
+//! }
+//!
+//! # fn main() -> Result<()> {
+//! report(&mut Plug::new([192, 168, 1, 100]))?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::cloud::Cloud;
+pub use crate::device::Device;
+pub use crate::emeter::Emeter;
+pub use crate::sys::Sys;
+pub use crate::sysinfo::SysInfo;
+pub use crate::time::Time;
+pub use crate::timer::Timer;
+pub use crate::wlan::Wlan;
+
+pub use crate::{
+    discover, Ack, Bulb, Config, Dimmer, Error, ErrorKind, Plug, Result, Switch, HS100, HS200,
+    HS220, LB110,
+};
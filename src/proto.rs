@@ -1,51 +1,22 @@
+use crate::audit::Audit;
 use crate::crypto;
 use crate::error::{self, Result};
+use crate::gate;
+use crate::lease;
+use crate::redact::Redacted;
+pub use crate::request::{Command, Request};
+use crate::wire::{self, DecodeError};
 
-use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::fmt;
-use std::hash::{Hash, Hasher};
-use std::io::ErrorKind;
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, ErrorKind};
 use std::net::{IpAddr, SocketAddr, UdpSocket};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
-pub struct Request {
-    pub target: String,
-    pub command: String,
-    pub arg: Option<Value>,
-}
-
-impl Request {
-    pub fn new(target: &str, command: &str, arg: Option<Value>) -> Request {
-        Request {
-            target: target.into(),
-            command: command.into(),
-            arg,
-        }
-    }
-}
-
-impl PartialEq for Request {
-    fn eq(&self, other: &Self) -> bool {
-        self.target == other.target && self.command == other.command
-    }
-}
-
-impl Eq for Request {}
-
-impl Hash for Request {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.target.hash(state);
-        self.command.hash(state);
-    }
-}
-
-impl fmt::Display for Request {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {})", self.target, self.command)
-    }
-}
+/// The port on which devices listen for requests.
+pub const DEFAULT_PORT: u16 = 9999;
 
 #[derive(Debug)]
 pub struct Builder {
@@ -54,7 +25,20 @@ pub struct Builder {
     read_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
     broadcast: bool,
-    tolerance: u32,
+    offline_tolerance: u32,
+    attempts: u32,
+    max_concurrent_requests: usize,
+    socket_options: SocketOptions,
+    audit: Audit,
+    lease_holder: Option<String>,
+}
+
+/// Options applied to the UDP sockets used to talk to devices.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct SocketOptions {
+    pub(crate) ttl: Option<u32>,
+    pub(crate) tos: Option<u8>,
+    pub(crate) reuse_address: bool,
 }
 
 impl Builder {
@@ -68,7 +52,12 @@ impl Builder {
             read_timeout: None,
             write_timeout: None,
             broadcast: false,
-            tolerance: 1,
+            offline_tolerance: 1,
+            attempts: 1,
+            max_concurrent_requests: 1,
+            socket_options: SocketOptions::default(),
+            audit: Audit::default(),
+            lease_holder: None,
         }
     }
 
@@ -92,8 +81,41 @@ impl Builder {
         self
     }
 
-    pub fn tolerance(&mut self, offline_tolerance: u32) -> &mut Builder {
-        self.tolerance = offline_tolerance;
+    /// Sets how many copies of each request datagram are sent on every
+    /// attempt, so that a request survives the loss of some of them. At
+    /// least one copy is always sent.
+    pub fn offline_tolerance(&mut self, offline_tolerance: u32) -> &mut Builder {
+        self.offline_tolerance = offline_tolerance.max(1);
+        self
+    }
+
+    /// Sets how many times a request is sent before giving up, sending it
+    /// again only when no response arrives within an even share of the
+    /// read timeout.
+    pub fn retry(&mut self, attempts: u32) -> &mut Builder {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Sets how many requests may be in flight to the device at a time,
+    /// across all handles to it in the process.
+    pub fn max_concurrent_requests(&mut self, limit: usize) -> &mut Builder {
+        self.max_concurrent_requests = limit;
+        self
+    }
+
+    pub(crate) fn socket_options(&mut self, socket_options: SocketOptions) -> &mut Builder {
+        self.socket_options = socket_options;
+        self
+    }
+
+    pub(crate) fn audit(&mut self, audit: Audit) -> &mut Builder {
+        self.audit = audit;
+        self
+    }
+
+    pub(crate) fn lease_holder(&mut self, holder: Option<String>) -> &mut Builder {
+        self.lease_holder = holder;
         self
     }
 
@@ -104,19 +126,83 @@ impl Builder {
             read_timeout: self.read_timeout,
             write_timeout: self.write_timeout,
             broadcast: self.broadcast,
-            tolerance: self.tolerance,
+            offline_tolerance: self.offline_tolerance,
+            attempts: self.attempts,
+            max_concurrent_requests: self.max_concurrent_requests,
+            socket_options: self.socket_options,
+            audit: self.audit.clone(),
+            lease_holder: self.lease_holder.clone(),
+            stats: Cell::default(),
+            unsupported: RefCell::default(),
+            #[cfg(feature = "schema-check")]
+            firmware: RefCell::default(),
         }
     }
 }
 
-#[derive(Debug)]
+/// Counters of the requests sent to a device over its connection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TransportStats {
+    requests: u64,
+    failures: u64,
+    timeouts: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    retransmissions: u64,
+}
+
+impl TransportStats {
+    /// Returns the number of requests sent.
+    pub fn requests(&self) -> u64 {
+        self.requests
+    }
+
+    /// Returns the number of requests that failed, including timeouts.
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// Returns the number of requests that timed out waiting for a response.
+    pub fn timeouts(&self) -> u64 {
+        self.timeouts
+    }
+
+    /// Returns the number of encrypted bytes sent, counting every copy of
+    /// a request sent to tolerate packet loss.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Returns the number of encrypted bytes received in responses from
+    /// the device.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Returns the number of times a request was sent again after getting
+    /// no response in time.
+    pub fn retransmissions(&self) -> u64 {
+        self.retransmissions
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Proto {
     addr: SocketAddr,
     buffer_size: usize,
     read_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
     broadcast: bool,
-    tolerance: u32,
+    offline_tolerance: u32,
+    attempts: u32,
+    max_concurrent_requests: usize,
+    socket_options: SocketOptions,
+    audit: Audit,
+    lease_holder: Option<String>,
+    stats: Cell<TransportStats>,
+    unsupported: RefCell<HashSet<String>>,
+    #[cfg(feature = "schema-check")]
+    firmware: RefCell<Option<String>>,
 }
 
 impl Proto {
@@ -128,28 +214,29 @@ impl Proto {
         self.read_timeout
     }
 
-    pub fn discover(&self, req: &[u8]) -> Result<HashMap<IpAddr, Vec<u8>>> {
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
+    pub fn stats(&self) -> TransportStats {
+        self.stats.get()
+    }
 
-        socket.set_broadcast(self.broadcast)?;
-        socket.set_read_timeout(self.read_timeout)?;
-        socket.set_write_timeout(self.write_timeout)?;
+    pub fn discover(&self, req: &[u8]) -> Result<HashMap<IpAddr, Vec<u8>>> {
+        let socket = self.socket()?;
 
-        for _ in 0..self.tolerance {
-            socket.send_to(&crypto::encrypt(req), &self.addr)?;
+        for _ in 0..self.offline_tolerance {
+            socket.send_to(&crypto::encrypt(req), self.addr)?;
         }
 
+        let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
         let mut responses = HashMap::new();
         let mut buf = vec![0; self.buffer_size];
         loop {
-            match socket.recv_from(&mut buf) {
+            match recv_before(&socket, &mut buf, deadline) {
                 Ok((recv, addr)) => {
                     responses
                         .entry(addr.ip())
                         .or_insert_with(|| crypto::decrypt(&buf[..recv]));
                 }
                 Err(e) => {
-                    return if e.kind() == ErrorKind::WouldBlock {
+                    return if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock {
                         Ok(responses)
                     } else {
                         Err(e.into())
@@ -160,36 +247,481 @@ impl Proto {
     }
 
     pub fn send_request(&self, req: &Request) -> Result<Value> {
-        let Request {
-            target,
-            command,
-            arg,
-        } = req;
-        serde_json::to_vec(&json!({ target: { command: arg } }))
-            .map_err(error::json)
-            .and_then(|req| self.send_bytes(&req))
-            .and_then(|res| {
-                serde_json::from_slice::<Value>(&res)
-                    .map(|mut value| value[target][command].take())
-                    .map_err(error::json)
-            })
+        self.send_request_with_timeout(req, self.read_timeout)
     }
 
-    fn send_bytes(&self, req: &[u8]) -> Result<Vec<u8>> {
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
+    /// Sends a request like `send_request`, but waits for the response for
+    /// the given read timeout rather than the proto's own, for commands the
+    /// device takes longer than usual to answer.
+    pub(crate) fn send_request_with_timeout(
+        &self,
+        req: &Request,
+        read_timeout: Option<Duration>,
+    ) -> Result<Value> {
+        let Request { target, arg, .. } = req;
+        if self.unsupported.borrow().contains(target) {
+            return Err(error::unsupported_namespace(target));
+        }
+        log::trace!("({}) {} {:?}", self.host(), req, arg.as_ref().map(Redacted));
+        if req.changes_state() {
+            if let Err(e) = lease::check(self.host(), self.lease_holder.as_deref()) {
+                let response = Err(e);
+                self.audit.record(self.host(), req, &response);
+                return response;
+            }
+        }
+
+        let permit = gate::acquire(self.addr, self.max_concurrent_requests);
+        let response = self
+            .send_bytes(&wire::encode_request(req), read_timeout)
+            .and_then(|datagram| match wire::decode_response(req, &datagram) {
+                Err(DecodeError::UnsupportedNamespace(_)) => Ok(None),
+                response => Ok(Some(response?)),
+            });
+        drop(permit);
+        self.record(&response);
+
+        let response = response.and_then(|response| match response {
+            Some(response) => {
+                #[cfg(feature = "schema-check")]
+                self.check_schema(req, &response);
+                Ok(response)
+            }
+            None => {
+                // Remembered for the lifetime of the device handle, so that
+                // later requests to the namespace fail without a round trip.
+                log::debug!("({}) namespace {} not supported", self.host(), target);
+                self.unsupported.borrow_mut().insert(target.clone());
+                Err(error::unsupported_namespace(target))
+            }
+        });
+        self.audit.record(self.host(), req, &response);
+        response
+    }
+
+    /// Logs the ways in which the response diverges from the schema of its
+    /// command, along with the model and firmware of the device as last
+    /// reported in its system information.
+    #[cfg(feature = "schema-check")]
+    fn check_schema(&self, req: &Request, response: &Value) {
+        if req.command == "get_sysinfo" {
+            if let (Some(model), Some(sw_ver)) =
+                (response["model"].as_str(), response["sw_ver"].as_str())
+            {
+                *self.firmware.borrow_mut() = Some(format!("{} {}", model, sw_ver));
+            }
+        }
+        let divergences = crate::conformance::check(&req.command, response);
+        if !divergences.is_empty() {
+            let firmware = self.firmware.borrow();
+            log::warn!(
+                "({}) [{}] {} response diverges from its schema: {}",
+                self.host(),
+                firmware.as_deref().unwrap_or("unknown model"),
+                req,
+                divergences.join("; ")
+            );
+        }
+    }
+
+    fn record<T>(&self, response: &Result<T>) {
+        self.count(|stats| {
+            stats.requests += 1;
+            if let Err(e) = response {
+                stats.failures += 1;
+                if let error::ErrorKind::Io(e) = e.kind() {
+                    if is_timeout(e) {
+                        stats.timeouts += 1;
+                    }
+                }
+            }
+        });
+    }
+
+    fn count<F: FnOnce(&mut TransportStats)>(&self, f: F) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    /// Sends the encrypted request datagram and returns the encrypted
+    /// response datagram.
+    fn send_bytes(&self, datagram: &[u8], read_timeout: Option<Duration>) -> Result<Vec<u8>> {
+        let socket = self.socket()?;
+
+        // The read timeout bounds the whole request rather than a single
+        // read, so datagrams from other hosts don't extend the wait. It is
+        // split evenly between the attempts, and the request is only sent
+        // again when an attempt gets no response in its share of it.
+        let deadline = read_timeout.map(|timeout| Instant::now() + timeout);
+        let attempt_timeout = read_timeout.map(|timeout| timeout / self.attempts);
+        let mut buf = vec![0; self.buffer_size];
+        for attempt in 1..=self.attempts {
+            if attempt > 1 {
+                log::debug!("({}) no response, sending attempt {}", self.host(), attempt);
+                self.count(|stats| stats.retransmissions += 1);
+            }
+            for _ in 0..self.offline_tolerance {
+                let sent = socket.send_to(datagram, self.addr)?;
+                self.count(|stats| stats.bytes_sent += sent as u64);
+            }
+
+            let attempt_deadline = match (attempt_timeout, deadline) {
+                (Some(timeout), Some(deadline)) if attempt < self.attempts => {
+                    Some(deadline.min(Instant::now() + timeout))
+                }
+                _ => deadline,
+            };
+            loop {
+                match recv_before(&socket, &mut buf, attempt_deadline) {
+                    Ok((recv, addr)) if addr == self.addr || self.broadcast => {
+                        self.count(|stats| stats.bytes_received += recv as u64);
+                        buf.truncate(recv);
+                        return Ok(buf);
+                    }
+                    Ok((_, addr)) => {
+                        log::debug!("ignoring datagram from unexpected host {}", addr);
+                    }
+                    Err(e) if attempt < self.attempts && is_timeout(&e) => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        unreachable!("the last attempt returns")
+    }
+
+    fn socket(&self) -> io::Result<UdpSocket> {
+        let socket = bind(&self.socket_options)?;
 
         socket.set_broadcast(self.broadcast)?;
-        socket.set_read_timeout(self.read_timeout)?;
+        if let Some(ttl) = self.socket_options.ttl {
+            socket.set_ttl(ttl)?;
+        }
+        #[cfg(all(unix, feature = "sockopt"))]
+        if let Some(tos) = self.socket_options.tos {
+            setsockopt(
+                &socket,
+                libc::IPPROTO_IP,
+                libc::IP_TOS,
+                libc::c_int::from(tos),
+            )?;
+        }
         socket.set_write_timeout(self.write_timeout)?;
+        #[cfg(all(unix, feature = "nonblocking"))]
+        socket.set_nonblocking(true)?;
 
-        for _ in 0..self.tolerance {
-            socket.send_to(&crypto::encrypt(req), self.addr)?;
+        Ok(socket)
+    }
+}
+
+#[cfg(not(all(unix, feature = "sockopt")))]
+fn bind(_options: &SocketOptions) -> io::Result<UdpSocket> {
+    UdpSocket::bind("0.0.0.0:0")
+}
+
+/// Binds a socket to an ephemeral port, setting `SO_REUSEADDR` before
+/// binding if requested, which `std` has no way to do.
+#[cfg(all(unix, feature = "sockopt"))]
+fn bind(options: &SocketOptions) -> io::Result<UdpSocket> {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    if !options.reuse_address {
+        return UdpSocket::bind("0.0.0.0:0");
+    }
+
+    // SAFETY: `socket` has no preconditions; on success the returned
+    // descriptor is owned by the `UdpSocket`, which closes it on drop.
+    let socket = unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
         }
+        UdpSocket::from_raw_fd(fd)
+    };
+    setsockopt(&socket, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
 
-        let mut buf = vec![0; self.buffer_size];
-        match socket.recv(&mut buf) {
-            Ok(recv) => Ok(crypto::decrypt(&buf[..recv])),
-            Err(e) => Err(e.into()),
+    // SAFETY: an all-zero `sockaddr_in` is valid, and denotes port 0 on
+    // INADDR_ANY once the family is set.
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    // SAFETY: `addr` is a valid `sockaddr_in` that outlives the call and
+    // the length passed is its size.
+    let ret = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(socket)
+}
+
+#[cfg(all(unix, feature = "sockopt"))]
+fn setsockopt(
+    socket: &UdpSocket,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: `value` is a valid c_int that outlives the call and the
+    // length passed is its size.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the time left until the given deadline, or a `TimedOut` error
+/// if the deadline has already passed.
+fn remaining(deadline: Option<Instant>) -> io::Result<Option<Duration>> {
+    match deadline {
+        Some(deadline) => {
+            let now = Instant::now();
+            if now < deadline {
+                Ok(Some(deadline - now))
+            } else {
+                Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "timed out waiting for a response",
+                ))
+            }
         }
+        None => Ok(None),
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock
+}
+
+/// Receives a single datagram, waiting no longer than the given deadline.
+#[cfg(not(all(unix, feature = "nonblocking")))]
+fn recv_before(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+    deadline: Option<Instant>,
+) -> io::Result<(usize, SocketAddr)> {
+    socket.set_read_timeout(remaining(deadline)?)?;
+    socket.recv_from(buf)
+}
+
+/// Receives a single datagram, waiting no longer than the given deadline.
+#[cfg(all(unix, feature = "nonblocking"))]
+fn recv_before(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+    deadline: Option<Instant>,
+) -> io::Result<(usize, SocketAddr)> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        match socket.recv_from(buf) {
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+            result => return result,
+        }
+
+        let timeout = remaining(deadline)?.map_or(-1, |timeout| {
+            // Round up so that a sub-millisecond remainder doesn't spin.
+            (timeout.as_micros() as u64)
+                .div_ceil(1000)
+                .min(i32::MAX as u64) as i32
+        });
+        let mut fd = libc::pollfd {
+            fd: socket.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fd` is a valid pollfd that outlives the call and the
+        // count passed matches the single descriptor being polled.
+        let ret = unsafe { libc::poll(&mut fd, 1, timeout) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::thread;
+
+    fn device() -> (UdpSocket, SocketAddr) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        (socket, addr)
+    }
+
+    #[test]
+    fn test_send_request() {
+        let (device, addr) = device();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            let (recv, from) = device.recv_from(&mut buf).unwrap();
+            let request: Value = serde_json::from_slice(&crypto::decrypt(&buf[..recv])).unwrap();
+            assert_eq!(request, json!({ "system": { "get_sysinfo": null } }));
+
+            let response = json!({ "system": { "get_sysinfo": { "alias": "plug" } } });
+            let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+            device.send_to(&response, from).unwrap();
+            (recv, response.len())
+        });
+
+        let proto = Builder::new(addr)
+            .read_timeout(Duration::from_secs(3))
+            .build();
+        let response = proto
+            .send_request(&Request::new("system", "get_sysinfo", None))
+            .unwrap();
+        assert_eq!(response, json!({ "alias": "plug" }));
+
+        let (sent, received) = handle.join().unwrap();
+        let stats = proto.stats();
+        assert_eq!(stats.bytes_sent(), sent as u64);
+        assert_eq!(stats.bytes_received(), received as u64);
+    }
+
+    #[test]
+    fn test_unsupported_namespace() {
+        let (device, addr) = device();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            let (_, from) = device.recv_from(&mut buf).unwrap();
+            let response = json!({ "emeter": { "err_code": -1, "err_msg": "module not support" } });
+            let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+            device.send_to(&response, from).unwrap();
+        });
+
+        let proto = Builder::new(addr)
+            .read_timeout(Duration::from_secs(3))
+            .build();
+        let request = Request::new("emeter", "get_realtime", None);
+        for _ in 0..2 {
+            let err = proto.send_request(&request).unwrap_err();
+            assert!(matches!(
+                err.kind(),
+                error::ErrorKind::UnsupportedNamespace { target } if target == "emeter"
+            ));
+        }
+        // The second request failed without reaching the device.
+        assert_eq!(proto.stats().requests(), 1);
+
+        handle.join().unwrap();
+    }
+
+    #[cfg(all(unix, feature = "sockopt"))]
+    #[test]
+    fn test_socket_options() {
+        let (_device, addr) = device();
+        let proto = Builder::new(addr)
+            .socket_options(SocketOptions {
+                ttl: Some(4),
+                tos: Some(0x10),
+                reuse_address: true,
+            })
+            .build();
+
+        let socket = proto.socket().unwrap();
+        assert_eq!(socket.ttl().unwrap(), 4);
+        assert!(socket.local_addr().unwrap().port() != 0);
+    }
+
+    #[test]
+    fn test_retry() {
+        let (device, addr) = device();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            // The first attempt gets lost.
+            device.recv_from(&mut buf).unwrap();
+            let (_, from) = device.recv_from(&mut buf).unwrap();
+            let response = json!({ "system": { "get_sysinfo": { "alias": "plug" } } });
+            let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+            device.send_to(&response, from).unwrap();
+        });
+
+        let proto = Builder::new(addr)
+            .read_timeout(Duration::from_secs(2))
+            .retry(4)
+            .build();
+        let start = Instant::now();
+        let response = proto
+            .send_request(&Request::new("system", "get_sysinfo", None))
+            .unwrap();
+        assert_eq!(response, json!({ "alias": "plug" }));
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let stats = proto.stats();
+        assert_eq!(stats.retransmissions(), 1);
+        assert_eq!(stats.failures(), 0);
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_offline_tolerance_zero_sends_once() {
+        let (device, addr) = device();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            let (_, from) = device.recv_from(&mut buf).unwrap();
+            let response = json!({ "system": { "get_sysinfo": { "alias": "plug" } } });
+            let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+            device.send_to(&response, from).unwrap();
+        });
+
+        let proto = Builder::new(addr)
+            .read_timeout(Duration::from_secs(2))
+            .offline_tolerance(0)
+            .build();
+        let response = proto
+            .send_request(&Request::new("system", "get_sysinfo", None))
+            .unwrap();
+        assert_eq!(response, json!({ "alias": "plug" }));
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_send_request_times_out() {
+        let (_device, addr) = device();
+
+        let timeout = Duration::from_millis(200);
+        let proto = Builder::new(addr).read_timeout(timeout).build();
+        let start = Instant::now();
+        let err = proto
+            .send_request(&Request::new("system", "get_sysinfo", None))
+            .unwrap_err();
+
+        assert!(start.elapsed() >= timeout);
+        match err.kind() {
+            crate::ErrorKind::Io(e) => assert!(
+                e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock,
+                "unexpected error: {}",
+                e
+            ),
+            kind => panic!("unexpected error kind: {:?}", kind),
+        }
+
+        let stats = proto.stats();
+        assert_eq!(stats.requests(), 1);
+        assert_eq!(stats.failures(), 1);
+        assert_eq!(stats.timeouts(), 1);
     }
 }
@@ -0,0 +1,386 @@
+//! Provisioning of plugs from declarative profiles.
+//!
+//! A [`Profile`] describes the settings a plug should end up with: its
+//! name, timezone, location, LED state, cloud binding and countdown timer
+//! rules. Settings the profile leaves unset are left as they are.
+//! Applying a profile only changes the settings that differ from it, so
+//! applying it again changes nothing, and reports the changes it made.
+//!
+//! Profiles are serializable, so the same profile can be kept in a file
+//! and applied to every device being onboarded. [`Profile::of`] exports
+//! the current settings of a plug as a profile, to restore them later.
+//!
+//! [`Profile`]: struct.Profile.html
+//! [`Profile::of`]: struct.Profile.html#method.of
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tplink::provisioning::{CloudBinding, Profile};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let profile = Profile::new()
+//!     .with_timezone(39)
+//!     .with_location(52.52, 13.405)
+//!     .with_led(false)
+//!     .with_cloud(CloudBinding::Unbound);
+//!
+//! for host in &[[192, 168, 1, 100], [192, 168, 1, 101]] {
+//!     let mut plug = tplink::Plug::new(*host);
+//!     for change in profile.apply(&mut plug)? {
+//!         println!("{:?}: {}", host, change);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Result;
+use crate::plug::timer::Rule;
+use crate::plug::Location;
+use crate::{Plug, HS100};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Whether a device should be bound to a cloud account.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloudBinding {
+    /// The device is not bound to any account.
+    Unbound,
+    /// The device is bound to the account with the given credentials.
+    Bound { username: String, password: String },
+}
+
+impl fmt::Debug for CloudBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CloudBinding::Unbound => write!(f, "Unbound"),
+            CloudBinding::Bound { username, .. } => f
+                .debug_struct("Bound")
+                .field("username", username)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// The settings a device should end up with. Settings left unset are left
+/// as they are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    alias: Option<String>,
+    timezone: Option<i32>,
+    location: Option<(f64, f64)>,
+    led_on: Option<bool>,
+    cloud: Option<CloudBinding>,
+    timer_rules: Option<Vec<Rule>>,
+}
+
+/// A change made, or to be made, to a device to match a [`Profile`].
+///
+/// [`Profile`]: struct.Profile.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The name (alias) changes.
+    Alias { old: String, new: String },
+    /// The index of the timezone changes.
+    Timezone { old: i32, new: i32 },
+    /// The latitude and longitude, in degrees, change.
+    Location { old: (f64, f64), new: (f64, f64) },
+    /// The LED is switched on or off.
+    Led { on: bool },
+    /// The account the device is bound to changes, `None` meaning none.
+    Cloud {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// The countdown timer rules are replaced, with the numbers of rules
+    /// before and after.
+    TimerRules { old: usize, new: usize },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let account = |account: &Option<String>| match account {
+            Some(username) => format!("bound to {}", username),
+            None => String::from("unbound"),
+        };
+        match self {
+            Change::Alias { old, new } => write!(f, "alias: {} -> {}", old, new),
+            Change::Timezone { old, new } => write!(f, "timezone: {} -> {}", old, new),
+            Change::Location { old, new } => write!(
+                f,
+                "location: ({}, {}) -> ({}, {})",
+                old.0, old.1, new.0, new.1
+            ),
+            Change::Led { on } => write!(f, "led: {}", if *on { "on" } else { "off" }),
+            Change::Cloud { old, new } => {
+                write!(f, "cloud: {} -> {}", account(old), account(new))
+            }
+            Change::TimerRules { old, new } => {
+                write!(f, "timer rules: {} -> {} rules", old, new)
+            }
+        }
+    }
+}
+
+impl Profile {
+    /// Creates a profile that leaves every setting as it is.
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    /// Sets the name (alias) of the device.
+    pub fn with_alias(mut self, alias: &str) -> Profile {
+        self.alias = Some(alias.to_string());
+        self
+    }
+
+    /// Sets the timezone of the device, by index.
+    pub fn with_timezone(mut self, index: i32) -> Profile {
+        self.timezone = Some(index);
+        self
+    }
+
+    /// Sets the location of the device, as a latitude and a longitude in
+    /// degrees.
+    pub fn with_location(mut self, latitude: f64, longitude: f64) -> Profile {
+        self.location = Some((latitude, longitude));
+        self
+    }
+
+    /// Sets whether the LED of the device is on.
+    pub fn with_led(mut self, on: bool) -> Profile {
+        self.led_on = Some(on);
+        self
+    }
+
+    /// Sets whether, and to which account, the device is bound to the
+    /// cloud.
+    pub fn with_cloud(mut self, cloud: CloudBinding) -> Profile {
+        self.cloud = Some(cloud);
+        self
+    }
+
+    /// Sets the countdown timer rules of the device, replacing any other.
+    pub fn with_timer_rules(mut self, rules: Vec<Rule>) -> Profile {
+        self.timer_rules = Some(rules);
+        self
+    }
+
+    /// Returns the profile of the current settings of the plug, which
+    /// restores them when applied, e.g. to a replacement of the plug.
+    ///
+    /// The profile leaves the cloud binding unset, since the password of
+    /// the account cannot be read back from the plug.
+    pub fn of(plug: &Plug<HS100>) -> Result<Profile> {
+        let sysinfo = plug.sysinfo()?;
+        Ok(Profile {
+            alias: Some(sysinfo.alias().to_string()),
+            timezone: Some(plug.timezone()?.index()),
+            location: Some(sysinfo.location().into()),
+            led_on: Some(plug.is_led_on()?),
+            cloud: None,
+            timer_rules: Some(plug.get_timer_rules()?.iter().cloned().collect()),
+        })
+    }
+
+    /// Returns the changes applying the profile to the plug would make,
+    /// without making them.
+    pub fn diff(&self, plug: &Plug<HS100>) -> Result<Vec<Change>> {
+        let mut changes = Vec::new();
+
+        if let Some(alias) = &self.alias {
+            let old = plug.alias()?;
+            if old != *alias {
+                changes.push(Change::Alias {
+                    old,
+                    new: alias.clone(),
+                });
+            }
+        }
+        if let Some(index) = self.timezone {
+            let old = plug.timezone()?.index();
+            if old != index {
+                changes.push(Change::Timezone { old, new: index });
+            }
+        }
+        if let Some(location) = self.location {
+            let old = plug.location()?;
+            if !same_location(&old, location) {
+                changes.push(Change::Location {
+                    old: (&old).into(),
+                    new: location,
+                });
+            }
+        }
+        if let Some(on) = self.led_on {
+            if plug.is_led_on()? != on {
+                changes.push(Change::Led { on });
+            }
+        }
+        if let Some(cloud) = &self.cloud {
+            let info = plug.get_cloud_info()?;
+            let old = if info.bounded() {
+                Some(info.username().to_string())
+            } else {
+                None
+            };
+            let new = match cloud {
+                CloudBinding::Unbound => None,
+                CloudBinding::Bound { username, .. } => Some(username.clone()),
+            };
+            if old != new {
+                changes.push(Change::Cloud { old, new });
+            }
+        }
+        if let Some(rules) = &self.timer_rules {
+            let old = plug.get_timer_rules()?;
+            let old = old.iter().cloned().collect::<Vec<_>>();
+            if !same_rules(&old, rules) {
+                changes.push(Change::TimerRules {
+                    old: old.len(),
+                    new: rules.len(),
+                });
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Applies the profile to the plug, changing only the settings that
+    /// differ from it, and returns the changes made.
+    ///
+    /// An error stops at the setting that failed, leaving the settings
+    /// changed before it as they are; applying the profile again resumes
+    /// from there.
+    pub fn apply(&self, plug: &mut Plug<HS100>) -> Result<Vec<Change>> {
+        let changes = self.diff(plug)?;
+
+        for change in &changes {
+            log::debug!("provisioning: {}", change);
+            match change {
+                Change::Alias { new, .. } => {
+                    plug.set_alias(new)?;
+                }
+                Change::Timezone { new, .. } => {
+                    plug.set_timezone(*new)?;
+                }
+                Change::Location { new, .. } => {
+                    plug.set_location(new.0, new.1)?;
+                }
+                Change::Led { on: true } => {
+                    plug.turn_on_led()?;
+                }
+                Change::Led { on: false } => {
+                    plug.turn_off_led()?;
+                }
+                Change::Cloud { .. } => match &self.cloud {
+                    Some(CloudBinding::Bound { username, password }) => {
+                        plug.bind(username, password)?;
+                    }
+                    _ => {
+                        plug.unbind()?;
+                    }
+                },
+                Change::TimerRules { .. } => {
+                    plug.delete_all_timer_rules()?;
+                    for rule in self.timer_rules.iter().flatten() {
+                        plug.add_timer_rule(rule.clone())?;
+                    }
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+/// Returns whether the location is the given latitude and longitude, to
+/// the precision the device keeps them with.
+fn same_location(location: &Location, (latitude, longitude): (f64, f64)) -> bool {
+    let units = |degrees: f64| (degrees * Location::UNITS_PER_DEGREE).round() as i64;
+    location.latitude == units(latitude) && location.longitude == units(longitude)
+}
+
+/// Returns whether the rules are the same, in the same order, regardless
+/// of the ids the device assigned them.
+fn same_rules(old: &[Rule], new: &[Rule]) -> bool {
+    old.len() == new.len()
+        && old.iter().zip(new).all(|(old, new)| {
+            old.turns_on() == new.turns_on()
+                && old.is_enabled() == new.is_enabled()
+                && old.delay() == new.delay()
+                && old.name() == new.name()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::time::Duration;
+
+    #[test]
+    fn test_diff() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("hs110_hw2.json");
+        let value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        // Nothing listens on the address, so the sysinfo must come from the cache.
+        let config = Config::for_host([127, 0, 0, 1])
+            .with_cache_enabled(Duration::from_secs(60), None)
+            .build();
+        let plug = Plug::from_sysinfo_with_config(config, value).unwrap();
+
+        let profile = Profile::new()
+            .with_alias("Fridge")
+            .with_location(52.52, 13.4049)
+            .with_led(true);
+        assert_eq!(profile.diff(&plug).unwrap(), []);
+
+        let profile = profile.with_alias("Freezer").with_led(false);
+        assert_eq!(
+            profile.diff(&plug).unwrap(),
+            [
+                Change::Alias {
+                    old: String::from("Fridge"),
+                    new: String::from("Freezer"),
+                },
+                Change::Led { on: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_same_rules() {
+        let rule = |delay| Rule::builder().delay(Duration::from_secs(delay)).build();
+        assert!(same_rules(&[rule(60)], &[rule(60)]));
+        assert!(!same_rules(&[rule(60)], &[rule(120)]));
+        assert!(!same_rules(&[rule(60)], &[]));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rule = Rule::builder().delay(Duration::from_secs(60)).build();
+        let profile = Profile::new()
+            .with_alias("Fridge")
+            .with_location(52.52, 13.4049)
+            .with_timer_rules(vec![rule]);
+
+        let json = serde_json::to_string(&profile).unwrap();
+        let restored: Profile = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.alias.as_deref(), Some("Fridge"));
+        assert_eq!(restored.location, Some((52.52, 13.4049)));
+        assert_eq!(restored.timezone, None);
+        assert!(same_rules(
+            restored.timer_rules.as_ref().unwrap(),
+            profile.timer_rules.as_ref().unwrap()
+        ));
+
+        let empty: Profile = serde_json::from_str("{}").unwrap();
+        assert!(empty.alias.is_none() && empty.cloud.is_none());
+    }
+}
@@ -0,0 +1,90 @@
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use serde_json::Value;
+
+/// Keys whose values are masked in logged requests and responses.
+const SENSITIVE_KEYS: &[&str] = &["password", "passwd", "pwd", "key", "token"];
+
+const MASK: &str = "<redacted>";
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables the redaction of credentials (passwords, keys and
+/// tokens) in the requests and responses the library logs.
+///
+/// Redaction is enabled by default. Disabling it can help when debugging
+/// a device, but the logs then contain credentials in plain text.
+///
+/// # Examples
+///
+/// ```
+/// tplink::set_log_redaction(false);
+/// ```
+pub fn set_log_redaction(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Formats a JSON value for logging with its sensitive fields masked,
+/// unless redaction has been disabled. The value is only copied when it
+/// is actually formatted, i.e. when the log level is enabled.
+pub(crate) struct Redacted<'a>(pub(crate) &'a Value);
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if ENABLED.load(Ordering::Relaxed) {
+            redact(self.0).fmt(f)
+        } else {
+            self.0.fmt(f)
+        }
+    }
+}
+
+/// Returns a copy of a JSON value with its sensitive fields masked, unless
+/// redaction has been disabled.
+pub(crate) fn redacted(value: &Value) -> Value {
+    if ENABLED.load(Ordering::Relaxed) {
+        redact(value)
+    } else {
+        value.clone()
+    }
+}
+
+fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if SENSITIVE_KEYS.contains(&key.to_lowercase().as_str()) {
+                        Value::from(MASK)
+                    } else {
+                        redact(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(redact).collect()),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact() {
+        let value = json!({
+            "cnCloud": {"bind": {"username": "alice@example.com", "password": "hunter2"}},
+            "netif": {"set_stainfo": {"ssid": "home", "key_type": 3, "Key": "secret"}},
+        });
+        assert_eq!(
+            redact(&value),
+            json!({
+                "cnCloud": {"bind": {"username": "alice@example.com", "password": MASK}},
+                "netif": {"set_stainfo": {"ssid": "home", "key_type": 3, "Key": MASK}},
+            })
+        );
+    }
+}
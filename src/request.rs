@@ -0,0 +1,264 @@
+//! Raw requests to devices and the commands they understand.
+//!
+//! Like [`wire`](../wire/index.html), this module sticks to `core`, `alloc`
+//! and `serde_json`, so the wire logic does not depend on the networking of
+//! the rest of the crate.
+
+use crate::redact::Redacted;
+
+use alloc::string::String;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// A raw request to a device: the `command` to run within the `target`
+/// namespace, with its optional argument.
+///
+/// On the wire, a request is sent as `{"<target>": {"<command>": <arg>}}`.
+/// A request addressed to a child of a device, such as an outlet of a
+/// power strip, also carries its id as `{"context": {"child_ids": [..]}}`.
+/// Two requests are equal when they run the same command in the same
+/// namespace of the same child, regardless of their arguments.
+///
+/// # Examples
+///
+/// ```
+/// use tplink::{Command, Request};
+///
+/// let request = Request::from(Command::SetRelayState { on: true });
+/// assert_eq!(request.target, "system");
+/// assert_eq!(request.command, "set_relay_state");
+/// assert_eq!(request.arg, Some(serde_json::json!({ "state": 1 })));
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Request {
+    pub target: String,
+    pub command: String,
+    pub arg: Option<Value>,
+    /// The id of the child of the device the request is addressed to, if
+    /// any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child_id: Option<String>,
+}
+
+impl Request {
+    /// Creates a new request for the given command within the given
+    /// target namespace.
+    pub fn new(target: &str, command: &str, arg: Option<Value>) -> Request {
+        Request {
+            target: target.into(),
+            command: command.into(),
+            arg,
+            child_id: None,
+        }
+    }
+
+    /// Addresses the request to the child of the device with the given
+    /// id, e.g. an outlet of a power strip.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tplink::Request;
+    ///
+    /// let request = Request::new("emeter", "get_realtime", None).with_child("00");
+    /// assert_eq!(request.child_id.as_deref(), Some("00"));
+    /// assert_ne!(request, Request::new("emeter", "get_realtime", None));
+    /// ```
+    pub fn with_child(mut self, child_id: &str) -> Request {
+        self.child_id = Some(child_id.into());
+        self
+    }
+
+    /// Returns whether the command changes the state of the device, i.e.
+    /// is anything but a `get_*` query.
+    pub(crate) fn changes_state(&self) -> bool {
+        !self.command.starts_with("get_")
+    }
+}
+
+/// The commands understood by TP-Link devices.
+///
+/// Commands that are not specific to bulbs or dimmers use the namespaces
+/// of plugs, switches and dimmers (`system` and `emeter`). Any other
+/// command can be sent as a raw [`Request`].
+///
+/// [`Request`]: struct.Request.html
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Command {
+    /// Returns the system information of any device.
+    GetSysinfo,
+    /// Switches the relay on or off.
+    SetRelayState { on: bool },
+    /// Switches the LED indicator off or on.
+    SetLedOff { off: bool },
+    /// Renames the device.
+    SetDevAlias { alias: String },
+    /// Reboots the device after the given delay.
+    Reboot { delay: Duration },
+    /// Returns the realtime energy meter reading.
+    GetEmeterRealtime,
+    /// Returns the light state of a bulb.
+    GetLightState,
+    /// Changes the light state of a bulb. Fields left unset keep their
+    /// current values.
+    TransitionLightState {
+        on: Option<bool>,
+        hue: Option<u32>,
+        saturation: Option<u32>,
+        brightness: Option<u32>,
+        color_temp: Option<u32>,
+        transition: Option<Duration>,
+    },
+    /// Sets the brightness of a dimmer.
+    SetBrightness { brightness: u32 },
+}
+
+impl Command {
+    /// Returns the namespace the command belongs to.
+    pub fn target(&self) -> &'static str {
+        match self {
+            Command::GetSysinfo
+            | Command::SetRelayState { .. }
+            | Command::SetLedOff { .. }
+            | Command::SetDevAlias { .. }
+            | Command::Reboot { .. } => "system",
+            Command::GetEmeterRealtime => "emeter",
+            Command::GetLightState | Command::TransitionLightState { .. } => {
+                "smartlife.iot.smartbulb.lightingservice"
+            }
+            Command::SetBrightness { .. } => "smartlife.iot.dimmer",
+        }
+    }
+
+    /// Returns the name of the command within its namespace.
+    pub fn command(&self) -> &'static str {
+        match self {
+            Command::GetSysinfo => "get_sysinfo",
+            Command::SetRelayState { .. } => "set_relay_state",
+            Command::SetLedOff { .. } => "set_led_off",
+            Command::SetDevAlias { .. } => "set_dev_alias",
+            Command::Reboot { .. } => "reboot",
+            Command::GetEmeterRealtime => "get_realtime",
+            Command::GetLightState => "get_light_state",
+            Command::TransitionLightState { .. } => "transition_light_state",
+            Command::SetBrightness { .. } => "set_brightness",
+        }
+    }
+
+    /// Returns the argument of the command, if any.
+    pub fn arg(&self) -> Option<Value> {
+        match self {
+            Command::GetSysinfo | Command::GetEmeterRealtime | Command::GetLightState => None,
+            Command::SetRelayState { on } => Some(json!({ "state": if *on { 1 } else { 0 } })),
+            Command::SetLedOff { off } => Some(json!({ "off": off })),
+            Command::SetDevAlias { alias } => Some(json!({ "alias": alias })),
+            Command::Reboot { delay } => Some(json!({ "delay": delay.as_secs() })),
+            Command::TransitionLightState {
+                on,
+                hue,
+                saturation,
+                brightness,
+                color_temp,
+                transition,
+            } => {
+                let mut arg = json!({});
+                if let Some(on) = on {
+                    arg["on_off"] = json!(if *on { 1 } else { 0 });
+                }
+                if let Some(hue) = hue {
+                    arg["hue"] = json!(hue);
+                }
+                if let Some(saturation) = saturation {
+                    arg["saturation"] = json!(saturation);
+                }
+                if let Some(brightness) = brightness {
+                    arg["brightness"] = json!(brightness);
+                }
+                if let Some(color_temp) = color_temp {
+                    arg["color_temp"] = json!(color_temp);
+                }
+                if let Some(transition) = transition {
+                    arg["transition_period"] = json!(transition.as_millis() as u64);
+                }
+                Some(arg)
+            }
+            Command::SetBrightness { brightness } => Some(json!({ "brightness": brightness })),
+        }
+    }
+}
+
+impl From<Command> for Request {
+    fn from(command: Command) -> Request {
+        Request::new(command.target(), command.command(), command.arg())
+    }
+}
+
+impl PartialEq for Request {
+    fn eq(&self, other: &Self) -> bool {
+        self.target == other.target
+            && self.command == other.command
+            && self.child_id == other.child_id
+    }
+}
+
+impl Eq for Request {}
+
+impl Hash for Request {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.target.hash(state);
+        self.command.hash(state);
+        self.child_id.hash(state);
+    }
+}
+
+impl fmt::Debug for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("target", &self.target)
+            .field("command", &self.command)
+            .field("arg", &self.arg.as_ref().map(Redacted))
+            .field("child_id", &self.child_id)
+            .finish()
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.child_id {
+            Some(child_id) => write!(f, "({}, {}, {})", self.target, self.command, child_id),
+            None => write!(f, "({}, {})", self.target, self.command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_into_request() {
+        let request = Request::from(Command::TransitionLightState {
+            on: Some(true),
+            hue: None,
+            saturation: None,
+            brightness: Some(30),
+            color_temp: Some(2700),
+            transition: Some(Duration::from_millis(500)),
+        });
+        assert_eq!(request.target, "smartlife.iot.smartbulb.lightingservice");
+        assert_eq!(request.command, "transition_light_state");
+        assert_eq!(
+            request.arg,
+            Some(json!({
+                "on_off": 1,
+                "brightness": 30,
+                "color_temp": 2700,
+                "transition_period": 500,
+            }))
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Wake-up and sleep routines for bulbs.
+//!
+//! A [`Routine`] gradually brightens a bulb at a time of day, to wake up
+//! to, or dims it down to off, to fall asleep to. Routines are driven from
+//! the host with [`Bulb::ramp_brightness`], so they can last longer than
+//! the bulb's own transitions allow, and can be saved to and loaded from a
+//! file with [`save`] and [`load`].
+//!
+//! [`Routine`]: struct.Routine.html
+//! [`Bulb::ramp_brightness`]: ../struct.Bulb.html#method.ramp_brightness
+//! [`save`]: fn.save.html
+//! [`load`]: fn.load.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tplink::routines::{self, Routine};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let wake_up = Routine::wake_up("weekday", 6, 30, Duration::from_secs(30 * 60))?
+//!     .with_brightness(80)
+//!     .with_color_temp(2700);
+//! routines::save("routines.json", &[wake_up])?;
+//!
+//! let mut bulb = tplink::Bulb::new([192, 168, 1, 101]);
+//! for routine in routines::load("routines.json")? {
+//!     routine.run_next(&mut bulb)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::ack::Ack;
+use crate::bulb::{Bulb, Easing, HSV, LB110};
+use crate::error::Result;
+use crate::time::DeviceTime;
+use crate::validate;
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// What a [`Routine`] does to a bulb.
+///
+/// [`Routine`]: struct.Routine.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutineKind {
+    /// Switches the bulb on at its lowest brightness and brightens it up
+    /// to the routine's brightness.
+    WakeUp,
+    /// Dims the bulb from its current brightness down and switches it off.
+    Sleep,
+}
+
+/// A wake-up or sleep routine, starting at a time of day in the bulb's
+/// local time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Routine {
+    name: String,
+    kind: RoutineKind,
+    hour: u32,
+    minute: u32,
+    duration_secs: u64,
+    brightness: u32,
+    color_temp: Option<u32>,
+    easing: Easing,
+}
+
+impl Routine {
+    /// Returns a wake-up routine starting at the given time of day and
+    /// brightening the bulb to full brightness over the given duration.
+    pub fn wake_up(name: &str, hour: u32, minute: u32, duration: Duration) -> Result<Routine> {
+        Routine::new(name, RoutineKind::WakeUp, hour, minute, duration)
+    }
+
+    /// Returns a sleep routine starting at the given time of day and
+    /// dimming the bulb down to off over the given duration.
+    pub fn sleep(name: &str, hour: u32, minute: u32, duration: Duration) -> Result<Routine> {
+        Routine::new(name, RoutineKind::Sleep, hour, minute, duration)
+    }
+
+    fn new(
+        name: &str,
+        kind: RoutineKind,
+        hour: u32,
+        minute: u32,
+        duration: Duration,
+    ) -> Result<Routine> {
+        validate::time_of_day(&format!("routine {}", name), hour, minute)?;
+        Ok(Routine {
+            name: name.to_string(),
+            kind,
+            hour,
+            minute,
+            duration_secs: duration.as_secs(),
+            brightness: 100,
+            color_temp: None,
+            easing: Easing::EaseInOut,
+        })
+    }
+
+    /// Sets the % brightness a wake-up routine brightens the bulb to. This
+    /// has no effect on sleep routines.
+    pub fn with_brightness(mut self, brightness: u32) -> Routine {
+        self.brightness = brightness;
+        self
+    }
+
+    /// Sets the color temperature, in kelvin, of the light of a wake-up
+    /// routine. This has no effect on sleep routines.
+    pub fn with_color_temp(mut self, color_temp: u32) -> Routine {
+        self.color_temp = Some(color_temp);
+        self
+    }
+
+    /// Sets the easing curve of the routine, `Easing::EaseInOut` by
+    /// default.
+    pub fn with_easing(mut self, easing: Easing) -> Routine {
+        self.easing = easing;
+        self
+    }
+
+    /// Returns the name of the routine.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns what the routine does.
+    pub fn kind(&self) -> RoutineKind {
+        self.kind
+    }
+
+    /// Returns the time of day the routine starts at, as an hour and a
+    /// minute.
+    pub fn start(&self) -> (u32, u32) {
+        (self.hour, self.minute)
+    }
+
+    /// Returns how long the routine lasts.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.duration_secs)
+    }
+
+    /// Returns the time from the given device time until the routine next
+    /// starts, which is zero if it starts at that very second.
+    pub fn time_until_start(&self, now: &DeviceTime) -> Duration {
+        let start = u64::from(self.hour * 3600 + self.minute * 60);
+        let now = u64::from(now.hour() * 3600 + now.minute() * 60 + now.second());
+        Duration::from_secs((start + SECS_PER_DAY - now) % SECS_PER_DAY)
+    }
+
+    /// Runs the routine on the given bulb now, blocking until it is
+    /// complete.
+    pub fn run<C>(&self, bulb: &mut Bulb<LB110, C>) -> Result<Ack> {
+        match self.kind {
+            RoutineKind::WakeUp => {
+                if let Some(color_temp) = self.color_temp {
+                    bulb.turn_on_with(None, Some(HSV::white(color_temp, 1)), None)?;
+                }
+                bulb.ramp_brightness(1, self.brightness, self.duration(), self.easing)
+            }
+            RoutineKind::Sleep => {
+                if bulb.is_on_fresh()? {
+                    let brightness = bulb.brightness()?;
+                    bulb.ramp_brightness(brightness, 1, self.duration(), self.easing)?;
+                }
+                bulb.turn_off()
+            }
+        }
+    }
+
+    /// Waits until the routine next starts, according to the bulb's
+    /// clock, and runs it on the bulb, blocking until it is complete.
+    pub fn run_next<C>(&self, bulb: &mut Bulb<LB110, C>) -> Result<Ack> {
+        let now = bulb.time()?;
+        thread::sleep(self.time_until_start(&now));
+        self.run(bulb)
+    }
+}
+
+/// Saves the given routines to the file at the given path.
+pub fn save<P: AsRef<Path>>(path: P, routines: &[Routine]) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, routines).map_err(io::Error::from)
+}
+
+/// Loads the routines saved by [`save`] from the file at the given path.
+///
+/// [`save`]: fn.save.html
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Routine>> {
+    let reader = BufReader::new(File::open(path)?);
+    let routines: Vec<Routine> = serde_json::from_reader(reader)?;
+    for routine in &routines {
+        validate::time_of_day(
+            &format!("routine {}", routine.name),
+            routine.hour,
+            routine.minute,
+        )
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    }
+    Ok(routines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_time_until_start() {
+        let routine = Routine::wake_up("weekday", 6, 30, Duration::from_secs(1800)).unwrap();
+        let at = |hour: u32, min: u32, sec: u32| -> DeviceTime {
+            serde_json::from_value(json!({
+                "year": 2020, "month": 4, "mday": 8, "hour": hour, "min": min, "sec": sec,
+            }))
+            .unwrap()
+        };
+        assert_eq!(
+            routine.time_until_start(&at(6, 0, 0)),
+            Duration::from_secs(1800)
+        );
+        assert_eq!(
+            routine.time_until_start(&at(6, 30, 0)),
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            routine.time_until_start(&at(6, 30, 1)),
+            Duration::from_secs(SECS_PER_DAY - 1)
+        );
+
+        assert!(Routine::sleep("late", 24, 0, Duration::from_secs(60)).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("tplink-routines-{}.json", std::process::id()));
+        let routines = [
+            Routine::wake_up("weekday", 6, 30, Duration::from_secs(1800))
+                .unwrap()
+                .with_brightness(80)
+                .with_color_temp(2700),
+            Routine::sleep("night", 22, 45, Duration::from_secs(900))
+                .unwrap()
+                .with_easing(Easing::Linear),
+        ];
+
+        save(&path, &routines).unwrap();
+        let loaded = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, routines);
+    }
+}
@@ -0,0 +1,95 @@
+//! Parsing of device responses into the library's response types.
+//!
+//! Firmware updates occasionally add fields to a response. By default
+//! responses are parsed leniently: fields the response types do not know
+//! about are ignored (or, for the system information, kept aside in the
+//! info struct), so a firmware update does not break parsing.
+//!
+//! With the `strict` feature enabled, a response carrying a field that
+//! is neither modelled nor kept aside is rejected instead, which helps to
+//! catch schema drift early while developing against new firmware.
+
+use crate::error::{self, Result};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::net::IpAddr;
+
+/// Fields every response may carry alongside its payload.
+#[cfg(feature = "strict")]
+const STATUS_FIELDS: &[&str] = &["err_code", "err_msg"];
+
+/// Parses a response from the device at `host`.
+///
+/// # Errors
+///
+/// Fails with `ErrorKind::Json` if the response does not match the
+/// expected type or, with the `strict` feature, if it carries unknown
+/// fields.
+pub(crate) fn parse<T: DeserializeOwned>(host: IpAddr, response: Value) -> Result<T> {
+    from_value(response).map_err(|err| {
+        log::debug!("invalid response from host with address {}: {}", host, err);
+        error::json(err)
+    })
+}
+
+#[cfg(not(feature = "strict"))]
+fn from_value<T: DeserializeOwned>(response: Value) -> serde_json::Result<T> {
+    serde_json::from_value(response)
+}
+
+#[cfg(feature = "strict")]
+fn from_value<T: DeserializeOwned>(response: Value) -> serde_json::Result<T> {
+    let mut unknown = Vec::new();
+    let value = serde_ignored::deserialize(response, |path| {
+        let path = path.to_string();
+        if !STATUS_FIELDS.contains(&path.as_str()) {
+            unknown.push(path);
+        }
+    })?;
+
+    if unknown.is_empty() {
+        Ok(value)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "unknown fields {:?}",
+            unknown
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Time {
+        hour: u32,
+    }
+
+    #[test]
+    fn test_parse() {
+        let time: Time = parse([127, 0, 0, 1].into(), json!({"hour": 7, "err_code": 0})).unwrap();
+        assert_eq!(time.hour, 7);
+
+        let time = parse::<Time>([127, 0, 0, 1].into(), json!({"hour": "7"}));
+        assert!(matches!(time.unwrap_err().kind(), ErrorKind::Json(_)));
+    }
+
+    #[test]
+    #[cfg(not(feature = "strict"))]
+    fn test_parse_unknown_fields() {
+        let time: Time = parse([127, 0, 0, 1].into(), json!({"hour": 7, "min": 30})).unwrap();
+        assert_eq!(time.hour, 7);
+    }
+
+    #[test]
+    #[cfg(feature = "strict")]
+    fn test_parse_unknown_fields() {
+        let time = parse::<Time>([127, 0, 0, 1].into(), json!({"hour": 7, "min": 30}));
+        assert!(time.unwrap_err().to_string().contains("unknown fields"));
+    }
+}
@@ -0,0 +1,394 @@
+//! A JSON-RPC bridge exposing the library over a local HTTP endpoint.
+//!
+//! The server accepts [JSON-RPC 2.0](https://www.jsonrpc.org/specification)
+//! requests sent as the body of an HTTP `POST`, which makes it possible to
+//! drive TP-Link devices from non-Rust home automation stacks through a
+//! single LAN bridge daemon. The following methods are supported:
+//!
+//! - `list_devices`: discovers the devices on the network and returns a
+//!   list of `{"host": "<ip>", "kind": "<plug|bulb|dimmer|switch|strip|unknown>"}`.
+//! - `invoke`: sends the command named by `target` and `command` (with an
+//!   optional `arg`) to the device at `host` and returns its response, e.g.
+//!   `{"host": "192.168.1.100", "target": "system", "command": "get_sysinfo"}`.
+//!   Only the devices returned by the last `list_devices` call can be sent
+//!   commands, and factory resets are refused: they must go through
+//!   `factory_reset_confirmed` on a device handle.
+//!
+//! Connections are served one at a time on the calling thread. Request
+//! headers larger than 8 KiB are rejected with `431 Request Header Fields
+//! Too Large`, and bodies larger than 8 KiB with `413 Payload Too Large`.
+//! Notifications (requests without an `id`) are run, but answered with an
+//! empty `204 No Content` rather than a JSON-RPC response.
+
+use crate::error::{self, ErrorKind, Result};
+use crate::proto::{self, Request};
+use crate::redact::Redacted;
+use crate::{discover, DeviceKind};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const DEVICE_ERROR: i64 = -32000;
+
+/// The largest request body accepted, in bytes.
+const MAX_BODY_SIZE: usize = 8 * 1024;
+
+/// The largest request line and headers accepted, in bytes.
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// Commands that are refused by `invoke`, as they bypass the safeguards of
+/// the device handles.
+const REFUSED_COMMANDS: &[&str] = &["reset"];
+
+/// A JSON-RPC server bridging HTTP clients to the devices on the network.
+///
+/// # Examples
+///
+/// ```no_run
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let server = tplink::serve::Server::bind("127.0.0.1:8080")?;
+///     server.run()?;
+///     Ok(())
+/// }
+/// ```
+pub struct Server {
+    listener: TcpListener,
+    timeout: Duration,
+    /// The hosts found by the last `list_devices` call.
+    hosts: RefCell<HashSet<IpAddr>>,
+}
+
+impl Server {
+    /// Creates a new server listening on the given address.
+    pub fn bind<A>(addr: A) -> Result<Server>
+    where
+        A: ToSocketAddrs,
+    {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            timeout: Duration::from_secs(3),
+            hosts: RefCell::default(),
+        })
+    }
+
+    /// Sets the timeout used for HTTP connections and device requests.
+    ///
+    /// Defaults to 3 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Server {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the local address the server is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serves incoming connections until accepting one fails.
+    pub fn run(&self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            if let Err(e) = self.serve_connection(stream) {
+                log::debug!("failed to serve connection: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn serve_connection(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let body = match read_body(&mut stream)? {
+            Body::Complete(body) => body,
+            Body::HeadersTooLarge => {
+                return empty_response(&mut stream, "431 Request Header Fields Too Large")
+            }
+            Body::TooLarge => return empty_response(&mut stream, "413 Payload Too Large"),
+        };
+        let response = match serde_json::from_slice::<Value>(&body) {
+            Ok(request) => self.handle(&request),
+            Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        let response = match response {
+            Some(response) => serde_json::to_vec(&response).map_err(error::json)?,
+            None => return empty_response(&mut stream, "204 No Content"),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.len()
+        )?;
+        stream.write_all(&response)?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// Handles a JSON-RPC request, returning its response, or `None` for a
+    /// notification, which must not be answered.
+    fn handle(&self, request: &Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            // Not a valid request, so not a notification either.
+            None => {
+                return Some(error_response(
+                    id.unwrap_or(Value::Null),
+                    INVALID_REQUEST,
+                    "missing method",
+                ))
+            }
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        log::trace!("(serve) {} {:?}", method, Redacted(&params));
+
+        let result = match method {
+            "list_devices" => self.list_devices().map_err(rpc_error),
+            "invoke" => match serde_json::from_value::<Invoke>(params) {
+                Ok(invoke) => self.invoke(invoke).map_err(rpc_error),
+                Err(e) => Err((INVALID_PARAMS, e.to_string())),
+            },
+            _ => Err((METHOD_NOT_FOUND, String::from(method))),
+        };
+
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn list_devices(&self) -> Result<Value> {
+        let devices = discover()?;
+        *self.hosts.borrow_mut() = devices.iter().map(|(host, _)| *host).collect();
+        let devices = devices
+            .iter()
+            .map(|(host, device)| {
+                let kind = match device {
+                    DeviceKind::Plug(_) => "plug",
+                    DeviceKind::Bulb(_) => "bulb",
+                    DeviceKind::Dimmer(_) => "dimmer",
+                    DeviceKind::Switch(_) => "switch",
+                    DeviceKind::Strip => "strip",
+                    DeviceKind::Unknown => "unknown",
+                };
+                json!({ "host": host, "kind": kind })
+            })
+            .collect();
+        Ok(Value::Array(devices))
+    }
+
+    fn invoke(&self, invoke: Invoke) -> Result<Value> {
+        if !self.hosts.borrow().contains(&invoke.host) {
+            return Err(error::invalid_parameter(&format!(
+                "invoke: host {} was not discovered, call list_devices first",
+                invoke.host
+            )));
+        }
+        if REFUSED_COMMANDS.contains(&invoke.command.as_str()) {
+            return Err(error::unsupported_operation(&format!(
+                "invoke {}.{}: use factory_reset_confirmed on a device handle",
+                invoke.target, invoke.command
+            )));
+        }
+
+        let proto = proto::Builder::new((invoke.host, proto::DEFAULT_PORT))
+            .read_timeout(self.timeout)
+            .write_timeout(self.timeout)
+            .build();
+        proto.send_request(&Request::new(&invoke.target, &invoke.command, invoke.arg))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Invoke {
+    host: IpAddr,
+    target: String,
+    command: String,
+    arg: Option<Value>,
+}
+
+/// Returns the JSON-RPC error code and message of an error, reporting
+/// invalid parameters, such as a host that was not discovered, as such.
+fn rpc_error(e: error::Error) -> (i64, String) {
+    let code = match e.kind() {
+        ErrorKind::InvalidParameter(_) => INVALID_PARAMS,
+        _ => DEVICE_ERROR,
+    };
+    (code, e.to_string())
+}
+
+fn empty_response(stream: &mut TcpStream, status: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// The body of an HTTP request, unless the request is too large.
+#[derive(Debug, PartialEq)]
+enum Body {
+    Complete(Vec<u8>),
+    /// The request line and headers are larger than `MAX_HEADER_SIZE`.
+    HeadersTooLarge,
+    /// The body is larger than `MAX_BODY_SIZE`.
+    TooLarge,
+}
+
+/// Reads the body of an HTTP request, reading at most `MAX_HEADER_SIZE`
+/// bytes of request line and headers before it.
+fn read_body<R: Read>(stream: R) -> Result<Body> {
+    let mut reader = BufReader::new(stream);
+    let mut content_length = 0;
+    let mut header_size = 0;
+    loop {
+        let mut line = String::new();
+        let limit = (MAX_HEADER_SIZE - header_size) as u64;
+        let read = reader.by_ref().take(limit).read_line(&mut line)?;
+        header_size += read;
+        if !line.ends_with('\n') && header_size == MAX_HEADER_SIZE {
+            return Ok(Body::HeadersTooLarge);
+        }
+        if read == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| error::invalid_parameter("content-length"))?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_SIZE {
+        return Ok(Body::TooLarge);
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Body::Complete(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> Server {
+        Server::bind("127.0.0.1:0").unwrap()
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        let response = server()
+            .handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": "nope" }))
+            .unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_notification() {
+        let server = server();
+        assert_eq!(
+            server.handle(&json!({ "jsonrpc": "2.0", "method": "nope" })),
+            None
+        );
+        // A request without a method is invalid rather than a notification.
+        let response = server.handle(&json!({ "jsonrpc": "2.0" })).unwrap();
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_invalid_invoke_params() {
+        let response = server()
+            .handle(&json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "invoke",
+                "params": { "host": "not an address" },
+            }))
+            .unwrap();
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_invoke_guards() {
+        let server = server();
+        let invoke = |command: &str| {
+            server
+                .handle(&json!({
+                    "jsonrpc": "2.0",
+                    "id": 3,
+                    "method": "invoke",
+                    "params": { "host": "192.0.2.1", "target": "system", "command": command },
+                }))
+                .unwrap()
+        };
+        let response = invoke("get_sysinfo");
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("not discovered"));
+
+        server.hosts.borrow_mut().insert([192, 0, 2, 1].into());
+        let response = invoke("reset");
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("factory_reset_confirmed"));
+    }
+
+    #[test]
+    fn test_read_body() {
+        let request = b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}";
+        assert_eq!(
+            read_body(&request[..]).unwrap(),
+            Body::Complete(b"{}".to_vec())
+        );
+
+        let request = b"POST / HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\n";
+        assert_eq!(read_body(&request[..]).unwrap(), Body::TooLarge);
+
+        let mut request = b"POST / HTTP/1.1\r\nX-Padding: ".to_vec();
+        request.resize(request.len() + MAX_HEADER_SIZE, b'a');
+        request.extend(b"\r\n\r\n");
+        assert_eq!(read_body(&request[..]).unwrap(), Body::HeadersTooLarge);
+
+        // Many short headers count towards the limit too.
+        let mut request = b"POST / HTTP/1.1\r\n".to_vec();
+        for _ in 0..MAX_HEADER_SIZE / 8 {
+            request.extend(b"X-A: b\r\n");
+        }
+        request.extend(b"\r\n");
+        assert_eq!(read_body(&request[..]).unwrap(), Body::HeadersTooLarge);
+    }
+}
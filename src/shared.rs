@@ -0,0 +1,73 @@
+//! The parts shared by the components of a device handle, whatever the
+//! model of the device.
+
+use crate::cache::{self, Cache, Persister, ResponseCache};
+use crate::config::Config;
+use crate::proto::{self, Proto};
+
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+/// The connection to a device and its response cache, as configured.
+pub(crate) struct Shared {
+    pub(crate) proto: Rc<Proto>,
+    pub(crate) cache: Arc<ResponseCache>,
+    pub(crate) persister: Arc<Persister>,
+}
+
+impl Shared {
+    /// Builds the connection to the configured device and its response
+    /// cache, loading the cache from its file and starting its prefetcher,
+    /// if the config asks for them.
+    pub(crate) fn new(config: &Config) -> Shared {
+        let proto = connect(config.addr, config);
+
+        let cache_config = &config.cache_config;
+        let cache = if cache_config.enable_cache {
+            let ttl = cache_config.ttl.unwrap();
+            let mut cache = cache_config.initial_capacity.map_or_else(
+                || Cache::with_ttl(ttl),
+                |capacity| Cache::with_ttl_and_capacity(ttl, capacity),
+            );
+            cache.set_negative_ttl(cache_config.negative_ttl);
+            if let Some(path) = cache_config.file.as_ref() {
+                if let Err(e) = cache.load(config.addr.ip(), path) {
+                    log::debug!("failed to load cache from {}: {}", path.display(), e);
+                }
+            }
+            cache.set_file(cache_config.file.clone());
+            Some(Mutex::new(cache))
+        } else {
+            None
+        };
+
+        let proto = Rc::new(proto);
+        let cache = Arc::new(cache);
+        if let Some(lead) = cache_config.prefetch {
+            cache::spawn_prefetcher(&cache, &proto, lead);
+        }
+
+        Shared {
+            persister: Persister::new(&cache, proto.host()),
+            proto,
+            cache,
+        }
+    }
+}
+
+/// Builds a connection to the device at `addr` with the timeouts, retries
+/// and socket settings of the config, whatever its configured address.
+pub(crate) fn connect(addr: SocketAddr, config: &Config) -> Proto {
+    proto::Builder::new(addr)
+        .read_timeout(config.read_timeout)
+        .write_timeout(config.write_timeout)
+        .buffer_size(config.buffer_size)
+        .offline_tolerance(config.offline_tolerance)
+        .retry(config.retry_attempts)
+        .max_concurrent_requests(config.max_concurrent_requests)
+        .socket_options(config.socket_options)
+        .audit(config.audit.clone())
+        .lease_holder(config.lease_holder.clone())
+        .build()
+}
@@ -0,0 +1,139 @@
+//! Sunrise and sunset times, computed locally from a location and a date.
+//!
+//! Plugs report the location they were set up at (see
+//! [`Plug::location`]), which is enough to work out when the sun rises
+//! and sets there without a network service, e.g. to switch a lamp on
+//! 15 minutes before sunset.
+//!
+//! The times follow the NOAA sunrise equation and are accurate to about a
+//! minute away from the polar circles.
+//!
+//! [`Plug::location`]: ../struct.Plug.html#method.location
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let plug = tplink::Plug::new([192, 168, 1, 100]);
+//! let times = plug.location()?.sun_times(2020, 6, 21);
+//! if let Some(sunset) = times.sunset() {
+//!     let lamp_on = sunset - Duration::from_secs(15 * 60);
+//!     println!("switch on at {:?}", lamp_on);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::util;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The Julian date of the Unix epoch.
+const UNIX_EPOCH_JD: f64 = 2_440_587.5;
+
+/// The Julian date of the J2000 epoch.
+const J2000_JD: f64 = 2_451_545.0;
+
+/// The sunrise and sunset of a day at a location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SunTimes {
+    sunrise: Option<SystemTime>,
+    sunset: Option<SystemTime>,
+}
+
+impl SunTimes {
+    /// Returns the time of sunrise, or `None` if the sun does not rise or
+    /// set that day (polar day or polar night).
+    pub fn sunrise(&self) -> Option<SystemTime> {
+        self.sunrise
+    }
+
+    /// Returns the time of sunset, or `None` if the sun does not rise or
+    /// set that day (polar day or polar night).
+    pub fn sunset(&self) -> Option<SystemTime> {
+        self.sunset
+    }
+}
+
+/// Returns the sunrise and sunset on the given day (UTC) at the given
+/// latitude and longitude, in degrees (north and east positive).
+///
+/// # Examples
+///
+/// ```
+/// let times = tplink::solar::sun_times(51.5074, -0.1278, 2020, 6, 21);
+/// assert!(times.sunrise().unwrap() < times.sunset().unwrap());
+/// ```
+pub fn sun_times(latitude: f64, longitude: f64, year: i32, month: u32, day: u32) -> SunTimes {
+    let days = util::days_from_civil(year, month, day) as f64;
+    // Days since J2000 at noon of the given day.
+    let n = (days + UNIX_EPOCH_JD + 0.5 - J2000_JD).round();
+
+    let mean_solar_noon = n - longitude / 360.0;
+    let mean_anomaly = (357.5291 + 0.985_600_28 * mean_solar_noon).rem_euclid(360.0);
+    let m = mean_anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.0200 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude = (mean_anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let lambda = ecliptic_longitude.to_radians();
+    let transit = J2000_JD + mean_solar_noon + 0.0053 * m.sin() - 0.0069 * (2.0 * lambda).sin();
+
+    let declination = (lambda.sin() * 23.4397_f64.to_radians().sin()).asin();
+    let phi = latitude.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - phi.sin() * declination.sin())
+        / (phi.cos() * declination.cos());
+
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return SunTimes {
+            sunrise: None,
+            sunset: None,
+        };
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    SunTimes {
+        sunrise: from_julian_date(transit - hour_angle / 360.0),
+        sunset: from_julian_date(transit + hour_angle / 360.0),
+    }
+}
+
+fn from_julian_date(jd: f64) -> Option<SystemTime> {
+    let secs = ((jd - UNIX_EPOCH_JD) * 86400.0).round();
+    if secs >= 0.0 {
+        Some(UNIX_EPOCH + Duration::from_secs(secs as u64))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes_utc(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).unwrap().as_secs() % 86400 / 60
+    }
+
+    #[test]
+    fn test_sun_times() {
+        // London on the summer solstice: sunrise 03:43 UTC, sunset 20:21 UTC.
+        let times = sun_times(51.5074, -0.1278, 2020, 6, 21);
+        let sunrise = minutes_utc(times.sunrise().unwrap());
+        let sunset = minutes_utc(times.sunset().unwrap());
+        assert!(
+            (3 * 60 + 41..=3 * 60 + 45).contains(&sunrise),
+            "{}",
+            sunrise
+        );
+        assert!(
+            (20 * 60 + 19..=20 * 60 + 23).contains(&sunset),
+            "{}",
+            sunset
+        );
+
+        // Tromsø in midwinter: polar night.
+        let times = sun_times(69.6492, 18.9553, 2020, 12, 21);
+        assert_eq!(times.sunrise(), None);
+    }
+}
@@ -0,0 +1,553 @@
+//! Snapshots of the state of a device, and the changes between them.
+//!
+//! A [`DeviceState`] captures what a device reports about its user-visible
+//! state (power, light settings, name, signal strength and power draw) at
+//! one point in time. Comparing two snapshots with [`DeviceState::diff`]
+//! yields the list of what changed, ignoring the jitter of noisy readings
+//! such as the signal strength and the power draw. A [`SignalHistory`]
+//! keeps the signal strength of the snapshots of several devices over a
+//! window of time, to find devices with a poor placement.
+//!
+//! [`DeviceState`]: struct.DeviceState.html
+//! [`DeviceState::diff`]: struct.DeviceState.html#method.diff
+//! [`SignalHistory`]: struct.SignalHistory.html
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::thread;
+//! use std::time::Duration;
+//! use tplink::state::DeviceState;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let plug = tplink::Plug::new([192, 168, 1, 100]);
+//! let mut old = DeviceState::of(&plug)?;
+//! loop {
+//!     thread::sleep(Duration::from_secs(10));
+//!     let new = DeviceState::of(&plug)?;
+//!     for change in DeviceState::diff(&old, &new) {
+//!         println!("{:?}", change);
+//!     }
+//!     old = new;
+//! }
+//! # }
+//! ```
+
+use crate::error::{self, Result};
+use crate::sysinfo::SysInfo;
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// A snapshot of the state of a device.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DeviceState {
+    on: Option<bool>,
+    brightness: Option<u32>,
+    hue: Option<u32>,
+    saturation: Option<u32>,
+    color_temp: Option<u32>,
+    alias: String,
+    rssi: Option<i64>,
+    power_w: Option<f64>,
+    on_time: Option<u64>,
+}
+
+impl DeviceState {
+    /// Takes a snapshot of the state of the given device from its system
+    /// information.
+    ///
+    /// The system information does not include the power draw; it can be
+    /// added from an energy meter reading with
+    /// [`with_power`](#method.with_power).
+    pub fn of<D>(device: &D) -> Result<DeviceState>
+    where
+        D: SysInfo,
+        D::Info: Serialize,
+    {
+        let sysinfo = serde_json::to_value(device.sysinfo()?).map_err(error::json)?;
+        Ok(DeviceState::from_sysinfo(&sysinfo))
+    }
+
+    /// Takes a snapshot of the state of a device from the given system
+    /// information, either the `get_sysinfo` object itself or a full
+    /// response, such as a discovery response, containing it under
+    /// `system.get_sysinfo`.
+    ///
+    /// The power draw is taken from the energy meter reading included in a
+    /// full response, if any.
+    pub fn from_sysinfo(value: &Value) -> DeviceState {
+        let (sysinfo, realtime) = if value.get("system").is_some() {
+            let realtime = ["emeter", "smartlife.iot.common.emeter"]
+                .iter()
+                .map(|target| &value[*target]["get_realtime"])
+                .find(|realtime| realtime.is_object());
+            (&value["system"]["get_sysinfo"], realtime)
+        } else {
+            (value, None)
+        };
+
+        let light_state = &sysinfo["light_state"];
+        // While a bulb is off, it reports the state it will turn on with.
+        let light = if light_state.get("dft_on_state").is_some() {
+            &light_state["dft_on_state"]
+        } else {
+            light_state
+        };
+        let light_u32 = |key: &str| light[key].as_u64().map(|value| value as u32);
+
+        let on = sysinfo["relay_state"]
+            .as_u64()
+            .or_else(|| light_state["on_off"].as_u64())
+            .map(|state| state == 1);
+        let brightness = light_u32("brightness")
+            .or_else(|| sysinfo["brightness"].as_u64().map(|value| value as u32));
+        let power_w = realtime.and_then(|realtime| {
+            realtime["power"]
+                .as_f64()
+                .or_else(|| realtime["power_mw"].as_f64().map(|mw| mw / 1000.0))
+        });
+
+        DeviceState {
+            on,
+            brightness,
+            hue: light_u32("hue"),
+            saturation: light_u32("saturation"),
+            color_temp: light_u32("color_temp"),
+            alias: sysinfo["alias"].as_str().unwrap_or_default().to_string(),
+            rssi: sysinfo["rssi"].as_i64(),
+            power_w,
+            on_time: sysinfo["on_time"].as_u64(),
+        }
+    }
+
+    /// Sets the power draw of the device, in W.
+    pub fn with_power(mut self, watts: f64) -> DeviceState {
+        self.power_w = Some(watts);
+        self
+    }
+
+    /// Returns whether the device is switched on, if it reports it.
+    pub fn is_on(&self) -> Option<bool> {
+        self.on
+    }
+
+    /// Returns the % brightness of the device, if it is dimmable.
+    pub fn brightness(&self) -> Option<u32> {
+        self.brightness
+    }
+
+    /// Returns the hue of the device, if it is a bulb.
+    pub fn hue(&self) -> Option<u32> {
+        self.hue
+    }
+
+    /// Returns the % saturation of the device, if it is a bulb.
+    pub fn saturation(&self) -> Option<u32> {
+        self.saturation
+    }
+
+    /// Returns the color temperature of the device, if it is a bulb.
+    pub fn color_temp(&self) -> Option<u32> {
+        self.color_temp
+    }
+
+    /// Returns the name (alias) of the device.
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device, if reported.
+    pub fn rssi(&self) -> Option<i64> {
+        self.rssi
+    }
+
+    /// Returns the power draw of the device in W, if known.
+    pub fn power(&self) -> Option<f64> {
+        self.power_w
+    }
+
+    /// Returns for how long, in seconds, the relay of the device has been
+    /// on, if reported.
+    pub fn on_time(&self) -> Option<u64> {
+        self.on_time
+    }
+
+    /// Returns the changes from the `old` to the `new` state, ignoring
+    /// changes of the noisy readings within the default [`Tolerance`].
+    ///
+    /// [`Tolerance`]: struct.Tolerance.html
+    pub fn diff(old: &DeviceState, new: &DeviceState) -> Vec<StateChange> {
+        DeviceState::diff_with(old, new, &Tolerance::default())
+    }
+
+    /// Returns the changes from the `old` to the `new` state, ignoring
+    /// changes of the noisy readings within the given tolerance.
+    pub fn diff_with(
+        old: &DeviceState,
+        new: &DeviceState,
+        tolerance: &Tolerance,
+    ) -> Vec<StateChange> {
+        let mut changes = Vec::new();
+
+        if let Some(on) = new.on.filter(|_| old.on != new.on) {
+            changes.push(StateChange::Power { on });
+        }
+        if old.brightness != new.brightness {
+            changes.push(StateChange::Brightness {
+                old: old.brightness,
+                new: new.brightness,
+            });
+        }
+        let color = |state: &DeviceState| (state.hue, state.saturation, state.color_temp);
+        if color(old) != color(new) {
+            changes.push(StateChange::Color {
+                hue: new.hue,
+                saturation: new.saturation,
+                color_temp: new.color_temp,
+            });
+        }
+        if old.alias != new.alias {
+            changes.push(StateChange::Alias {
+                old: old.alias.clone(),
+                new: new.alias.clone(),
+            });
+        }
+        if exceeds(old.rssi, new.rssi, |old, new| {
+            (old - new).abs() > tolerance.rssi
+        }) {
+            changes.push(StateChange::Rssi {
+                old: old.rssi,
+                new: new.rssi,
+            });
+        }
+        if exceeds(old.power_w, new.power_w, |old, new| {
+            (old - new).abs() > tolerance.power_w
+        }) {
+            changes.push(StateChange::PowerDraw {
+                old: old.power_w,
+                new: new.power_w,
+            });
+        }
+        // The relay restarts counting its on time when the device reboots
+        // or is switched off and on again, and the snapshots cannot tell
+        // the two apart, so a device that stayed on with a lower on time
+        // is reported as rebooted or power-cycled.
+        if let (Some(true), Some(true)) = (old.on, new.on) {
+            if let (Some(old_on_time), Some(on_time)) = (old.on_time, new.on_time) {
+                if on_time < old_on_time {
+                    changes.push(StateChange::Rebooted { on_time });
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+/// Returns whether a reading changed beyond its tolerance, or appeared or
+/// disappeared.
+fn exceeds<T: Copy>(old: Option<T>, new: Option<T>, beyond: impl Fn(T, T) -> bool) -> bool {
+    match (old, new) {
+        (Some(old), Some(new)) => beyond(old, new),
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+/// The amounts by which noisy readings may change before the change is
+/// reported by [`DeviceState::diff_with`].
+///
+/// [`DeviceState::diff_with`]: struct.DeviceState.html#method.diff_with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// The tolerance of the Wi-Fi signal strength, in dBm.
+    pub rssi: i64,
+    /// The tolerance of the power draw, in W.
+    pub power_w: f64,
+}
+
+impl Default for Tolerance {
+    /// A tolerance of 5 dBm for the signal strength and 1 W for the power
+    /// draw.
+    fn default() -> Tolerance {
+        Tolerance {
+            rssi: 5,
+            power_w: 1.0,
+        }
+    }
+}
+
+/// A change between two snapshots of the state of a device.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub enum StateChange {
+    /// The device was switched on or off.
+    Power { on: bool },
+    /// The % brightness changed.
+    Brightness { old: Option<u32>, new: Option<u32> },
+    /// The color (hue, saturation or color temperature) changed to the
+    /// given values.
+    Color {
+        hue: Option<u32>,
+        saturation: Option<u32>,
+        color_temp: Option<u32>,
+    },
+    /// The name (alias) changed.
+    Alias { old: String, new: String },
+    /// The Wi-Fi signal strength changed beyond its tolerance.
+    Rssi { old: Option<i64>, new: Option<i64> },
+    /// The power draw, in W, changed beyond its tolerance.
+    PowerDraw { old: Option<f64>, new: Option<f64> },
+    /// The device was on in both snapshots, but its relay restarted
+    /// counting how long it has been on, so it rebooted (e.g. after a
+    /// crash or a power cut) or was power-cycled (switched off and on
+    /// again) in between. The snapshots alone cannot tell which. It has
+    /// been on for `on_time` seconds since.
+    Rebooted { on_time: u64 },
+}
+
+/// The Wi-Fi signal strength of devices over a rolling window of time,
+/// by device.
+///
+/// Devices are identified by a key of the caller's choosing, such as
+/// their address or alias.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+/// use tplink::state::{DeviceState, SignalHistory};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let hosts = [[192, 168, 1, 100], [192, 168, 1, 101]];
+/// let mut history = SignalHistory::new(Duration::from_secs(3600));
+/// loop {
+///     for host in &hosts {
+///         let plug = tplink::Plug::new(*host);
+///         history.add(*host, &DeviceState::of(&plug)?);
+///         if let Some(stats) = history.signal_history(host) {
+///             if stats.average < -70.0 {
+///                 println!("{:?} has a weak signal: {:?}", host, stats);
+///             }
+///         }
+///     }
+///     thread::sleep(Duration::from_secs(60));
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SignalHistory<K> {
+    window: Duration,
+    readings: HashMap<K, VecDeque<(Instant, i64)>>,
+}
+
+impl<K: Eq + Hash> SignalHistory<K> {
+    /// Creates a history over the given window of time.
+    pub fn new(window: Duration) -> SignalHistory<K> {
+        SignalHistory {
+            window,
+            readings: HashMap::new(),
+        }
+    }
+
+    /// Adds the signal strength of a snapshot of the given device taken
+    /// now. Snapshots without a signal strength are ignored.
+    pub fn add(&mut self, device: K, state: &DeviceState) {
+        if let Some(rssi) = state.rssi() {
+            self.push(device, Instant::now(), rssi);
+        }
+    }
+
+    /// Adds a signal strength, in dBm, of the given device read at the
+    /// given instant, which must not be earlier than the previous one of
+    /// the device.
+    pub fn push(&mut self, device: K, at: Instant, rssi: i64) {
+        let readings = self.readings.entry(device).or_default();
+        readings.push_back((at, rssi));
+        while let Some(&(first, _)) = readings.front() {
+            if at.duration_since(first) < self.window {
+                break;
+            }
+            readings.pop_front();
+        }
+    }
+
+    /// Returns the statistics of the signal strength of the given device
+    /// over the window, or `None` if none was added.
+    pub fn signal_history(&self, device: &K) -> Option<SignalStats> {
+        let readings = self.readings.get(device)?;
+        let (_, first) = *readings.front()?;
+        let mut stats = SignalStats {
+            min: first,
+            max: first,
+            average: 0.0,
+            readings: readings.len(),
+        };
+        let mut sum = 0;
+        for &(_, rssi) in readings {
+            stats.min = stats.min.min(rssi);
+            stats.max = stats.max.max(rssi);
+            sum += rssi;
+        }
+        stats.average = sum as f64 / readings.len() as f64;
+        Some(stats)
+    }
+
+    /// Returns the devices with a signal strength history, and its
+    /// statistics.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, SignalStats)> {
+        self.readings
+            .keys()
+            .filter_map(move |device| Some((device, self.signal_history(device)?)))
+    }
+}
+
+/// The statistics of the signal strength of a device over the window of
+/// a [`SignalHistory`].
+///
+/// [`SignalHistory`]: struct.SignalHistory.html
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SignalStats {
+    /// The weakest signal strength, in dBm.
+    pub min: i64,
+    /// The strongest signal strength, in dBm.
+    pub max: i64,
+    /// The average signal strength, in dBm.
+    pub average: f64,
+    /// The number of readings in the window.
+    pub readings: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_sysinfo() {
+        let state = DeviceState::from_sysinfo(&json!({
+            "system": {"get_sysinfo": {
+                "alias": "Bedroom",
+                "rssi": -49,
+                "light_state": {
+                    "on_off": 0,
+                    "dft_on_state": {"hue": 270, "saturation": 55, "color_temp": 0, "brightness": 90},
+                },
+            }},
+            "smartlife.iot.common.emeter": {"get_realtime": {"power_mw": 10800, "err_code": 0}},
+        }));
+        assert_eq!(state.is_on(), Some(false));
+        assert_eq!(state.brightness(), Some(90));
+        assert_eq!(state.hue(), Some(270));
+        assert_eq!(state.power(), Some(10.8));
+
+        let state = DeviceState::from_sysinfo(&json!({"alias": "Fridge", "relay_state": 1}));
+        assert_eq!(state.is_on(), Some(true));
+        assert_eq!(state.brightness(), None);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = DeviceState::from_sysinfo(&json!({
+            "alias": "Fridge",
+            "relay_state": 1,
+            "rssi": -52,
+        }))
+        .with_power(80.0);
+
+        // Jitter of the noisy readings is not a change.
+        let new = DeviceState {
+            rssi: Some(-55),
+            ..old.clone()
+        }
+        .with_power(80.6);
+        assert!(DeviceState::diff(&old, &new).is_empty());
+
+        let new = DeviceState {
+            on: Some(false),
+            alias: String::from("Freezer"),
+            rssi: Some(-70),
+            ..old.clone()
+        }
+        .with_power(0.0);
+        assert_eq!(
+            DeviceState::diff(&old, &new),
+            [
+                StateChange::Power { on: false },
+                StateChange::Alias {
+                    old: String::from("Fridge"),
+                    new: String::from("Freezer"),
+                },
+                StateChange::Rssi {
+                    old: Some(-52),
+                    new: Some(-70),
+                },
+                StateChange::PowerDraw {
+                    old: Some(80.0),
+                    new: Some(0.0),
+                },
+            ]
+        );
+
+        let tolerance = Tolerance {
+            rssi: 20,
+            power_w: 100.0,
+        };
+        assert_eq!(DeviceState::diff_with(&old, &new, &tolerance).len(), 2);
+    }
+
+    #[test]
+    fn test_rebooted() {
+        let state = |relay_state, on_time| {
+            DeviceState::from_sysinfo(&json!({ "relay_state": relay_state, "on_time": on_time }))
+        };
+
+        assert!(DeviceState::diff(&state(1, 3600), &state(1, 3660)).is_empty());
+        assert_eq!(
+            DeviceState::diff(&state(1, 3600), &state(1, 20)),
+            [StateChange::Rebooted { on_time: 20 }]
+        );
+        assert_eq!(
+            DeviceState::diff(&state(1, 3600), &state(0, 0)),
+            [StateChange::Power { on: false }]
+        );
+    }
+
+    #[test]
+    fn test_signal_history() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut history = SignalHistory::new(Duration::from_secs(60));
+        assert_eq!(history.signal_history(&"kitchen"), None);
+
+        history.push("kitchen", at(0), -80);
+        history.push("kitchen", at(30), -60);
+        history.push("hall", at(30), -45);
+        history.push("kitchen", at(50), -70);
+        assert_eq!(
+            history.signal_history(&"kitchen"),
+            Some(SignalStats {
+                min: -80,
+                max: -60,
+                average: -70.0,
+                readings: 3,
+            })
+        );
+
+        // Only the readings of the last 60 s count.
+        history.push("kitchen", at(60), -64);
+        let stats = history.signal_history(&"kitchen").unwrap();
+        assert_eq!((stats.min, stats.max, stats.readings), (-70, -60, 3));
+        assert_eq!(history.signal_history(&"hall").unwrap().average, -45.0);
+
+        let state = DeviceState::from_sysinfo(&json!({ "alias": "Fridge" }));
+        history.add("hall", &state);
+        assert_eq!(history.signal_history(&"hall").unwrap().readings, 1);
+        assert_eq!(history.iter().count(), 2);
+    }
+}
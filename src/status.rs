@@ -0,0 +1,292 @@
+//! A snapshot of the status of many devices at once, for display by
+//! command line tools and dashboards.
+
+use crate::config::Config;
+use crate::discover::{DiscoveryInfo, DiscoveryResult};
+use crate::emeter::RealtimeStats;
+use crate::proto::{Proto, Request};
+use crate::schema;
+use crate::shared;
+use crate::state::DeviceState;
+
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::thread;
+
+/// The status of a device, as polled by [`status`].
+///
+/// A device that does not answer still has a row, with the error in place
+/// of the values it would have reported.
+///
+/// [`status`]: fn.status.html
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusRow {
+    host: IpAddr,
+    alias: Option<String>,
+    on: Option<bool>,
+    power_w: Option<f64>,
+    rssi: Option<i64>,
+    error: Option<String>,
+    power_error: Option<String>,
+}
+
+impl StatusRow {
+    fn new(host: IpAddr) -> StatusRow {
+        StatusRow {
+            host,
+            alias: None,
+            on: None,
+            power_w: None,
+            rssi: None,
+            error: None,
+            power_error: None,
+        }
+    }
+
+    /// Returns the address of the device.
+    pub fn host(&self) -> IpAddr {
+        self.host
+    }
+
+    /// Returns the name (alias) of the device, if it answered.
+    pub fn alias(&self) -> Option<&str> {
+        self.alias.as_deref()
+    }
+
+    /// Returns whether the device is on, if it answered and has a relay
+    /// or a light.
+    pub fn is_on(&self) -> Option<bool> {
+        self.on
+    }
+
+    /// Returns the power draw of the device in W, if it has an energy
+    /// meter that answered.
+    pub fn power_w(&self) -> Option<f64> {
+        self.power_w
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device, if it
+    /// answered.
+    pub fn rssi(&self) -> Option<i64> {
+        self.rssi
+    }
+
+    /// Returns why the system information of the device could not be
+    /// polled, if it could not.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Returns why the energy meter of the device could not be read, if
+    /// it has one and it could not.
+    pub fn power_error(&self) -> Option<&str> {
+        self.power_error.as_deref()
+    }
+}
+
+/// Polls the alias, on/off state, power draw and signal strength of the
+/// discovered devices concurrently, and returns a row per device, ordered
+/// by address.
+///
+/// Each device is polled afresh over a connection with the default
+/// configuration of [`Config::for_host`], including its read and write
+/// timeouts, bypassing the cache of its handle. A device that fails to
+/// answer in time gets a row carrying the error rather than failing the
+/// call.
+///
+/// [`Config::for_host`]: struct.Config.html#method.for_host
+///
+/// # Examples
+///
+/// ```no_run
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     for row in tplink::status(&tplink::discover()?) {
+///         match row.error() {
+///             Some(error) => println!("{}: {}", row.host(), error),
+///             None => println!(
+///                 "{}: {} on={:?} power={:?} rssi={:?}",
+///                 row.host(),
+///                 row.alias().unwrap_or_default(),
+///                 row.is_on(),
+///                 row.power_w(),
+///                 row.rssi(),
+///             ),
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn status(devices: &DiscoveryResult) -> Vec<StatusRow> {
+    poll_all(devices, |host| {
+        let config = Config::for_host(host).build();
+        shared::connect(config.addr, &config)
+    })
+}
+
+/// Polls the discovered devices like [`status`], with the given
+/// configuration.
+///
+/// The configured host is ignored: each device is polled at its own
+/// address, on the configured port, with the timeouts, retries and socket
+/// options of the configuration.
+///
+/// [`status`]: fn.status.html
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let config = tplink::Config::for_host([0, 0, 0, 0])
+///         .with_read_timeout(Duration::from_millis(500))
+///         .build();
+///     let rows = tplink::status_with_config(&tplink::discover()?, &config);
+///     println!("{} devices polled", rows.len());
+///     Ok(())
+/// }
+/// ```
+pub fn status_with_config(devices: &DiscoveryResult, config: &Config) -> Vec<StatusRow> {
+    poll_all(devices, |host| {
+        shared::connect(SocketAddr::new(host, config.addr.port()), config)
+    })
+}
+
+fn poll_all<F>(devices: &DiscoveryResult, connect: F) -> Vec<StatusRow>
+where
+    F: Fn(IpAddr) -> Proto,
+{
+    let mut hosts: Vec<_> = devices
+        .iter()
+        .map(|(host, _)| (*host, devices.info(host).and_then(emeter_target)))
+        .collect();
+    hosts.sort_by_key(|(host, _)| *host);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = hosts
+            .into_iter()
+            .map(|(host, emeter)| {
+                let proto = connect(host);
+                scope.spawn(move || poll(&proto, emeter))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("status poll panicked"))
+            .collect()
+    })
+}
+
+/// Returns the namespace of the energy meter of the device, if it has one.
+fn emeter_target(info: &DiscoveryInfo) -> Option<&'static str> {
+    if !info.capabilities().iter().any(|c| c == "emeter") {
+        None
+    } else if info.kind() == "bulb" {
+        Some("smartlife.iot.common.emeter")
+    } else {
+        Some("emeter")
+    }
+}
+
+fn poll(proto: &Proto, emeter: Option<&str>) -> StatusRow {
+    let mut row = StatusRow::new(proto.host());
+    match proto.send_request(&Request::new("system", "get_sysinfo", None)) {
+        Ok(sysinfo) => {
+            let state = DeviceState::from_sysinfo(&sysinfo);
+            row.alias = Some(state.alias().to_string());
+            row.on = state.is_on();
+            row.rssi = state.rssi();
+        }
+        Err(e) => {
+            // Not worth waiting on the energy meter of an unreachable device.
+            row.error = Some(e.to_string());
+            return row;
+        }
+    }
+
+    if let Some(target) = emeter {
+        match proto
+            .send_request(&Request::new(target, "get_realtime", None))
+            .and_then(|realtime| schema::parse::<RealtimeStats>(proto.host(), realtime))
+        {
+            Ok(realtime) => row.power_w = realtime.power_w(),
+            Err(e) => row.power_error = Some(e.to_string()),
+        }
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+    use crate::proto;
+    use serde_json::{json, Value};
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    #[test]
+    fn test_poll() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = device.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            for response in [
+                json!({ "system": { "get_sysinfo": { "alias": "Fridge", "relay_state": 1, "rssi": -58 } } }),
+                json!({ "emeter": { "get_realtime": { "power_mw": 61240, "err_code": 0 } } }),
+            ] {
+                let (_, from) = device.recv_from(&mut buf).unwrap();
+                let response = crypto::encrypt(&serde_json::to_vec(&response).unwrap());
+                device.send_to(&response, from).unwrap();
+            }
+        });
+
+        let proto = proto::Builder::new(addr)
+            .read_timeout(Duration::from_secs(3))
+            .build();
+        let row = poll(&proto, Some("emeter"));
+        handle.join().unwrap();
+        assert_eq!(row.alias(), Some("Fridge"));
+        assert_eq!(row.is_on(), Some(true));
+        assert_eq!(row.rssi(), Some(-58));
+        assert!((row.power_w().unwrap() - 61.24).abs() < 1e-9);
+        assert_eq!(row.error(), None);
+
+        let proto = proto::Builder::new(addr)
+            .read_timeout(Duration::from_millis(50))
+            .build();
+        let row = poll(&proto, Some("emeter"));
+        assert_eq!(row.alias(), None);
+        assert!(row.error().is_some());
+        assert_eq!(row.power_error(), None);
+        let _: Value = serde_json::to_value(&row).unwrap();
+    }
+
+    #[test]
+    fn test_status_times_out() {
+        // The device never answers, so its row must carry an error rather
+        // than the call blocking forever.
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let host = IpAddr::from([127, 0, 0, 1]);
+        let mut devices = DiscoveryResult::default();
+        devices.insert(
+            host,
+            &json!({ "system": { "get_sysinfo": {
+                "alias": "Fridge",
+                "mic_type": "IOT.SMARTPLUGSWITCH",
+                "relay_state": 1,
+            } } }),
+            &mut |_| true,
+        );
+
+        let config = Config::for_host([0, 0, 0, 0])
+            .with_port(device.local_addr().unwrap().port())
+            .with_read_timeout(Duration::from_millis(100))
+            .build();
+        let rows = status_with_config(&devices, &config);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].host(), host);
+        assert!(rows[0].error().is_some());
+        assert_eq!(rows[0].alias(), None);
+    }
+}
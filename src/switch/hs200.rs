@@ -0,0 +1,378 @@
+use crate::ack::Ack;
+use crate::cache::{self, Persister, ResponseCache};
+use crate::cloud::{Cloud, CloudInfo, CloudSettings};
+use crate::config::Config;
+use crate::device::Device;
+use crate::diagnostics::{self, CallResult, Diagnostics};
+use crate::error::{self, Result};
+use crate::proto::{Command, Proto, Request};
+use crate::shared::Shared;
+use crate::sys::{Sys, System};
+use crate::sysinfo::{self, FeatureFlags, MacAddress, SysInfo, SystemInfo};
+use crate::time::{DeviceTime, DeviceTimeZone, Time, TimeSettings};
+use crate::timer::{Rule, RuleId, RuleList, Timer, TimerSettings};
+use crate::wlan::{AccessPoint, Netif, Wlan};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A TP-Link Wi-Fi Smart Light Switch (HS200).
+#[derive(Clone)]
+pub struct HS200 {
+    proto: Rc<Proto>,
+    cache: Arc<ResponseCache>,
+    _persister: Arc<Persister>,
+    system: System,
+    time_settings: TimeSettings,
+    timer_settings: TimerSettings,
+    cloud_settings: CloudSettings,
+    netif: Netif,
+    sysinfo: SystemInfo<HS200Info>,
+}
+
+impl HS200 {
+    pub(super) fn new<A>(host: A) -> HS200
+    where
+        A: Into<IpAddr>,
+    {
+        HS200::with_config(Config::for_host(host).build())
+    }
+
+    pub(super) fn with_config(config: Config) -> HS200 {
+        let Shared {
+            proto,
+            cache,
+            persister,
+        } = Shared::new(&config);
+
+        let mut device = HS200 {
+            system: System::new("system", proto.clone(), cache.clone()),
+            time_settings: TimeSettings::new("time", proto.clone()),
+            timer_settings: TimerSettings::new("count_down", proto.clone(), cache.clone()),
+            cloud_settings: CloudSettings::new("cnCloud", proto.clone(), cache.clone()),
+            netif: Netif::new(proto.clone()),
+            sysinfo: SystemInfo::new(proto.clone(), cache.clone()),
+            _persister: persister,
+            proto,
+            cache,
+        };
+        device
+            .system
+            .set_reset_confirmation(config.reset_confirmation);
+
+        device
+    }
+
+    pub(super) fn from_sysinfo(config: Config, sysinfo: Value) -> Result<HS200> {
+        let response = sysinfo::into_response(sysinfo);
+        HS200Info::deserialize(&response["system"]["get_sysinfo"]).map_err(error::json)?;
+
+        let device = HS200::with_config(config);
+        cache::seed(&device.cache, &response);
+        Ok(device)
+    }
+
+    pub(super) fn with_meta<R, F>(&self, call: F) -> Result<CallResult<R>>
+    where
+        F: FnOnce(&HS200) -> Result<R>,
+    {
+        diagnostics::measure(&self.proto, || call(self))
+    }
+
+    pub(super) fn diagnostics(&self) -> Diagnostics {
+        Diagnostics::collect(
+            &self.proto,
+            || self.cloud_settings.get_info(),
+            || self.time_settings.get_time(),
+        )
+    }
+
+    pub(super) fn sw_ver(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.sw_ver)
+    }
+
+    pub(super) fn hw_ver(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.hw_ver)
+    }
+
+    pub(super) fn model(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.model)
+    }
+
+    pub(super) fn alias(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.alias)
+    }
+
+    pub(super) fn mac_address(&self) -> Result<String> {
+        self.sysinfo().map(|sysinfo| sysinfo.mac.to_string())
+    }
+
+    pub(super) fn rssi(&self) -> Result<i64> {
+        self.sysinfo().map(|sysinfo| sysinfo.rssi)
+    }
+}
+
+impl Device for HS200 {
+    fn turn_on(&mut self) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetRelayState { on: true }))?;
+
+        log::trace!("(system) {:?}", response);
+
+        Ack::from_response(response)
+    }
+
+    fn turn_off(&mut self) -> Result<Ack> {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.lock().unwrap().retain(|k, _| k.target != "system");
+        }
+
+        let response = self
+            .proto
+            .send_request(&Request::from(Command::SetRelayState { on: false }))?;
+
+        log::trace!("(system) {:?}", response);
+
+        Ack::from_response(response)
+    }
+
+    fn is_on(&self) -> Result<bool> {
+        self.sysinfo().map(|sysinfo| sysinfo.is_on())
+    }
+
+    fn is_on_fresh(&self) -> Result<bool> {
+        self.sysinfo_fresh().map(|sysinfo| sysinfo.is_on())
+    }
+}
+
+impl Sys for HS200 {
+    fn reboot(&mut self, delay: Option<Duration>) -> Result<Ack> {
+        self.system.reboot(delay)
+    }
+
+    fn factory_reset(&mut self, delay: Option<Duration>) -> Result<Ack> {
+        self.system.reset(delay)
+    }
+
+    fn factory_reset_confirmed(
+        &mut self,
+        confirmation: &str,
+        delay: Option<Duration>,
+    ) -> Result<Ack> {
+        self.system.reset_confirmed(confirmation, delay)
+    }
+}
+
+impl Time for HS200 {
+    fn time(&self) -> Result<DeviceTime> {
+        self.time_settings.get_time()
+    }
+
+    fn timezone(&self) -> Result<DeviceTimeZone> {
+        self.time_settings.get_timezone()
+    }
+}
+
+impl Timer for HS200 {
+    fn get_timer_rules(&self) -> Result<RuleList> {
+        self.timer_settings.get_rules()
+    }
+
+    fn add_timer_rule(&mut self, rule: Rule) -> Result<RuleId> {
+        let is_table_empty = self.get_timer_rules().map(|list| list.is_empty())?;
+        if is_table_empty {
+            self.timer_settings.add_rule(rule)
+        } else {
+            Err(error::unsupported_operation(
+                "add_timer_rule: table is full",
+            ))
+        }
+    }
+
+    fn edit_timer_rule(&mut self, id: &RuleId, rule: Rule) -> Result<Ack> {
+        self.timer_settings.edit_rule(id, rule)
+    }
+
+    fn delete_timer_rule_with_id(&mut self, id: &RuleId) -> Result<Ack> {
+        self.timer_settings.delete_rule_with_id(id)
+    }
+
+    fn delete_all_timer_rules(&mut self) -> Result<Ack> {
+        self.timer_settings.delete_all_rules()
+    }
+}
+
+impl Cloud for HS200 {
+    fn get_cloud_info(&self) -> Result<CloudInfo> {
+        self.cloud_settings.get_info()
+    }
+
+    fn bind(&mut self, username: &str, password: &str) -> Result<Ack> {
+        self.cloud_settings.bind(username, password)
+    }
+
+    fn unbind(&mut self) -> Result<Ack> {
+        self.cloud_settings.unbind()
+    }
+
+    fn get_firmware_list(&self) -> Result<Vec<String>> {
+        self.cloud_settings.get_firmware_list()
+    }
+
+    fn set_server_url(&mut self, url: &str) -> Result<Ack> {
+        self.cloud_settings.set_server_url(url)
+    }
+}
+
+impl Wlan for HS200 {
+    fn get_scan_info(&self, refresh: bool, timeout: Option<Duration>) -> Result<Vec<AccessPoint>> {
+        self.netif.get_scan_info(refresh, timeout)
+    }
+}
+
+impl SysInfo for HS200 {
+    type Info = HS200Info;
+
+    fn sysinfo(&self) -> Result<Self::Info> {
+        self.sysinfo.get_sysinfo()
+    }
+
+    fn sysinfo_fresh(&self) -> Result<Self::Info> {
+        self.sysinfo.get_sysinfo_fresh()
+    }
+}
+
+/// The system information of TP-Link Wi-Fi Smart Light Switch (HS200).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HS200Info {
+    sw_ver: String,
+    hw_ver: String,
+    model: String,
+    #[serde(rename = "type")]
+    device_type: String,
+    mac: MacAddress,
+    alias: String,
+    relay_state: u64,
+    rssi: i64,
+    #[serde(default)]
+    feature: FeatureFlags,
+    #[serde(flatten)]
+    other: Map<String, Value>,
+}
+
+impl HS200Info {
+    /// Returns the software version of the device.
+    pub fn sw_ver(&self) -> &str {
+        &self.sw_ver
+    }
+
+    /// Returns the hardware version of the device.
+    pub fn hw_ver(&self) -> &str {
+        &self.hw_ver
+    }
+
+    /// Returns the model of the device.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Returns the name (alias) of the device.
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    /// Returns the mac address of the device, formatted as
+    /// `AA:BB:CC:DD:EE:FF`.
+    pub fn mac_address(&self) -> &str {
+        self.mac.as_str()
+    }
+
+    /// Returns the Wi-Fi signal strength (rssi) of the device.
+    pub fn rssi(&self) -> i64 {
+        self.rssi
+    }
+
+    /// Returns the features advertised by the device.
+    pub fn features(&self) -> FeatureFlags {
+        self.feature
+    }
+
+    /// Returns the system information as JSON, as reported by the device.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(error::json)
+    }
+
+    /// Returns whether the device is on.
+    fn is_on(&self) -> bool {
+        self.relay_state == 1
+    }
+}
+
+impl fmt::Display for HS200Info {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        sysinfo::write_fields(
+            f,
+            &[
+                ("alias", &self.alias),
+                ("model", &self.model),
+                ("hw_ver", &self.hw_ver),
+                ("sw_ver", &self.sw_ver),
+                ("mac", &self.mac),
+                ("state", &sysinfo::on_off(self.is_on())),
+                ("rssi", &format!("{} dBm", self.rssi)),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> Value {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name);
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sysinfo() {
+        let mut value = fixture("hs200_hw2.json");
+        let info: HS200Info =
+            crate::schema::parse([127, 0, 0, 1].into(), value["system"]["get_sysinfo"].take())
+                .unwrap();
+        assert_eq!(info.alias(), "Porch");
+        assert_eq!(info.model(), "HS200(US)");
+        assert_eq!(info.mac_address(), "B0:BE:76:00:00:03");
+        assert_eq!(info.rssi(), -55);
+        assert!(info.is_on());
+        assert!(info
+            .to_string()
+            .lines()
+            .any(|line| line.starts_with("state:") && line.ends_with(" on")));
+    }
+
+    #[test]
+    fn test_from_sysinfo_seeds_cache() {
+        // Nothing listens on the address, so reads must be served from the cache.
+        let config = Config::for_host([127, 0, 0, 1])
+            .with_cache_enabled(Duration::from_secs(60), None)
+            .build();
+
+        let switch = HS200::from_sysinfo(config, fixture("hs200_hw2.json")).unwrap();
+        assert_eq!(switch.sysinfo().unwrap().alias(), "Porch");
+    }
+}
@@ -1,13 +1,64 @@
-pub fn u32_in_range(val: u32, min: u32, max: u32) -> bool {
-    val >= min && val <= max
-}
+use crate::models::Model;
+
+/// The color temperature range assumed for tunable bulbs whose model has
+/// no known range. Every known tunable bulb supports at least this range.
+const DEFAULT_COLOR_TEMP_RANGE: (u32, u32) = (2700, 5000);
 
 pub fn valid_color_temp_range(model: &str) -> (u32, u32) {
-    let devices = [("LB120", (2700, 6500)), ("LB130", (2500, 9000))]
-        .iter()
-        .filter(|(name, _)| model.contains(name))
-        .map(|(_, range)| *range)
-        .collect::<Vec<_>>();
-    // TODO: Verify range before returning.
-    devices[0]
+    Model::from_name(model)
+        .and_then(Model::color_temp_range)
+        .unwrap_or_else(|| {
+            log::debug!(
+                "no known color temperature range for {}, assuming {}-{}K",
+                model,
+                DEFAULT_COLOR_TEMP_RANGE.0,
+                DEFAULT_COLOR_TEMP_RANGE.1
+            );
+            DEFAULT_COLOR_TEMP_RANGE
+        })
+}
+
+// Days since 1970-01-01 of the given proleptic Gregorian date.
+pub fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Number of days in the given month of the proleptic Gregorian calendar.
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    (days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_color_temp_range() {
+        assert_eq!(valid_color_temp_range("LB130(US)"), (2500, 9000));
+        assert_eq!(valid_color_temp_range("KL130(US)"), (2500, 9000));
+        assert_eq!(
+            valid_color_temp_range("KL999(EU)"),
+            DEFAULT_COLOR_TEMP_RANGE
+        );
+    }
+
+    #[test]
+    fn test_days_in_month() {
+        assert_eq!(days_in_month(2020, 2), 29);
+        assert_eq!(days_in_month(2021, 2), 28);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2021, 12), 31);
+        assert_eq!(days_in_month(2021, 4), 30);
+    }
 }
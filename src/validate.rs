@@ -0,0 +1,105 @@
+//! Validation of the parameters passed to device operations.
+//!
+//! Each check takes the name of the operation being validated, e.g.
+//! `"LB110(US) set_hue"`, and fails with an `InvalidParameter` error that
+//! carries the parameter name, the value provided and its valid range.
+
+use crate::error::{self, Result};
+use crate::util;
+
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// Checks a hue, in degrees (0-360).
+pub(crate) fn hue(op: &str, hue: u32) -> Result<()> {
+    in_range(op, "hue", hue, 0..=360, "°")
+}
+
+/// Checks a percentage (0-100), such as a saturation or a brightness.
+pub(crate) fn percent(op: &str, field: &'static str, value: u32) -> Result<()> {
+    in_range(op, field, value, 0..=100, "%")
+}
+
+/// Checks a color temperature, in kelvin, against the range supported by
+/// the given model.
+pub(crate) fn color_temp(op: &str, model: &str, color_temp: u32) -> Result<()> {
+    let (min, max) = util::valid_color_temp_range(model);
+    in_range(op, "color_temp", color_temp, min..=max, "K")
+}
+
+/// Checks a month of the year (1-12).
+pub(crate) fn month(op: &str, month: u32) -> Result<()> {
+    in_range(op, "month", month, 1..=12, "")
+}
+
+/// Checks a time of day, as an hour (0-23) and a minute (0-59).
+pub(crate) fn time_of_day(op: &str, hour: u32, minute: u32) -> Result<()> {
+    in_range(op, "hour", hour, 0..=23, "")?;
+    in_range(op, "minute", minute, 0..=59, "")
+}
+
+/// Checks a delay, with a resolution of seconds, against the given range
+/// of seconds.
+pub(crate) fn delay(
+    op: &str,
+    field: &'static str,
+    delay: Duration,
+    range: RangeInclusive<u32>,
+) -> Result<()> {
+    let secs = u32::try_from(delay.as_secs()).unwrap_or(u32::MAX);
+    in_range(op, field, secs, range, "s")
+}
+
+fn in_range(
+    op: &str,
+    field: &'static str,
+    value: u32,
+    range: RangeInclusive<u32>,
+    unit: &str,
+) -> Result<()> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(error::out_of_range(
+            op,
+            field,
+            i64::from(value),
+            i64::from(*range.start())..=i64::from(*range.end()),
+            unit,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_out_of_range() {
+        assert!(hue("LB130(US) set_hue", 360).is_ok());
+
+        let err = hue("LB130(US) set_hue", 400).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid parameter: LB130(US) set_hue: hue 400° (valid range: 0-360°)"
+        );
+        match err.kind() {
+            ErrorKind::InvalidParameter(param) => {
+                assert_eq!(param.field(), Some("hue"));
+                assert_eq!(param.value(), Some(400));
+                assert_eq!(param.range(), Some(&(0..=360)));
+            }
+            kind => panic!("unexpected error kind: {:?}", kind),
+        }
+
+        let err = delay(
+            "power_cycle",
+            "off_duration",
+            Duration::from_millis(500),
+            1..=u32::MAX,
+        );
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! The wire format of the protocol, independent of any transport.
+//!
+//! Devices exchange JSON documents of the form
+//! `{"<target>": {"<command>": <arg>}}`, optionally along with the
+//! `{"context": {"child_ids": [..]}}` of the child addressed, obfuscated
+//! with an autokey XOR cipher. Over UDP each document is sent as a single datagram; over TCP
+//! it is prefixed with its length as 4 big-endian bytes.
+//!
+//! This module only encodes and decodes bytes, and sticks to `core`,
+//! `alloc` and `serde_json` (or simd-json, with the `simd-json` feature),
+//! so gateways that talk to devices over their own transports can reuse
+//! the wire logic without the networking of the rest of the crate.
+//!
+//! # Examples
+//!
+//! ```
+//! use serde_json::json;
+//! use tplink::{wire, Command, Request};
+//!
+//! let request = Request::from(Command::GetSysinfo);
+//! let datagram = wire::encode_request(&request);
+//!
+//! // ... send `datagram` to the device and receive its answer ...
+//! # let answer = wire::encrypt(br#"{"system":{"get_sysinfo":{"alias":"Fridge"}}}"#);
+//!
+//! let sysinfo = wire::decode_response(&request, &answer)?;
+//! assert_eq!(sysinfo, json!({ "alias": "Fridge" }));
+//! # Ok::<(), wire::DecodeError>(())
+//! ```
+
+use crate::json;
+use crate::request::Request;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+pub use crate::crypto::{decrypt, decrypt_with_header, encrypt, encrypt_with_header};
+
+/// The error code devices answer with when a request targets a namespace
+/// (module) their firmware does not implement.
+const ERR_MODULE_NOT_SUPPORTED: i64 = -1;
+
+/// An error decoding a response from a device.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The response is not valid JSON, or does not match the expected type.
+    Json(serde_json::Error),
+    /// The device does not implement the namespace of the request.
+    UnsupportedNamespace(String),
+    /// The device rejected the request within its namespace, without
+    /// running the command, e.g. with `-2` (`member not support`) for a
+    /// command the namespace does not know.
+    Device {
+        /// The namespace of the request.
+        target: String,
+        /// The error code reported by the device.
+        code: i64,
+        /// The error message reported by the device, if any.
+        msg: Option<String>,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Json(e) => write!(f, "invalid response: {}", e),
+            DecodeError::UnsupportedNamespace(target) => {
+                write!(f, "namespace {} not supported", target)
+            }
+            DecodeError::Device { target, code, msg } => {
+                write!(f, "{} failed with error code {}", target, code)?;
+                match msg {
+                    Some(msg) => write!(f, ": {}", msg),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Returns the request as the JSON document sent to the device, before
+/// encryption.
+pub fn request_json(request: &Request) -> Vec<u8> {
+    let Request {
+        target,
+        command,
+        arg,
+        child_id,
+    } = request;
+    let json = match child_id {
+        Some(child_id) => json!({
+            "context": { "child_ids": [child_id] },
+            target: { command: arg },
+        }),
+        None => json!({ target: { command: arg } }),
+    };
+    // A map keyed by strings always serializes.
+    serde_json::to_vec(&json).unwrap()
+}
+
+/// Returns the request as an encrypted UDP datagram.
+pub fn encode_request(request: &Request) -> Vec<u8> {
+    encrypt(&request_json(request))
+}
+
+/// Returns the request as encrypted bytes prefixed with their length, as
+/// sent over TCP.
+pub fn encode_request_with_header(request: &Request) -> Vec<u8> {
+    encrypt_with_header(&request_json(request))
+}
+
+/// Decodes the encrypted UDP datagram answering the given request, and
+/// returns the result of its command.
+pub fn decode_response(request: &Request, datagram: &[u8]) -> Result<Value, DecodeError> {
+    let mut json = decrypt(datagram);
+    let response = json::from_mut_slice(&mut json).map_err(DecodeError::Json)?;
+    take_result(request, response)
+}
+
+/// Decodes the length-prefixed encrypted bytes answering the given request
+/// over TCP, and returns the result of its command.
+pub fn decode_response_with_header(request: &Request, bytes: &[u8]) -> Result<Value, DecodeError> {
+    let mut json = decrypt_with_header(bytes);
+    let response = json::from_mut_slice(&mut json).map_err(DecodeError::Json)?;
+    take_result(request, response)
+}
+
+/// Returns the result of the command of the given request from the
+/// decrypted JSON document answering it.
+pub fn response_from_json(request: &Request, json: &[u8]) -> Result<Value, DecodeError> {
+    let response = json::from_slice(json).map_err(DecodeError::Json)?;
+    take_result(request, response)
+}
+
+fn take_result(request: &Request, mut response: Value) -> Result<Value, DecodeError> {
+    let module = &mut response[&request.target];
+    match module["err_code"].as_i64() {
+        Some(ERR_MODULE_NOT_SUPPORTED) => {
+            Err(DecodeError::UnsupportedNamespace(request.target.clone()))
+        }
+        // An error reported next to the command rather than within its
+        // result means the command did not run.
+        Some(code) if code != 0 => Err(DecodeError::Device {
+            target: request.target.clone(),
+            code,
+            msg: module["err_msg"].as_str().map(String::from),
+        }),
+        _ => Ok(module[&request.command].take()),
+    }
+}
+
+/// Decodes the encrypted UDP datagram answering a `get_sysinfo` request
+/// into the system information of a device, such as a [`HS100Info`] or a
+/// [`LB110Info`]. Fields the type does not know about are ignored.
+///
+/// [`HS100Info`]: ../struct.HS100Info.html
+/// [`LB110Info`]: ../struct.LB110Info.html
+pub fn decode_sysinfo<T: DeserializeOwned>(datagram: &[u8]) -> Result<T, DecodeError> {
+    let request = Request::new("system", "get_sysinfo", None);
+    let sysinfo = decode_response(&request, datagram)?;
+    serde_json::from_value(sysinfo).map_err(DecodeError::Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HS100Info;
+
+    #[test]
+    fn test_round_trip() {
+        let request = Request::new("system", "set_relay_state", Some(json!({ "state": 1 })));
+        assert_eq!(
+            decrypt(&encode_request(&request)),
+            br#"{"system":{"set_relay_state":{"state":1}}}"#.to_vec()
+        );
+        assert_eq!(
+            decrypt_with_header(&encode_request_with_header(&request)),
+            request_json(&request)
+        );
+        assert_eq!(
+            request_json(&request.clone().with_child("01")),
+            br#"{"context":{"child_ids":["01"]},"system":{"set_relay_state":{"state":1}}}"#
+                .to_vec()
+        );
+
+        let response = encrypt(br#"{"system":{"set_relay_state":{"err_code":0}}}"#);
+        assert_eq!(
+            decode_response(&request, &response).unwrap(),
+            json!({ "err_code": 0 })
+        );
+
+        let response = encrypt(br#"{"emeter":{"err_code":-1,"err_msg":"module not support"}}"#);
+        let request = Request::new("emeter", "get_realtime", None);
+        assert!(matches!(
+            decode_response(&request, &response),
+            Err(DecodeError::UnsupportedNamespace(target)) if target == "emeter"
+        ));
+        assert!(matches!(
+            decode_response(&request, b"\x00\x01"),
+            Err(DecodeError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_module_error() {
+        let request = Request::new("system", "set_dev_location", None);
+        let response = encrypt(br#"{"system":{"err_code":-2,"err_msg":"member not support"}}"#);
+        let err = decode_response(&request, &response).unwrap_err();
+        assert!(matches!(
+            &err,
+            DecodeError::Device { target, code: -2, msg: Some(msg) }
+                if target == "system" && msg == "member not support"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "system failed with error code -2: member not support"
+        );
+
+        // An error within the result of the command is left to the caller.
+        let response = encrypt(br#"{"system":{"set_dev_location":{"err_code":-3}}}"#);
+        assert_eq!(
+            decode_response(&request, &response).unwrap(),
+            json!({ "err_code": -3 })
+        );
+    }
+
+    #[test]
+    fn test_decode_sysinfo() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("hs110_hw2.json");
+        let datagram = encrypt(&std::fs::read(path).unwrap());
+        let info: HS100Info = decode_sysinfo(&datagram).unwrap();
+        assert_eq!(info.alias(), "Fridge");
+    }
+}